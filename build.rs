@@ -0,0 +1,13 @@
+//! Generates the gRPC code of `proto/tasks.proto` with the vendored protoc, see `src/grpc.rs`.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::configure().compile(
+        &["proto/tasks.proto"],
+        &[
+            "proto",
+            &protoc_bin_vendored::include_path()?.to_string_lossy(),
+        ],
+    )?;
+    Ok(())
+}
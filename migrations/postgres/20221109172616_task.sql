@@ -0,0 +1,6 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS task (
+    id  BIGSERIAL PRIMARY KEY,
+    task varchar(255) NOT NULL
+
+);
@@ -0,0 +1,10 @@
+-- fields of iCalendar VTODO components, uid identifies a task in calendar apps
+ALTER TABLE task ADD COLUMN uid TEXT;
+UPDATE task SET uid = md5(random()::text || id::text);
+ALTER TABLE task ALTER COLUMN uid SET NOT NULL;
+ALTER TABLE task ADD CONSTRAINT task_uid UNIQUE (uid);
+ALTER TABLE task ADD COLUMN status TEXT;
+ALTER TABLE task ADD COLUMN due_at TIMESTAMPTZ;
+ALTER TABLE task ADD COLUMN priority INTEGER;
+ALTER TABLE task ADD COLUMN categories TEXT;
+ALTER TABLE task ADD COLUMN rrule TEXT;
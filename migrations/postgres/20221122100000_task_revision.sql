@@ -0,0 +1,10 @@
+-- change tracking for CalDAV sync: every write gives the task a new revision,
+-- deleted tasks leave a tombstone with the revision of the delete
+CREATE SEQUENCE task_revision;
+ALTER TABLE task ADD COLUMN revision BIGINT NOT NULL DEFAULT 0;
+UPDATE task SET revision = nextval('task_revision');
+CREATE INDEX task_revision_idx ON task (revision);
+CREATE TABLE task_tombstone (
+    uid TEXT PRIMARY KEY,
+    revision BIGINT NOT NULL
+);
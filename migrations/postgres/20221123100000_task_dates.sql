@@ -0,0 +1,3 @@
+-- creation and completion day, as kept by todo.txt
+ALTER TABLE task ADD COLUMN created_on DATE;
+ALTER TABLE task ADD COLUMN completed_on DATE;
@@ -0,0 +1,4 @@
+-- time of the reminder and the remind_at of the last reminder sent, see reminders.rs
+ALTER TABLE task ADD COLUMN remind_at TIMESTAMPTZ;
+ALTER TABLE task ADD COLUMN reminded_at TIMESTAMPTZ;
+CREATE INDEX task_remind_at ON task (remind_at);
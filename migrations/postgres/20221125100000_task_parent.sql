@@ -0,0 +1,3 @@
+-- subtasks: deleting a task deletes its subtasks (the repositories also leave tombstones for them)
+ALTER TABLE task ADD COLUMN parent_id BIGINT REFERENCES task (id) ON DELETE CASCADE;
+CREATE INDEX task_parent_id ON task (parent_id);
@@ -0,0 +1,21 @@
+-- manual order of the tasks by rank keys (see src/rank.rs), which replace the positions in the
+-- lists. The tasks keep their order by id, except that the tasks of a list take the places of
+-- the list in the order of their positions.
+-- The keys are compared byte by byte, whatever the collation of the database.
+ALTER TABLE task ADD COLUMN rank TEXT COLLATE "C" NOT NULL DEFAULT '';
+WITH numbered AS (
+    SELECT id, list_id,
+        ROW_NUMBER() OVER (ORDER BY id) AS place,
+        ROW_NUMBER() OVER (PARTITION BY list_id ORDER BY id) AS by_id,
+        ROW_NUMBER() OVER (PARTITION BY list_id ORDER BY position, id) AS by_position
+    FROM task
+)
+UPDATE task SET rank = lpad(slot.place::text, 10, '0')
+FROM numbered AS moved
+JOIN numbered AS slot
+    ON slot.list_id IS NOT DISTINCT FROM moved.list_id AND slot.by_id = moved.by_position
+WHERE moved.id = task.id;
+DROP INDEX task_list_id;
+ALTER TABLE task DROP COLUMN position;
+CREATE INDEX task_rank ON task (rank, id);
+CREATE INDEX task_list_id ON task (list_id, rank);
@@ -0,0 +1,19 @@
+-- kanban boards: the columns of a board map to task statuses, tasks without a status are in
+-- the needs-action column. A board of a list shows its tasks only and is deleted along with it.
+CREATE TABLE IF NOT EXISTS board (
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    list_id BIGINT REFERENCES task_list (id) ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS board_column (
+    id BIGSERIAL PRIMARY KEY,
+    board_id BIGINT NOT NULL REFERENCES board (id) ON DELETE CASCADE,
+    -- left to right
+    position BIGINT NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    status TEXT NOT NULL,
+    -- most tasks in the column, moves beyond it are refused
+    wip_limit BIGINT,
+    UNIQUE (board_id, status)
+);
+CREATE INDEX board_column_board_id ON board_column (board_id, position);
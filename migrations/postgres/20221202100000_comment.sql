@@ -0,0 +1,33 @@
+-- comments on tasks (Markdown), the earlier versions of edited comments and the notification
+-- events of @mentions. All of them are deleted along with their task.
+CREATE TABLE IF NOT EXISTS task_comment (
+    id BIGSERIAL PRIMARY KEY,
+    task_id BIGINT NOT NULL REFERENCES task (id) ON DELETE CASCADE,
+    author VARCHAR(64) NOT NULL,
+    body TEXT NOT NULL,
+    -- comma separated names mentioned in the body
+    mentions TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL,
+    edited_by VARCHAR(64),
+    edited_at TIMESTAMPTZ
+);
+CREATE INDEX task_comment_task_id ON task_comment (task_id);
+CREATE TABLE IF NOT EXISTS comment_version (
+    id BIGSERIAL PRIMARY KEY,
+    comment_id BIGINT NOT NULL REFERENCES task_comment (id) ON DELETE CASCADE,
+    author VARCHAR(64) NOT NULL,
+    body TEXT NOT NULL,
+    written_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX comment_version_comment_id ON comment_version (comment_id);
+CREATE TABLE IF NOT EXISTS notification (
+    id BIGSERIAL PRIMARY KEY,
+    recipient VARCHAR(64) NOT NULL,
+    task_id BIGINT NOT NULL REFERENCES task (id) ON DELETE CASCADE,
+    comment_id BIGINT NOT NULL REFERENCES task_comment (id) ON DELETE CASCADE,
+    author VARCHAR(64) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX notification_recipient ON notification (recipient, id);
+-- every name is notified once per comment
+CREATE UNIQUE INDEX notification_comment_id ON notification (comment_id, recipient);
@@ -0,0 +1,9 @@
+-- fields of iCalendar VTODO components, uid identifies a task in calendar apps
+ALTER TABLE task ADD COLUMN uid TEXT;
+UPDATE task SET uid = lower(hex(randomblob(16)));
+CREATE UNIQUE INDEX task_uid ON task (uid);
+ALTER TABLE task ADD COLUMN status TEXT;
+ALTER TABLE task ADD COLUMN due_at DATETIME;
+ALTER TABLE task ADD COLUMN priority INTEGER;
+ALTER TABLE task ADD COLUMN categories TEXT;
+ALTER TABLE task ADD COLUMN rrule TEXT;
@@ -0,0 +1,9 @@
+-- change tracking for CalDAV sync: every write gives the task a new revision,
+-- deleted tasks leave a tombstone with the revision of the delete
+ALTER TABLE task ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;
+UPDATE task SET revision = id;
+CREATE INDEX task_revision ON task (revision);
+CREATE TABLE task_tombstone (
+    uid TEXT PRIMARY KEY,
+    revision INTEGER NOT NULL
+);
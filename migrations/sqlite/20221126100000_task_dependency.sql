@@ -0,0 +1,7 @@
+-- task_id is blocked by blocker_id until the blocker is completed or cancelled
+CREATE TABLE IF NOT EXISTS task_dependency (
+    task_id INTEGER NOT NULL REFERENCES task (id) ON DELETE CASCADE,
+    blocker_id INTEGER NOT NULL REFERENCES task (id) ON DELETE CASCADE,
+    PRIMARY KEY (task_id, blocker_id)
+);
+CREATE INDEX task_dependency_blocker_id ON task_dependency (blocker_id);
@@ -0,0 +1,15 @@
+-- tags of tasks, many to many; deleting a task or a tag deletes its attachments
+CREATE TABLE IF NOT EXISTS tag (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name VARCHAR(64) NOT NULL,
+    -- the name folded in Rust (see models::tag::name_key): unique regardless of case and the
+    -- order of the tags, the same in every backend
+    name_key TEXT NOT NULL UNIQUE,
+    colour VARCHAR(7)
+);
+CREATE TABLE IF NOT EXISTS task_tag (
+    task_id INTEGER NOT NULL REFERENCES task (id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tag (id) ON DELETE CASCADE,
+    PRIMARY KEY (task_id, tag_id)
+);
+CREATE INDEX task_tag_tag_id ON task_tag (tag_id);
@@ -0,0 +1,12 @@
+-- lists (projects) of tasks: a task is in at most one list, ordered by position within it.
+-- The repositories delete the tasks of a list (with tombstones) or refuse to delete it.
+CREATE TABLE IF NOT EXISTS task_list (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    archived BOOLEAN NOT NULL DEFAULT FALSE
+);
+ALTER TABLE task ADD COLUMN list_id INTEGER REFERENCES task_list (id);
+ALTER TABLE task ADD COLUMN position INTEGER;
+-- archived along with the list
+ALTER TABLE task ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE INDEX task_list_id ON task (list_id, position);
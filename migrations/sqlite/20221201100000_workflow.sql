@@ -0,0 +1,27 @@
+-- workflows of tasks: named states, each giving its tasks a status, and the allowed transitions
+-- between them. A task following a workflow is in one of its states.
+CREATE TABLE IF NOT EXISTS workflow (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name VARCHAR(255) NOT NULL
+);
+CREATE TABLE IF NOT EXISTS workflow_state (
+    workflow_id INTEGER NOT NULL REFERENCES workflow (id) ON DELETE CASCADE,
+    -- the first state is the one of new tasks
+    position INTEGER NOT NULL,
+    name VARCHAR(64) NOT NULL,
+    status TEXT NOT NULL,
+    PRIMARY KEY (workflow_id, name)
+);
+CREATE TABLE IF NOT EXISTS workflow_transition (
+    workflow_id INTEGER NOT NULL REFERENCES workflow (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    from_state VARCHAR(64) NOT NULL,
+    to_state VARCHAR(64) NOT NULL,
+    -- comma separated fields the task must have after the transition
+    required_fields TEXT NOT NULL DEFAULT '',
+    PRIMARY KEY (workflow_id, from_state, to_state)
+);
+-- the repositories refuse to delete a workflow tasks follow
+ALTER TABLE task ADD COLUMN workflow_id INTEGER REFERENCES workflow (id);
+ALTER TABLE task ADD COLUMN state VARCHAR(64);
+CREATE INDEX task_workflow_id ON task (workflow_id, state);
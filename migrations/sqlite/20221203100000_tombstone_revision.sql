@@ -0,0 +1,2 @@
+-- the current revision is the highest one of the tasks and the tombstones
+CREATE INDEX task_tombstone_revision ON task_tombstone (revision);
@@ -0,0 +1,321 @@
+//! Online backups of the SQLite database and offline restore.
+//!
+//! Backups are taken with `VACUUM INTO` while the server keeps running and are stored as
+//! `backup-<UTC timestamp>.db` (or `.db.gz` when compressed) in `BACKUP_DIR`.
+//! A restore replaces the database file and therefore needs the server to be stopped, it refuses
+//! to run while another connection holds the database:
+//!
+//! ```not_rust
+//! cargo run --release -- restore backup-20221117T120000.000Z.db.gz
+//! ```
+//!
+//! Configuration (environment):
+//! - `BACKUP_DIR` - directory for backups (default `backups`)
+//! - `BACKUP_COMPRESS` - `true` to gzip backups (default `false`)
+//! - `BACKUP_KEEP` - prune keeps at least this many newest backups (default 7)
+//! - `BACKUP_MAX_AGE_DAYS` - prune also keeps all backups younger than this (default: unset)
+
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode};
+use sqlx::{ConnectOptions, Connection};
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::models::backup::{Backup, PruneReport};
+use crate::repository::{RepositoryError, TaskRepository};
+
+const PREFIX: &str = "backup-";
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+const EXTENSION: &str = ".db";
+const COMPRESSED_EXTENSION: &str = ".db.gz";
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackupError {
+    #[error("backups are only supported for sqlite databases")]
+    Unsupported,
+    #[error("backup {0} not found")]
+    NotFound(String),
+    #[error("integrity check of {0} failed: {1}")]
+    Integrity(String, String),
+    #[error("database {0} is in use, stop the server before restoring")]
+    InUse(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<RepositoryError> for BackupError {
+    fn from(err: RepositoryError) -> BackupError {
+        match err {
+            RepositoryError::Database(err) => BackupError::Database(err),
+            _ => BackupError::Unsupported,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, BackupError>;
+
+#[derive(Clone, Debug)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    pub compress: bool,
+    /// number of newest backups prune never deletes
+    pub keep_last: usize,
+    /// prune never deletes backups younger than this
+    pub max_age: Option<Duration>,
+}
+
+impl BackupConfig {
+    pub fn from_env() -> anyhow::Result<BackupConfig> {
+        let dir = env::var("BACKUP_DIR").unwrap_or_else(|_| "backups".to_string());
+        let compress = match env::var("BACKUP_COMPRESS") {
+            Ok(compress) => compress.parse()?,
+            Err(_) => false,
+        };
+        let keep_last = match env::var("BACKUP_KEEP") {
+            Ok(keep) => keep.parse()?,
+            Err(_) => 7,
+        };
+        let max_age = match env::var("BACKUP_MAX_AGE_DAYS") {
+            Ok(days) => Some(Duration::days(days.parse()?)),
+            Err(_) => None,
+        };
+        Ok(BackupConfig {
+            dir: dir.into(),
+            compress,
+            keep_last,
+            max_age,
+        })
+    }
+}
+
+/** Take a consistent online backup of the database behind `repo`,
+  gzip it if configured and return its description.
+*/
+pub async fn create_backup(repo: &dyn TaskRepository, config: &BackupConfig) -> Result<Backup> {
+    fs::create_dir_all(&config.dir)?;
+    let created_at = Utc::now();
+    let name = format!(
+        "{}{}{}",
+        PREFIX,
+        created_at.format(TIMESTAMP_FORMAT),
+        if config.compress {
+            COMPRESSED_EXTENSION
+        } else {
+            EXTENSION
+        }
+    );
+    let path = config.dir.join(&name);
+    // written under a temporary name, so listings never contain incomplete backups
+    let part = config.dir.join(format!("{}.part", name));
+
+    repo.backup_to(&part).await?;
+    if config.compress {
+        let uncompressed = part.clone();
+        let compressed = path.clone();
+        tokio::task::spawn_blocking(move || gzip(&uncompressed, &compressed))
+            .await
+            .map_err(io::Error::other)??;
+        fs::remove_file(&part)?;
+    } else {
+        fs::rename(&part, &path)?;
+    }
+    tracing::info!(backup = %name, "created backup");
+
+    Ok(Backup {
+        name,
+        created_at,
+        size_bytes: fs::metadata(&path)?.len(),
+        compressed: config.compress,
+    })
+}
+
+/// All backups in `dir`, newest first
+pub fn list_backups(dir: &Path) -> Result<Vec<Backup>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some((created_at, compressed)) = parse_name(&name) {
+            backups.push(Backup {
+                name,
+                created_at,
+                size_bytes: entry.metadata()?.len(),
+                compressed,
+            });
+        }
+    }
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+    Ok(backups)
+}
+
+/** Delete backups according to the retention policy: a backup is kept if it is one of
+  the `keep_last` newest backups or younger than `max_age`.
+*/
+pub fn prune_backups(config: &BackupConfig) -> Result<PruneReport> {
+    let now = Utc::now();
+    let mut removed = Vec::new();
+    let mut kept = 0;
+    for (index, backup) in list_backups(&config.dir)?.into_iter().enumerate() {
+        let young = config
+            .max_age
+            .is_some_and(|max_age| now - backup.created_at < max_age);
+        if index < config.keep_last || young {
+            kept += 1;
+        } else {
+            fs::remove_file(config.dir.join(&backup.name))?;
+            tracing::info!(backup = %backup.name, "pruned backup");
+            removed.push(backup);
+        }
+    }
+    Ok(PruneReport { removed, kept })
+}
+
+/** Replace the sqlite database of `database_url` with backup `name`.
+  The backup is decompressed next to the database and must pass `PRAGMA integrity_check`
+  before it is swapped in. The replaced database is checkpointed first and kept as
+  `<database>.pre-restore-<UTC timestamp>`. The server must not be running.
+*/
+pub async fn restore_backup(config: &BackupConfig, name: &str, database_url: &str) -> Result<()> {
+    if parse_name(name).is_none() {
+        return Err(BackupError::NotFound(name.to_string()));
+    }
+    let backup = config.dir.join(name);
+    if !backup.is_file() {
+        return Err(BackupError::NotFound(name.to_string()));
+    }
+    let database = sqlite_database_path(database_url).ok_or(BackupError::Unsupported)?;
+
+    let candidate = with_suffix(&database, ".restore");
+    if name.ends_with(COMPRESSED_EXTENSION) {
+        let (backup, candidate) = (backup.clone(), candidate.clone());
+        tokio::task::spawn_blocking(move || gunzip(&backup, &candidate))
+            .await
+            .map_err(io::Error::other)??;
+    } else {
+        fs::copy(&backup, &candidate)?;
+    }
+
+    if let Err(err) = verify_integrity(&candidate).await {
+        fs::remove_file(&candidate)?;
+        return Err(match err {
+            BackupError::Integrity(_, problems) => {
+                BackupError::Integrity(name.to_string(), problems)
+            }
+            BackupError::Database(err) => BackupError::Integrity(name.to_string(), err.to_string()),
+            err => err,
+        });
+    }
+
+    if database.exists() {
+        if let Err(err) = checkpoint_alone(&database).await {
+            fs::remove_file(&candidate)?;
+            return Err(err);
+        }
+        let kept = with_suffix(
+            &database,
+            &format!(".pre-restore-{}", Utc::now().format(TIMESTAMP_FORMAT)),
+        );
+        fs::rename(&database, &kept)?;
+        tracing::info!(database = %kept.display(), "kept replaced database");
+    }
+    fs::rename(&candidate, &database)?;
+    tracing::info!(backup = %name, database = %database.display(), "restored backup");
+    Ok(())
+}
+
+/** Move all committed data of the sqlite database at `path` into the database file itself.
+  Leaving WAL mode checkpoints the WAL and deletes the -wal/-shm files, but sqlite only
+  allows it to the one connection to the database, so this fails with `InUse` while
+  any other connection (e.g. a running server) holds it.
+*/
+async fn checkpoint_alone(path: &Path) -> Result<()> {
+    let conn = SqliteConnectOptions::new()
+        .filename(path)
+        .journal_mode(SqliteJournalMode::Delete)
+        .busy_timeout(std::time::Duration::ZERO)
+        .connect()
+        .await;
+    match conn {
+        Ok(conn) => Ok(conn.close().await?),
+        Err(sqlx::Error::Database(err)) if err.code().as_deref() == Some("5") => {
+            Err(BackupError::InUse(path.display().to_string()))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Run `PRAGMA integrity_check` on the sqlite database file at `path`
+pub async fn verify_integrity(path: &Path) -> Result<()> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .connect()
+        .await?;
+    let problems: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(&mut conn)
+        .await?;
+    conn.close().await?;
+    if problems == ["ok"] {
+        Ok(())
+    } else {
+        Err(BackupError::Integrity(
+            path.display().to_string(),
+            problems.join("; "),
+        ))
+    }
+}
+
+/// creation time and compression of a backup file name, None for other files
+fn parse_name(name: &str) -> Option<(DateTime<Utc>, bool)> {
+    let timestamp = name.strip_prefix(PREFIX)?;
+    let (timestamp, compressed) = match timestamp.strip_suffix(COMPRESSED_EXTENSION) {
+        Some(timestamp) => (timestamp, true),
+        None => (timestamp.strip_suffix(EXTENSION)?, false),
+    };
+    let created_at = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+    Some((Utc.from_utc_datetime(&created_at), compressed))
+}
+
+/// file of a `sqlite:` database url, None for other databases and in-memory sqlite
+fn sqlite_database_path(database_url: &str) -> Option<PathBuf> {
+    let database = database_url
+        .strip_prefix("sqlite://")
+        .or_else(|| database_url.strip_prefix("sqlite:"))?
+        .split('?')
+        .next()?;
+    if database.is_empty() || database.contains(":memory:") {
+        return None;
+    }
+    // sqlx accepts the same url, so this also validates it
+    SqliteConnectOptions::from_str(database_url).ok()?;
+    Some(PathBuf::from(database))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+fn gzip(from: &Path, to: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(File::create(to)?, Compression::default());
+    io::copy(&mut File::open(from)?, &mut encoder)?;
+    encoder.finish()?.sync_all()
+}
+
+fn gunzip(from: &Path, to: &Path) -> io::Result<()> {
+    let mut decoder = GzDecoder::new(File::open(from)?);
+    io::copy(&mut decoder, &mut File::create(to)?)?;
+    Ok(())
+}
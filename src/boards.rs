@@ -0,0 +1,73 @@
+//! Kanban boards: the columns of a board map to task statuses and show the tasks with their
+//! status as cards in the manual order (`crate::rank`). Tasks without a status are in the
+//! needs-action column, tasks with a status the board has no column for are not shown, neither
+//! are archived tasks. A board of a list shows the tasks of the list only.
+//!
+//! Moving a card (`POST /boards/{id}/cards/{task_id}/move`) sets the status of the task and,
+//! with anchors, its place among the cards in one transaction. A column may limit the tasks in
+//! it (work in progress limit), a move beyond the limit is a Conflict. Moving a card into the
+//! completed column completes the task like `PUT /tasks/{id}`: a blocked task can not be
+//! completed and a recurring task gets its next occurrence. A task with a workflow changes to
+//! the next state with the status of the column, see `crate::workflows`.
+//!
+//! Columns name statuses only, not workflow states: the states of a workflow sharing a status
+//! share its column, and a card move is refused when no single next state has the status of
+//! the column.
+
+use chrono::Utc;
+use std::collections::HashMap;
+
+use crate::models::board::{Board, BoardTasks, CardMove, ColumnTasks};
+use crate::models::task::{Task, TaskStatus};
+use crate::repository::{self, TaskRepository};
+
+/// `board` with those of `tasks` on it, which are all tasks or the tasks of its list in the
+/// manual order
+pub fn board_tasks(board: Board, tasks: Vec<Task>) -> BoardTasks {
+    let mut cards: HashMap<TaskStatus, Vec<Task>> = HashMap::new();
+    for task in tasks.into_iter().filter(|task| !task.archived) {
+        let status = task.status.unwrap_or(TaskStatus::NeedsAction);
+        cards.entry(status).or_default().push(task);
+    }
+    BoardTasks {
+        id: board.id,
+        name: board.name,
+        list_id: board.list_id,
+        columns: board
+            .columns
+            .into_iter()
+            .map(|column| ColumnTasks {
+                tasks: cards.remove(&column.status).unwrap_or_default(),
+                id: column.id,
+                name: column.name,
+                status: column.status,
+                wip_limit: column.wip_limit,
+            })
+            .collect(),
+    }
+}
+
+/// Board `id` with its tasks, NotFound if there is no such board
+pub async fn load(repo: &dyn TaskRepository, id: i64) -> repository::Result<BoardTasks> {
+    let board = repo.board(id).await?;
+    let tasks = match board.list_id {
+        Some(list_id) => repo.list_tasks(list_id).await?,
+        None => repo.all_tasks().await?,
+    };
+    Ok(board_tasks(board, tasks))
+}
+
+/** Move task `id` like [`TaskRepository::move_card`], completing it on the current day, and
+  return the board afterwards. A task with a workflow needs a transition to the next state with
+  the status of the column.
+*/
+pub async fn move_card(
+    repo: &dyn TaskRepository,
+    board_id: i64,
+    id: i64,
+    card: &CardMove,
+) -> repository::Result<BoardTasks> {
+    repo.move_card(board_id, id, card, Utc::now().date_naive())
+        .await?;
+    load(repo, board_id).await
+}
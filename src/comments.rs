@@ -0,0 +1,137 @@
+//! Comments on tasks: discussion threads with author attribution, Markdown bodies and an edit
+//! history (`/tasks/{id}/comments`).
+//!
+//! `@name` in a body mentions `name` (letters, digits, `_`, `.` and `-`), unless it is in
+//! inline code or a fenced code block, part of a word (`mail@example.com`) or escaped (`\@`).
+//! Every mention produces a notification event for the name, once per comment - an edit
+//! notifies the names it mentions for the first time, writers are not notified of their own
+//! mentions. The events are kept in the database (`GET /notifications`, polled with `after`)
+//! and logged (level info).
+//!
+//! The comments of a task are deleted along with it, like its subtasks: there is no tombstone
+//! for them, CalDAV does not sync comments.
+
+use chrono::Utc;
+
+use crate::models::comment::{Comment, NewComment, Notification, MAX_AUTHOR_LENGTH};
+use crate::repository::{self, TaskRepository};
+
+/// The names mentioned in the Markdown `body`, in order of appearance without duplicates
+pub fn mentions(body: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut fence: Option<&str> = None;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if let Some(open) = fence {
+            if trimmed.starts_with(open) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(open) = ["```", "~~~"].into_iter().find(|f| trimmed.starts_with(f)) {
+            fence = Some(open);
+            continue;
+        }
+        for name in line_mentions(line) {
+            if !names.iter().any(|known| known == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Mentions of a line outside of code spans
+fn line_mentions(line: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut previous: Option<char> = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '`' {
+            // a code span ends with a backtick run of the same length, otherwise the
+            // backticks are text
+            let run = line[i..].chars().take_while(|c| *c == '`').count();
+            let rest = &line[i + run..];
+            if let Some(end) = closing_run(rest, run) {
+                let skip = run + end + run;
+                while chars.peek().is_some_and(|(j, _)| *j < i + skip) {
+                    chars.next();
+                }
+                previous = Some('`');
+                continue;
+            }
+        } else if c == '@' && previous.is_none_or(|p| p.is_whitespace() || "([{\"',;:".contains(p))
+        {
+            let name: &str = {
+                let rest = &line[i + 1..];
+                let len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || "_.-".contains(c)))
+                    .unwrap_or(rest.len());
+                // a sentence may end right after the name
+                rest[..len].trim_end_matches(['.', '-'])
+            };
+            if !name.is_empty() && name.chars().count() <= MAX_AUTHOR_LENGTH {
+                names.push(name);
+            }
+        }
+        previous = Some(c);
+    }
+    names
+}
+
+/// Offset of the first run of exactly `len` backticks in `text`
+fn closing_run(text: &str, len: usize) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find('`') {
+        let start = offset + start;
+        let run = text[start..].chars().take_while(|c| *c == '`').count();
+        if run == len {
+            return Some(start);
+        }
+        offset = start + run;
+    }
+    None
+}
+
+/// Add a comment to task `task_id` like [`TaskRepository::create_comment`] with the mentions
+/// of its body
+pub async fn create_comment(
+    repo: &dyn TaskRepository,
+    task_id: i64,
+    comment: &NewComment,
+) -> repository::Result<Comment> {
+    let mentions = mentions(&comment.body);
+    let (created, notified) = repo
+        .create_comment(task_id, comment, &mentions, Utc::now())
+        .await?;
+    log(&notified);
+    Ok(created)
+}
+
+/// Edit comment `id` of task `task_id` like [`TaskRepository::update_comment`] with the
+/// mentions of its new body
+pub async fn update_comment(
+    repo: &dyn TaskRepository,
+    task_id: i64,
+    id: i64,
+    comment: &NewComment,
+) -> repository::Result<Comment> {
+    let mentions = mentions(&comment.body);
+    let (updated, notified) = repo
+        .update_comment(task_id, id, comment, &mentions, Utc::now())
+        .await?;
+    log(&notified);
+    Ok(updated)
+}
+
+fn log(notifications: &[Notification]) {
+    for notification in notifications {
+        tracing::info!(
+            recipient = %notification.recipient,
+            task_id = notification.task_id,
+            comment_id = notification.comment_id,
+            author = %notification.author,
+            "mention"
+        );
+    }
+}
@@ -1 +1,10 @@
+pub mod admin;
+pub mod board;
+pub mod caldav;
+pub mod comment;
+pub mod graphql;
+pub mod list;
+pub mod tag;
 pub mod task;
+pub mod transfer;
+pub mod workflow;
@@ -0,0 +1,85 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+
+use crate::backup::{self, BackupConfig, BackupError};
+use crate::models::error::ErrorResponse;
+use crate::repository::DynTaskRepository;
+
+/// Create backup
+///
+/// Take a consistent online backup of the database (sqlite only)
+#[utoipa::path(
+        post,
+        path = "/admin/backups",
+        responses(
+            (status = 201, description = "Backup created successfully", body = Backup),
+            (status = 500, description = "Backup could not be written", body = ErrorResponse),
+            (status = 501, description = "Storage backend does not support backups", body = ErrorResponse),
+        )
+    )]
+pub async fn create_backup(
+    Extension(repo): Extension<DynTaskRepository>,
+    Extension(config): Extension<BackupConfig>,
+) -> impl IntoResponse {
+    match backup::create_backup(repo.as_ref(), &config).await {
+        Ok(backup) => (StatusCode::CREATED, Json(backup)).into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not create backup");
+            error_response(err)
+        }
+    }
+}
+
+/// List backups
+///
+/// List all backups, newest first
+#[utoipa::path(
+        get,
+        path = "/admin/backups",
+        responses(
+            (status = 200, description = "List all backups successfully", body = [Backup]),
+            (status = 500, description = "Backup directory could not be read", body = ErrorResponse),
+        )
+    )]
+pub async fn all_backups(Extension(config): Extension<BackupConfig>) -> impl IntoResponse {
+    match backup::list_backups(&config.dir) {
+        Ok(backups) => (StatusCode::OK, Json(backups)).into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not list backups");
+            error_response(err)
+        }
+    }
+}
+
+/// Prune backups
+///
+/// Delete old backups according to the configured retention policy
+#[utoipa::path(
+        post,
+        path = "/admin/backups/prune",
+        responses(
+            (status = 200, description = "Old backups deleted", body = PruneReport),
+            (status = 500, description = "Backups could not be deleted", body = ErrorResponse),
+        )
+    )]
+pub async fn prune_backups(Extension(config): Extension<BackupConfig>) -> impl IntoResponse {
+    match backup::prune_backups(&config) {
+        Ok(report) => (StatusCode::OK, Json(report)).into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not prune backups");
+            error_response(err)
+        }
+    }
+}
+
+fn error_response(err: BackupError) -> Response {
+    let status = match err {
+        BackupError::Unsupported => StatusCode::NOT_IMPLEMENTED,
+        BackupError::NotFound(_) => StatusCode::NOT_FOUND,
+        BackupError::Integrity(..) => StatusCode::UNPROCESSABLE_ENTITY,
+        BackupError::InUse(_) => StatusCode::CONFLICT,
+        BackupError::Io(_) | BackupError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(ErrorResponse::new(err.to_string()))).into_response()
+}
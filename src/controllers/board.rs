@@ -0,0 +1,193 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use serde_json::json;
+
+use super::task::{error_response, not_found_response, unprocessable};
+use crate::boards;
+use crate::models::board::{CardMove, NewBoard};
+use crate::repository::DynTaskRepository;
+
+/// List all Boards
+///
+/// List all boards with their columns ordered by id, without their tasks
+#[utoipa::path(
+        get,
+        path = "/boards",
+        responses(
+            (status = 200, description = "All boards", body = [Board]),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        )
+    )]
+pub async fn all_boards(Extension(repo): Extension<DynTaskRepository>) -> impl IntoResponse {
+    match repo.boards().await {
+        Ok(boards) => (StatusCode::OK, Json(boards)).into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not list boards");
+            error_response(err)
+        }
+    }
+}
+
+/// Create new Board
+///
+/// Create a board with its columns, each showing the tasks of one status
+#[utoipa::path(
+        post,
+        path = "/boards",
+        request_body = NewBoard,
+        responses(
+            (status = 201, description = "Board created successfully", body = Board),
+            (status = 422, description = "Invalid board or list not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        )
+    )]
+pub async fn new_board(
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(board): Json<NewBoard>,
+) -> impl IntoResponse {
+    if let Err(msg) = board.validate() {
+        return unprocessable(msg);
+    }
+    match repo.create_board(&board).await {
+        Ok(board) => (
+            StatusCode::CREATED,
+            [("Location", format!("/boards/{}", board.id))],
+            Json(board),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not create board");
+            error_response(err)
+        }
+    }
+}
+
+/// Get Board by id
+///
+/// Get a board with the tasks of each column in their manual order
+#[utoipa::path(
+        get,
+        path = "/boards/{id}",
+        responses(
+            (status = 200, description = "Board with its tasks", body = BoardTasks),
+            (status = 404, description = "Board not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Board database id")
+        )
+    )]
+pub async fn board(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match boards::load(repo.as_ref(), id).await {
+        Ok(board) => (StatusCode::OK, Json(board)).into_response(),
+        Err(err) => {
+            tracing::error!(board_id = id, error = %err, "could not find board");
+            not_found_response(err, "board not found")
+        }
+    }
+}
+
+/// Replace Board by id
+///
+/// Replace name, list and columns of a board. Columns keep their id as long as their status
+/// stays on the board.
+#[utoipa::path(
+        put,
+        path = "/boards/{id}",
+        request_body = NewBoard,
+        responses(
+            (status = 200, description = "Board replaced", body = Board),
+            (status = 404, description = "Board not found", body = ErrorResponse),
+            (status = 422, description = "Invalid board or list not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Board database id")
+        )
+    )]
+pub async fn update_board(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(board): Json<NewBoard>,
+) -> impl IntoResponse {
+    if let Err(msg) = board.validate() {
+        return unprocessable(msg);
+    }
+    match repo.update_board(id, &board).await {
+        Ok(board) => (StatusCode::OK, Json(board)).into_response(),
+        Err(err) => {
+            tracing::error!(board_id = id, error = %err, "could not update board");
+            not_found_response(err, "board not found")
+        }
+    }
+}
+
+/// Delete Board by id
+///
+/// Delete a board, its tasks stay as they are
+#[utoipa::path(
+        delete,
+        path = "/boards/{id}",
+        responses(
+            (status = 200, description = "Board was deleted"),
+            (status = 404, description = "Board not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Board database id")
+        )
+    )]
+pub async fn delete_board(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.delete_board(id).await {
+        Ok(()) => (StatusCode::OK, Json(json!({"msg": "Board Deleted"}))).into_response(),
+        Err(err) => {
+            tracing::error!(board_id = id, error = %err, "could not delete board");
+            not_found_response(err, "board not found")
+        }
+    }
+}
+
+/// Move a Card
+///
+/// Move a task into a column of the board, which gives it the status of the column, and with
+/// `before` and/or `after` to its place among the cards - all at once. Returns the board with
+/// its tasks afterwards.
+#[utoipa::path(
+        post,
+        path = "/boards/{id}/cards/{task_id}/move",
+        request_body = CardMove,
+        responses(
+            (status = 200, description = "Card moved", body = BoardTasks),
+            (status = 404, description = "Board or task not found", body = ErrorResponse),
+            (status = 409, description = "The column is at its work in progress limit or the task is blocked", body = ErrorResponse),
+            (status = 422, description = "Column or anchor not found or the task is not on the board", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Board database id"),
+            ("task_id" = i64, Path, description = "Task database id")
+        )
+    )]
+pub async fn move_card(
+    Path(ids): Path<(i64, i64)>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(card): Json<CardMove>,
+) -> impl IntoResponse {
+    // a tuple pattern in the arguments is not understood by utoipa
+    let (id, task_id) = ids;
+    match boards::move_card(repo.as_ref(), id, task_id, &card).await {
+        Ok(board) => (StatusCode::OK, Json(board)).into_response(),
+        Err(err) => {
+            tracing::error!(board_id = id, task_id, error = %err, "could not move card");
+            not_found_response(err, "board or task not found")
+        }
+    }
+}
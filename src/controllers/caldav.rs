@@ -0,0 +1,518 @@
+//! CalDAV (RFC 4791) access to the tasks as one calendar of VTODOs, with collection
+//! synchronization (RFC 6578), so calendar apps like Thunderbird or DAVx5 can sync tasks and
+//! write their edits back.
+//!
+//! Resources below the path the api is served on:
+//! - `/.well-known/caldav` redirects to `/caldav/`
+//! - `/caldav/` is the principal (there are no users) and its calendar home
+//! - `/caldav/tasks/` is the calendar holding all tasks
+//! - `/caldav/tasks/<uid>.ics` is one task, named by its percent-encoded iCalendar UID
+//!
+//! The ETag of a task is its revision, the sync token and ctag of the calendar the highest
+//! revision, see [`crate::repository::TaskRepository::changes_since`]. The If-Match and
+//! If-None-Match preconditions of PUT and DELETE are checked in the writing transaction.
+
+use axum::body::Bytes;
+use axum::extract::{OriginalUri, Path};
+use axum::http::header::{ALLOW, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH};
+use axum::http::{HeaderMap, HeaderName, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use super::task::error_response;
+use crate::formats::dav::{
+    self, Multistatus, Prop, PropRequest, Report, CALDAV, CALENDARSERVER, DAV,
+};
+use crate::formats::ical;
+use crate::models::error::ErrorResponse;
+use crate::models::task::Task;
+use crate::repository::{DynTaskRepository, RepositoryError};
+
+const PRINCIPAL_PATH: &str = "/caldav/";
+const CALENDAR_PATH: &str = "/caldav/tasks/";
+const SYNC_TOKEN_PREFIX: &str = "urn:axum-crud-api:sync:";
+/// compliance classes of the `DAV` header
+const DAV_COMPLIANCE: &str = "1, 3, calendar-access";
+const COLLECTION_METHODS: &str = "OPTIONS, PROPFIND, REPORT";
+const TASK_METHODS: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND";
+const ICALENDAR: &str = "text/calendar; charset=utf-8";
+/// characters left as they are in resource names, everything else of the uid is percent-encoded
+const NAME_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// `/.well-known/caldav` (RFC 6764), where clients start to look for the calendars
+pub async fn well_known(OriginalUri(uri): OriginalUri) -> impl IntoResponse {
+    let base = uri
+        .path()
+        .strip_suffix("/.well-known/caldav")
+        .unwrap_or_default();
+    Redirect::permanent(&format!("{}{}", base, PRINCIPAL_PATH))
+}
+
+/// The principal, which is its own calendar home as well
+pub async fn principal(
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    Extension(repo): Extension<DynTaskRepository>,
+    body: Bytes,
+) -> impl IntoResponse {
+    if method == Method::OPTIONS {
+        return options(COLLECTION_METHODS);
+    }
+    if method.as_str() != "PROPFIND" {
+        return method_not_allowed(COLLECTION_METHODS);
+    }
+    let props = match dav::parse_propfind(&body) {
+        Ok(props) => props,
+        Err(err) => return bad_request(err),
+    };
+    let base = base_path(&uri);
+    let mut multistatus = Multistatus::new();
+    respond(
+        &mut multistatus,
+        &format!("{}{}", base, PRINCIPAL_PATH),
+        &props,
+        principal_props(base),
+    );
+    if depth(&headers) > 0 {
+        match repo.current_revision().await {
+            Ok(revision) => respond(
+                &mut multistatus,
+                &format!("{}{}", base, CALENDAR_PATH),
+                &props,
+                calendar_props(base, revision),
+            ),
+            Err(err) => return repository_error(err),
+        }
+    }
+    multistatus_response(multistatus)
+}
+
+/// The calendar: properties of the collection and its tasks, and the REPORTs to query them
+pub async fn calendar(
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    Extension(repo): Extension<DynTaskRepository>,
+    body: Bytes,
+) -> impl IntoResponse {
+    let base = base_path(&uri);
+    match method.as_str() {
+        "OPTIONS" => options(COLLECTION_METHODS),
+        "PROPFIND" => match dav::parse_propfind(&body) {
+            Ok(props) => calendar_propfind(&repo, base, depth(&headers), props).await,
+            Err(err) => bad_request(err),
+        },
+        "REPORT" => match dav::parse_report(&body) {
+            Ok(report) => calendar_report(&repo, base, report).await,
+            Err(err) => bad_request(err),
+        },
+        _ => method_not_allowed(COLLECTION_METHODS),
+    }
+}
+
+/// One task as iCalendar object
+pub async fn task(
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Extension(repo): Extension<DynTaskRepository>,
+    body: Bytes,
+) -> impl IntoResponse {
+    let uid = match name.strip_suffix(".ics") {
+        Some(uid) => uid,
+        None => return error_response(RepositoryError::NotFound),
+    };
+    match method.as_str() {
+        "OPTIONS" => return options(TASK_METHODS),
+        "PUT" => return put_task(&repo, uid, &headers, &body).await,
+        "DELETE" => {
+            let precondition = |revision| precondition_holds(&headers, revision);
+            return match repo.delete_task_by_uid(uid, &precondition).await {
+                Ok(()) => StatusCode::NO_CONTENT.into_response(),
+                Err(RepositoryError::PreconditionFailed) => {
+                    StatusCode::PRECONDITION_FAILED.into_response()
+                }
+                Err(RepositoryError::NotFound) => error_response(RepositoryError::NotFound),
+                Err(err) => repository_error(err),
+            };
+        }
+        _ => {}
+    }
+    let stored = match repo.task_by_uid(uid).await {
+        Ok(task) => Some(task),
+        Err(RepositoryError::NotFound) => None,
+        Err(err) => return repository_error(err),
+    };
+    match (method.as_str(), stored) {
+        ("GET" | "HEAD" | "PROPFIND", None) => error_response(RepositoryError::NotFound),
+        ("GET" | "HEAD", Some(task)) => (
+            [(CONTENT_TYPE, ICALENDAR.to_string()), (ETAG, etag(&task))],
+            calendar_data(&task, Utc::now()),
+        )
+            .into_response(),
+        ("PROPFIND", Some(task)) => match dav::parse_propfind(&body) {
+            Ok(props) => {
+                let base = base_path(&uri);
+                let mut multistatus = Multistatus::new();
+                respond(
+                    &mut multistatus,
+                    &task_href(base, &task),
+                    &props,
+                    task_props(&task, &props, Utc::now()),
+                );
+                multistatus_response(multistatus)
+            }
+            Err(err) => bad_request(err),
+        },
+        _ => method_not_allowed(TASK_METHODS),
+    }
+}
+
+/// Create or replace the task with a calendar holding exactly one VTODO
+async fn put_task(
+    repo: &DynTaskRepository,
+    uid: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Response {
+    let mut rows = match ical::parse_vtodos(body) {
+        Ok(rows) => rows,
+        Err(err) => return bad_request(err),
+    };
+    if rows.len() != 1 {
+        return bad_request("calendar must contain exactly one VTODO");
+    }
+    let mut new_task = match rows.remove(0).task {
+        Ok(task) => task,
+        Err(reason) => return bad_request(reason),
+    };
+    match &new_task.uid {
+        Some(todo_uid) if todo_uid != uid => {
+            return bad_request("UID of the VTODO does not match the resource name")
+        }
+        Some(_) => {}
+        None => new_task.uid = Some(uid.to_string()),
+    }
+    let precondition = |revision| precondition_holds(headers, revision);
+    match repo.put_task(&new_task, &precondition).await {
+        Ok((task, created)) => {
+            let status = match created {
+                true => StatusCode::CREATED,
+                false => StatusCode::NO_CONTENT,
+            };
+            (status, [(ETAG, etag(&task))]).into_response()
+        }
+        Err(RepositoryError::PreconditionFailed) => StatusCode::PRECONDITION_FAILED.into_response(),
+        // the task is blocked by an open task, calendar apps know no dependencies
+        Err(RepositoryError::Conflict(msg)) => (
+            StatusCode::PRECONDITION_FAILED,
+            Json(ErrorResponse::new(msg)),
+        )
+            .into_response(),
+        Err(err) => repository_error(err),
+    }
+}
+
+async fn calendar_propfind(
+    repo: &DynTaskRepository,
+    base: &str,
+    depth: u8,
+    props: PropRequest,
+) -> Response {
+    // the revision is read first, so it never claims changes which are not listed
+    let revision = match repo.current_revision().await {
+        Ok(revision) => revision,
+        Err(err) => return repository_error(err),
+    };
+    let mut multistatus = Multistatus::new();
+    respond(
+        &mut multistatus,
+        &format!("{}{}", base, CALENDAR_PATH),
+        &props,
+        calendar_props(base, revision),
+    );
+    if depth > 0 {
+        match repo.all_tasks().await {
+            Ok(tasks) => respond_tasks(&mut multistatus, base, &props, &tasks),
+            Err(err) => return repository_error(err),
+        }
+    }
+    multistatus_response(multistatus)
+}
+
+async fn calendar_report(repo: &DynTaskRepository, base: &str, report: Report) -> Response {
+    let mut multistatus = Multistatus::new();
+    match report {
+        Report::CalendarQuery { props, component } => {
+            // there are only VTODOs, a query for events finds nothing
+            if matches!(&component, Some(name) if !name.eq_ignore_ascii_case("VTODO")) {
+                return multistatus_response(multistatus);
+            }
+            match repo.all_tasks().await {
+                Ok(tasks) => respond_tasks(&mut multistatus, base, &props, &tasks),
+                Err(err) => return repository_error(err),
+            }
+        }
+        Report::CalendarMultiget { props, hrefs } => {
+            let now = Utc::now();
+            for href in hrefs {
+                let uid = uid_of_href(&href);
+                match repo.task_by_uid(&uid).await {
+                    Ok(task) => respond(
+                        &mut multistatus,
+                        &href,
+                        &props,
+                        task_props(&task, &props, now),
+                    ),
+                    Err(RepositoryError::NotFound) => multistatus.not_found(&href),
+                    Err(err) => return repository_error(err),
+                }
+            }
+        }
+        Report::SyncCollection { props, sync_token } => {
+            let since = match &sync_token {
+                None => 0,
+                Some(token) => match parse_sync_token(token) {
+                    Some(revision) => revision,
+                    None => return invalid_sync_token(),
+                },
+            };
+            let changes = match repo.changes_since(since).await {
+                Ok(changes) => changes,
+                Err(err) => return repository_error(err),
+            };
+            // a token from the future was not issued by this database
+            if since > changes.revision {
+                return invalid_sync_token();
+            }
+            respond_tasks(&mut multistatus, base, &props, &changes.changed);
+            // the initial sync lists only existing tasks
+            if sync_token.is_some() {
+                for uid in changes.deleted {
+                    multistatus.not_found(&format!("{}{}", base, task_path(&uid)));
+                }
+            }
+            multistatus.sync_token(&sync_token_of(changes.revision));
+        }
+    }
+    multistatus_response(multistatus)
+}
+
+fn respond(multistatus: &mut Multistatus, href: &str, props: &PropRequest, available: Vec<Prop>) {
+    let (found, missing) = props.select(available);
+    multistatus.response(href, &found, &missing);
+}
+
+fn respond_tasks(multistatus: &mut Multistatus, base: &str, props: &PropRequest, tasks: &[Task]) {
+    let now = Utc::now();
+    for task in tasks {
+        respond(
+            multistatus,
+            &task_href(base, task),
+            props,
+            task_props(task, props, now),
+        );
+    }
+}
+
+fn principal_props(base: &str) -> Vec<Prop> {
+    let href = format!(
+        "<d:href>{}</d:href>",
+        dav::escape(&format!("{}{}", base, PRINCIPAL_PATH))
+    );
+    vec![
+        Prop::new(DAV, "resourcetype", "<d:collection/><d:principal/>"),
+        Prop::new(DAV, "displayname", "Tasks"),
+        Prop::new(DAV, "current-user-principal", href.clone()),
+        Prop::new(DAV, "principal-URL", href.clone()),
+        Prop::new(CALDAV, "calendar-home-set", href),
+    ]
+}
+
+fn calendar_props(base: &str, revision: i64) -> Vec<Prop> {
+    let principal = format!(
+        "<d:href>{}</d:href>",
+        dav::escape(&format!("{}{}", base, PRINCIPAL_PATH))
+    );
+    let reports: String = [
+        "<c:calendar-query/>",
+        "<c:calendar-multiget/>",
+        "<d:sync-collection/>",
+    ]
+    .iter()
+    .map(|report| {
+        format!(
+            "<d:supported-report><d:report>{}</d:report></d:supported-report>",
+            report
+        )
+    })
+    .collect();
+    vec![
+        Prop::new(DAV, "resourcetype", "<d:collection/><c:calendar/>"),
+        Prop::new(DAV, "displayname", "Tasks"),
+        Prop::new(DAV, "current-user-principal", principal.clone()),
+        Prop::new(DAV, "owner", principal),
+        Prop::new(
+            DAV,
+            "current-user-privilege-set",
+            "<d:privilege><d:read/></d:privilege><d:privilege><d:write/></d:privilege>",
+        ),
+        Prop::new(DAV, "supported-report-set", reports),
+        Prop::new(DAV, "sync-token", dav::escape(&sync_token_of(revision))),
+        Prop::new(CALENDARSERVER, "getctag", revision.to_string()),
+        Prop::new(
+            CALDAV,
+            "supported-calendar-component-set",
+            r#"<c:comp name="VTODO"/>"#,
+        ),
+    ]
+}
+
+/// The calendar data is sent only if asked for by name, it is not part of `allprop`
+fn task_props(task: &Task, props: &PropRequest, now: DateTime<Utc>) -> Vec<Prop> {
+    let mut available = vec![
+        Prop::new(DAV, "resourcetype", ""),
+        Prop::new(DAV, "getetag", dav::escape(&etag(task))),
+        Prop::new(
+            DAV,
+            "getcontenttype",
+            "text/calendar; charset=utf-8; component=VTODO",
+        ),
+    ];
+    if props.wants(CALDAV, "calendar-data") {
+        available.push(Prop::new(
+            CALDAV,
+            "calendar-data",
+            dav::escape(&calendar_data(task, now)),
+        ));
+    }
+    available
+}
+
+/// Calendar with the VTODO of the task, `now` is the time of the response (its DTSTAMP) like
+/// in the calendar export
+fn calendar_data(task: &Task, now: DateTime<Utc>) -> String {
+    ical::calendar_begin() + &ical::vtodo(task, now) + &ical::calendar_end()
+}
+
+fn etag(task: &Task) -> String {
+    revision_etag(task.revision)
+}
+
+fn revision_etag(revision: i64) -> String {
+    format!("\"{}\"", revision)
+}
+
+/** Whether the If-Match and If-None-Match headers allow changing the task with `revision`
+  (None: there is no such task). `*` matches any stored task, otherwise the ETags are compared.
+*/
+fn precondition_holds(headers: &HeaderMap, revision: Option<i64>) -> bool {
+    let matches = |header: HeaderName| {
+        headers.get(header).map(|value| {
+            let etag = revision.map(revision_etag);
+            value.to_str().unwrap_or_default().split(',').any(|tag| {
+                let tag = tag.trim();
+                (tag == "*" && revision.is_some()) || Some(tag) == etag.as_deref()
+            })
+        })
+    };
+    matches(IF_MATCH) != Some(false) && matches(IF_NONE_MATCH) != Some(true)
+}
+
+/// Path the api is served on, the part of the request path in front of `/caldav/`
+fn base_path(uri: &Uri) -> &str {
+    let path = uri.path();
+    match path.find(PRINCIPAL_PATH.trim_end_matches('/')) {
+        Some(index) => &path[..index],
+        None => "",
+    }
+}
+
+fn task_path(uid: &str) -> String {
+    format!(
+        "{}{}.ics",
+        CALENDAR_PATH,
+        utf8_percent_encode(uid, NAME_SET)
+    )
+}
+
+fn task_href(base: &str, task: &Task) -> String {
+    format!("{}{}", base, task_path(&task.uid))
+}
+
+/// Uid of the task named by the last segment of `href` (a path or an absolute URL)
+fn uid_of_href(href: &str) -> String {
+    let name = href.rsplit('/').next().unwrap_or_default();
+    let name = name.strip_suffix(".ics").unwrap_or(name);
+    percent_decode_str(name).decode_utf8_lossy().into_owned()
+}
+
+fn sync_token_of(revision: i64) -> String {
+    format!("{}{}", SYNC_TOKEN_PREFIX, revision)
+}
+
+fn parse_sync_token(token: &str) -> Option<i64> {
+    token.strip_prefix(SYNC_TOKEN_PREFIX)?.parse().ok()
+}
+
+/// `Depth` header, infinity is treated as 1 as the calendar is the deepest collection
+fn depth(headers: &HeaderMap) -> u8 {
+    match headers.get("depth").and_then(|depth| depth.to_str().ok()) {
+        Some("0") => 0,
+        _ => 1,
+    }
+}
+
+fn multistatus_response(multistatus: Multistatus) -> Response {
+    (
+        StatusCode::MULTI_STATUS,
+        [(CONTENT_TYPE, "application/xml; charset=utf-8")],
+        multistatus.finish(),
+    )
+        .into_response()
+}
+
+fn options(methods: &'static str) -> Response {
+    (
+        [
+            (ALLOW, methods),
+            (HeaderName::from_static("dav"), DAV_COMPLIANCE),
+        ],
+        (),
+    )
+        .into_response()
+}
+
+fn method_not_allowed(methods: &'static str) -> Response {
+    (StatusCode::METHOD_NOT_ALLOWED, [(ALLOW, methods)]).into_response()
+}
+
+fn invalid_sync_token() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        [(CONTENT_TYPE, "application/xml; charset=utf-8")],
+        dav::error("valid-sync-token"),
+    )
+        .into_response()
+}
+
+fn bad_request(err: impl ToString) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new(err.to_string())),
+    )
+        .into_response()
+}
+
+fn repository_error(err: RepositoryError) -> Response {
+    tracing::error!(error = %err, "caldav request failed");
+    error_response(err)
+}
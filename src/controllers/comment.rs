@@ -0,0 +1,236 @@
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use serde_json::json;
+
+use super::task::{error_response, not_found_response, unprocessable};
+use crate::comments;
+use crate::models::comment::{NewComment, NotificationQuery};
+use crate::repository::DynTaskRepository;
+
+/// List Comments of a Task
+///
+/// List the comments of a task, oldest first
+#[utoipa::path(
+        get,
+        path = "/tasks/{id}/comments",
+        responses(
+            (status = 200, description = "Comments of the task", body = [Comment]),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id")
+        )
+    )]
+pub async fn comments(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.comments(id).await {
+        Ok(comments) => (StatusCode::OK, Json(comments)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not list comments");
+            error_response(err)
+        }
+    }
+}
+
+/// Comment on a Task
+///
+/// Add a comment in Markdown, every `@name` in it produces a notification event for `name`
+#[utoipa::path(
+        post,
+        path = "/tasks/{id}/comments",
+        request_body = NewComment,
+        responses(
+            (status = 201, description = "Comment created successfully", body = Comment),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 422, description = "Invalid comment", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id")
+        )
+    )]
+pub async fn new_comment(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(comment): Json<NewComment>,
+) -> impl IntoResponse {
+    if let Err(msg) = comment.validate() {
+        return unprocessable(msg);
+    }
+    match comments::create_comment(repo.as_ref(), id, &comment).await {
+        Ok(comment) => (
+            StatusCode::CREATED,
+            [("Location", format!("/tasks/{}/comments/{}", id, comment.id))],
+            Json(comment),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not create comment");
+            error_response(err)
+        }
+    }
+}
+
+/// Get Comment by id
+#[utoipa::path(
+        get,
+        path = "/tasks/{id}/comments/{comment_id}",
+        responses(
+            (status = 200, description = "Comment found", body = Comment),
+            (status = 404, description = "Task or comment not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id"),
+            ("comment_id" = i64, Path, description = "Comment database id")
+        )
+    )]
+pub async fn comment(
+    Path(ids): Path<(i64, i64)>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    // a tuple pattern in the arguments is not understood by utoipa
+    let (id, comment_id) = ids;
+    match repo.comment(id, comment_id).await {
+        Ok(comment) => (StatusCode::OK, Json(comment)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, comment_id, error = %err, "could not find comment");
+            not_found_response(err, "comment not found")
+        }
+    }
+}
+
+/// Edit Comment by id
+///
+/// Replace the body of a comment, `author` is the author of the edit. The previous version
+/// goes to the history of the comment, names mentioned for the first time are notified.
+#[utoipa::path(
+        put,
+        path = "/tasks/{id}/comments/{comment_id}",
+        request_body = NewComment,
+        responses(
+            (status = 200, description = "Comment edited", body = Comment),
+            (status = 404, description = "Task or comment not found", body = ErrorResponse),
+            (status = 422, description = "Invalid comment", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id"),
+            ("comment_id" = i64, Path, description = "Comment database id")
+        )
+    )]
+pub async fn update_comment(
+    Path(ids): Path<(i64, i64)>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(comment): Json<NewComment>,
+) -> impl IntoResponse {
+    // a tuple pattern in the arguments is not understood by utoipa
+    let (id, comment_id) = ids;
+    if let Err(msg) = comment.validate() {
+        return unprocessable(msg);
+    }
+    match comments::update_comment(repo.as_ref(), id, comment_id, &comment).await {
+        Ok(comment) => (StatusCode::OK, Json(comment)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, comment_id, error = %err, "could not update comment");
+            not_found_response(err, "comment not found")
+        }
+    }
+}
+
+/// Delete Comment by id
+///
+/// Delete a comment with its history and notifications
+#[utoipa::path(
+        delete,
+        path = "/tasks/{id}/comments/{comment_id}",
+        responses(
+            (status = 200, description = "Comment was deleted"),
+            (status = 404, description = "Task or comment not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id"),
+            ("comment_id" = i64, Path, description = "Comment database id")
+        )
+    )]
+pub async fn delete_comment(
+    Path(ids): Path<(i64, i64)>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    // a tuple pattern in the arguments is not understood by utoipa
+    let (id, comment_id) = ids;
+    match repo.delete_comment(id, comment_id).await {
+        Ok(()) => (StatusCode::OK, Json(json!({"msg": "Comment Deleted"}))).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, comment_id, error = %err, "could not delete comment");
+            not_found_response(err, "comment not found")
+        }
+    }
+}
+
+/// Edit history of a Comment
+///
+/// The earlier versions of a comment with their authors, oldest first
+#[utoipa::path(
+        get,
+        path = "/tasks/{id}/comments/{comment_id}/history",
+        responses(
+            (status = 200, description = "Earlier versions of the comment", body = [CommentVersion]),
+            (status = 404, description = "Task or comment not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id"),
+            ("comment_id" = i64, Path, description = "Comment database id")
+        )
+    )]
+pub async fn comment_history(
+    Path(ids): Path<(i64, i64)>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    // a tuple pattern in the arguments is not understood by utoipa
+    let (id, comment_id) = ids;
+    match repo.comment_history(id, comment_id).await {
+        Ok(history) => (StatusCode::OK, Json(history)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, comment_id, error = %err, "could not read comment history");
+            not_found_response(err, "comment not found")
+        }
+    }
+}
+
+/// List Notifications
+///
+/// Notification events of @mentions in comments ordered by id, e.g. polled with the last id
+/// seen as `after`
+#[utoipa::path(
+        get,
+        path = "/notifications",
+        responses(
+            (status = 200, description = "Notifications", body = [Notification]),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(NotificationQuery)
+    )]
+pub async fn notifications(
+    Query(query): Query<NotificationQuery>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    let recipient = query.recipient.as_deref();
+    match repo
+        .notifications(recipient, query.after.unwrap_or(0))
+        .await
+    {
+        Ok(notifications) => (StatusCode::OK, Json(notifications)).into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not list notifications");
+            error_response(err)
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! GraphQL endpoint and the GraphiQL playground, the schema is in [`crate::graphql`].
+
+use async_graphql::http::GraphiQLSource;
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::extract::OriginalUri;
+use axum::response::{Html, IntoResponse};
+use axum::Extension;
+
+use crate::graphql::TaskSchema;
+
+/// Queries and mutations, as POST with a json body or as GET with the query string
+pub async fn graphql(
+    Extension(schema): Extension<TaskSchema>,
+    req: GraphQLRequest,
+) -> GraphQLResponse {
+    schema.execute(req.into_inner()).await.into()
+}
+
+/// GraphiQL, subscriptions run over the websocket at `/graphql/ws`.
+/// The endpoints are next to the playground, also when the app is nested under a prefix.
+pub async fn graphiql(OriginalUri(uri): OriginalUri) -> impl IntoResponse {
+    let base = uri.path().strip_suffix("/graphiql").unwrap_or_default();
+    Html(
+        GraphiQLSource::build()
+            .endpoint(&format!("{}/graphql", base))
+            .subscription_endpoint(&format!("{}/graphql/ws", base))
+            .finish(),
+    )
+}
@@ -0,0 +1,362 @@
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use serde_json::json;
+
+use super::task::{error_response, not_found_response, unprocessable};
+use crate::models::list::{DeleteListOptions, ListOrder, MoveToList, NewTaskList};
+use crate::models::task::NewTask;
+use crate::repository::{DynTaskRepository, RepositoryError};
+
+/// List all Lists
+///
+/// List all task lists (projects) ordered by id, archived ones as well
+#[utoipa::path(
+        get,
+        path = "/lists",
+        responses(
+            (status = 200, description = "All lists", body = [TaskList]),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        )
+    )]
+pub async fn all_lists(Extension(repo): Extension<DynTaskRepository>) -> impl IntoResponse {
+    match repo.lists().await {
+        Ok(lists) => (StatusCode::OK, Json(lists)).into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not list lists");
+            error_response(err)
+        }
+    }
+}
+
+/// Create new List
+#[utoipa::path(
+        post,
+        path = "/lists",
+        request_body = NewTaskList,
+        responses(
+            (status = 201, description = "List created successfully", body = TaskList),
+            (status = 422, description = "Invalid name", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        )
+    )]
+pub async fn new_list(
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(list): Json<NewTaskList>,
+) -> impl IntoResponse {
+    if let Err(msg) = list.validate() {
+        return unprocessable(msg);
+    }
+    match repo.create_list(&list).await {
+        Ok(list) => (
+            StatusCode::CREATED,
+            [("Location", format!("/lists/{}", list.id))],
+            Json(list),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not create list");
+            error_response(err)
+        }
+    }
+}
+
+/// Get List by id
+#[utoipa::path(
+        get,
+        path = "/lists/{id}",
+        responses(
+            (status = 200, description = "List found", body = TaskList),
+            (status = 404, description = "List not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "List database id")
+        )
+    )]
+pub async fn list(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.list(id).await {
+        Ok(list) => (StatusCode::OK, Json(list)).into_response(),
+        Err(err) => {
+            tracing::error!(list_id = id, error = %err, "could not find list");
+            not_found_response(err, "list not found")
+        }
+    }
+}
+
+/// Rename List by id
+#[utoipa::path(
+        put,
+        path = "/lists/{id}",
+        request_body = NewTaskList,
+        responses(
+            (status = 200, description = "List renamed", body = TaskList),
+            (status = 404, description = "List not found", body = ErrorResponse),
+            (status = 422, description = "Invalid name", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "List database id")
+        )
+    )]
+pub async fn update_list(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(list): Json<NewTaskList>,
+) -> impl IntoResponse {
+    if let Err(msg) = list.validate() {
+        return unprocessable(msg);
+    }
+    match repo.update_list(id, &list).await {
+        Ok(list) => (StatusCode::OK, Json(list)).into_response(),
+        Err(err) => {
+            tracing::error!(list_id = id, error = %err, "could not update list");
+            not_found_response(err, "list not found")
+        }
+    }
+}
+
+/// Delete List by id
+///
+/// Only an empty list is deleted, unless `cascade=true` deletes its tasks (with their subtasks)
+/// as well
+#[utoipa::path(
+        delete,
+        path = "/lists/{id}",
+        params(
+            ("id" = i64, Path, description = "List database id"),
+            DeleteListOptions
+        ),
+        responses(
+            (status = 200, description = "List was deleted"),
+            (status = 404, description = "List not found", body = ErrorResponse),
+            (status = 409, description = "The list has tasks and cascade is not set", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        )
+    )]
+pub async fn delete_list(
+    Path(id): Path<i64>,
+    Query(options): Query<DeleteListOptions>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    let cascade = options.cascade.unwrap_or_default();
+    match repo.delete_list(id, cascade).await {
+        Ok(()) => (StatusCode::OK, Json(json!({"msg": "List Deleted"}))).into_response(),
+        Err(err) => {
+            tracing::error!(list_id = id, cascade, error = %err, "could not delete list");
+            not_found_response(err, "list not found")
+        }
+    }
+}
+
+/// Archive List by id
+///
+/// Archive a list together with its tasks, which are left out of `GET /tasks` from now on
+/// (see its `archived` parameter) and can not be moved to other lists
+#[utoipa::path(
+        post,
+        path = "/lists/{id}/archive",
+        responses(
+            (status = 200, description = "List archived", body = TaskList),
+            (status = 404, description = "List not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "List database id")
+        )
+    )]
+pub async fn archive_list(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.archive_list(id, true).await {
+        Ok(list) => (StatusCode::OK, Json(list)).into_response(),
+        Err(err) => {
+            tracing::error!(list_id = id, error = %err, "could not archive list");
+            not_found_response(err, "list not found")
+        }
+    }
+}
+
+/// Restore an archived List by id
+///
+/// Restore an archived list together with its tasks
+#[utoipa::path(
+        delete,
+        path = "/lists/{id}/archive",
+        responses(
+            (status = 200, description = "List restored", body = TaskList),
+            (status = 404, description = "List not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "List database id")
+        )
+    )]
+pub async fn restore_list(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.archive_list(id, false).await {
+        Ok(list) => (StatusCode::OK, Json(list)).into_response(),
+        Err(err) => {
+            tracing::error!(list_id = id, error = %err, "could not restore list");
+            not_found_response(err, "list not found")
+        }
+    }
+}
+
+/// Tasks of a List
+///
+/// List the tasks of a list in their order, archived or not
+#[utoipa::path(
+        get,
+        path = "/lists/{id}/tasks",
+        responses(
+            (status = 200, description = "Tasks of the list", body = [Task]),
+            (status = 404, description = "List not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "List database id")
+        )
+    )]
+pub async fn list_tasks(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.list_tasks(id).await {
+        Ok(tasks) => (StatusCode::OK, Json(tasks)).into_response(),
+        Err(err) => {
+            tracing::error!(list_id = id, error = %err, "could not list tasks of list");
+            not_found_response(err, "list not found")
+        }
+    }
+}
+
+/// Create new Task in a List
+///
+/// Like `POST /tasks` with the `list_id` of the path, the task is added to the end of the list
+#[utoipa::path(
+        post,
+        path = "/lists/{id}/tasks",
+        request_body = NewTask,
+        responses(
+            (status = 201, description = "Task created successfully", body = Task),
+            (status = 404, description = "List not found", body = ErrorResponse),
+            (status = 409, description = "The list is archived", body = ErrorResponse),
+            (status = 422, description = "Empty or too long task or priority out of range", body = ErrorResponse),
+            (status = 500, description = "Task could not be created", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "List database id")
+        )
+    )]
+pub async fn new_list_task(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(task): Json<NewTask>,
+) -> impl IntoResponse {
+    let task = NewTask {
+        list_id: Some(id),
+        ..task
+    };
+    if let Err(msg) = task.validate() {
+        return unprocessable(msg);
+    }
+    match repo.create_task(&task).await {
+        Ok(task) => (
+            StatusCode::CREATED,
+            [("Location", format!("/tasks/{}", task.id))],
+            Json(task),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!(list_id = id, error = %err, "could not create task in list");
+            match err {
+                // the list of the path is missing rather than a referenced one
+                RepositoryError::Invalid(_) => {
+                    not_found_response(RepositoryError::NotFound, "list not found")
+                }
+                err => error_response(err),
+            }
+        }
+    }
+}
+
+/// Order the Tasks of a List
+///
+/// Put the tasks of a list in the given order, which has to name every task of the list once.
+/// Returns the tasks in their new order.
+#[utoipa::path(
+        put,
+        path = "/lists/{id}/order",
+        request_body = ListOrder,
+        responses(
+            (status = 200, description = "Tasks of the list in their new order", body = [Task]),
+            (status = 404, description = "List not found", body = ErrorResponse),
+            (status = 422, description = "The ids are not the tasks of the list", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "List database id")
+        )
+    )]
+pub async fn reorder_list(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(order): Json<ListOrder>,
+) -> impl IntoResponse {
+    let reordered = match repo.reorder_list(id, &order.task_ids).await {
+        Ok(()) => repo.list_tasks(id).await,
+        Err(err) => Err(err),
+    };
+    match reordered {
+        Ok(tasks) => (StatusCode::OK, Json(tasks)).into_response(),
+        Err(err) => {
+            tracing::error!(list_id = id, error = %err, "could not order list");
+            not_found_response(err, "list not found")
+        }
+    }
+}
+
+/// Move a Task to a List
+///
+/// Move a task with its subtasks to the end of another list, or out of its list with
+/// `"list_id": null`. Tasks of archived lists stay where they are.
+#[utoipa::path(
+        put,
+        path = "/tasks/{id}/list",
+        request_body = MoveToList,
+        responses(
+            (status = 200, description = "Task moved", body = Task),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 409, description = "The task or the list is archived", body = ErrorResponse),
+            (status = 422, description = "List not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id")
+        )
+    )]
+pub async fn move_to_list(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(target): Json<MoveToList>,
+) -> impl IntoResponse {
+    let moved = match repo.move_to_list(id, target.list_id).await {
+        Ok(()) => repo.task(id).await,
+        Err(err) => Err(err),
+    };
+    match moved {
+        Ok(task) => (StatusCode::OK, Json(task)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not move task to list");
+            error_response(err)
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use serde_json::json;
+
+use super::task::{error_response, not_found_response, unprocessable};
+use crate::models::tag::NewTag;
+use crate::repository::DynTaskRepository;
+
+/// List all Tags
+///
+/// List all tags ordered by name
+#[utoipa::path(
+        get,
+        path = "/tags",
+        responses(
+            (status = 200, description = "All tags", body = [Tag]),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        )
+    )]
+pub async fn all_tags(Extension(repo): Extension<DynTaskRepository>) -> impl IntoResponse {
+    match repo.tags().await {
+        Ok(tags) => (StatusCode::OK, Json(tags)).into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not list tags");
+            error_response(err)
+        }
+    }
+}
+
+/// Tag counts
+///
+/// List all tags ordered by name with the number of tasks they are attached to
+#[utoipa::path(
+        get,
+        path = "/tags/counts",
+        responses(
+            (status = 200, description = "All tags with their number of tasks", body = [TagCount]),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        )
+    )]
+pub async fn tag_counts(Extension(repo): Extension<DynTaskRepository>) -> impl IntoResponse {
+    match repo.tag_counts().await {
+        Ok(counts) => (StatusCode::OK, Json(counts)).into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not count tags");
+            error_response(err)
+        }
+    }
+}
+
+/// Create new Tag
+///
+/// Tag names are unique regardless of case
+#[utoipa::path(
+        post,
+        path = "/tags",
+        request_body = NewTag,
+        responses(
+            (status = 201, description = "Tag created successfully", body = Tag),
+            (status = 409, description = "There is a tag with the name", body = ErrorResponse),
+            (status = 422, description = "Invalid name or colour", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        )
+    )]
+pub async fn new_tag(
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(tag): Json<NewTag>,
+) -> impl IntoResponse {
+    if let Err(msg) = tag.validate() {
+        return unprocessable(msg);
+    }
+    match repo.create_tag(&tag).await {
+        Ok(tag) => (
+            StatusCode::CREATED,
+            [("Location", format!("/tags/{}", tag.id))],
+            Json(tag),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not create tag");
+            error_response(err)
+        }
+    }
+}
+
+/// Get Tag by id
+#[utoipa::path(
+        get,
+        path = "/tags/{id}",
+        responses(
+            (status = 200, description = "Tag found", body = Tag),
+            (status = 404, description = "Tag not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Tag database id")
+        )
+    )]
+pub async fn tag(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.tag(id).await {
+        Ok(tag) => (StatusCode::OK, Json(tag)).into_response(),
+        Err(err) => {
+            tracing::error!(tag_id = id, error = %err, "could not find tag");
+            not_found_response(err, "tag not found")
+        }
+    }
+}
+
+/// Update Tag by id
+///
+/// Replace name and colour of a tag, the tasks with the tag show the change
+#[utoipa::path(
+        put,
+        path = "/tags/{id}",
+        request_body = NewTag,
+        responses(
+            (status = 200, description = "Tag updated successfully", body = Tag),
+            (status = 404, description = "Tag not found", body = ErrorResponse),
+            (status = 409, description = "There is another tag with the name", body = ErrorResponse),
+            (status = 422, description = "Invalid name or colour", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Tag database id")
+        )
+    )]
+pub async fn update_tag(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(tag): Json<NewTag>,
+) -> impl IntoResponse {
+    if let Err(msg) = tag.validate() {
+        return unprocessable(msg);
+    }
+    match repo.update_tag(id, &tag).await {
+        Ok(tag) => (StatusCode::OK, Json(tag)).into_response(),
+        Err(err) => {
+            tracing::error!(tag_id = id, error = %err, "could not update tag");
+            not_found_response(err, "tag not found")
+        }
+    }
+}
+
+/// Delete Tag by id
+///
+/// Delete a tag, which removes it from all its tasks
+#[utoipa::path(
+        delete,
+        path = "/tags/{id}",
+        responses(
+            (status = 200, description = "Tag was deleted"),
+            (status = 404, description = "Tag not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Tag database id")
+        )
+    )]
+pub async fn delete_tag(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.delete_tag(id).await {
+        Ok(()) => (StatusCode::OK, Json(json!({"msg": "Tag Deleted"}))).into_response(),
+        Err(err) => {
+            tracing::error!(tag_id = id, error = %err, "could not delete tag");
+            not_found_response(err, "tag not found")
+        }
+    }
+}
+
+/// Attach a Tag to a Task
+///
+/// Returns the task with its tags, attaching a tag twice changes nothing
+#[utoipa::path(
+        put,
+        path = "/tasks/{id}/tags/{tag_id}",
+        responses(
+            (status = 200, description = "Tag attached", body = Task),
+            (status = 404, description = "Task or tag not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id"),
+            ("tag_id" = i64, Path, description = "Tag database id")
+        )
+    )]
+pub async fn attach_tag(
+    Path(ids): Path<(i64, i64)>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    // a tuple pattern in the arguments is not understood by utoipa
+    let (id, tag_id) = ids;
+    let attached = match repo.attach_tag(id, tag_id).await {
+        Ok(()) => repo.task(id).await,
+        Err(err) => Err(err),
+    };
+    match attached {
+        Ok(task) => (StatusCode::OK, Json(task)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, tag_id, error = %err, "could not attach tag");
+            not_found_response(err, "task or tag not found")
+        }
+    }
+}
+
+/// Detach a Tag from a Task
+///
+/// Returns the task with its remaining tags
+#[utoipa::path(
+        delete,
+        path = "/tasks/{id}/tags/{tag_id}",
+        responses(
+            (status = 200, description = "Tag detached", body = Task),
+            (status = 404, description = "Tag is not attached to the task", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id"),
+            ("tag_id" = i64, Path, description = "Tag database id")
+        )
+    )]
+pub async fn detach_tag(
+    Path(ids): Path<(i64, i64)>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    // a tuple pattern in the arguments is not understood by utoipa
+    let (id, tag_id) = ids;
+    let detached = match repo.detach_tag(id, tag_id).await {
+        Ok(()) => repo.task(id).await,
+        Err(err) => Err(err),
+    };
+    match detached {
+        Ok(task) => (StatusCode::OK, Json(task)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, tag_id, error = %err, "could not detach tag");
+            not_found_response(err, "tag is not attached to the task")
+        }
+    }
+}
@@ -1,38 +1,80 @@
-use axum::extract::Path;
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::extract::{Path, Query};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 
 use axum::{Extension, Json};
+use chrono::{Duration, Utc};
 use serde_json::json;
-use sqlx::SqlitePool;
 
-use crate::models::task;
+use super::transfer::{ndjson_response, NDJSON};
+use crate::dependencies;
+use crate::formats::markdown;
+use crate::hierarchy::TaskTree;
+use crate::models::error::ErrorResponse;
+use crate::models::task::{
+    self, Dependency, MoveTask, NewBlocker, Occurrence, OccurrenceRange, RankAnchors, TaskFilter,
+    UpdateOptions,
+};
+use crate::negotiation::{self, Accepted, Format, Negotiated};
+use crate::recurrence::{
+    RecurrenceRule, MAX_OCCURRENCES, MAX_OCCURRENCE_DAYS, MAX_OCCURRENCE_STEPS,
+};
+use crate::repository::{DynTaskRepository, RepositoryError};
+
+const MARKDOWN: &str = "text/markdown";
 
 /// List all Tasks
 ///
-/// List all Tasks in database
+/// List all Tasks in database in the manual order (see `POST /tasks/{id}/move`), also as
+/// Markdown checklist with `Accept: text/markdown`.
+/// `Accept: application/x-ndjson` streams the tasks one per line without buffering them.
+/// The query parameters filter the list, `due=overdue` and `due=today` select tasks by due date,
+/// `tags=release,urgent` tasks with all of the tags (with `tag_match=any`: with any of them).
 #[utoipa::path(
         get,
         path = "/tasks",
+        params(TaskFilter),
         responses(
-            (status = 200, description = "List all tasks successfully", body = [Task]),
-            (status = 500, description = "Internal server error when retrieving list of all tasks", body = [Task])
+            (status = 200, description = "List all tasks successfully", body = [Task], content_type = ["application/json", "text/markdown", "application/x-ndjson"]),
+            (status = 500, description = "Internal server error when retrieving list of all tasks", body = ErrorResponse)
         )
     )]
-pub async fn all_tasks(Extension(pool): Extension<SqlitePool>) -> impl IntoResponse {
-    let sql = "SELECT id, task FROM task ".to_string();
-
-    let result: Result<Vec<task::Task>, sqlx::Error> =
-        sqlx::query_as::<_, task::Task>(&sql).fetch_all(&pool).await;
-
-    match result {
-        Ok(tasks) => (StatusCode::OK, Json(tasks)),
+pub async fn all_tasks(
+    headers: HeaderMap,
+    Query(filter): Query<TaskFilter>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    let mut offered: Vec<&str> = Format::ALL.iter().map(Format::media_type).collect();
+    offered.extend([MARKDOWN, NDJSON]);
+    let media_type = match negotiation::preferred(&headers, &offered) {
+        Some(media_type) => media_type,
+        None => return negotiation::not_acceptable(),
+    };
+    if media_type == NDJSON {
+        return ndjson_response(&repo, filter);
+    }
+    let now = Utc::now();
+    let tasks = repo.all_tasks().await.map(|tasks| {
+        tasks
+            .into_iter()
+            .filter(|task| filter.matches_at(task, now))
+            .collect::<Vec<_>>()
+    });
+    match tasks {
+        Ok(tasks) if media_type == MARKDOWN => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "text/markdown; charset=utf-8")],
+            markdown::checklist(&tasks),
+        )
+            .into_response(),
+        Ok(tasks) => match Format::from_media_type(media_type) {
+            Some(format) => (StatusCode::OK, Accepted(format).encode(tasks)).into_response(),
+            None => negotiation::not_acceptable(),
+        },
         Err(err) => {
-            tracing::error!("error retrieving tasks: {:?}", err);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(Vec::<task::Task>::new()),
-            )
+            tracing::error!(error = %err, "error retrieving tasks");
+            error_response(err)
         }
     }
 }
@@ -46,36 +88,28 @@ pub async fn all_tasks(Extension(pool): Extension<SqlitePool>) -> impl IntoRespo
         request_body = NewTask,
         responses(
             (status = 201, description = "Task created successfully", body = Task),
-            (status = 500, description = "Task could not be created", body = Task),
+            (status = 422, description = "Empty or too long task or priority out of range", body = ErrorResponse),
+            (status = 500, description = "Task could not be created", body = ErrorResponse),
         )
     )]
 pub async fn new_task(
-    Json(task): Json<task::NewTask>,
-    Extension(pool): Extension<SqlitePool>,
+    accepted: Accepted,
+    Negotiated(task): Negotiated<task::NewTask>,
+    Extension(repo): Extension<DynTaskRepository>,
 ) -> impl IntoResponse {
-    // we use "RETURNING" - non-standard SQL syntax (which is supported by sqlite and postgres) to return the new ID created by the database
-    // to our caller
-    let sql = "INSERT INTO task (task) values ($1) RETURNING *";
-
-    let result: Result<task::Task, sqlx::Error> =
-        sqlx::query_as(sql).bind(&task.task).fetch_one(&pool).await;
-
-    match result {
+    if let Err(msg) = task.validate() {
+        return unprocessable(msg);
+    }
+    match repo.create_task(&task).await {
         Ok(taskwithid) => (
             StatusCode::CREATED,
             [("Location", format!("/tasks/{:?}", taskwithid.id))],
-            Json(taskwithid),
-        ),
+            accepted.encode(taskwithid),
+        )
+            .into_response(),
         Err(err) => {
-            tracing::error!("could not create task. error: {:?}", err);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                [("Location", "/tasks/{id}".to_string())],
-                Json(task::Task {
-                    id: 0,
-                    task: task.task,
-                }),
-            )
+            tracing::error!(error = %err, "could not create task");
+            error_response(err)
         }
     }
 }
@@ -87,8 +121,9 @@ pub async fn new_task(
         get,
         path = "/tasks/{id}",
         responses(
-            (status = 200, description = "Task returned successfully"),
-            (status = 404, description = "Task not found")
+            (status = 200, description = "Task returned successfully", body = Task),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
         ),
         params(
             ("id" = i64, Path, description = "Task database id")
@@ -96,41 +131,37 @@ pub async fn new_task(
     )]
 pub async fn task(
     Path(id): Path<i64>,
-    Extension(pool): Extension<SqlitePool>,
+    accepted: Accepted,
+    Extension(repo): Extension<DynTaskRepository>,
 ) -> impl IntoResponse {
-    let sql = "SELECT * FROM task where id=$1".to_string();
-
-    let result: Result<task::Task, sqlx::Error> =
-        sqlx::query_as(&sql).bind(id).fetch_one(&pool).await;
-
-    match result {
-        Ok(task) => (StatusCode::OK, Json(task)),
+    match repo.task(id).await {
+        Ok(task) => (StatusCode::OK, accepted.encode(task)).into_response(),
         Err(err) => {
-            tracing::error!("could not find task with id: {:?} error: {:?}", id, err);
-            (
-                StatusCode::NOT_FOUND,
-                Json(task::Task {
-                    id,
-                    task: "".to_string(),
-                }),
-            )
+            tracing::error!(task_id = id, error = %err, "could not find task");
+            error_response(err)
         }
     }
 }
 
 /// Update Task with new description by id
 ///
-/// Update Task with id
+/// Update Task with id. Completing a recurring task (see `rrule`) creates its next occurrence,
+/// `subtasks=complete` completes its open subtasks as well and `subtasks=block` refuses with 409
+/// while it has open subtasks. A task blocked by open tasks can not be completed (409).
 #[utoipa::path(
         put,
         path = "/tasks/{id}",
         request_body = UpdateTask,
         responses(
-            (status = 200, description = "Task updated successfully"),
-            (status = 404, description = "Task was not found"),
+            (status = 200, description = "Task updated successfully", body = UpdateTask),
+            (status = 404, description = "Task was not found", body = ErrorResponse),
+            (status = 409, description = "Task has open subtasks (subtasks=block) or open blockers, or its workflow allows no change to the state", body = ErrorResponse),
+            (status = 422, description = "Invalid task (see `POST /tasks`), unknown state or a field the transition requires is missing", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse),
         ),
         params(
-            ("id" = i64, Path, description = "Task database id")
+            ("id" = i64, Path, description = "Task database id"),
+            UpdateOptions
         ),
         security(
             (), // <-- make optional authentication
@@ -139,22 +170,74 @@ pub async fn task(
     )]
 pub async fn update_task(
     Path(id): Path<i64>,
-    Json(task): Json<task::UpdateTask>,
-    Extension(pool): Extension<SqlitePool>,
+    Query(options): Query<UpdateOptions>,
+    accepted: Accepted,
+    Negotiated(task): Negotiated<task::UpdateTask>,
+    Extension(repo): Extension<DynTaskRepository>,
 ) -> impl IntoResponse {
-    match sqlx::query("UPDATE task SET task=$1 WHERE id=$2")
-        .bind(&task.task)
-        .bind(id)
-        .execute(&pool)
-        .await
-    {
-        Ok(queryresult) => match queryresult.rows_affected() {
-            1 => (StatusCode::OK, Json(task)),
-            _ => (StatusCode::NOT_FOUND, Json(task)),
-        },
-        Err(e) => {
-            tracing::error!("could not find task with id: {:?} error: {:?}", id, e);
-            (StatusCode::NOT_FOUND, Json(task))
+    if let Err(msg) = task.validate() {
+        return unprocessable(msg);
+    }
+    match repo.update_task(id, &task, options.subtasks).await {
+        Ok(_) => (StatusCode::OK, accepted.encode(task)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not update task");
+            error_response(err)
+        }
+    }
+}
+
+/// Update some fields of a Task by id
+///
+/// Update the fields of task id given as JSON merge patch (RFC 7396): null clears a field, left
+/// out fields stay as they are. Afterwards like `PUT /tasks/{id}`: a task with a workflow
+/// changes its state along the transitions only, without `state` to the one its status leads to.
+#[utoipa::path(
+        patch,
+        path = "/tasks/{id}",
+        request_body(content = UpdateTask, description = "the fields to change, all optional"),
+        responses(
+            (status = 200, description = "Task updated successfully", body = UpdateTask),
+            (status = 404, description = "Task was not found", body = ErrorResponse),
+            (status = 409, description = "Task has open subtasks (subtasks=block) or open blockers, or its workflow allows no change to the state", body = ErrorResponse),
+            (status = 422, description = "Invalid patch (not an object or an unknown field) or task (see `POST /tasks`), unknown state or a field the transition requires is missing", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse),
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id"),
+            UpdateOptions
+        ),
+        security(
+            (), // <-- make optional authentication
+            ("api_key" = [])
+        )
+    )]
+pub async fn patch_task(
+    Path(id): Path<i64>,
+    Query(options): Query<UpdateOptions>,
+    accepted: Accepted,
+    Negotiated(patch): Negotiated<serde_json::Value>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    let task = match repo.task(id).await {
+        Ok(task) => task::UpdateTask::from(&task),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not find task");
+            return error_response(err);
+        }
+    };
+    let task = match task.patch(&patch) {
+        Ok(task) => task,
+        Err(msg) => return unprocessable(msg),
+    };
+    if let Err(msg) = task.validate() {
+        return unprocessable(msg);
+    }
+    match repo.update_task(id, &task, options.subtasks).await {
+        Ok(_) => (StatusCode::OK, accepted.encode(task)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not update task");
+            error_response(err)
         }
     }
 }
@@ -167,7 +250,8 @@ pub async fn update_task(
         path = "/tasks/{id}",
         responses(
             (status = 200, description = "Task was deleted"),
-            (status = 404, description = "Task was not found"),
+            (status = 404, description = "Task was not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse),
               ),
         params(
             ("id" = i64, Path, description = "Task database id")
@@ -175,26 +259,421 @@ pub async fn update_task(
     )]
 pub async fn delete_task(
     Path(id): Path<i64>,
-    Extension(pool): Extension<SqlitePool>,
+    accepted: Accepted,
+    Extension(repo): Extension<DynTaskRepository>,
 ) -> impl IntoResponse {
-    match sqlx::query("DELETE FROM task WHERE id=$1")
-        .bind(id)
-        .execute(&pool)
-        .await
-    {
-        Ok(queryresult) => match queryresult.rows_affected() {
-            1 => (StatusCode::OK, Json(json!({"msg": "Task Deleted"}))),
-            _ => (
-                StatusCode::NOT_FOUND,
-                Json(json!({"msg": "task not found"})),
-            ),
+    match repo.delete_task(id).await {
+        Ok(()) => (
+            StatusCode::OK,
+            accepted.encode(json!({"msg": "Task Deleted"})),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not delete task");
+            error_response(err)
+        }
+    }
+}
+
+/// Occurrences of a Task
+///
+/// Preview the occurrences of a recurring task due between `from` (default: now) and `to`
+/// (default: a year after `from`), at most 1000. The first occurrence is the `due_at` of the task,
+/// a task without `rrule` occurs only then. The range may span at most ten years and start at
+/// most 100000 occurrences after the `due_at`.
+#[utoipa::path(
+        get,
+        path = "/tasks/{id}/occurrences",
+        responses(
+            (status = 200, description = "Occurrences in the range", body = [Occurrence]),
+            (status = 400, description = "Range is reversed, too long or starts too late", body = ErrorResponse),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 422, description = "Task has no due_at or an unsupported rrule", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id"),
+            OccurrenceRange
+        )
+    )]
+pub async fn occurrences(
+    Path(id): Path<i64>,
+    Query(range): Query<OccurrenceRange>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    let task = match repo.task(id).await {
+        Ok(task) => task,
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not find task");
+            return error_response(err);
+        }
+    };
+    let due_at = match task.due_at {
+        Some(due_at) => due_at,
+        None => return unprocessable("task has no due_at"),
+    };
+    let rule: Option<RecurrenceRule> = match task.rrule.as_deref().map(str::parse).transpose() {
+        Ok(rule) => rule,
+        Err(err) => return unprocessable(err),
+    };
+    let times: Box<dyn Iterator<Item = _>> = match &rule {
+        Some(rule) => Box::new(rule.occurrences(due_at)),
+        None => Box::new(std::iter::once(due_at)),
+    };
+    let from = range.from.unwrap_or_else(Utc::now);
+    let to = match range.to {
+        Some(to) => to,
+        None => match from.checked_add_signed(Duration::days(365)) {
+            Some(to) => to,
+            None => return bad_request("`from` is too late"),
+        },
+    };
+    if to < from {
+        return bad_request("`to` is before `from`");
+    }
+    if to - from > Duration::days(MAX_OCCURRENCE_DAYS) {
+        return bad_request(format!(
+            "the range is longer than {} days",
+            MAX_OCCURRENCE_DAYS
+        ));
+    }
+    let mut occurrences = Vec::new();
+    for (step, time) in times.enumerate() {
+        if time > to || occurrences.len() == MAX_OCCURRENCES {
+            break;
+        }
+        if step == MAX_OCCURRENCE_STEPS {
+            return bad_request(format!(
+                "the range starts more than {} occurrences after the due_at",
+                MAX_OCCURRENCE_STEPS
+            ));
+        }
+        if time >= from {
+            occurrences.push(Occurrence {
+                due_at: time,
+                remind_at: task.remind_at.map(|remind_at| remind_at + (time - due_at)),
+            });
+        }
+    }
+    (StatusCode::OK, Json(occurrences)).into_response()
+}
+
+/// Subtasks of a Task
+///
+/// List the direct subtasks of a task in the manual order
+#[utoipa::path(
+        get,
+        path = "/tasks/{id}/children",
+        responses(
+            (status = 200, description = "Subtasks of the task", body = [Task]),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id")
+        )
+    )]
+pub async fn children(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.children(id).await {
+        Ok(children) => (StatusCode::OK, Json(children)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not list subtasks");
+            error_response(err)
+        }
+    }
+}
+
+/// Task tree
+///
+/// Return a task with its subtasks at any depth
+#[utoipa::path(
+        get,
+        path = "/tasks/{id}/tree",
+        responses(
+            (status = 200, description = "Task with its subtasks", body = TaskTree),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id")
+        )
+    )]
+pub async fn tree(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.subtree(id).await {
+        Ok(mut tasks) => match tasks.iter().position(|task| task.id == id) {
+            Some(root) => {
+                let root = tasks.swap_remove(root);
+                (StatusCode::OK, Json(TaskTree::build(root, tasks))).into_response()
+            }
+            None => error_response(RepositoryError::NotFound),
         },
-        Err(e) => {
-            tracing::error!("could not find task with id: {:?} error: {:?}", id, e);
-            (
-                StatusCode::NOT_FOUND,
-                Json(json!({"msg": "task not found"})),
-            )
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not find task tree");
+            error_response(err)
+        }
+    }
+}
+
+/// Move a Task
+///
+/// Make a task with its subtasks a subtask of another task, or a top-level task with
+/// `"parent_id": null`. A task can not become a subtask of itself or of its subtasks.
+#[utoipa::path(
+        put,
+        path = "/tasks/{id}/parent",
+        request_body = MoveTask,
+        responses(
+            (status = 200, description = "Task moved", body = Task),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 409, description = "Parent is the task itself or one of its subtasks", body = ErrorResponse),
+            (status = 422, description = "Parent task not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id")
+        )
+    )]
+pub async fn move_task(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(target): Json<MoveTask>,
+) -> impl IntoResponse {
+    let moved = match repo.move_task(id, target.parent_id).await {
+        Ok(()) => repo.task(id).await,
+        Err(err) => Err(err),
+    };
+    match moved {
+        Ok(task) => (StatusCode::OK, Json(task)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not move task");
+            error_response(err)
+        }
+    }
+}
+
+/// Reorder a Task
+///
+/// Move a task right after the task `after` and/or right before the task `before` in the manual
+/// order of all task lists, e.g. after dragging it. Only the moved task is changed, its list and
+/// its subtasks stay as they are.
+#[utoipa::path(
+        post,
+        path = "/tasks/{id}/move",
+        request_body = RankAnchors,
+        responses(
+            (status = 200, description = "Task moved", body = Task),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 422, description = "Anchors missing, not found or in the wrong order", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id")
+        )
+    )]
+pub async fn rank_task(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(anchors): Json<RankAnchors>,
+) -> impl IntoResponse {
+    let moved = match repo.rank_task(id, &anchors).await {
+        Ok(()) => repo.task(id).await,
+        Err(err) => Err(err),
+    };
+    match moved {
+        Ok(task) => (StatusCode::OK, Json(task)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not reorder task");
+            error_response(err)
+        }
+    }
+}
+
+/// Blockers of a Task
+///
+/// List the tasks blocking a task ordered by id, open or not
+#[utoipa::path(
+        get,
+        path = "/tasks/{id}/blockers",
+        responses(
+            (status = 200, description = "Tasks blocking the task", body = [Task]),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id")
+        )
+    )]
+pub async fn blockers(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.blockers(id).await {
+        Ok(blockers) => (StatusCode::OK, Json(blockers)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not list blockers");
+            error_response(err)
+        }
+    }
+}
+
+/// Add a blocker to a Task
+///
+/// The task can not be completed before the blocker is completed or cancelled. Adding an existing
+/// blocker again changes nothing, a blocker which is (indirectly) blocked by the task is refused.
+#[utoipa::path(
+        post,
+        path = "/tasks/{id}/blockers",
+        request_body = NewBlocker,
+        responses(
+            (status = 201, description = "Dependency added", body = Dependency),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 409, description = "Dependency would create a cycle", body = ErrorResponse),
+            (status = 422, description = "Blocker task not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id")
+        )
+    )]
+pub async fn add_blocker(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(blocker): Json<NewBlocker>,
+) -> impl IntoResponse {
+    match repo.add_dependency(id, blocker.blocker_id).await {
+        Ok(()) => (
+            StatusCode::CREATED,
+            Json(Dependency {
+                task_id: id,
+                blocker_id: blocker.blocker_id,
+            }),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not add blocker");
+            error_response(err)
+        }
+    }
+}
+
+/// Remove a blocker from a Task
+#[utoipa::path(
+        delete,
+        path = "/tasks/{id}/blockers/{blocker_id}",
+        responses(
+            (status = 200, description = "Dependency removed"),
+            (status = 404, description = "Task is not blocked by the blocker", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id"),
+            ("blocker_id" = i64, Path, description = "Database id of the blocking task")
+        )
+    )]
+pub async fn remove_blocker(
+    Path(ids): Path<(i64, i64)>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    // a tuple pattern in the arguments is not understood by utoipa
+    let (id, blocker_id) = ids;
+    match repo.remove_dependency(id, blocker_id).await {
+        Ok(()) => (StatusCode::OK, Json(json!({"msg": "Blocker Removed"}))).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not remove blocker");
+            error_response(err)
+        }
+    }
+}
+
+/// Tasks in dependency order
+///
+/// List all tasks so that every task comes after the tasks blocking it, otherwise ordered by id
+#[utoipa::path(
+        get,
+        path = "/tasks/order",
+        responses(
+            (status = 200, description = "All tasks in topological order", body = [Task]),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        )
+    )]
+pub async fn task_order(Extension(repo): Extension<DynTaskRepository>) -> impl IntoResponse {
+    let ordered = match (repo.all_tasks().await, repo.dependencies().await) {
+        (Ok(tasks), Ok(dependencies)) => dependencies::topological_order(tasks, &dependencies),
+        (Err(err), _) | (_, Err(err)) => Err(err),
+    };
+    match ordered {
+        Ok(tasks) => (StatusCode::OK, Json(tasks)).into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not order tasks");
+            error_response(err)
+        }
+    }
+}
+
+fn bad_request(msg: impl Into<String>) -> Response {
+    (StatusCode::BAD_REQUEST, Json(ErrorResponse::new(msg))).into_response()
+}
+
+pub(crate) fn unprocessable(msg: impl Into<String>) -> Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(ErrorResponse::new(msg)),
+    )
+        .into_response()
+}
+
+/// NotFound is answered with 404, Unsupported with 501, Conflict and Transition with 409 (the
+/// latter with the allowed next states), PreconditionFailed with 412, Invalid with 422, all
+/// other repository errors with 500
+pub(crate) fn error_response(err: RepositoryError) -> Response {
+    match err {
+        RepositoryError::NotFound => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new("task not found")),
+        )
+            .into_response(),
+        RepositoryError::Unsupported(_) => (
+            StatusCode::NOT_IMPLEMENTED,
+            Json(ErrorResponse::new(err.to_string())),
+        )
+            .into_response(),
+        RepositoryError::Conflict(msg) => {
+            (StatusCode::CONFLICT, Json(ErrorResponse::new(msg))).into_response()
+        }
+        RepositoryError::Transition { ref allowed, .. } => {
+            let allowed_states = Some(allowed.clone());
+            let body = ErrorResponse {
+                allowed_states,
+                ..ErrorResponse::new(err.to_string())
+            };
+            (StatusCode::CONFLICT, Json(body)).into_response()
+        }
+        RepositoryError::PreconditionFailed => (
+            StatusCode::PRECONDITION_FAILED,
+            Json(ErrorResponse::new(err.to_string())),
+        )
+            .into_response(),
+        RepositoryError::Invalid(msg) => unprocessable(msg),
+        RepositoryError::Database(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("database error")),
+        )
+            .into_response(),
+    }
+}
+
+/// Like [`error_response`], with `not_found` as message of a 404
+pub(crate) fn not_found_response(err: RepositoryError, not_found: &str) -> Response {
+    match err {
+        RepositoryError::NotFound => {
+            (StatusCode::NOT_FOUND, Json(ErrorResponse::new(not_found))).into_response()
         }
+        err => error_response(err),
     }
 }
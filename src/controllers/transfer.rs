@@ -0,0 +1,192 @@
+//! Export and import of tasks in other file formats, see [`crate::formats`].
+
+use axum::body::{Bytes, StreamBody};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{BoxError, Extension, Json};
+use chrono::Utc;
+use futures::{future, stream, StreamExt, TryStreamExt};
+
+use super::task::error_response;
+use crate::formats::{self, csv, ical, markdown, todotxt, ImportRow};
+use crate::models::error::ErrorResponse;
+use crate::models::task::TaskFilter;
+use crate::repository::DynTaskRepository;
+
+pub(crate) const NDJSON: &str = "application/x-ndjson";
+
+/// Export tasks as CSV
+///
+/// Stream all tasks with every column as CSV with a header row
+#[utoipa::path(
+        get,
+        path = "/tasks/export.csv",
+        responses(
+            (status = 200, description = "All tasks as CSV", body = String, content_type = "text/csv"),
+        )
+    )]
+pub async fn export_csv(Extension(repo): Extension<DynTaskRepository>) -> impl IntoResponse {
+    let rows = repo
+        .task_stream()
+        .map_ok(|task| csv::task_row(&task))
+        // the status is already sent, all we can do is to log and abort the response
+        .inspect_err(|err| tracing::error!(error = %err, "could not export tasks"));
+    let body = StreamBody::new(stream::once(async { Ok(csv::header()) }).chain(rows));
+    (
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (CONTENT_DISPOSITION, "attachment; filename=\"tasks.csv\""),
+        ],
+        body,
+    )
+}
+
+/// Export tasks as iCalendar
+///
+/// Stream all tasks as VTODO components of one calendar, e.g. to subscribe to in calendar apps
+#[utoipa::path(
+        get,
+        path = "/tasks/export.ics",
+        responses(
+            (status = 200, description = "All tasks as iCalendar", body = String, content_type = "text/calendar"),
+        )
+    )]
+pub async fn export_ics(Extension(repo): Extension<DynTaskRepository>) -> impl IntoResponse {
+    let dtstamp = Utc::now();
+    let todos = repo
+        .task_stream()
+        .map_ok(move |task| ical::vtodo(&task, dtstamp))
+        .inspect_err(|err| tracing::error!(error = %err, "could not export tasks"));
+    let body = StreamBody::new(
+        stream::once(async { Ok(ical::calendar_begin()) })
+            .chain(todos)
+            .chain(stream::once(async { Ok(ical::calendar_end()) })),
+    );
+    (
+        [
+            (CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (CONTENT_DISPOSITION, "attachment; filename=\"tasks.ics\""),
+        ],
+        body,
+    )
+}
+
+/// Export tasks as todo.txt
+///
+/// Stream all tasks in the todo.txt format, one task per line
+#[utoipa::path(
+        get,
+        path = "/tasks/export.txt",
+        responses(
+            (status = 200, description = "All tasks as todo.txt", body = String, content_type = "text/plain"),
+        )
+    )]
+pub async fn export_todo_txt(Extension(repo): Extension<DynTaskRepository>) -> impl IntoResponse {
+    let lines = repo
+        .task_stream()
+        .map_ok(|task| todotxt::task_line(&task))
+        .inspect_err(|err| tracing::error!(error = %err, "could not export tasks"));
+    (
+        [
+            (CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (CONTENT_DISPOSITION, "attachment; filename=\"todo.txt\""),
+        ],
+        StreamBody::new(lines),
+    )
+}
+
+/// Export tasks as NDJSON
+///
+/// Stream all tasks as newline delimited JSON, one task object per line
+#[utoipa::path(
+        get,
+        path = "/tasks/export.ndjson",
+        responses(
+            (status = 200, description = "All tasks as NDJSON", body = [Task], content_type = "application/x-ndjson"),
+        )
+    )]
+pub async fn export_ndjson(Extension(repo): Extension<DynTaskRepository>) -> impl IntoResponse {
+    ndjson_response(&repo, TaskFilter::default())
+}
+
+/** The tasks matching `filter` as NDJSON, fetched row by row while the body is sent: hyper only polls the body
+  when the client takes more data, so memory stays constant, and drops it (with the query and
+  its connection) when the client disconnects.
+*/
+pub(crate) fn ndjson_response(repo: &DynTaskRepository, filter: TaskFilter) -> Response {
+    let now = Utc::now();
+    let lines = repo
+        .task_stream()
+        .try_filter(move |task| future::ready(filter.matches_at(task, now)))
+        .map(|task| -> Result<Vec<u8>, BoxError> {
+            let mut line = serde_json::to_vec(&task?)?;
+            line.push(b'\n');
+            Ok(line)
+        })
+        .inspect_err(|err| tracing::error!(error = %err, "could not export tasks"));
+    ([(CONTENT_TYPE, NDJSON)], StreamBody::new(lines)).into_response()
+}
+
+/// Import tasks from CSV, iCalendar, todo.txt or Markdown
+///
+/// Create a task for every valid row of a CSV file with a header row containing a `task` column,
+/// for every VTODO of an iCalendar file (`Content-Type: text/calendar`), for every line of a
+/// todo.txt file (`Content-Type: text/plain`) or for every `- [ ]` item of a Markdown checklist
+/// (`Content-Type: text/markdown`, headings become categories). VTODOs with the UID of a stored task update that
+/// task. Valid rows are stored in one transaction, invalid rows are reported with their line number.
+#[utoipa::path(
+        post,
+        path = "/tasks/import",
+        request_body(content = String, description = "CSV with header row, iCalendar, todo.txt or Markdown checklist", content_type = "text/csv"),
+        responses(
+            (status = 200, description = "Valid rows imported, report of accepted and rejected rows", body = ImportReport),
+            (status = 400, description = "Not a CSV file with task column or not a calendar", body = ErrorResponse),
+            (status = 500, description = "Tasks could not be stored, nothing was imported", body = ErrorResponse),
+        )
+    )]
+pub async fn import(
+    Extension(repo): Extension<DynTaskRepository>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .unwrap_or_default();
+    // CSV is the default for clients which do not send a content type
+    let rows = if content_type.starts_with("text/calendar") {
+        ical::parse_vtodos(&body).map_err(|err| err.to_string())
+    } else if content_type.starts_with("text/markdown") {
+        markdown::parse_checklist(&body).map_err(|err| err.to_string())
+    } else if content_type.starts_with("text/plain") {
+        todotxt::parse_tasks(&body).map_err(|err| err.to_string())
+    } else {
+        csv::parse_tasks(&body).map_err(|err| err.to_string())
+    };
+    match rows {
+        Ok(rows) => import_rows(&repo, rows).await,
+        Err(err) => {
+            tracing::info!(error = %err, content_type, "rejected import");
+            (StatusCode::BAD_REQUEST, Json(ErrorResponse::new(err))).into_response()
+        }
+    }
+}
+
+/// Store the valid rows in one transaction and report which rows were accepted or rejected
+async fn import_rows(repo: &DynTaskRepository, rows: Vec<ImportRow>) -> Response {
+    match formats::import_rows(repo.as_ref(), rows).await {
+        Ok(report) => {
+            tracing::info!(
+                accepted = report.accepted.len(),
+                rejected = report.rejected.len(),
+                "imported tasks"
+            );
+            (StatusCode::OK, Json(report)).into_response()
+        }
+        Err(err) => {
+            tracing::error!(error = %err, "could not import tasks");
+            error_response(err)
+        }
+    }
+}
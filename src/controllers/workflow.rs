@@ -0,0 +1,191 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use serde_json::json;
+
+use super::task::{error_response, not_found_response, unprocessable};
+use crate::models::workflow::{NewWorkflow, TaskWorkflow};
+use crate::repository::DynTaskRepository;
+
+/// List all Workflows
+///
+/// List all workflows with their states and transitions ordered by id
+#[utoipa::path(
+        get,
+        path = "/admin/workflows",
+        responses(
+            (status = 200, description = "All workflows", body = [Workflow]),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        )
+    )]
+pub async fn all_workflows(Extension(repo): Extension<DynTaskRepository>) -> impl IntoResponse {
+    match repo.workflows().await {
+        Ok(workflows) => (StatusCode::OK, Json(workflows)).into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not list workflows");
+            error_response(err)
+        }
+    }
+}
+
+/// Create new Workflow
+///
+/// Create a workflow with its states and the allowed transitions between them
+#[utoipa::path(
+        post,
+        path = "/admin/workflows",
+        request_body = NewWorkflow,
+        responses(
+            (status = 201, description = "Workflow created successfully", body = Workflow),
+            (status = 422, description = "Invalid workflow", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        )
+    )]
+pub async fn new_workflow(
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(workflow): Json<NewWorkflow>,
+) -> impl IntoResponse {
+    if let Err(msg) = workflow.validate() {
+        return unprocessable(msg);
+    }
+    match repo.create_workflow(&workflow).await {
+        Ok(workflow) => (
+            StatusCode::CREATED,
+            [("Location", format!("/admin/workflows/{}", workflow.id))],
+            Json(workflow),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "could not create workflow");
+            error_response(err)
+        }
+    }
+}
+
+/// Get Workflow by id
+#[utoipa::path(
+        get,
+        path = "/admin/workflows/{id}",
+        responses(
+            (status = 200, description = "Workflow found", body = Workflow),
+            (status = 404, description = "Workflow not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Workflow database id")
+        )
+    )]
+pub async fn workflow(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.workflow(id).await {
+        Ok(workflow) => (StatusCode::OK, Json(workflow)).into_response(),
+        Err(err) => {
+            tracing::error!(workflow_id = id, error = %err, "could not find workflow");
+            not_found_response(err, "workflow not found")
+        }
+    }
+}
+
+/// Replace Workflow by id
+///
+/// Replace name, states and transitions of a workflow. The tasks in a state get its new status,
+/// a state tasks are in must stay.
+#[utoipa::path(
+        put,
+        path = "/admin/workflows/{id}",
+        request_body = NewWorkflow,
+        responses(
+            (status = 200, description = "Workflow replaced", body = Workflow),
+            (status = 404, description = "Workflow not found", body = ErrorResponse),
+            (status = 409, description = "A left out state has tasks", body = ErrorResponse),
+            (status = 422, description = "Invalid workflow", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Workflow database id")
+        )
+    )]
+pub async fn update_workflow(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(workflow): Json<NewWorkflow>,
+) -> impl IntoResponse {
+    if let Err(msg) = workflow.validate() {
+        return unprocessable(msg);
+    }
+    match repo.update_workflow(id, &workflow).await {
+        Ok(workflow) => (StatusCode::OK, Json(workflow)).into_response(),
+        Err(err) => {
+            tracing::error!(workflow_id = id, error = %err, "could not update workflow");
+            not_found_response(err, "workflow not found")
+        }
+    }
+}
+
+/// Delete Workflow by id
+///
+/// Delete a workflow no task follows
+#[utoipa::path(
+        delete,
+        path = "/admin/workflows/{id}",
+        responses(
+            (status = 200, description = "Workflow was deleted"),
+            (status = 404, description = "Workflow not found", body = ErrorResponse),
+            (status = 409, description = "Tasks follow the workflow", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Workflow database id")
+        )
+    )]
+pub async fn delete_workflow(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+) -> impl IntoResponse {
+    match repo.delete_workflow(id).await {
+        Ok(()) => (StatusCode::OK, Json(json!({"msg": "Workflow Deleted"}))).into_response(),
+        Err(err) => {
+            tracing::error!(workflow_id = id, error = %err, "could not delete workflow");
+            not_found_response(err, "workflow not found")
+        }
+    }
+}
+
+/// Set the Workflow of a Task
+///
+/// Let a task follow a workflow, starting in its first state, or with a null `workflow_id` no
+/// workflow anymore. Returns the task afterwards.
+#[utoipa::path(
+        put,
+        path = "/tasks/{id}/workflow",
+        request_body = TaskWorkflow,
+        responses(
+            (status = 200, description = "Workflow of the task set", body = Task),
+            (status = 404, description = "Task not found", body = ErrorResponse),
+            (status = 422, description = "Workflow not found", body = ErrorResponse),
+            (status = 500, description = "Database error", body = ErrorResponse)
+        ),
+        params(
+            ("id" = i64, Path, description = "Task database id")
+        )
+    )]
+pub async fn set_task_workflow(
+    Path(id): Path<i64>,
+    Extension(repo): Extension<DynTaskRepository>,
+    Json(body): Json<TaskWorkflow>,
+) -> impl IntoResponse {
+    let result = match repo.set_workflow(id, body.workflow_id).await {
+        Ok(()) => repo.task(id).await,
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(task) => (StatusCode::OK, Json(task)).into_response(),
+        Err(err) => {
+            tracing::error!(task_id = id, error = %err, "could not set workflow of task");
+            error_response(err)
+        }
+    }
+}
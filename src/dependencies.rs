@@ -0,0 +1,57 @@
+//! Dependencies between tasks: a task is blocked by other tasks until these are completed or
+//! cancelled, like the "blocked by" relations of a release checklist.
+//!
+//! The repositories refuse dependencies which would close a cycle, so the tasks always have a
+//! topological order (`GET /tasks/order`): every task after the tasks blocking it. A task can
+//! not be completed while one of its blockers is open, the repositories check that in the
+//! completing transaction.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+
+use crate::models::task::{Dependency, Task};
+use crate::repository::{self, RepositoryError};
+
+/** `tasks` ordered so that every task comes after its blockers, otherwise by id.
+  Dependencies of tasks not in `tasks` are ignored. A Conflict if the dependencies form a cycle.
+*/
+pub fn topological_order(
+    tasks: Vec<Task>,
+    dependencies: &[Dependency],
+) -> repository::Result<Vec<Task>> {
+    let mut tasks: BTreeMap<i64, Task> = tasks.into_iter().map(|task| (task.id, task)).collect();
+    let mut waiting: HashMap<i64, usize> = HashMap::new();
+    let mut blocked: HashMap<i64, Vec<i64>> = HashMap::new();
+    for dependency in dependencies {
+        if tasks.contains_key(&dependency.task_id) && tasks.contains_key(&dependency.blocker_id) {
+            *waiting.entry(dependency.task_id).or_default() += 1;
+            blocked
+                .entry(dependency.blocker_id)
+                .or_default()
+                .push(dependency.task_id);
+        }
+    }
+    // Kahn's algorithm, the heap hands out the lowest id of the unblocked tasks
+    let mut ready: BinaryHeap<Reverse<i64>> = tasks
+        .keys()
+        .filter(|id| !waiting.contains_key(id))
+        .map(|id| Reverse(*id))
+        .collect();
+    let mut order = Vec::with_capacity(tasks.len());
+    while let Some(Reverse(id)) = ready.pop() {
+        order.extend(tasks.remove(&id));
+        for task_id in blocked.remove(&id).unwrap_or_default() {
+            let blockers = waiting.entry(task_id).or_default();
+            *blockers -= 1;
+            if *blockers == 0 {
+                ready.push(Reverse(task_id));
+            }
+        }
+    }
+    match tasks.is_empty() {
+        true => Ok(order),
+        false => Err(RepositoryError::Conflict(
+            "the dependencies form a cycle".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,68 @@
+//! Conversion of tasks from and to file formats of other tools (spreadsheets, calendars, ...).
+
+use crate::models::import::{AcceptedRow, ImportReport, RejectedRow};
+use crate::models::task::NewTask;
+use crate::repository::{self, TaskRepository};
+
+pub mod csv;
+pub mod dav;
+pub mod ical;
+pub mod markdown;
+pub mod todotxt;
+
+/// Row of an imported file: the task or the reason it was rejected
+pub struct ImportRow {
+    /// line in the file, starting at 1
+    pub line: u64,
+    pub task: Result<NewTask, String>,
+    /// line of the row of the parent task, e.g. of a nested checklist item
+    pub parent: Option<u64>,
+}
+
+/** Store the valid rows in one transaction and report which rows were accepted or rejected,
+  also by the repository, e.g. a completed task blocked by an open task
+*/
+pub async fn import_rows(
+    repo: &dyn TaskRepository,
+    rows: Vec<ImportRow>,
+) -> repository::Result<ImportReport> {
+    let mut valid: Vec<(u64, NewTask)> = Vec::new();
+    let mut rejected = Vec::new();
+    for row in rows {
+        // the parent is referred to by its index in the imported tasks
+        let parent = match row.parent {
+            Some(parent) => match valid.iter().position(|(line, _)| *line == parent) {
+                Some(index) => Some(index),
+                None => {
+                    rejected.push(RejectedRow {
+                        line: row.line,
+                        reason: format!("the parent on line {} is rejected", parent),
+                    });
+                    continue;
+                }
+            },
+            None => None,
+        };
+        match row.task {
+            Ok(task) => valid.push((row.line, NewTask { parent, ..task })),
+            Err(reason) => rejected.push(RejectedRow {
+                line: row.line,
+                reason,
+            }),
+        }
+    }
+    let (lines, tasks): (Vec<u64>, Vec<NewTask>) = valid.into_iter().unzip();
+
+    let mut accepted = Vec::new();
+    for (line, stored) in lines.into_iter().zip(repo.import_tasks(&tasks).await?) {
+        match stored {
+            Ok(task) => accepted.push(AcceptedRow { line, task }),
+            Err(err) => rejected.push(RejectedRow {
+                line,
+                reason: err.to_string(),
+            }),
+        }
+    }
+    rejected.sort_by_key(|row| row.line);
+    Ok(ImportReport { accepted, rejected })
+}
@@ -0,0 +1,192 @@
+//! CSV with a header row, as read and written by spreadsheets.
+//!
+//! Export writes every column of a task, empty fields for unset values, the times `due_at` and
+//! `remind_at` in RFC 3339 and
+//! the days `created_on` and `completed_on` as `YYYY-MM-DD`.
+//! Import maps header names (case-insensitive) to the fields of [`NewTask`] and ignores other
+//! columns like `id`, so an export can be imported again.
+
+use ::csv::{ReaderBuilder, StringRecord, Trim, WriterBuilder};
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::ImportRow;
+use crate::models::task::{NewTask, Task};
+
+/// Columns written by [`task_row`], in order
+pub const COLUMNS: [&str; 10] = [
+    "id",
+    "task",
+    "status",
+    "due_at",
+    "priority",
+    "categories",
+    "rrule",
+    "created_on",
+    "completed_on",
+    // added later, at the end so older readers of the export keep working
+    "remind_at",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum CsvError {
+    #[error("header row has no \"task\" column")]
+    MissingTaskColumn,
+    #[error("invalid header row: {0}")]
+    Header(::csv::Error),
+}
+
+/// Header row including the line break
+pub fn header() -> String {
+    row(&COLUMNS)
+}
+
+/// Row of `task` including the line break, quoted where needed
+pub fn task_row(task: &Task) -> String {
+    row(&[
+        &task.id.to_string(),
+        &task.task,
+        task.status.map_or("", |status| status.as_str()),
+        &task
+            .due_at
+            .map(|due_at| due_at.to_rfc3339())
+            .unwrap_or_default(),
+        &task
+            .priority
+            .map(|priority| priority.to_string())
+            .unwrap_or_default(),
+        task.categories.as_deref().unwrap_or_default(),
+        task.rrule.as_deref().unwrap_or_default(),
+        &format_date(task.created_on),
+        &format_date(task.completed_on),
+        &task
+            .remind_at
+            .map(|remind_at| remind_at.to_rfc3339())
+            .unwrap_or_default(),
+    ])
+}
+
+fn row(fields: &[&str]) -> String {
+    let mut writer = WriterBuilder::new().from_writer(Vec::new());
+    writer
+        .write_record(fields)
+        .expect("writing to a Vec can not fail");
+    let bytes = writer.into_inner().expect("writing to a Vec can not fail");
+    String::from_utf8(bytes).expect("csv of strings is utf-8")
+}
+
+/** Parse `data` into one [`ImportRow`] per record, rows which can not be
+  read or fail validation are returned as rejected. Only a broken header row fails the whole import.
+*/
+pub fn parse_tasks(data: &[u8]) -> Result<Vec<ImportRow>, CsvError> {
+    let mut reader = ReaderBuilder::new()
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(data);
+    let headers = reader.headers().map_err(CsvError::Header)?.clone();
+    // position in the record of every known column
+    let positions: Vec<(usize, &str)> = headers
+        .iter()
+        .enumerate()
+        .filter_map(|(position, header)| {
+            COLUMNS
+                .iter()
+                .find(|column| header.eq_ignore_ascii_case(column))
+                .map(|column| (position, *column))
+        })
+        .collect();
+    if !positions.iter().any(|(_, column)| *column == "task") {
+        return Err(CsvError::MissingTaskColumn);
+    }
+
+    let mut rows = Vec::new();
+    let mut record = StringRecord::new();
+    loop {
+        let line = reader.position().line();
+        match reader.read_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {
+                let line = record.position().map_or(line, |position| position.line());
+                rows.push(ImportRow {
+                    line,
+                    task: to_task(&record, headers.len(), &positions),
+                    parent: None,
+                });
+            }
+            Err(err) => {
+                let line = err.position().map_or(line, |position| position.line());
+                if !matches!(err.kind(), ::csv::ErrorKind::Utf8 { .. }) {
+                    // the reader can not recover from other (io) errors
+                    rows.push(ImportRow {
+                        line,
+                        task: Err(err.to_string()),
+                        parent: None,
+                    });
+                    break;
+                }
+                rows.push(ImportRow {
+                    line,
+                    task: Err("invalid UTF-8".to_string()),
+                    parent: None,
+                });
+            }
+        }
+    }
+    Ok(rows)
+}
+
+fn to_task(
+    record: &StringRecord,
+    columns: usize,
+    positions: &[(usize, &str)],
+) -> Result<NewTask, String> {
+    if record.len() != columns {
+        return Err(format!(
+            "expected {} fields but found {}",
+            columns,
+            record.len()
+        ));
+    }
+    let mut task = NewTask::default();
+    for (position, column) in positions {
+        let value = &record[*position];
+        if value.is_empty() {
+            continue;
+        }
+        match *column {
+            "task" => task.task = value.to_string(),
+            "status" => task.status = Some(value.parse()?),
+            "due_at" => task.due_at = Some(parse_time(column, value)?),
+            "remind_at" => task.remind_at = Some(parse_time(column, value)?),
+            "priority" => {
+                task.priority = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid priority {:?}", value))?,
+                )
+            }
+            "categories" => task.categories = Some(value.to_string()),
+            "rrule" => task.rrule = Some(value.to_string()),
+            "created_on" => task.created_on = Some(parse_date(column, value)?),
+            "completed_on" => task.completed_on = Some(parse_date(column, value)?),
+            _ => {}
+        }
+    }
+    task.validate()?;
+    Ok(task)
+}
+
+fn parse_time(column: &str, value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| format!("invalid {} {:?}, expected RFC 3339", column, value))
+}
+
+fn parse_date(column: &str, value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("invalid {} {:?}, expected YYYY-MM-DD", column, value))
+}
+
+fn format_date(date: Option<NaiveDate>) -> String {
+    date.map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,314 @@
+//! WebDAV (RFC 4918) and CalDAV (RFC 4791) XML bodies: the PROPFIND and REPORT requests sent by
+//! calendar apps and the 207 Multi-Status responses answering them.
+//!
+//! Responses declare the prefixes `d:` (DAV), `c:` (CalDAV) and `cs:` (calendarserver) on the
+//! root element, so property values can be written as XML using them.
+
+use roxmltree::{Document, Node};
+
+pub const DAV: &str = "DAV:";
+pub const CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+/// namespace of `getctag`, which older clients poll instead of using sync tokens
+pub const CALENDARSERVER: &str = "http://calendarserver.org/ns/";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DavError {
+    #[error("request body is not valid UTF-8")]
+    Utf8,
+    #[error("request body is not valid XML: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("unsupported request element {0:?}")]
+    Unsupported(String),
+}
+
+/// Name of a property, qualified by its namespace
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PropName {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl PropName {
+    pub fn new(namespace: &str, name: &str) -> PropName {
+        PropName {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    fn is(&self, namespace: &str, name: &str) -> bool {
+        self.namespace == namespace && self.name == name
+    }
+}
+
+/// Property with its value as XML (escaped already, see [`escape`])
+pub struct Prop {
+    pub name: PropName,
+    pub value: String,
+}
+
+impl Prop {
+    pub fn new(namespace: &str, name: &str, value: impl Into<String>) -> Prop {
+        Prop {
+            name: PropName::new(namespace, name),
+            value: value.into(),
+        }
+    }
+}
+
+/// Properties asked for by a PROPFIND or REPORT
+#[derive(Debug, PartialEq, Eq)]
+pub enum PropRequest {
+    /// `allprop`, `propname` or an empty PROPFIND body, answered with all properties
+    All,
+    Names(Vec<PropName>),
+}
+
+impl PropRequest {
+    /// Whether the property is asked for by name, for properties which are not part of `allprop`
+    pub fn wants(&self, namespace: &str, name: &str) -> bool {
+        match self {
+            PropRequest::All => false,
+            PropRequest::Names(names) => names.iter().any(|prop| prop.is(namespace, name)),
+        }
+    }
+
+    /// The requested properties out of `available` and the names of the requested but unknown ones
+    pub fn select(&self, available: Vec<Prop>) -> (Vec<Prop>, Vec<PropName>) {
+        match self {
+            PropRequest::All => (available, Vec::new()),
+            PropRequest::Names(names) => {
+                let missing = names
+                    .iter()
+                    .filter(|name| !available.iter().any(|prop| &prop.name == *name))
+                    .cloned()
+                    .collect();
+                let found = available
+                    .into_iter()
+                    .filter(|prop| names.contains(&prop.name))
+                    .collect();
+                (found, missing)
+            }
+        }
+    }
+}
+
+/// REPORT requests understood by the calendar collection
+#[derive(Debug, PartialEq, Eq)]
+pub enum Report {
+    /// `component` names the component filter inside VCALENDAR, if there is one.
+    /// Finer filters (time ranges, properties) are not evaluated.
+    CalendarQuery {
+        props: PropRequest,
+        component: Option<String>,
+    },
+    CalendarMultiget {
+        props: PropRequest,
+        hrefs: Vec<String>,
+    },
+    /// `sync_token` is None for the initial sync
+    SyncCollection {
+        props: PropRequest,
+        sync_token: Option<String>,
+    },
+}
+
+pub fn parse_propfind(body: &[u8]) -> Result<PropRequest, DavError> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(PropRequest::All);
+    }
+    let body = std::str::from_utf8(body).map_err(|_| DavError::Utf8)?;
+    let document = Document::parse(body)?;
+    let root = document.root_element();
+    if !is(root, DAV, "propfind") {
+        return Err(unsupported(root));
+    }
+    Ok(prop_request(root))
+}
+
+pub fn parse_report(body: &[u8]) -> Result<Report, DavError> {
+    let body = std::str::from_utf8(body).map_err(|_| DavError::Utf8)?;
+    let document = Document::parse(body)?;
+    let root = document.root_element();
+    let props = prop_request(root);
+    if is(root, CALDAV, "calendar-query") {
+        let component = child(root, CALDAV, "filter")
+            .and_then(|filter| child(filter, CALDAV, "comp-filter"))
+            .and_then(|calendar| child(calendar, CALDAV, "comp-filter"))
+            .and_then(|component| component.attribute("name"))
+            .map(str::to_string);
+        Ok(Report::CalendarQuery { props, component })
+    } else if is(root, CALDAV, "calendar-multiget") {
+        let hrefs = root
+            .children()
+            .filter(|node| is(*node, DAV, "href"))
+            .map(text)
+            .collect();
+        Ok(Report::CalendarMultiget { props, hrefs })
+    } else if is(root, DAV, "sync-collection") {
+        let sync_token = child(root, DAV, "sync-token")
+            .map(text)
+            .filter(|token| !token.is_empty());
+        Ok(Report::SyncCollection { props, sync_token })
+    } else {
+        Err(unsupported(root))
+    }
+}
+
+fn prop_request(parent: Node) -> PropRequest {
+    match child(parent, DAV, "prop") {
+        Some(prop) => PropRequest::Names(
+            prop.children()
+                .filter(Node::is_element)
+                .map(|node| {
+                    PropName::new(
+                        node.tag_name().namespace().unwrap_or_default(),
+                        node.tag_name().name(),
+                    )
+                })
+                .collect(),
+        ),
+        None => PropRequest::All,
+    }
+}
+
+fn is(node: Node, namespace: &str, name: &str) -> bool {
+    node.is_element()
+        && node.tag_name().namespace() == Some(namespace)
+        && node.tag_name().name() == name
+}
+
+fn child<'a, 'input>(
+    node: Node<'a, 'input>,
+    namespace: &str,
+    name: &str,
+) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| is(*child, namespace, name))
+}
+
+fn text(node: Node) -> String {
+    node.text().unwrap_or_default().trim().to_string()
+}
+
+fn unsupported(node: Node) -> DavError {
+    DavError::Unsupported(node.tag_name().name().to_string())
+}
+
+/// Body of a 207 Multi-Status response, one `response` per resource
+pub struct Multistatus {
+    xml: String,
+}
+
+impl Default for Multistatus {
+    fn default() -> Multistatus {
+        Multistatus::new()
+    }
+}
+
+impl Multistatus {
+    pub fn new() -> Multistatus {
+        Multistatus {
+            xml: format!(
+                r#"<?xml version="1.0" encoding="utf-8"?><d:multistatus xmlns:d="{}" xmlns:c="{}" xmlns:cs="{}">"#,
+                DAV, CALDAV, CALENDARSERVER
+            ),
+        }
+    }
+
+    /// Response for `href` with the found properties and the missing ones (status 404)
+    pub fn response(&mut self, href: &str, found: &[Prop], missing: &[PropName]) {
+        self.xml.push_str("<d:response>");
+        self.href(href);
+        if !found.is_empty() {
+            self.xml.push_str("<d:propstat><d:prop>");
+            for prop in found {
+                self.element(&prop.name, &prop.value);
+            }
+            self.xml.push_str("</d:prop>");
+            self.status("200 OK");
+            self.xml.push_str("</d:propstat>");
+        }
+        if !missing.is_empty() {
+            self.xml.push_str("<d:propstat><d:prop>");
+            for name in missing {
+                self.element(name, "");
+            }
+            self.xml.push_str("</d:prop>");
+            self.status("404 Not Found");
+            self.xml.push_str("</d:propstat>");
+        }
+        self.xml.push_str("</d:response>");
+    }
+
+    /// Response for a resource which does not exist (anymore)
+    pub fn not_found(&mut self, href: &str) {
+        self.xml.push_str("<d:response>");
+        self.href(href);
+        self.status("404 Not Found");
+        self.xml.push_str("</d:response>");
+    }
+
+    /// The new sync token of a sync-collection report, after all responses
+    pub fn sync_token(&mut self, token: &str) {
+        self.xml
+            .push_str(&format!("<d:sync-token>{}</d:sync-token>", escape(token)));
+    }
+
+    pub fn finish(mut self) -> String {
+        self.xml.push_str("</d:multistatus>");
+        self.xml
+    }
+
+    fn href(&mut self, href: &str) {
+        self.xml
+            .push_str(&format!("<d:href>{}</d:href>", escape(href)));
+    }
+
+    fn status(&mut self, status: &str) {
+        self.xml
+            .push_str(&format!("<d:status>HTTP/1.1 {}</d:status>", status));
+    }
+
+    fn element(&mut self, name: &PropName, value: &str) {
+        let (tag, declaration) = match name.namespace.as_str() {
+            DAV => (format!("d:{}", name.name), String::new()),
+            CALDAV => (format!("c:{}", name.name), String::new()),
+            CALENDARSERVER => (format!("cs:{}", name.name), String::new()),
+            namespace => (
+                format!("x:{}", name.name),
+                format!(r#" xmlns:x="{}""#, escape(namespace)),
+            ),
+        };
+        if value.is_empty() {
+            self.xml.push_str(&format!("<{}{}/>", tag, declaration));
+        } else {
+            self.xml
+                .push_str(&format!("<{}{}>{}</{}>", tag, declaration, value, tag));
+        }
+    }
+}
+
+/// Body of an error response naming the failed precondition, e.g. `valid-sync-token`
+pub fn error(precondition: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><d:error xmlns:d="{}"><d:{}/></d:error>"#,
+        DAV, precondition
+    )
+}
+
+/// Escape text and attribute values
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            // would be normalized to \n by XML parsers, but iCalendar lines end with CRLF
+            '\r' => escaped.push_str("&#13;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
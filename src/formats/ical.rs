@@ -0,0 +1,353 @@
+//! iCalendar (RFC 5545) VTODO components, as read and written by calendar apps.
+//!
+//! A task maps to a VTODO with UID, SUMMARY, STATUS, DUE, PRIORITY, CATEGORIES and RRULE.
+//! Import keeps the UID, so importing a calendar again updates the tasks instead of
+//! duplicating them. Local times with a TZID are converted with the time zone database (rows with
+//! an unknown TZID are rejected), floating times are read as UTC and date-only DUE values as
+//! midnight UTC.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use super::ImportRow;
+use crate::models::task::{NewTask, Task, TaskStatus};
+
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+/// content lines longer than this many octets are folded
+const MAX_LINE_LENGTH: usize = 75;
+
+#[derive(Debug, thiserror::Error)]
+pub enum IcalError {
+    #[error("calendar is not valid UTF-8")]
+    Utf8,
+    #[error("no VCALENDAR found")]
+    NotACalendar,
+}
+
+/// Start of a calendar, followed by [`vtodo`]s and [`calendar_end`]
+pub fn calendar_begin() -> String {
+    [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//axum_crud_api//tasks//EN",
+    ]
+    .iter()
+    .map(|line| content_line(line))
+    .collect()
+}
+
+pub fn calendar_end() -> String {
+    content_line("END:VCALENDAR")
+}
+
+/// VTODO component of `task`, `dtstamp` is the time the calendar is created
+pub fn vtodo(task: &Task, dtstamp: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", escape(&task.uid)),
+        format!("DTSTAMP:{}", format_utc(dtstamp)),
+        format!("SUMMARY:{}", escape(&task.task)),
+    ];
+    if let Some(status) = task.status {
+        lines.push(format!("STATUS:{}", status.as_str().to_ascii_uppercase()));
+    }
+    if let Some(due_at) = task.due_at {
+        lines.push(format!("DUE:{}", format_utc(due_at)));
+    }
+    if let Some(priority) = task.priority {
+        lines.push(format!("PRIORITY:{}", priority));
+    }
+    if let Some(categories) = &task.categories {
+        let categories: Vec<String> = categories
+            .split(',')
+            .map(|category| escape(category.trim()))
+            .collect();
+        lines.push(format!("CATEGORIES:{}", categories.join(",")));
+    }
+    if let Some(rrule) = &task.rrule {
+        lines.push(format!("RRULE:{}", rrule));
+    }
+    lines.push("END:VTODO".to_string());
+    lines.iter().map(|line| content_line(line)).collect()
+}
+
+/** Parse every VTODO of the calendar in `data` into an [`ImportRow`], numbered by the line of
+  its `BEGIN:VTODO`. Other components (events, time zones, alarms) are skipped.
+*/
+pub fn parse_vtodos(data: &[u8]) -> Result<Vec<ImportRow>, IcalError> {
+    let data = std::str::from_utf8(data).map_err(|_| IcalError::Utf8)?;
+    let lines = unfold(data);
+    if !lines
+        .iter()
+        .any(|(_, line)| line.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        return Err(IcalError::NotACalendar);
+    }
+
+    let mut rows = Vec::new();
+    let mut todo: Option<(u64, VTodo)> = None;
+    // depth of components nested in the current VTODO (e.g. VALARM)
+    let mut nested = 0;
+    for (line, content) in lines {
+        let (name, params, value) = match parse_content_line(&content) {
+            Some(property) => property,
+            None => {
+                if let Some((_, todo)) = &mut todo {
+                    todo.errors.push(format!("invalid content line {}", line));
+                }
+                continue;
+            }
+        };
+        match (todo.as_mut(), name.as_str()) {
+            (None, "BEGIN") if value.eq_ignore_ascii_case("VTODO") => {
+                todo = Some((line, VTodo::default()));
+            }
+            (None, _) => {}
+            (Some(_), "BEGIN") => nested += 1,
+            (Some(_), "END") if nested > 0 => nested -= 1,
+            (Some(_), "END") => {
+                if let Some((line, todo)) = todo.take() {
+                    rows.push(ImportRow {
+                        line,
+                        task: todo.into_task(),
+                        parent: None,
+                    });
+                }
+            }
+            (Some(_), _) if nested > 0 => {}
+            (Some((_, todo)), _) => todo.set(&name, &params, &value),
+        }
+    }
+    if let Some((line, _)) = todo {
+        rows.push(ImportRow {
+            line,
+            task: Err("VTODO is not terminated by END:VTODO".to_string()),
+            parent: None,
+        });
+    }
+    Ok(rows)
+}
+
+/// Properties of a VTODO collected while parsing
+#[derive(Default)]
+struct VTodo {
+    task: NewTask,
+    summary: bool,
+    errors: Vec<String>,
+}
+
+impl VTodo {
+    fn set(&mut self, name: &str, params: &str, value: &str) {
+        let result = match name {
+            "UID" => {
+                self.task.uid = Some(unescape(value));
+                Ok(())
+            }
+            "SUMMARY" => {
+                self.summary = true;
+                self.task.task = unescape(value);
+                Ok(())
+            }
+            "STATUS" => value.parse().map(|status: TaskStatus| {
+                self.task.status = Some(status);
+            }),
+            "DUE" => parse_date_time(params, value).map(|due_at| self.task.due_at = Some(due_at)),
+            "PRIORITY" => match value.parse::<i32>() {
+                // 0 means undefined
+                Ok(0) => Ok(()),
+                Ok(priority) => {
+                    self.task.priority = Some(priority);
+                    Ok(())
+                }
+                Err(_) => Err(format!("invalid PRIORITY {:?}", value)),
+            },
+            "CATEGORIES" => {
+                // the property may be repeated, all values are collected
+                let mut categories: Vec<String> = self.task.categories.take().into_iter().collect();
+                categories.extend(
+                    split_list(value)
+                        .into_iter()
+                        .map(unescape)
+                        .filter(|category| !category.trim().is_empty()),
+                );
+                self.task.categories = Some(categories.join(",")).filter(|c| !c.is_empty());
+                Ok(())
+            }
+            "RRULE" => {
+                self.task.rrule = Some(value.to_string());
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            self.errors.push(err);
+        }
+    }
+
+    fn into_task(self) -> Result<NewTask, String> {
+        if !self.errors.is_empty() {
+            return Err(self.errors.join("; "));
+        }
+        if !self.summary {
+            return Err("VTODO has no SUMMARY".to_string());
+        }
+        self.task.validate()?;
+        Ok(self.task)
+    }
+}
+
+/// Content line with line break, folded after 75 octets without splitting characters
+fn content_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            // the leading space counts
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// Logical content lines with the (1-based) line number they start on
+fn unfold(data: &str) -> Vec<(u64, String)> {
+    let mut lines: Vec<(u64, String)> = Vec::new();
+    for (number, line) in data.lines().enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, previous))) => previous.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push((number as u64 + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Upper case name, parameters (as written) and value of a content line `NAME;PARAM=...:VALUE`
+fn parse_content_line(line: &str) -> Option<(String, String, String)> {
+    // the value starts at the first colon outside of quoted parameter values
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(index, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(index),
+        _ => None,
+    })?;
+    let (name_and_params, value) = (&line[..colon], &line[colon + 1..]);
+    let (name, params) = name_and_params
+        .split_once(';')
+        .unwrap_or((name_and_params, ""));
+    if name.is_empty() {
+        return None;
+    }
+    Some((
+        name.to_ascii_uppercase(),
+        // TZID values are case sensitive
+        params.to_string(),
+        value.to_string(),
+    ))
+}
+
+fn parse_date_time(params: &str, value: &str) -> Result<DateTime<Utc>, String> {
+    let invalid = || format!("invalid DUE {:?}", value);
+    let upper = params.to_ascii_uppercase();
+    let date_only = upper.contains("VALUE=DATE") && !upper.contains("VALUE=DATE-TIME");
+    if date_only || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid())?;
+        let midnight = date.and_hms_opt(0, 0, 0).ok_or_else(invalid)?;
+        return Ok(Utc.from_utc_datetime(&midnight));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let date_time =
+            NaiveDateTime::parse_from_str(utc, DATE_TIME_FORMAT).map_err(|_| invalid())?;
+        return Ok(Utc.from_utc_datetime(&date_time));
+    }
+    let date_time =
+        NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT).map_err(|_| invalid())?;
+    let tzid = params.split(';').find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.eq_ignore_ascii_case("TZID")
+            .then(|| value.trim_matches('"'))
+    });
+    match tzid {
+        // floating time
+        None => Ok(Utc.from_utc_datetime(&date_time)),
+        Some(tzid) => {
+            // a leading slash marks a globally unique id, which is the Olson name here
+            let tz: Tz = tzid
+                .trim_start_matches('/')
+                .parse()
+                .map_err(|_| format!("unknown TZID {:?}", tzid))?;
+            // the earlier time if the clocks go back, none if they skip it
+            let local = tz
+                .from_local_datetime(&date_time)
+                .earliest()
+                .ok_or_else(|| format!("DUE {:?} does not exist in {}", value, tzid))?;
+            Ok(local.with_timezone(&Utc))
+        }
+    }
+}
+
+fn format_utc(date_time: DateTime<Utc>) -> String {
+    format!("{}Z", date_time.format(DATE_TIME_FORMAT))
+}
+
+/// Escape TEXT values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Split a list value at commas which are not escaped
+fn split_list(value: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (index, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                parts.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
@@ -0,0 +1,189 @@
+//! Markdown checklists, as pasted from meeting notes:
+//!
+//! ```markdown
+//! ## Action items
+//! - [ ] send minutes
+//! - [x] book room
+//!   - [ ] nested items are tasks as well
+//! ```
+//!
+//! Every `- [ ]` or `- [x]` item (also with `*`, `+` or `1.` as marker) is a task, `[x]` marks it
+//! completed. The heading an item is under becomes its categories, other lines are ignored.
+//! An item indented below another item is a subtask of it.
+//! Export groups the tasks under their categories and indents subtasks below their parent, so an
+//! export can be imported again.
+
+use std::collections::{HashMap, HashSet};
+
+use super::ImportRow;
+use crate::models::task::{NewTask, Task, TaskStatus};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MarkdownError {
+    #[error("markdown is not valid UTF-8")]
+    Utf8,
+}
+
+/** Parse every checklist item of `data` into an [`ImportRow`], numbered by its line, with the
+  line of the item it is nested in as parent. Code blocks are skipped, so examples in the notes are
+  not imported.
+*/
+pub fn parse_checklist(data: &[u8]) -> Result<Vec<ImportRow>, MarkdownError> {
+    let data = std::str::from_utf8(data).map_err(|_| MarkdownError::Utf8)?;
+    let mut rows = Vec::new();
+    let mut heading: Option<&str> = None;
+    let mut fence: Option<&str> = None;
+    // indentation and line of the items the current item may be nested in
+    let mut items: Vec<(usize, u64)> = Vec::new();
+    for (index, line) in data.lines().enumerate() {
+        let line_number = index as u64 + 1;
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(marker) = ["```", "~~~"]
+            .into_iter()
+            .find(|marker| trimmed.starts_with(marker))
+        {
+            fence = Some(marker);
+        } else if let Some(text) = parse_heading(trimmed) {
+            heading = Some(text).filter(|text| !text.is_empty());
+            items.clear();
+        } else if let Some((completed, text)) = parse_item(trimmed) {
+            let indent = indentation(line);
+            while items.last().is_some_and(|(parent, _)| *parent >= indent) {
+                items.pop();
+            }
+            let parent = items.last().map(|(_, line)| *line);
+            items.push((indent, line_number));
+            let task = NewTask {
+                task: text.to_string(),
+                status: completed.then_some(TaskStatus::Completed),
+                categories: heading.map(str::to_string),
+                ..Default::default()
+            };
+            rows.push(ImportRow {
+                line: line_number,
+                task: task.validate().map(|_| task),
+                parent,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+/** Checklist of `tasks`, tasks without categories first and then one section per categories.
+  Subtasks follow their parent, whatever their categories are.
+*/
+pub fn checklist(tasks: &[Task]) -> String {
+    let ids: HashSet<i64> = tasks.iter().map(|task| task.id).collect();
+    let mut children: HashMap<i64, Vec<&Task>> = HashMap::new();
+    for task in tasks {
+        if let Some(parent_id) = task.parent_id.filter(|parent_id| ids.contains(parent_id)) {
+            children.entry(parent_id).or_default().push(task);
+        }
+    }
+    let mut markdown = String::new();
+    // sections in the order their categories first appear
+    let mut sections: Vec<(&str, String)> = Vec::new();
+    for task in tasks {
+        if task
+            .parent_id
+            .is_some_and(|parent_id| ids.contains(&parent_id))
+        {
+            continue;
+        }
+        let items = match task.categories.as_deref() {
+            None => &mut markdown,
+            Some(categories) => match sections.iter().position(|(name, _)| *name == categories) {
+                Some(position) => &mut sections[position].1,
+                None => {
+                    sections.push((categories, String::new()));
+                    &mut sections.last_mut().unwrap().1
+                }
+            },
+        };
+        push_items(items, task, &children, 0);
+    }
+    for (categories, items) in sections {
+        if !markdown.is_empty() {
+            markdown.push('\n');
+        }
+        markdown.push_str(&format!("## {}\n\n{}", categories, items));
+    }
+    markdown
+}
+
+/// Item of `task` followed by the items of its subtasks, indented by two spaces per `depth`
+fn push_items(
+    markdown: &mut String,
+    task: &Task,
+    children: &HashMap<i64, Vec<&Task>>,
+    depth: usize,
+) {
+    let mark = match task.status {
+        Some(TaskStatus::Completed) => 'x',
+        _ => ' ',
+    };
+    // an item is one line
+    markdown.push_str(&format!(
+        "{}- [{}] {}\n",
+        "  ".repeat(depth),
+        mark,
+        task.task.replace(['\r', '\n'], " ")
+    ));
+    for child in children.get(&task.id).into_iter().flatten() {
+        push_items(markdown, child, children, depth + 1);
+    }
+}
+
+/// Width of the leading whitespace of `line`, a tab goes to the next multiple of 4
+fn indentation(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .fold(0, |width, c| match c {
+            '\t' => width + 4 - width % 4,
+            _ => width + 1,
+        })
+}
+
+/// Text of an ATX heading (`## text ##`)
+fn parse_heading(line: &str) -> Option<&str> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let text = &line[level..];
+    if !text.is_empty() && !text.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some(text.trim().trim_end_matches('#').trim_end())
+}
+
+/// Whether a checklist item (`- [x] text`) is checked and its text
+fn parse_item(line: &str) -> Option<(bool, &str)> {
+    let rest = match line.strip_prefix(['-', '*', '+']) {
+        Some(rest) => rest,
+        None => {
+            // ordered list: 1. or 1)
+            let digits = line.chars().take_while(char::is_ascii_digit).count();
+            if digits == 0 {
+                return None;
+            }
+            line[digits..].strip_prefix(['.', ')'])?
+        }
+    };
+    let rest = rest.strip_prefix([' ', '\t'])?.trim_start();
+    let (completed, text) = match rest.get(..3)? {
+        "[ ]" => (false, &rest[3..]),
+        "[x]" | "[X]" => (true, &rest[3..]),
+        _ => return None,
+    };
+    if !text.is_empty() && !text.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some((completed, text.trim()))
+}
@@ -0,0 +1,305 @@
+//! [todo.txt](https://github.com/todotxt/todo.txt), one task per line:
+//!
+//! ```text
+//! (A) 2022-11-01 call mom +family @phone due:2022-11-25
+//! x 2022-11-20 2022-11-01 buy milk +shopping pri:B
+//! ```
+//!
+//! - `x` marks a completed task, followed by the completion and the creation date
+//! - `(A)` to `(I)` are the priorities 1 to 9, completed tasks keep theirs as `pri:A`. The lower
+//!   priorities `(J)` to `(Z)` have no task priority, they are kept in the text as `pri:J`.
+//! - the rest is the task text as it is, including `+project` and `@context` tags and `key:value`
+//!   extensions. The tags are the categories of the task as well and `due:` is its due date.
+//!
+//! Export writes the text back and only adds or removes tags, `due:` and `pri:` where the fields
+//! were changed since, so exporting an imported file gives the same file. Only blank lines are
+//! dropped and line breaks are written as `\n`. A line break in a task text (e.g. from the api)
+//! is written as a space, a completed task without completion date keeps its creation date as
+//! `created:` extension.
+
+use chrono::{NaiveDate, TimeZone, Utc};
+
+use super::ImportRow;
+use crate::models::task::{NewTask, Task, TaskStatus};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+/// letter of priority 1, todo.txt priorities beyond `I` have no task priority
+const HIGHEST_PRIORITY: u8 = b'A';
+const LOWEST_PRIORITY: u8 = b'I';
+/// the lowest todo.txt priority
+const LOWEST_LETTER: u8 = b'Z';
+
+#[derive(Debug, thiserror::Error)]
+pub enum TodoTxtError {
+    #[error("todo.txt is not valid UTF-8")]
+    Utf8,
+}
+
+/// Line of `task` including the line break
+pub fn task_line(task: &Task) -> String {
+    let mut line = String::new();
+    let completed = task.status == Some(TaskStatus::Completed);
+    // one task per line
+    let text = task.task.lines().collect::<Vec<&str>>().join(" ");
+    let mut words: Vec<String> = text
+        .replace('\r', " ")
+        .split(' ')
+        .map(str::to_string)
+        .collect();
+    let created_on = task
+        .created_on
+        .map(|created_on| created_on.format(DATE_FORMAT).to_string());
+    if completed {
+        line.push_str("x ");
+        // a single date after `x` is read as completion date, so the creation date needs both
+        match task.completed_on {
+            Some(completed_on) => {
+                line.push_str(&format!("{} ", completed_on.format(DATE_FORMAT)));
+                if let Some(created_on) = &created_on {
+                    line.push_str(&format!("{} ", created_on));
+                }
+                set_extension(&mut words, "created", None);
+            }
+            None => set_extension(&mut words, "created", created_on),
+        }
+    } else {
+        let low_priority = low_priority(&words);
+        match task.priority.and_then(priority_letter) {
+            Some(priority) => line.push_str(&format!("({}) ", priority)),
+            None => {
+                if let Some(priority) = low_priority {
+                    line.push_str(&format!("({}) ", priority));
+                }
+            }
+        }
+        // the leading priority replaces pri:, see parse_line
+        if task.priority.is_some() || low_priority.is_some() {
+            set_extension(&mut words, "pri", None);
+        }
+        if let Some(created_on) = &created_on {
+            line.push_str(&format!("{} ", created_on));
+        }
+    }
+
+    let categories = categories(task.categories.as_deref());
+    // tags of removed categories are dropped, new categories are added as projects
+    let mut words: Vec<String> = words
+        .into_iter()
+        .filter(|word| tag(word).is_none_or(|tag| categories.contains(&tag)))
+        .collect();
+    for category in &categories {
+        if !words.iter().any(|word| tag(word) == Some(category)) {
+            words.push(format!("+{}", category.replace(char::is_whitespace, "-")));
+        }
+    }
+    let due = task
+        .due_at
+        .map(|due_at| due_at.date_naive().format(DATE_FORMAT).to_string());
+    set_extension(&mut words, "due", due);
+    if completed {
+        // a low priority without task priority stays
+        if task.priority.is_some() || low_priority(&words).is_none() {
+            let priority = task
+                .priority
+                .and_then(priority_letter)
+                .map(|priority| priority.to_string());
+            set_extension(&mut words, "pri", priority);
+        }
+    }
+    line.push_str(&words.join(" "));
+    line.push('\n');
+    line
+}
+
+/** Parse every line of `data` into an [`ImportRow`], blank lines are skipped.
+  Lines which can not be mapped to a task (e.g. `due:` without a valid date) are returned as
+  rejected.
+*/
+pub fn parse_tasks(data: &[u8]) -> Result<Vec<ImportRow>, TodoTxtError> {
+    let data = std::str::from_utf8(data).map_err(|_| TodoTxtError::Utf8)?;
+    Ok(data
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| ImportRow {
+            line: index as u64 + 1,
+            task: parse_line(line),
+            parent: None,
+        })
+        .collect())
+}
+
+fn parse_line(line: &str) -> Result<NewTask, String> {
+    let mut task = NewTask::default();
+    let mut rest = line;
+    let mut low_priority = None;
+    if let Some(after_mark) = line.strip_prefix("x ") {
+        task.status = Some(TaskStatus::Completed);
+        rest = after_mark;
+        if let Some((completed_on, after_date)) = leading_date(rest) {
+            task.completed_on = Some(completed_on);
+            rest = after_date;
+            if let Some((created_on, after_date)) = leading_date(rest) {
+                task.created_on = Some(created_on);
+                rest = after_date;
+            }
+        }
+    } else {
+        if let Some((letter, after_priority)) = leading_priority(rest) {
+            match priority_of_letter(letter) {
+                Some(priority) => task.priority = Some(priority),
+                None => low_priority = Some(letter),
+            }
+            rest = after_priority;
+        }
+        if let Some((created_on, after_date)) = leading_date(rest) {
+            task.created_on = Some(created_on);
+            rest = after_date;
+        }
+    }
+
+    let mut categories: Vec<&str> = Vec::new();
+    for word in rest.split(' ') {
+        if let Some(tag) = tag(word) {
+            if !categories.contains(&tag) {
+                categories.push(tag);
+            }
+        }
+    }
+    if !categories.is_empty() {
+        task.categories = Some(categories.join(","));
+    }
+    if let Some(due) = extension(rest, "due")? {
+        let due =
+            parse_date(due).ok_or_else(|| format!("invalid due:{}, expected YYYY-MM-DD", due))?;
+        let midnight = due.and_hms_opt(0, 0, 0).unwrap_or_default();
+        task.due_at = Some(Utc.from_utc_datetime(&midnight));
+    }
+    if task.status == Some(TaskStatus::Completed) {
+        if let Some(priority) = extension(rest, "pri")? {
+            if !is_priority_letter(priority) {
+                return Err(format!("invalid pri:{}", priority));
+            }
+            task.priority = priority_of_letter(priority);
+        }
+        if task.created_on.is_none() {
+            if let Some(created) = extension(rest, "created")? {
+                task.created_on =
+                    Some(parse_date(created).ok_or_else(|| {
+                        format!("invalid created:{}, expected YYYY-MM-DD", created)
+                    })?);
+            }
+        }
+    }
+    task.task = match low_priority {
+        Some(letter) if extension(rest, "pri")?.is_none() => format!("{} pri:{}", rest, letter),
+        _ => rest.to_string(),
+    };
+    task.validate()?;
+    Ok(task)
+}
+
+/// Letter of `(A) ` at the start of the line
+fn leading_priority(line: &str) -> Option<(&str, &str)> {
+    let bytes = line.as_bytes();
+    if bytes.len() < 4
+        || bytes[0] != b'('
+        || !bytes[1].is_ascii_uppercase()
+        || &bytes[2..4] != b") "
+    {
+        return None;
+    }
+    Some((&line[1..2], &line[4..]))
+}
+
+/// Letter of a `pri:J` to `pri:Z` extension, a priority without task priority
+fn low_priority(words: &[String]) -> Option<char> {
+    let mut letters = words
+        .iter()
+        .filter_map(|word| extension_value(word, "pri"))
+        .filter(|letter| is_priority_letter(letter) && priority_of_letter(letter).is_none());
+    letters.next().and_then(|letter| letter.chars().next())
+}
+
+fn is_priority_letter(letter: &str) -> bool {
+    matches!(letter.as_bytes(), [HIGHEST_PRIORITY..=LOWEST_LETTER])
+}
+
+/// `YYYY-MM-DD ` at the start of `text`
+fn leading_date(text: &str) -> Option<(NaiveDate, &str)> {
+    let (date, rest) = text.split_once(' ')?;
+    Some((parse_date(date)?, rest))
+}
+
+/// Only the exact format, `2022-11-5` would not be written back the same way
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    if date.len() != 10 {
+        return None;
+    }
+    NaiveDate::parse_from_str(date, DATE_FORMAT).ok()
+}
+
+fn priority_of_letter(letter: &str) -> Option<i32> {
+    match letter.as_bytes() {
+        [letter @ HIGHEST_PRIORITY..=LOWEST_PRIORITY] => {
+            Some(i32::from(letter - HIGHEST_PRIORITY) + 1)
+        }
+        _ => None,
+    }
+}
+
+fn priority_letter(priority: i32) -> Option<char> {
+    u8::try_from(priority - 1)
+        .ok()
+        .map(|offset| HIGHEST_PRIORITY + offset)
+        .filter(|letter| *letter <= LOWEST_PRIORITY)
+        .map(char::from)
+}
+
+/// Name of a `+project` or `@context` tag, both are categories
+fn tag(word: &str) -> Option<&str> {
+    let name = word.strip_prefix(['+', '@'])?;
+    // a comma would split the category
+    (!name.is_empty() && !name.contains(',')).then_some(name)
+}
+
+fn categories(categories: Option<&str>) -> Vec<&str> {
+    categories
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|category| !category.is_empty())
+        .collect()
+}
+
+/// Value of the `key:value` extension, if it is there only once
+fn extension<'a>(text: &'a str, key: &str) -> Result<Option<&'a str>, String> {
+    let mut values = text
+        .split(' ')
+        .filter_map(|word| extension_value(word, key));
+    match (values.next(), values.next()) {
+        (Some(_), Some(_)) => Err(format!("more than one {}: extension", key)),
+        (value, _) => Ok(value),
+    }
+}
+
+fn extension_value<'a>(word: &'a str, key: &str) -> Option<&'a str> {
+    word.strip_prefix(key)?
+        .strip_prefix(':')
+        .filter(|value| !value.is_empty())
+}
+
+/// Replace the value of the `key:value` extension, add it at the end or remove it for None
+fn set_extension(words: &mut Vec<String>, key: &str, value: Option<String>) {
+    let position = words
+        .iter()
+        .position(|word| extension_value(word, key).is_some());
+    match (position, value) {
+        (Some(position), Some(value)) => words[position] = format!("{}:{}", key, value),
+        (Some(position), None) => {
+            words.remove(position);
+        }
+        (None, Some(value)) => words.push(format!("{}:{}", key, value)),
+        (None, None) => {}
+    }
+}
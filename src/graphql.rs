@@ -0,0 +1,345 @@
+//! GraphQL schema of the task api, served by [`crate::controllers::graphql`].
+//!
+//! Queries read tasks by id or as filtered pages, the mutations mirror the REST handlers and
+//! the `taskChanges` subscription reports created, updated and deleted tasks as they happen.
+
+use async_graphql::{
+    Context, Error, ErrorExtensions, Object, Result, Schema, SimpleObject, Subscription,
+};
+use futures::{Stream, TryStreamExt};
+use std::time::Duration;
+
+use crate::boards;
+use crate::comments;
+use crate::models::board::{Board, BoardTasks, CardMove, NewBoard};
+use crate::models::comment::{Comment, NewComment};
+use crate::models::list::{NewTaskList, TaskList};
+use crate::models::tag::{NewTag, Tag};
+use crate::models::task::{NewTask, RankAnchors, SubtaskPolicy, Task, TaskFilter, UpdateTask};
+use crate::models::workflow::Workflow;
+use crate::repository::{self, Changes, DynTaskRepository, RepositoryError};
+
+pub type TaskSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+/// Interval of the subscriptions in the schema data
+struct PollInterval(Duration);
+
+/// Schema on top of the given store, subscriptions poll for changes every `poll_interval`
+pub fn schema(repo: DynTaskRepository, poll_interval: Duration) -> TaskSchema {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .data(repo)
+        .data(PollInterval(poll_interval))
+        .finish()
+}
+
+/// A page of the tasks matching a filter
+#[derive(SimpleObject)]
+pub struct TaskPage {
+    /// number of matching tasks on all pages
+    pub total: usize,
+    pub tasks: Vec<Task>,
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Task by id, null if there is none
+    async fn task(&self, ctx: &Context<'_>, id: i64) -> Result<Option<Task>> {
+        match repo(ctx).task(id).await {
+            Ok(task) => Ok(Some(task)),
+            Err(RepositoryError::NotFound) => Ok(None),
+            Err(err) => Err(error(err)),
+        }
+    }
+
+    /// Tasks matching `filter` in the manual order, `offset` and `limit` (at most 1000) select the page
+    async fn tasks(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<TaskFilter>,
+        #[graphql(default)] offset: usize,
+        #[graphql(default = 100, validator(maximum = 1000))] limit: usize,
+    ) -> Result<TaskPage> {
+        let filter = filter.unwrap_or_default();
+        // streamed, so only the page is held in memory
+        let mut tasks = repo(ctx).task_stream();
+        let mut page = TaskPage {
+            total: 0,
+            tasks: Vec::new(),
+        };
+        while let Some(task) = tasks.try_next().await.map_err(error)? {
+            if filter.matches(&task) {
+                if page.total >= offset && page.tasks.len() < limit {
+                    page.tasks.push(task);
+                }
+                page.total += 1;
+            }
+        }
+        Ok(page)
+    }
+
+    /// Tasks blocking task `id`, like `GET /tasks/{id}/blockers`
+    async fn blockers(&self, ctx: &Context<'_>, id: i64) -> Result<Vec<Task>> {
+        repo(ctx).blockers(id).await.map_err(error)
+    }
+
+    /// All tags ordered by name, like `GET /tags`
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        repo(ctx).tags().await.map_err(error)
+    }
+
+    /// All lists ordered by id, like `GET /lists`
+    async fn lists(&self, ctx: &Context<'_>) -> Result<Vec<TaskList>> {
+        repo(ctx).lists().await.map_err(error)
+    }
+
+    /// Tasks of list `id` in their order, like `GET /lists/{id}/tasks`
+    async fn list_tasks(&self, ctx: &Context<'_>, id: i64) -> Result<Vec<Task>> {
+        repo(ctx).list_tasks(id).await.map_err(error)
+    }
+
+    /// All boards ordered by id, like `GET /boards`
+    async fn boards(&self, ctx: &Context<'_>) -> Result<Vec<Board>> {
+        repo(ctx).boards().await.map_err(error)
+    }
+
+    /// Board `id` with the tasks of its columns, like `GET /boards/{id}`
+    async fn board(&self, ctx: &Context<'_>, id: i64) -> Result<BoardTasks> {
+        boards::load(repo(ctx).as_ref(), id).await.map_err(error)
+    }
+
+    /// Comments of task `id`, oldest first, like `GET /tasks/{id}/comments`
+    async fn comments(&self, ctx: &Context<'_>, id: i64) -> Result<Vec<Comment>> {
+        repo(ctx).comments(id).await.map_err(error)
+    }
+
+    /// All workflows ordered by id, like `GET /admin/workflows`
+    async fn workflows(&self, ctx: &Context<'_>) -> Result<Vec<Workflow>> {
+        repo(ctx).workflows().await.map_err(error)
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// Create a new task, like `POST /tasks`
+    async fn create_task(&self, ctx: &Context<'_>, task: NewTask) -> Result<Task> {
+        task.validate().map_err(Error::new)?;
+        repo(ctx).create_task(&task).await.map_err(error)
+    }
+
+    /// Replace all fields of a task, like `PUT /tasks/{id}`. Completing a recurring task creates
+    /// its next occurrence, `subtasks` decides about its open subtasks, open blockers refuse it.
+    async fn update_task(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        task: UpdateTask,
+        subtasks: Option<SubtaskPolicy>,
+    ) -> Result<Task> {
+        task.validate().map_err(Error::new)?;
+        let repo = repo(ctx);
+        repo.update_task(id, &task, subtasks).await.map_err(error)?;
+        repo.task(id).await.map_err(error)
+    }
+
+    /// Make a task with its subtasks a subtask of `parentId` (null: a top-level task),
+    /// like `PUT /tasks/{id}/parent`
+    async fn move_task(&self, ctx: &Context<'_>, id: i64, parent_id: Option<i64>) -> Result<Task> {
+        let repo = repo(ctx);
+        repo.move_task(id, parent_id).await.map_err(error)?;
+        repo.task(id).await.map_err(error)
+    }
+
+    /// Move a task right after task `after` and/or right before task `before` in the manual
+    /// order, like `POST /tasks/{id}/move`
+    async fn rank_task(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        before: Option<i64>,
+        after: Option<i64>,
+    ) -> Result<Task> {
+        let repo = repo(ctx);
+        let anchors = RankAnchors { before, after };
+        repo.rank_task(id, &anchors).await.map_err(error)?;
+        repo.task(id).await.map_err(error)
+    }
+
+    /// Create a new board, like `POST /boards`
+    async fn create_board(&self, ctx: &Context<'_>, board: NewBoard) -> Result<Board> {
+        board.validate().map_err(Error::new)?;
+        repo(ctx).create_board(&board).await.map_err(error)
+    }
+
+    /// Move task `id` into column `columnId` of board `boardId`, right after task `after` and/or
+    /// right before task `before`, like `POST /boards/{id}/cards/{task_id}/move`
+    async fn move_card(
+        &self,
+        ctx: &Context<'_>,
+        board_id: i64,
+        id: i64,
+        column_id: i64,
+        before: Option<i64>,
+        after: Option<i64>,
+    ) -> Result<BoardTasks> {
+        let card = CardMove {
+            column_id,
+            before,
+            after,
+        };
+        boards::move_card(repo(ctx).as_ref(), board_id, id, &card)
+            .await
+            .map_err(error)
+    }
+
+    /// Make task `id` blocked by task `blockerId`, like `POST /tasks/{id}/blockers`
+    async fn add_blocker(&self, ctx: &Context<'_>, id: i64, blocker_id: i64) -> Result<bool> {
+        repo(ctx)
+            .add_dependency(id, blocker_id)
+            .await
+            .map_err(error)?;
+        Ok(true)
+    }
+
+    /// Like `DELETE /tasks/{id}/blockers/{blocker_id}`
+    async fn remove_blocker(&self, ctx: &Context<'_>, id: i64, blocker_id: i64) -> Result<bool> {
+        repo(ctx)
+            .remove_dependency(id, blocker_id)
+            .await
+            .map_err(error)?;
+        Ok(true)
+    }
+
+    /// Create a new tag, like `POST /tags`
+    async fn create_tag(&self, ctx: &Context<'_>, tag: NewTag) -> Result<Tag> {
+        tag.validate().map_err(Error::new)?;
+        repo(ctx).create_tag(&tag).await.map_err(error)
+    }
+
+    /// Attach tag `tagId` to task `id`, like `PUT /tasks/{id}/tags/{tag_id}`
+    async fn attach_tag(&self, ctx: &Context<'_>, id: i64, tag_id: i64) -> Result<Task> {
+        let repo = repo(ctx);
+        repo.attach_tag(id, tag_id).await.map_err(error)?;
+        repo.task(id).await.map_err(error)
+    }
+
+    /// Detach tag `tagId` from task `id`, like `DELETE /tasks/{id}/tags/{tag_id}`
+    async fn detach_tag(&self, ctx: &Context<'_>, id: i64, tag_id: i64) -> Result<Task> {
+        let repo = repo(ctx);
+        repo.detach_tag(id, tag_id).await.map_err(error)?;
+        repo.task(id).await.map_err(error)
+    }
+
+    /// Create a new list, like `POST /lists`
+    async fn create_list(&self, ctx: &Context<'_>, list: NewTaskList) -> Result<TaskList> {
+        list.validate().map_err(Error::new)?;
+        repo(ctx).create_list(&list).await.map_err(error)
+    }
+
+    /// Archive list `id` with its tasks, or with `archived: false` restore them,
+    /// like `POST /lists/{id}/archive` and `DELETE /lists/{id}/archive`
+    async fn archive_list(&self, ctx: &Context<'_>, id: i64, archived: bool) -> Result<TaskList> {
+        repo(ctx).archive_list(id, archived).await.map_err(error)
+    }
+
+    /// Delete list `id`, with `cascade` its tasks as well, like `DELETE /lists/{id}`
+    async fn delete_list(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        #[graphql(default)] cascade: bool,
+    ) -> Result<bool> {
+        repo(ctx).delete_list(id, cascade).await.map_err(error)?;
+        Ok(true)
+    }
+
+    /// Move a task with its subtasks to the end of list `listId` (null: out of its list),
+    /// like `PUT /tasks/{id}/list`
+    async fn move_to_list(&self, ctx: &Context<'_>, id: i64, list_id: Option<i64>) -> Result<Task> {
+        let repo = repo(ctx);
+        repo.move_to_list(id, list_id).await.map_err(error)?;
+        repo.task(id).await.map_err(error)
+    }
+
+    /// Comment on task `id`, like `POST /tasks/{id}/comments`
+    async fn add_comment(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        comment: NewComment,
+    ) -> Result<Comment> {
+        comment.validate().map_err(Error::new)?;
+        comments::create_comment(repo(ctx).as_ref(), id, &comment)
+            .await
+            .map_err(error)
+    }
+
+    /// Let a task follow workflow `workflowId` from its first state (null: no workflow), like
+    /// `PUT /tasks/{id}/workflow`
+    async fn set_workflow(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        workflow_id: Option<i64>,
+    ) -> Result<Task> {
+        let repo = repo(ctx);
+        repo.set_workflow(id, workflow_id).await.map_err(error)?;
+        repo.task(id).await.map_err(error)
+    }
+
+    /// Delete a task and its subtasks, like `DELETE /tasks/{id}`
+    async fn delete_task(&self, ctx: &Context<'_>, id: i64) -> Result<bool> {
+        repo(ctx).delete_task(id).await.map_err(error)?;
+        Ok(true)
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /** Tasks created, updated or deleted after `revision` as they happen, from now on without one.
+      Pass the revision of the last changes received to resume after a reconnect.
+    */
+    async fn task_changes(
+        &self,
+        ctx: &Context<'_>,
+        revision: Option<i64>,
+    ) -> impl Stream<Item = Result<Changes>> {
+        let PollInterval(interval) = ctx.data_unchecked::<PollInterval>();
+        repository::watch(repo(ctx).clone(), revision, *interval).map_err(error)
+    }
+}
+
+fn repo<'a>(ctx: &Context<'a>) -> &'a DynTaskRepository {
+    ctx.data_unchecked::<DynTaskRepository>()
+}
+
+/// Error with the extension `code` NOT_FOUND, UNSUPPORTED, CONFLICT, PRECONDITION_FAILED,
+/// INVALID or DATABASE, like the REST status codes. A refused workflow transition is a CONFLICT with the extension
+/// `allowedStates`.
+fn error(err: RepositoryError) -> Error {
+    tracing::error!(error = %err, "graphql request failed");
+    let mut allowed_states = None;
+    let (msg, code) = match err {
+        RepositoryError::NotFound => ("task not found".to_string(), "NOT_FOUND"),
+        RepositoryError::Unsupported(_) => (err.to_string(), "UNSUPPORTED"),
+        RepositoryError::Conflict(msg) => (msg, "CONFLICT"),
+        RepositoryError::Transition { ref allowed, .. } => {
+            allowed_states = Some(allowed.clone());
+            (err.to_string(), "CONFLICT")
+        }
+        RepositoryError::PreconditionFailed => (err.to_string(), "PRECONDITION_FAILED"),
+        RepositoryError::Invalid(msg) => (msg, "INVALID"),
+        RepositoryError::Database(_) => ("database error".to_string(), "DATABASE"),
+    };
+    Error::new(msg).extend_with(|_, extensions| {
+        extensions.set("code", code);
+        if let Some(allowed_states) = allowed_states {
+            extensions.set("allowedStates", allowed_states);
+        }
+    })
+}
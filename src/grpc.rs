@@ -0,0 +1,312 @@
+//! gRPC api of the tasks (`proto/tasks.proto`), served on its own port next to the REST api.
+//!
+//! The code of the messages and the service trait is generated by `build.rs`. The service
+//! works on the same [`TaskRepository`](crate::repository::TaskRepository) as the axum handlers
+//! and validates tasks like imports and GraphQL do ([`NewTask::validate`]).
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use futures::stream::BoxStream;
+use futures::{Future, TryStreamExt};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{Request, Response, Status};
+
+use crate::models::task::{NewTask, Task, TaskStatus, UpdateTask};
+use crate::repository::{self, Changes, DynTaskRepository, RepositoryError};
+
+/// Generated from `proto/tasks.proto`
+pub mod proto {
+    tonic::include_proto!("tasks.v1");
+}
+
+use proto::task_service_server::{TaskService, TaskServiceServer};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// gRPC service on top of the given store, `Watch` polls for changes every `watch_interval`
+pub fn service(
+    repo: DynTaskRepository,
+    watch_interval: Duration,
+) -> TaskServiceServer<GrpcTaskService> {
+    TaskServiceServer::new(GrpcTaskService {
+        repo,
+        watch_interval,
+    })
+}
+
+/** Bind the gRPC server to `addr` without serving yet, like [`crate::bind`].
+  Returns the actual address (with port 0 the os picks a free port) and the server to await.
+*/
+pub async fn bind(
+    addr: &SocketAddr,
+    repo: DynTaskRepository,
+) -> anyhow::Result<(
+    SocketAddr,
+    impl Future<Output = Result<(), tonic::transport::Error>>,
+)> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+    let server = tonic::transport::Server::builder()
+        .trace_fn(|request| tracing::info_span!("grpc", rpc.method = %request.uri().path()))
+        .add_service(service(repo, repository::WATCH_INTERVAL))
+        .serve_with_incoming(TcpListenerStream::new(listener));
+    Ok((local_addr, server))
+}
+
+pub struct GrpcTaskService {
+    repo: DynTaskRepository,
+    watch_interval: Duration,
+}
+
+#[tonic::async_trait]
+impl TaskService for GrpcTaskService {
+    async fn create_task(
+        &self,
+        request: Request<proto::CreateTaskRequest>,
+    ) -> Result<Response<proto::Task>, Status> {
+        let task = new_task(request.into_inner().task).map_err(Status::invalid_argument)?;
+        match self.repo.create_task(&task).await {
+            Ok(task) => Ok(Response::new(task.into())),
+            Err(err) => {
+                tracing::error!(error = %err, "could not create task");
+                Err(status(err))
+            }
+        }
+    }
+
+    async fn get_task(
+        &self,
+        request: Request<proto::GetTaskRequest>,
+    ) -> Result<Response<proto::Task>, Status> {
+        let id = request.into_inner().id;
+        match self.repo.task(id).await {
+            Ok(task) => Ok(Response::new(task.into())),
+            Err(err) => {
+                tracing::error!(task_id = id, error = %err, "could not find task");
+                Err(status(err))
+            }
+        }
+    }
+
+    type ListTasksStream = BoxStream<'static, Result<proto::Task, Status>>;
+
+    async fn list_tasks(
+        &self,
+        _request: Request<proto::ListTasksRequest>,
+    ) -> Result<Response<Self::ListTasksStream>, Status> {
+        // dropped with the query when the client cancels
+        let tasks = self
+            .repo
+            .task_stream()
+            .map_ok(proto::Task::from)
+            .map_err(|err| {
+                tracing::error!(error = %err, "could not list tasks");
+                status(err)
+            });
+        Ok(Response::new(Box::pin(tasks)))
+    }
+
+    async fn update_task(
+        &self,
+        request: Request<proto::UpdateTaskRequest>,
+    ) -> Result<Response<proto::Task>, Status> {
+        let request = request.into_inner();
+        let id = request.id;
+        let task = new_task(request.task).map_err(Status::invalid_argument)?;
+        let task = update_task(task);
+        // completing a recurring task creates its next occurrence, open blockers refuse it
+        let updated = match self.repo.update_task(id, &task, None).await {
+            Ok(_) => self.repo.task(id).await,
+            Err(err) => Err(err),
+        };
+        match updated {
+            Ok(task) => Ok(Response::new(task.into())),
+            Err(err) => {
+                tracing::error!(task_id = id, error = %err, "could not update task");
+                Err(status(err))
+            }
+        }
+    }
+
+    async fn delete_task(
+        &self,
+        request: Request<proto::DeleteTaskRequest>,
+    ) -> Result<Response<proto::DeleteTaskResponse>, Status> {
+        let id = request.into_inner().id;
+        match self.repo.delete_task(id).await {
+            Ok(()) => Ok(Response::new(proto::DeleteTaskResponse {})),
+            Err(err) => {
+                tracing::error!(task_id = id, error = %err, "could not delete task");
+                Err(status(err))
+            }
+        }
+    }
+
+    type WatchStream = BoxStream<'static, Result<proto::TaskChanges, Status>>;
+
+    async fn watch(
+        &self,
+        request: Request<proto::WatchRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let revision = request.into_inner().revision;
+        let changes = repository::watch(self.repo.clone(), revision, self.watch_interval)
+            .map_ok(proto::TaskChanges::from)
+            .map_err(|err| {
+                tracing::error!(error = %err, "could not watch tasks");
+                status(err)
+            });
+        Ok(Response::new(Box::pin(changes)))
+    }
+}
+
+/** NotFound is answered with NOT_FOUND, Unsupported with UNIMPLEMENTED, Conflict, Transition
+  and PreconditionFailed with FAILED_PRECONDITION, Invalid with INVALID_ARGUMENT, the rest with
+  INTERNAL
+*/
+fn status(err: RepositoryError) -> Status {
+    match err {
+        RepositoryError::NotFound => Status::not_found("task not found"),
+        RepositoryError::Unsupported(_) => Status::unimplemented(err.to_string()),
+        RepositoryError::Conflict(msg) => Status::failed_precondition(msg),
+        RepositoryError::Transition { .. } => Status::failed_precondition(err.to_string()),
+        RepositoryError::PreconditionFailed => Status::failed_precondition(err.to_string()),
+        RepositoryError::Invalid(msg) => Status::invalid_argument(msg),
+        RepositoryError::Database(_) => Status::internal("database error"),
+    }
+}
+
+/// Valid task out of the request fields, the error is answered with INVALID_ARGUMENT
+fn new_task(fields: Option<proto::TaskFields>) -> Result<NewTask, String> {
+    let fields = fields.ok_or("task is missing")?;
+    let status = match proto::TaskStatus::from_i32(fields.status) {
+        Some(proto::TaskStatus::Unspecified) => None,
+        Some(proto::TaskStatus::NeedsAction) => Some(TaskStatus::NeedsAction),
+        Some(proto::TaskStatus::InProcess) => Some(TaskStatus::InProcess),
+        Some(proto::TaskStatus::Completed) => Some(TaskStatus::Completed),
+        Some(proto::TaskStatus::Cancelled) => Some(TaskStatus::Cancelled),
+        None => {
+            return Err(format!("unknown status {}", fields.status));
+        }
+    };
+    let task = NewTask {
+        task: fields.task,
+        uid: None,
+        status,
+        due_at: parse_time("due_at", fields.due_at)?,
+        remind_at: parse_time("remind_at", fields.remind_at)?,
+        priority: fields.priority,
+        categories: fields.categories,
+        rrule: fields.rrule,
+        created_on: parse_date("created_on", fields.created_on)?,
+        completed_on: parse_date("completed_on", fields.completed_on)?,
+        parent_id: fields.parent_id,
+        parent: None,
+        list_id: fields.list_id,
+        workflow_id: None,
+    };
+    task.validate()?;
+    Ok(task)
+}
+
+fn update_task(task: NewTask) -> UpdateTask {
+    UpdateTask {
+        task: task.task,
+        status: task.status,
+        due_at: task.due_at,
+        remind_at: task.remind_at,
+        priority: task.priority,
+        categories: task.categories,
+        rrule: task.rrule,
+        created_on: task.created_on,
+        completed_on: task.completed_on,
+        state: None,
+    }
+}
+
+fn parse_date(field: &str, date: Option<String>) -> Result<Option<NaiveDate>, String> {
+    date.map(|date| {
+        NaiveDate::parse_from_str(&date, DATE_FORMAT)
+            .map_err(|_| format!("invalid {} {:?}, expected YYYY-MM-DD", field, date))
+    })
+    .transpose()
+}
+
+fn parse_time(
+    field: &str,
+    time: Option<prost_types::Timestamp>,
+) -> Result<Option<DateTime<Utc>>, String> {
+    time.map(|time| {
+        u32::try_from(time.nanos)
+            .ok()
+            .and_then(|nanos| Utc.timestamp_opt(time.seconds, nanos).single())
+            .ok_or_else(|| format!("invalid {}", field))
+    })
+    .transpose()
+}
+
+fn timestamp(time: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+impl From<Task> for proto::Task {
+    fn from(task: Task) -> proto::Task {
+        let status = task
+            .status
+            .map_or(proto::TaskStatus::Unspecified, proto::TaskStatus::from);
+        proto::Task {
+            id: task.id,
+            task: task.task,
+            uid: task.uid,
+            status: status as i32,
+            due_at: task.due_at.map(timestamp),
+            priority: task.priority,
+            categories: task.categories,
+            rrule: task.rrule,
+            created_on: task
+                .created_on
+                .map(|date| date.format(DATE_FORMAT).to_string()),
+            completed_on: task
+                .completed_on
+                .map(|date| date.format(DATE_FORMAT).to_string()),
+            revision: task.revision,
+            remind_at: task.remind_at.map(timestamp),
+            parent_id: task.parent_id,
+            list_id: task.list_id,
+            tags: task
+                .tags
+                .into_iter()
+                .map(|tag| proto::Tag {
+                    id: tag.id,
+                    name: tag.name,
+                    colour: tag.colour,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<Changes> for proto::TaskChanges {
+    fn from(changes: Changes) -> proto::TaskChanges {
+        proto::TaskChanges {
+            revision: changes.revision,
+            changed: changes.changed.into_iter().map(proto::Task::from).collect(),
+            deleted: changes.deleted,
+        }
+    }
+}
+
+impl From<TaskStatus> for proto::TaskStatus {
+    fn from(status: TaskStatus) -> proto::TaskStatus {
+        match status {
+            TaskStatus::NeedsAction => proto::TaskStatus::NeedsAction,
+            TaskStatus::InProcess => proto::TaskStatus::InProcess,
+            TaskStatus::Completed => proto::TaskStatus::Completed,
+            TaskStatus::Cancelled => proto::TaskStatus::Cancelled,
+        }
+    }
+}
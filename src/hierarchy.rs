@@ -0,0 +1,46 @@
+//! Subtasks: every task can have a parent task (`parent_id`), which makes the tasks a forest.
+//!
+//! Deleting a task deletes its subtasks, moving a task (`PUT /tasks/{id}/parent`) moves its
+//! subtasks along and a task can never become a subtask of itself or of its own subtasks.
+//! When a task is completed, the [`SubtaskPolicy`](crate::models::task::SubtaskPolicy) decides
+//! whether its open subtasks are left alone, completed along with it or prevent the completion,
+//! see [`TaskRepository::update_task`](crate::repository::TaskRepository::update_task).
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+use crate::models::task::Task;
+
+/// A task with its subtasks at any depth
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct TaskTree {
+    pub task: Task,
+    pub subtasks: Vec<TaskTree>,
+}
+
+impl TaskTree {
+    /// Tree of `root` out of `tasks`, which are the task and its subtasks as returned by
+    /// [`TaskRepository::subtree`](crate::repository::TaskRepository::subtree). Subtasks are in the manual order (see `crate::rank`).
+    pub fn build(root: Task, tasks: Vec<Task>) -> TaskTree {
+        let mut children: HashMap<i64, Vec<Task>> = HashMap::new();
+        for task in tasks {
+            if let Some(parent_id) = task.parent_id {
+                children.entry(parent_id).or_default().push(task);
+            }
+        }
+        TaskTree::attach(root, &mut children)
+    }
+
+    fn attach(task: Task, children: &mut HashMap<i64, Vec<Task>>) -> TaskTree {
+        let mut subtasks = children.remove(&task.id).unwrap_or_default();
+        subtasks.sort_by(|a, b| (&a.rank, a.id).cmp(&(&b.rank, b.id)));
+        TaskTree {
+            task,
+            subtasks: subtasks
+                .into_iter()
+                .map(|subtask| TaskTree::attach(subtask, children))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,385 @@
+//! Task CRUD REST API as a library.
+//!
+//! The binary (`src/main.rs`) just reads its [`Config`] from the environment and calls [`run`].
+//! Other axum applications can embed the task API by building the router on top of
+//! their own store and nesting it:
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use axum::Router;
+//!
+//! let repo = axum_crud_api::repository::connect("sqlite:tasks.db").await?;
+//! let app = Router::new().nest("/todo", axum_crud_api::app(repo));
+//! let server = axum_crud_api::bind(&"127.0.0.1:0".parse()?, app)?;
+//! println!("listening on {}", server.local_addr());
+//! server.await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Tests can drive the router in-process without a socket, e.g. with `tower::ServiceExt::oneshot`.
+
+use async_graphql_axum::GraphQLSubscription;
+use axum::{
+    extract::Extension,
+    middleware,
+    routing::{any, delete, get, get_service, patch, post, put, IntoMakeService},
+    Router,
+};
+use hyper::server::conn::AddrIncoming;
+use std::env;
+use std::net::SocketAddr;
+use tower_http::trace::TraceLayer;
+
+// openAPI doc
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+pub mod backup;
+pub mod boards;
+pub mod comments;
+pub mod controllers;
+pub mod dependencies;
+pub mod formats;
+pub mod graphql;
+pub mod grpc;
+pub mod hierarchy;
+pub mod models;
+pub mod negotiation;
+pub mod rank;
+pub mod recurrence;
+pub mod reminders;
+pub mod repository;
+pub mod request_id;
+pub mod telemetry;
+pub mod workflows;
+
+#[cfg(test)]
+mod tests;
+
+/// Server returned by [`bind`], await it to serve requests
+pub type Server = axum::Server<AddrIncoming, IntoMakeService<Router>>;
+
+pub struct Config {
+    /// `DATABASE_URL`, see [`repository`] for the supported schemes (default `sqlite:tasks.db`)
+    pub database_url: String,
+    /// `BIND_ADDR`, use port 0 to let the os pick a free port (default `127.0.0.1:3000`)
+    pub bind_addr: SocketAddr,
+    /// `GRPC_ADDR` of the gRPC api, see [`grpc`] (default `127.0.0.1:50051`)
+    pub grpc_addr: SocketAddr,
+    /// see [`backup`]
+    pub backup: backup::BackupConfig,
+    /// see [`reminders`]
+    pub reminders: reminders::ReminderConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Config> {
+        let database_url =
+            env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:tasks.db".to_string());
+        let bind_addr = match env::var("BIND_ADDR") {
+            Ok(addr) => addr.parse()?,
+            Err(_) => SocketAddr::from(([127, 0, 0, 1], 3000)),
+        };
+        let grpc_addr = match env::var("GRPC_ADDR") {
+            Ok(addr) => addr.parse()?,
+            Err(_) => SocketAddr::from(([127, 0, 0, 1], 50051)),
+        };
+        Ok(Config {
+            database_url,
+            bind_addr,
+            grpc_addr,
+            backup: backup::BackupConfig::from_env()?,
+            reminders: reminders::ReminderConfig::from_env()?,
+        })
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        controllers::task::all_tasks,
+        controllers::task::new_task,
+        controllers::task::task,
+        controllers::task::update_task,
+        controllers::task::patch_task,
+        controllers::task::delete_task,
+        controllers::task::occurrences,
+        controllers::task::children,
+        controllers::task::tree,
+        controllers::task::move_task,
+        controllers::task::rank_task,
+        controllers::task::blockers,
+        controllers::task::add_blocker,
+        controllers::task::remove_blocker,
+        controllers::task::task_order,
+        controllers::tag::all_tags,
+        controllers::tag::tag_counts,
+        controllers::tag::new_tag,
+        controllers::tag::tag,
+        controllers::tag::update_tag,
+        controllers::tag::delete_tag,
+        controllers::tag::attach_tag,
+        controllers::tag::detach_tag,
+        controllers::list::all_lists,
+        controllers::list::new_list,
+        controllers::list::list,
+        controllers::list::update_list,
+        controllers::list::delete_list,
+        controllers::list::archive_list,
+        controllers::list::restore_list,
+        controllers::list::list_tasks,
+        controllers::list::new_list_task,
+        controllers::list::reorder_list,
+        controllers::list::move_to_list,
+        controllers::board::all_boards,
+        controllers::board::new_board,
+        controllers::board::board,
+        controllers::board::update_board,
+        controllers::board::delete_board,
+        controllers::board::move_card,
+        controllers::workflow::set_task_workflow,
+        controllers::comment::comments,
+        controllers::comment::new_comment,
+        controllers::comment::comment,
+        controllers::comment::update_comment,
+        controllers::comment::delete_comment,
+        controllers::comment::comment_history,
+        controllers::comment::notifications,
+        controllers::transfer::export_csv,
+        controllers::transfer::export_ics,
+        controllers::transfer::export_todo_txt,
+        controllers::transfer::export_ndjson,
+        controllers::transfer::import,
+        controllers::admin::create_backup,
+        controllers::admin::all_backups,
+        controllers::admin::prune_backups,
+        controllers::workflow::all_workflows,
+        controllers::workflow::new_workflow,
+        controllers::workflow::workflow,
+        controllers::workflow::update_workflow,
+        controllers::workflow::delete_workflow,
+    ),
+    components(
+        schemas(models::task::Task, models::task::NewTask, models::task::UpdateTask, models::error::ErrorResponse,
+            models::backup::Backup, models::backup::PruneReport,
+            models::task::TaskStatus, models::task::Occurrence, models::task::MoveTask, models::task::RankAnchors,
+            models::task::SubtaskPolicy, hierarchy::TaskTree, models::task::Dependency,
+            models::task::NewBlocker, models::task::TagMatch, models::tag::Tag, models::tag::NewTag,
+            models::tag::TagCount, models::list::TaskList, models::list::NewTaskList,
+            models::list::MoveToList, models::list::ListOrder, models::board::Board, models::board::BoardColumn,
+            models::board::NewBoard, models::board::NewBoardColumn, models::board::BoardTasks,
+            models::board::ColumnTasks, models::board::CardMove, models::workflow::Workflow,
+            models::workflow::WorkflowState, models::workflow::Transition, models::workflow::NewWorkflow,
+            models::workflow::TaskWorkflow, models::comment::Comment, models::comment::NewComment,
+            models::comment::CommentVersion, models::comment::Notification, models::import::ImportReport, models::import::AcceptedRow, models::import::RejectedRow)
+    ),
+    modifiers(&NEGOTIATED_MEDIA_TYPES),
+    tags(
+        (name = "task", description = "Tasks management API"),
+        (name = "admin", description = "Database administration (backups)"),
+        (name = "workflow", description = "Workflows of tasks, defined by administrators")
+    )
+)]
+pub struct ApiDoc;
+
+/// Paths of the task CRUD handlers, which speak all [`negotiation::Format`]s
+const NEGOTIATED_MEDIA_TYPES: negotiation::NegotiatedMediaTypes =
+    negotiation::NegotiatedMediaTypes {
+        paths: &["/tasks", "/tasks/{id}"],
+    };
+
+/// Connect to the configured database, send [`reminders`] in the background and serve the
+/// REST and gRPC apis until a server fails.
+/// Tracing is left to the caller (see [`telemetry::init_tracing`]).
+pub async fn run(config: Config) -> anyhow::Result<()> {
+    let repo = repository::connect(&config.database_url).await?;
+
+    reminders::spawn(repo.clone(), &config.reminders)?;
+
+    let (grpc_addr, grpc_server) = grpc::bind(&config.grpc_addr, repo.clone()).await?;
+    tracing::debug!("gRPC listening on {}", grpc_addr);
+    let app = app(repo.clone()).merge(admin_app(repo, config.backup));
+    let server = bind(&config.bind_addr, app)?;
+    tracing::debug!("Listening on {}", server.local_addr());
+    tokio::try_join!(async { server.await.map_err(anyhow::Error::from) }, async {
+        grpc_server.await.map_err(anyhow::Error::from)
+    },)?;
+
+    Ok(())
+}
+
+/// build our application with its routes on top of the given store
+pub fn app(repo: repository::DynTaskRepository) -> Router {
+    let schema = graphql::schema(repo.clone(), repository::WATCH_INTERVAL);
+    let router = Router::new()
+        // openAPI doc under: http://127.0.0.1:3000/swagger-ui
+        .merge(SwaggerUi::new("/swagger-ui/*tail").url("/api-doc/openapi.json", ApiDoc::openapi()))
+        // GraphQL playground under: http://127.0.0.1:3000/graphiql
+        .route("/graphiql", get(controllers::graphql::graphiql))
+        .route(
+            "/graphql",
+            get(controllers::graphql::graphql).post(controllers::graphql::graphql),
+        )
+        .route(
+            "/graphql/ws",
+            get_service(GraphQLSubscription::new(schema.clone())),
+        )
+        .route("/hello", get(root))
+        .route("/tasks", get(controllers::task::all_tasks))
+        .route("/tasks", post(controllers::task::new_task))
+        .route("/tasks/export.csv", get(controllers::transfer::export_csv))
+        .route("/tasks/export.ics", get(controllers::transfer::export_ics))
+        .route(
+            "/tasks/export.txt",
+            get(controllers::transfer::export_todo_txt),
+        )
+        .route(
+            "/tasks/export.ndjson",
+            get(controllers::transfer::export_ndjson),
+        )
+        .route("/tasks/import", post(controllers::transfer::import))
+        .route("/tasks/order", get(controllers::task::task_order))
+        .route("/tasks/:id", get(controllers::task::task))
+        .route("/tasks/:id", put(controllers::task::update_task))
+        .route("/tasks/:id", patch(controllers::task::patch_task))
+        .route("/tasks/:id", delete(controllers::task::delete_task))
+        .route(
+            "/tasks/:id/occurrences",
+            get(controllers::task::occurrences),
+        )
+        .route("/tasks/:id/children", get(controllers::task::children))
+        .route("/tasks/:id/tree", get(controllers::task::tree))
+        .route("/tasks/:id/parent", put(controllers::task::move_task))
+        .route("/tasks/:id/move", post(controllers::task::rank_task))
+        .route(
+            "/tasks/:id/blockers",
+            get(controllers::task::blockers).post(controllers::task::add_blocker),
+        )
+        .route(
+            "/tasks/:id/blockers/:blocker_id",
+            delete(controllers::task::remove_blocker),
+        )
+        .route(
+            "/tasks/:id/tags/:tag_id",
+            put(controllers::tag::attach_tag).delete(controllers::tag::detach_tag),
+        )
+        .route("/tasks/:id/list", put(controllers::list::move_to_list))
+        .route(
+            "/tasks/:id/workflow",
+            put(controllers::workflow::set_task_workflow),
+        )
+        .route(
+            "/tasks/:id/comments",
+            get(controllers::comment::comments).post(controllers::comment::new_comment),
+        )
+        .route(
+            "/tasks/:id/comments/:comment_id",
+            get(controllers::comment::comment)
+                .put(controllers::comment::update_comment)
+                .delete(controllers::comment::delete_comment),
+        )
+        .route(
+            "/tasks/:id/comments/:comment_id/history",
+            get(controllers::comment::comment_history),
+        )
+        .route("/notifications", get(controllers::comment::notifications))
+        .route(
+            "/lists",
+            get(controllers::list::all_lists).post(controllers::list::new_list),
+        )
+        .route(
+            "/lists/:id",
+            get(controllers::list::list)
+                .put(controllers::list::update_list)
+                .delete(controllers::list::delete_list),
+        )
+        .route(
+            "/lists/:id/archive",
+            post(controllers::list::archive_list).delete(controllers::list::restore_list),
+        )
+        .route(
+            "/lists/:id/tasks",
+            get(controllers::list::list_tasks).post(controllers::list::new_list_task),
+        )
+        .route("/lists/:id/order", put(controllers::list::reorder_list))
+        .route(
+            "/boards",
+            get(controllers::board::all_boards).post(controllers::board::new_board),
+        )
+        .route(
+            "/boards/:id",
+            get(controllers::board::board)
+                .put(controllers::board::update_board)
+                .delete(controllers::board::delete_board),
+        )
+        .route(
+            "/boards/:id/cards/:task_id/move",
+            post(controllers::board::move_card),
+        )
+        .route(
+            "/tags",
+            get(controllers::tag::all_tags).post(controllers::tag::new_tag),
+        )
+        .route("/tags/counts", get(controllers::tag::tag_counts))
+        .route(
+            "/tags/:id",
+            get(controllers::tag::tag)
+                .put(controllers::tag::update_tag)
+                .delete(controllers::tag::delete_tag),
+        )
+        // CalDAV, methods like PROPFIND and REPORT are dispatched by the handlers
+        .route("/.well-known/caldav", any(controllers::caldav::well_known))
+        .route("/caldav", any(controllers::caldav::principal))
+        .route("/caldav/", any(controllers::caldav::principal))
+        .route("/caldav/tasks", any(controllers::caldav::calendar))
+        .route("/caldav/tasks/", any(controllers::caldav::calendar))
+        .route("/caldav/tasks/:name", any(controllers::caldav::task))
+        .layer(Extension(repo))
+        .layer(Extension(schema));
+    with_common_layers(router)
+}
+
+/** Administration routes (backups, workflows) on top of the given store.
+  The api has no authentication, so when embedding mount these only where they are protected.
+*/
+pub fn admin_app(repo: repository::DynTaskRepository, backups: backup::BackupConfig) -> Router {
+    let router = Router::new()
+        .route("/admin/backups", get(controllers::admin::all_backups))
+        .route("/admin/backups", post(controllers::admin::create_backup))
+        .route(
+            "/admin/backups/prune",
+            post(controllers::admin::prune_backups),
+        )
+        .route(
+            "/admin/workflows",
+            get(controllers::workflow::all_workflows).post(controllers::workflow::new_workflow),
+        )
+        .route(
+            "/admin/workflows/:id",
+            get(controllers::workflow::workflow)
+                .put(controllers::workflow::update_workflow)
+                .delete(controllers::workflow::delete_workflow),
+        )
+        .layer(Extension(repo))
+        .layer(Extension(backups));
+    with_common_layers(router)
+}
+
+fn with_common_layers(router: Router) -> Router {
+    router
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::make_span))
+        // outermost so that the request span already carries the request id
+        .layer(middleware::from_fn(request_id::propagate_request_id))
+}
+
+/** Bind `app` to `addr` without serving yet.
+  With port 0 the os picks a free port, `local_addr()` of the returned server tells which.
+*/
+pub fn bind(addr: &SocketAddr, app: Router) -> anyhow::Result<Server> {
+    Ok(axum::Server::try_bind(addr)?.serve(app.into_make_service()))
+}
+
+async fn root() -> &'static str {
+    "Hello, World"
+}
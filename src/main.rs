@@ -3,152 +3,114 @@
 //! ```not_rust
 //! cargo run --release
 //! ```
-//! 
-//! To run integration tests (using testtasks.db) run with
-//! 
+//!
+//! Database administration (see `backup` module for configuration):
+//!
 //! ```not_rust
-//! cargo test 
+//! cargo run --release -- backup          # online backup, works while the server is running
+//! cargo run --release -- backups         # list backups, newest first
+//! cargo run --release -- prune           # delete old backups by retention policy
+//! cargo run --release -- restore <name>  # verify and restore a backup, stop the server first!
 //! ```
-//! 
-//! Note: since our database IS stateful we need to run one test at a time
-//! which we implement by locking a tokio::sync::Mutex and holding a tokio::sync::MutexGuard for the lifetime of each test
-
-use axum::{
-    extract::Extension,
-    routing::{delete, get, post, put},
-    Router,
-};
-use std::net::SocketAddr;
-use tower_http::trace::TraceLayer;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-
-// sqlx
-use anyhow::Context;
-use sqlx::{Pool, Sqlite, sqlite::{SqlitePoolOptions, SqliteConnectOptions, SqliteJournalMode}, ConnectOptions};
-use sqlx::Connection;
-use std::str::FromStr;
-use std::env;
-
-mod controllers;
-mod models;
-
-#[cfg(test)]
-mod tests;
-#[macro_use]
-extern crate lazy_static;
+//!
+//! Tasks in the todo.txt format (see `formats::todotxt`):
+//!
+//! ```not_rust
+//! cargo run --release -- export-todo > todo.txt
+//! cargo run --release -- import-todo todo.txt  # prints the lines which were rejected
+//! ```
+//!
+//! To run integration tests run with
+//!
+//! ```not_rust
+//! cargo test
+//! ```
+//!
+//! Every testcase starts its own server on a free port (or drives the router in-process)
+//! with its own in-memory store, so tests are isolated from each other and run in parallel
 
+use axum_crud_api::formats::{self, todotxt};
+use axum_crud_api::{backup, repository, telemetry, Config};
+use futures::TryStreamExt;
+use std::io::Write;
 
-lazy_static! {    
-    pub static ref DATABASE_URL: String = env::var("DATABASE_URL").unwrap_or_else(|_| if cfg!(test) {
-        "sqlite:testtasks.db"
-     } else {
-        "sqlite:tasks.db"
-    }.to_string());
-}
-
-// openAPI doc
-use utoipa::{
-    OpenApi,
-};
-use utoipa_swagger_ui::SwaggerUi;
+const USAGE: &str =
+    "usage: axum_crud_api [serve | backup | backups | prune | restore <backup name> \
+                     | export-todo | import-todo <file>]";
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    run().await?;
-    Ok(())
+    telemetry::init_tracing();
+
+    let config = Config::from_env()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        [] | ["serve"] => axum_crud_api::run(config).await,
+        ["backup"] => create_backup(&config).await,
+        ["backups"] => list_backups(&config),
+        ["prune"] => prune_backups(&config),
+        ["restore", name] => backup::restore_backup(&config.backup, name, &config.database_url)
+            .await
+            .map_err(anyhow::Error::from),
+        ["export-todo"] => export_todo(&config).await,
+        ["import-todo", file] => import_todo(&config, file).await,
+        _ => Err(anyhow::anyhow!(USAGE)),
+    };
+
+    telemetry::shutdown_tracing();
+    result
 }
 
-// we extract main logic into run to re-use it in testcases
-async fn run() -> anyhow::Result<()>{
-    #[derive(OpenApi)]
-    #[openapi(
-        paths(
-            controllers::task::all_tasks,
-            controllers::task::new_task,
-            controllers::task::task,
-            controllers::task::update_task,
-            controllers::task::delete_task,
-            
-        ),
-        components(
-            schemas(models::task::Task,models::task::NewTask, models::task::UpdateTask)
-        ),
-        tags(
-            (name = "task", description = "Tasks management API")
-        )
-    )]
-    struct ApiDoc;
-
-    init_tracing();
-    
-    let pool = prepare_database().await?;
-
-    // build our application with a route
-    let app = Router::new()
-         // openAPI doc under: http://127.0.0.1:3000/swagger-ui
-        .merge(SwaggerUi::new("/swagger-ui/*tail").url("/api-doc/openapi.json", ApiDoc::openapi()))
-        .route("/hello", get(root))
-        .route("/tasks", get(controllers::task::all_tasks))
-        .route("/tasks", post(controllers::task::new_task))
-        .route("/tasks/:id", get(controllers::task::task))
-        .route("/tasks/:id", put(controllers::task::update_task))
-        .route("/tasks/:id", delete(controllers::task::delete_task))
-        .layer(Extension(pool))
-        .layer(TraceLayer::new_for_http());
-
-    // run it
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-    tracing::debug!("Listening on {}", addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await?;
-
+async fn create_backup(config: &Config) -> anyhow::Result<()> {
+    let repo = repository::connect(&config.database_url).await?;
+    let backup = backup::create_backup(repo.as_ref(), &config.backup).await?;
+    println!("{}", backup.name);
     Ok(())
 }
 
-async fn root() -> &'static str {
-    "Hello, World"
+fn list_backups(config: &Config) -> anyhow::Result<()> {
+    for backup in backup::list_backups(&config.backup.dir)? {
+        println!(
+            "{}\t{}\t{} bytes",
+            backup.name,
+            backup.created_at.to_rfc3339(),
+            backup.size_bytes
+        );
+    }
+    Ok(())
 }
 
-
-fn init_tracing(){
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("tower_http=debug,axum_crud_api=debug")
-                .unwrap_or_else(|_| if cfg!(test) {
-                    "tower_http=error"
-                } else {
-                    "axum_crud_api=debug,tower_http=debug"
-                }.into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+fn prune_backups(config: &Config) -> anyhow::Result<()> {
+    let report = backup::prune_backups(&config.backup)?;
+    for backup in report.removed {
+        println!("removed {}", backup.name);
+    }
+    println!("kept {} backups", report.kept);
+    Ok(())
 }
 
-/** Create database "tasks.db" in current directory if it does not exist.
-   Create schema (invoke migrations).
-   Return a database pool (sqlx - sqlite)
-   In test configuration uses database name "testtasks.db" instead
- */
-async fn prepare_database() -> anyhow::Result<Pool<Sqlite>> {
-    
-    // create database if it does not exist 
-    let conn = SqliteConnectOptions::from_str(&DATABASE_URL)?
-    .journal_mode(SqliteJournalMode::Wal).create_if_missing(true)
-    .connect().await?;
-    conn.close();
-
-    // prepare connection pool
-    let pool = SqlitePoolOptions::new()
-        .max_connections(50)
-        .connect(&DATABASE_URL)
-        .await
-        .context("could not connect to database_url")?;
-
-    // prepare schema in db if it does not yet exist
-    sqlx::migrate!().run(&pool).await?;
-
-    
+async fn export_todo(config: &Config) -> anyhow::Result<()> {
+    let repo = repository::connect(&config.database_url).await?;
+    let mut out = std::io::stdout().lock();
+    let mut tasks = repo.task_stream();
+    while let Some(task) = tasks.try_next().await? {
+        out.write_all(todotxt::task_line(&task).as_bytes())?;
+    }
+    Ok(())
+}
 
-    Ok(pool)
+async fn import_todo(config: &Config, file: &str) -> anyhow::Result<()> {
+    let rows = todotxt::parse_tasks(&std::fs::read(file)?)?;
+    let repo = repository::connect(&config.database_url).await?;
+    let report = formats::import_rows(repo.as_ref(), rows).await?;
+    for row in &report.rejected {
+        eprintln!("{}:{}: {}", file, row.line, row.reason);
+    }
+    println!(
+        "imported {} tasks, rejected {} lines",
+        report.accepted.len(),
+        report.rejected.len()
+    );
+    Ok(())
 }
@@ -1 +1,9 @@
+pub mod backup;
+pub mod board;
+pub mod comment;
+pub mod error;
+pub mod import;
+pub mod list;
+pub mod tag;
 pub mod task;
+pub mod workflow;
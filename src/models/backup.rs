@@ -0,0 +1,23 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+// swagger openapi
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct Backup {
+    #[schema(example = "backup-20221117T120000.000Z.db.gz")]
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    #[schema(example = 16384)]
+    pub size_bytes: u64,
+    /// gzip compressed
+    pub compressed: bool,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct PruneReport {
+    /// backups deleted by the retention policy
+    pub removed: Vec<Backup>,
+    /// backups still available
+    pub kept: usize,
+}
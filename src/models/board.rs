@@ -0,0 +1,135 @@
+use async_graphql::{InputObject, SimpleObject};
+use serde::{Deserialize, Serialize};
+// swagger openapi
+use utoipa::ToSchema;
+
+use super::task::{RankAnchors, Task, TaskStatus};
+
+/// Length limit of the name of a board or a column (varchar(255) in the database)
+pub const MAX_BOARD_NAME_LENGTH: usize = 255;
+
+/// A kanban board, see `crate::boards`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema, SimpleObject)]
+pub struct Board {
+    pub id: i64,
+    #[schema(example = "Release")]
+    pub name: String,
+    /// the board shows the tasks of this list only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_id: Option<i64>,
+    /// left to right
+    pub columns: Vec<BoardColumn>,
+}
+
+/// A column of a board, showing the tasks with its status; workflow states with that status
+/// share the column
+#[derive(
+    Clone, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::FromRow, ToSchema, SimpleObject,
+)]
+pub struct BoardColumn {
+    pub id: i64,
+    #[schema(example = "Doing")]
+    pub name: String,
+    /// tasks without a status are in the needs-action column
+    pub status: TaskStatus,
+    /// most tasks in the column, a card move beyond it is refused
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 3)]
+    pub wip_limit: Option<i64>,
+}
+
+/// Fields of a board, to create or replace it
+#[derive(Default, Deserialize, Serialize, ToSchema, InputObject)]
+pub struct NewBoard {
+    #[schema(example = "Release")]
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_id: Option<i64>,
+    /// left to right, every status in one column at most
+    pub columns: Vec<NewBoardColumn>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, InputObject)]
+pub struct NewBoardColumn {
+    #[schema(example = "Doing")]
+    pub name: String,
+    pub status: TaskStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 3)]
+    pub wip_limit: Option<i64>,
+}
+
+impl NewBoard {
+    /// Error message if the board can not be stored
+    pub fn validate(&self) -> Result<(), String> {
+        let names = std::iter::once(&self.name).chain(self.columns.iter().map(|c| &c.name));
+        for name in names {
+            if name.trim().is_empty() {
+                return Err("name must not be empty".to_string());
+            } else if name.chars().count() > MAX_BOARD_NAME_LENGTH {
+                return Err(format!(
+                    "name is longer than {} characters",
+                    MAX_BOARD_NAME_LENGTH
+                ));
+            }
+        }
+        if self.columns.is_empty() {
+            return Err("a board needs at least one column".to_string());
+        }
+        for (i, column) in self.columns.iter().enumerate() {
+            if self.columns[..i].iter().any(|c| c.status == column.status) {
+                return Err(format!(
+                    "status {} is in more than one column",
+                    column.status
+                ));
+            } else if column.wip_limit.is_some_and(|limit| limit < 1) {
+                return Err("wip_limit must be at least 1".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A board with its tasks, returned by `GET /boards/{id}`
+#[derive(Deserialize, Serialize, ToSchema, SimpleObject)]
+pub struct BoardTasks {
+    pub id: i64,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_id: Option<i64>,
+    pub columns: Vec<ColumnTasks>,
+}
+
+/// A column of a board with its tasks in the manual order (see `crate::rank`)
+#[derive(Deserialize, Serialize, ToSchema, SimpleObject)]
+pub struct ColumnTasks {
+    pub id: i64,
+    pub name: String,
+    pub status: TaskStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wip_limit: Option<i64>,
+    pub tasks: Vec<Task>,
+}
+
+/// Body of `POST /boards/{id}/cards/{task_id}/move`
+#[derive(Default, Deserialize, Serialize, ToSchema)]
+pub struct CardMove {
+    /// the column the task is moved to, which gives it the status of the column
+    pub column_id: i64,
+    /// move the task right before this task, usually a card of the column
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<i64>,
+    /// move the task right after this task; without before and after the task keeps its rank
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<i64>,
+}
+
+impl CardMove {
+    /// The place of the task in the manual order, None if it keeps its rank
+    pub fn anchors(&self) -> Option<RankAnchors> {
+        (self.before.is_some() || self.after.is_some()).then_some(RankAnchors {
+            before: self.before,
+            after: self.after,
+        })
+    }
+}
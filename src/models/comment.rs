@@ -0,0 +1,148 @@
+use async_graphql::{InputObject, SimpleObject};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::database::{Database, HasValueRef};
+use sqlx::error::BoxDynError;
+use sqlx::{Decode, Type};
+// swagger openapi
+use utoipa::{IntoParams, ToSchema};
+
+/// Length limit of an author name (varchar(64) in the database), names longer than this are no
+/// mentions
+pub const MAX_AUTHOR_LENGTH: usize = 64;
+
+/// Length limit of a comment body
+pub const MAX_COMMENT_LENGTH: usize = 10_000;
+
+/// A comment on a task, see `crate::comments`
+#[derive(
+    Clone, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::FromRow, ToSchema, SimpleObject,
+)]
+pub struct Comment {
+    pub id: i64,
+    pub task_id: i64,
+    #[schema(example = "alice")]
+    pub author: String,
+    /// Markdown
+    #[schema(example = "@bob can you review the `release` branch?")]
+    pub body: String,
+    /// the names @mentioned in the body, in order of appearance
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[sqlx(try_from = "NamesColumn")]
+    #[schema(example = json!(["bob"]))]
+    pub mentions: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    /// author of the last edit, see `GET /tasks/{id}/comments/{comment_id}/history`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edited_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+/// Body of a new or edited comment
+#[derive(Default, Deserialize, Serialize, ToSchema, InputObject)]
+pub struct NewComment {
+    /// who writes the comment or the edit
+    #[schema(example = "alice")]
+    pub author: String,
+    /// Markdown, `@name` outside of code mentions `name`
+    #[schema(example = "@bob can you review the `release` branch?")]
+    pub body: String,
+}
+
+impl NewComment {
+    /// Error message if the comment can not be stored
+    pub fn validate(&self) -> Result<(), String> {
+        if self.author.trim().is_empty() {
+            Err("author must not be empty".to_string())
+        } else if self.author.chars().count() > MAX_AUTHOR_LENGTH {
+            Err(format!(
+                "author is longer than {} characters",
+                MAX_AUTHOR_LENGTH
+            ))
+        } else if self.body.trim().is_empty() {
+            Err("body must not be empty".to_string())
+        } else if self.body.chars().count() > MAX_COMMENT_LENGTH {
+            Err(format!(
+                "body is longer than {} characters",
+                MAX_COMMENT_LENGTH
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// An earlier version of an edited comment
+#[derive(
+    Clone, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::FromRow, ToSchema, SimpleObject,
+)]
+pub struct CommentVersion {
+    /// who wrote this version
+    pub author: String,
+    pub body: String,
+    pub written_at: DateTime<Utc>,
+}
+
+/// Notification event: `recipient` was @mentioned by `author` in a comment
+#[derive(
+    Clone, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::FromRow, ToSchema, SimpleObject,
+)]
+pub struct Notification {
+    /// ascending, see [`NotificationQuery::after`]
+    pub id: i64,
+    #[schema(example = "bob")]
+    pub recipient: String,
+    pub task_id: i64,
+    pub comment_id: i64,
+    #[schema(example = "alice")]
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Query of `GET /notifications`
+#[derive(Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NotificationQuery {
+    /// notifications of this name only
+    pub recipient: Option<String>,
+    /// notifications with a higher id only, the last id seen by a poller
+    pub after: Option<i64>,
+}
+
+/** Comma separated names as stored, e.g. the [`Comment::mentions`].
+  Only used to read them from a row.
+*/
+pub struct NamesColumn(String);
+
+impl From<NamesColumn> for Vec<String> {
+    fn from(NamesColumn(names): NamesColumn) -> Vec<String> {
+        names
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl<DB: Database> Type<DB> for NamesColumn
+where
+    String: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <String as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <String as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for NamesColumn
+where
+    String: Decode<'r, DB>,
+{
+    fn decode(value: <DB as HasValueRef<'r>>::ValueRef) -> Result<NamesColumn, BoxDynError> {
+        Ok(NamesColumn(String::decode(value)?))
+    }
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+// swagger openapi
+use utoipa::ToSchema;
+
+use crate::request_id;
+
+/// Body of all error responses
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    #[schema(example = "task not found")]
+    pub msg: String,
+    /// X-Request-Id of the failed request, quote it when reporting problems
+    #[schema(example = "5b0b4a8e-0b4f-4a3e-9a3c-8f2f0e0b9c1d")]
+    pub request_id: Option<String>,
+    /// the states the task can change to, with an invalid change of its workflow state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = json!(["review"]))]
+    pub allowed_states: Option<Vec<String>>,
+}
+
+impl ErrorResponse {
+    /// Error for the request currently being handled
+    pub fn new(msg: impl Into<String>) -> ErrorResponse {
+        ErrorResponse {
+            msg: msg.into(),
+            request_id: request_id::current(),
+            allowed_states: None,
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+// swagger openapi
+use utoipa::ToSchema;
+
+use super::task::Task;
+
+/// Outcome of a bulk import, rows are identified by their line in the uploaded file
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub accepted: Vec<AcceptedRow>,
+    pub rejected: Vec<RejectedRow>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct AcceptedRow {
+    #[schema(example = 2)]
+    pub line: u64,
+    pub task: Task,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct RejectedRow {
+    #[schema(example = 3)]
+    pub line: u64,
+    #[schema(example = "task must not be empty")]
+    pub reason: String,
+}
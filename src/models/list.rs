@@ -0,0 +1,66 @@
+use async_graphql::{InputObject, SimpleObject};
+use serde::{Deserialize, Serialize};
+// swagger openapi
+use utoipa::{IntoParams, ToSchema};
+
+/// Length limit of a list name (varchar(255) in the database)
+pub const MAX_LIST_NAME_LENGTH: usize = 255;
+
+/// A list (project) of tasks, every task is in at most one list
+#[derive(
+    Clone, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::FromRow, ToSchema, SimpleObject,
+)]
+pub struct TaskList {
+    pub id: i64,
+    #[schema(example = "Garden")]
+    pub name: String,
+    /// archived lists and their tasks are left out of the task list, see `TaskFilter::archived`
+    pub archived: bool,
+}
+
+/// Fields of a list, to create or rename it
+#[derive(Default, Deserialize, Serialize, ToSchema, InputObject)]
+pub struct NewTaskList {
+    #[schema(example = "Garden")]
+    pub name: String,
+}
+
+impl NewTaskList {
+    /// Error message if the list can not be stored
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            Err("name must not be empty".to_string())
+        } else if self.name.chars().count() > MAX_LIST_NAME_LENGTH {
+            Err(format!(
+                "name is longer than {} characters",
+                MAX_LIST_NAME_LENGTH
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Options of `DELETE /lists/{id}`
+#[derive(Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteListOptions {
+    /// delete the tasks of the list (with their subtasks) as well, otherwise a list with tasks
+    /// is not deleted
+    pub cascade: Option<bool>,
+}
+
+/// New list of a task and its subtasks
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct MoveToList {
+    /// null takes the task out of its list
+    pub list_id: Option<i64>,
+}
+
+/// Body of `PUT /lists/{id}/order`
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct ListOrder {
+    /// all tasks of the list, each once, in their new order
+    #[schema(example = json!([3, 1, 2]))]
+    pub task_ids: Vec<i64>,
+}
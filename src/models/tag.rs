@@ -0,0 +1,109 @@
+use async_graphql::{InputObject, SimpleObject};
+use serde::{Deserialize, Serialize};
+use sqlx::database::{Database, HasValueRef};
+use sqlx::error::BoxDynError;
+use sqlx::types::Json;
+use sqlx::{Decode, Type};
+// swagger openapi
+use utoipa::ToSchema;
+
+/// Length limit of a tag name
+pub const MAX_TAG_LENGTH: usize = 64;
+
+/** Key of a tag name: tag names are unique regardless of case (also beyond ASCII, e.g. "Äpfel"
+  and "äpfel") and tags are ordered by the key in byte order, the same in every backend
+*/
+pub fn name_key(name: &str) -> String {
+    name.to_lowercase()
+}
+
+/// A label which can be attached to any number of tasks
+#[derive(
+    Clone, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::FromRow, ToSchema, SimpleObject,
+)]
+pub struct Tag {
+    pub id: i64,
+    #[schema(example = "release")]
+    pub name: String,
+    /// `#rrggbb`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "#1e90ff")]
+    pub colour: Option<String>,
+}
+
+/// Fields of a tag, to create or replace it
+#[derive(Default, Deserialize, Serialize, ToSchema, InputObject)]
+pub struct NewTag {
+    /// unique
+    #[schema(example = "release")]
+    pub name: String,
+    /// `#rrggbb`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "#1e90ff")]
+    pub colour: Option<String>,
+}
+
+impl NewTag {
+    /// Error message if the tag can not be stored
+    pub fn validate(&self) -> Result<(), String> {
+        let colour = self.colour.as_deref().unwrap_or("#000000");
+        if self.name.trim().is_empty() {
+            Err("name must not be empty".to_string())
+        } else if self.name.trim() != self.name || self.name.contains(',') {
+            Err("name must not contain commas or start or end with spaces".to_string())
+        } else if self.name.chars().count() > MAX_TAG_LENGTH {
+            Err(format!("name is longer than {} characters", MAX_TAG_LENGTH))
+        } else if !(colour.len() == 7
+            && colour.starts_with('#')
+            && colour[1..].chars().all(|c| c.is_ascii_hexdigit()))
+        {
+            Err("colour must be like #rrggbb".to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A tag with the number of its tasks
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::FromRow, ToSchema)]
+pub struct TagCount {
+    pub id: i64,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colour: Option<String>,
+    /// number of tasks with the tag
+    pub tasks: i64,
+}
+
+/** The tags of a task as selected along with the task: a json array built by the database.
+  Only used to read [`Task::tags`](super::task::Task::tags) from a row.
+*/
+pub struct TagsColumn(Json<Vec<Tag>>);
+
+impl From<TagsColumn> for Vec<Tag> {
+    fn from(TagsColumn(Json(tags)): TagsColumn) -> Vec<Tag> {
+        tags
+    }
+}
+
+impl<DB: Database> Type<DB> for TagsColumn
+where
+    Json<Vec<Tag>>: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <Json<Vec<Tag>> as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <Json<Vec<Tag>> as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for TagsColumn
+where
+    Json<Vec<Tag>>: Decode<'r, DB>,
+{
+    fn decode(value: <DB as HasValueRef<'r>>::ValueRef) -> Result<TagsColumn, BoxDynError> {
+        Ok(TagsColumn(Json::decode(value)?))
+    }
+}
@@ -1,22 +1,498 @@
+use async_graphql::{Enum, InputObject, SimpleObject};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 // swagger openapi
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(sqlx::FromRow, Deserialize, Serialize, ToSchema)]
+use super::tag::{name_key, Tag, TagsColumn};
+
+/// Length limit of the task text (varchar(255) in the database)
+pub const MAX_TASK_LENGTH: usize = 255;
+
+/// Progress of a task, the values of the iCalendar VTODO STATUS property
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize, ToSchema, sqlx::Type, Enum,
+)]
+#[serde(rename_all = "kebab-case")]
+#[sqlx(type_name = "TEXT", rename_all = "kebab-case")]
+pub enum TaskStatus {
+    NeedsAction,
+    InProcess,
+    Completed,
+    Cancelled,
+}
+
+impl TaskStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::NeedsAction => "needs-action",
+            TaskStatus::InProcess => "in-process",
+            TaskStatus::Completed => "completed",
+            TaskStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TaskStatus {
+    type Err = String;
+
+    /// Case-insensitive, so the iCalendar spelling (`NEEDS-ACTION`) is accepted as well
+    fn from_str(status: &str) -> Result<TaskStatus, String> {
+        match status.to_ascii_lowercase().as_str() {
+            "needs-action" => Ok(TaskStatus::NeedsAction),
+            "in-process" => Ok(TaskStatus::InProcess),
+            "completed" => Ok(TaskStatus::Completed),
+            "cancelled" => Ok(TaskStatus::Cancelled),
+            _ => Err(format!("unknown status {:?}", status)),
+        }
+    }
+}
+
+/** A task. All fields but id and task are optional and left out of the json if not set.
+  The uid identifies the task in calendar apps (iCalendar UID), it is not part of the json api
+  and neither are the revision and the rank, which is given by the order of the task lists.
+  GraphQL has the uid, as deleted tasks are reported by uid.
+*/
+#[derive(sqlx::FromRow, Clone, Default, Deserialize, Serialize, ToSchema, SimpleObject)]
 pub struct Task {
     pub id: i64,
     #[schema(example = "Buy groceries")]
     pub task: String,
+    /// identifies the task in calendar apps (iCalendar UID) and in change notifications
+    #[serde(skip)]
+    pub uid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TaskStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_at: Option<DateTime<Utc>>,
+    /// time to send a reminder of the task, see `crate::reminders`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remind_at: Option<DateTime<Utc>>,
+    /// 1 (highest) to 9 (lowest)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1)]
+    pub priority: Option<i32>,
+    /// comma separated, like iCalendar CATEGORIES
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "shopping,home")]
+    pub categories: Option<String>,
+    /// recurrence rule, the value of an iCalendar RRULE
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "FREQ=WEEKLY;BYDAY=SA")]
+    pub rrule: Option<String>,
+    /// day the task was written down, like the creation date of todo.txt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_on: Option<NaiveDate>,
+    /// day the task was done, like the completion date of todo.txt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_on: Option<NaiveDate>,
+    /// the task this is a subtask of, changed with `PUT /tasks/{id}/parent`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<i64>,
+    /// the list the task is in, changed with `PUT /tasks/{id}/list`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_id: Option<i64>,
+    /// the workflow the task follows, changed with `PUT /tasks/{id}/workflow`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workflow_id: Option<i64>,
+    /// state of the task in its workflow, which decides its status
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "review")]
+    pub state: Option<String>,
+    /// place of the task in the manual order, see `crate::rank`
+    #[serde(skip)]
+    #[graphql(skip)]
+    pub rank: String,
+    /// archived along with its list
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    /// increased on every change, used as ETag by CalDAV
+    #[serde(skip)]
+    #[graphql(skip)]
+    pub revision: i64,
+    /// attached with `PUT /tasks/{id}/tags/{tag_id}`, ordered by name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[sqlx(try_from = "TagsColumn")]
+    pub tags: Vec<Tag>,
 }
 
-#[derive(sqlx::FromRow, Deserialize, Serialize, ToSchema)]
+#[derive(sqlx::FromRow, Clone, Default, Deserialize, Serialize, ToSchema, InputObject)]
 pub struct NewTask {
     #[schema(example = "Buy groceries")]
     pub task: String,
+    /// set by imports only, a new uid is generated if missing
+    #[serde(skip)]
+    #[graphql(skip)]
+    pub uid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TaskStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remind_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1)]
+    pub priority: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "shopping,home")]
+    pub categories: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "FREQ=WEEKLY;BYDAY=SA")]
+    pub rrule: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_on: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_on: Option<NaiveDate>,
+    /// create the task as subtask of this task
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<i64>,
+    /// set by imports only: the parent is the imported task with this index
+    #[serde(skip)]
+    #[graphql(skip)]
+    pub parent: Option<usize>,
+    /// create the task at the end of this list (ignored by imports)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_id: Option<i64>,
+    /// the task follows this workflow, starting in its first state (ignored by imports)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workflow_id: Option<i64>,
+}
+
+impl Task {
+    /// Neither completed nor cancelled
+    pub fn is_open(&self) -> bool {
+        !matches!(
+            self.status,
+            Some(TaskStatus::Completed | TaskStatus::Cancelled)
+        )
+    }
 }
 
-#[derive(Deserialize, Serialize, sqlx::FromRow, ToSchema)]
+/// Reason why a task with these fields can not be stored, the same for every api and import
+fn validate_task(task: &str, priority: Option<i32>) -> Result<(), String> {
+    if task.trim().is_empty() {
+        Err("task must not be empty".to_string())
+    } else if task.chars().count() > MAX_TASK_LENGTH {
+        Err(format!(
+            "task is longer than {} characters",
+            MAX_TASK_LENGTH
+        ))
+    } else if matches!(priority, Some(priority) if !(1..=9).contains(&priority)) {
+        Err("priority must be between 1 and 9".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+impl NewTask {
+    /// Reason why the task can not be stored, used by the apis and for rows of bulk imports
+    pub fn validate(&self) -> Result<(), String> {
+        validate_task(&self.task, self.priority)
+    }
+
+    /// The given uid or a new random one
+    pub fn uid_or_new(&self) -> String {
+        self.uid
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+    }
+
+    /// `task` replaced by this task (a re-import), which stays where it is in the hierarchy, its
+    /// list and the manual order and keeps its workflow, state and tags
+    pub fn replace(&self, task: &Task) -> Task {
+        Task {
+            task: self.task.clone(),
+            status: self.status,
+            due_at: self.due_at,
+            remind_at: self.remind_at,
+            priority: self.priority,
+            categories: self.categories.clone(),
+            rrule: self.rrule.clone(),
+            created_on: self.created_on,
+            completed_on: self.completed_on,
+            ..task.clone()
+        }
+    }
+}
+
+/// The fields of an [`UpdateTask`], which a patch can change
+const UPDATE_FIELDS: &[&str] = &[
+    "task",
+    "status",
+    "due_at",
+    "remind_at",
+    "priority",
+    "categories",
+    "rrule",
+    "created_on",
+    "completed_on",
+    "state",
+];
+
+/// Replaces all fields of a task, optional fields which are left out are cleared
+#[derive(Clone, Default, Deserialize, Serialize, sqlx::FromRow, ToSchema, InputObject)]
 pub struct UpdateTask {
     #[schema(example = "Buy many groceries")]
     pub task: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TaskStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remind_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 2)]
+    pub priority: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "shopping")]
+    pub categories: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "FREQ=WEEKLY;BYDAY=SA")]
+    pub rrule: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_on: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_on: Option<NaiveDate>,
+    /// new state of a task with a workflow, which decides its status; left out, the state
+    /// follows the status
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "done")]
+    pub state: Option<String>,
+}
+
+impl UpdateTask {
+    /// Reason why the task can not be stored, see [`NewTask::validate`]
+    pub fn validate(&self) -> Result<(), String> {
+        validate_task(&self.task, self.priority)
+    }
+
+    /// `task` with the fields of this update, its state only if the update names one
+    pub fn apply(&self, task: &Task) -> Task {
+        Task {
+            task: self.task.clone(),
+            status: self.status,
+            due_at: self.due_at,
+            remind_at: self.remind_at,
+            priority: self.priority,
+            categories: self.categories.clone(),
+            rrule: self.rrule.clone(),
+            created_on: self.created_on,
+            completed_on: self.completed_on,
+            state: self.state.clone().or_else(|| task.state.clone()),
+            ..task.clone()
+        }
+    }
+
+    /// This update with the fields of `patch`, a JSON merge patch (RFC 7396): null clears a
+    /// field, left out fields stay as they are, a field the update does not have is refused
+    pub fn patch(&self, patch: &serde_json::Value) -> Result<UpdateTask, String> {
+        let mut patched = serde_json::to_value(self).map_err(|err| err.to_string())?;
+        match (patched.as_object_mut(), patch.as_object()) {
+            (Some(fields), Some(patch)) => {
+                if let Some(name) = patch
+                    .keys()
+                    .find(|name| !UPDATE_FIELDS.contains(&name.as_str()))
+                {
+                    return Err(format!("unknown field `{}`", name));
+                }
+                for (name, value) in patch {
+                    match value {
+                        serde_json::Value::Null => fields.remove(name),
+                        value => fields.insert(name.clone(), value.clone()),
+                    };
+                }
+            }
+            _ => return Err("the patch must be an object".to_string()),
+        }
+        serde_json::from_value(patched).map_err(|err| err.to_string())
+    }
+}
+
+impl From<&Task> for UpdateTask {
+    /// The fields of `task` without its state, which then follows the status
+    fn from(task: &Task) -> Self {
+        UpdateTask {
+            task: task.task.clone(),
+            status: task.status,
+            due_at: task.due_at,
+            remind_at: task.remind_at,
+            priority: task.priority,
+            categories: task.categories.clone(),
+            rrule: task.rrule.clone(),
+            created_on: task.created_on,
+            completed_on: task.completed_on,
+            state: None,
+        }
+    }
+}
+
+/// Due date criteria relative to the current time, days are UTC days
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema, Enum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DueFilter {
+    /// due in the past and neither completed nor cancelled
+    Overdue,
+    /// due on the current day
+    Today,
+}
+
+/// Whether a task needs all or any of the tags of a [`TaskFilter`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema, Enum)]
+#[serde(rename_all = "kebab-case")]
+pub enum TagMatch {
+    #[default]
+    All,
+    Any,
+}
+
+/// Criteria of a task list, a task has to match all given ones
+#[derive(Default, Deserialize, InputObject, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TaskFilter {
+    pub status: Option<TaskStatus>,
+    /// one of the comma separated categories of the task, case-insensitive
+    pub category: Option<String>,
+    /// part of the task text, case-insensitive
+    pub search: Option<String>,
+    /// tasks due before this time
+    pub due_before: Option<DateTime<Utc>>,
+    /// tasks due at or after this time
+    pub due_after: Option<DateTime<Utc>>,
+    /// overdue tasks or tasks due today
+    pub due: Option<DueFilter>,
+    /// comma separated tag names, case-insensitive
+    pub tags: Option<String>,
+    /// whether a task needs all (default) or any of the `tags`
+    pub tag_match: Option<TagMatch>,
+    /// tasks of this list
+    pub list_id: Option<i64>,
+    /// archived tasks instead of the others
+    pub archived: Option<bool>,
+}
+
+impl TaskFilter {
+    pub fn matches(&self, task: &Task) -> bool {
+        self.matches_at(task, Utc::now())
+    }
+
+    /// Like [`matches`](Self::matches) with `now` as the current time
+    pub fn matches_at(&self, task: &Task, now: DateTime<Utc>) -> bool {
+        let due_at = task.due_at;
+        task.archived == self.archived.unwrap_or_default()
+            && self
+                .list_id
+                .is_none_or(|list_id| task.list_id == Some(list_id))
+            && self.status.is_none_or(|status| task.status == Some(status))
+            && self.category.as_deref().is_none_or(|category| {
+                task.categories
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split(',')
+                    .any(|candidate| candidate.trim().eq_ignore_ascii_case(category.trim()))
+            })
+            && self
+                .search
+                .as_deref()
+                .is_none_or(|search| task.task.to_lowercase().contains(&search.to_lowercase()))
+            && self
+                .due_before
+                .is_none_or(|before| due_at.is_some_and(|due_at| due_at < before))
+            && self
+                .due_after
+                .is_none_or(|after| due_at.is_some_and(|due_at| due_at >= after))
+            && self.due.is_none_or(|due| match due {
+                DueFilter::Overdue => due_at.is_some_and(|due_at| due_at < now) && task.is_open(),
+                DueFilter::Today => {
+                    due_at.is_some_and(|due_at| due_at.date_naive() == now.date_naive())
+                }
+            })
+            && self.tags.as_deref().is_none_or(|tags| {
+                let mut wanted = tags.split(',').map(str::trim).filter(|tag| !tag.is_empty());
+                let has_tag = |name: &str| {
+                    task.tags
+                        .iter()
+                        .any(|tag| name_key(&tag.name) == name_key(name))
+                };
+                match self.tag_match.unwrap_or_default() {
+                    TagMatch::All => wanted.all(has_tag),
+                    TagMatch::Any => wanted.any(has_tag),
+                }
+            })
+    }
+}
+
+/// Time range of an occurrence preview, see `controllers::task::occurrences`
+#[derive(Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OccurrenceRange {
+    /// first due time of the preview (default: now)
+    pub from: Option<DateTime<Utc>>,
+    /// last due time of the preview (default: a year after `from`)
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// One occurrence of a recurring task
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct Occurrence {
+    pub due_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remind_at: Option<DateTime<Utc>>,
+}
+
+/// What completing a task does to its open subtasks, see `crate::hierarchy`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema, Enum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SubtaskPolicy {
+    /// complete the open subtasks as well
+    Complete,
+    /// refuse to complete the task while it has open subtasks
+    Block,
+}
+
+/// Options of `PUT /tasks/{id}`
+#[derive(Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UpdateOptions {
+    /// what completing the task does to its open subtasks (default: nothing)
+    pub subtasks: Option<SubtaskPolicy>,
+}
+
+/// New parent of a task and its subtasks
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct MoveTask {
+    /// null makes the task a top-level task
+    pub parent_id: Option<i64>,
+}
+
+/// Place of a task in the manual order, next to one or between two other tasks
+#[derive(Default, Deserialize, Serialize, ToSchema)]
+pub struct RankAnchors {
+    /// move the task right before this task
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<i64>,
+    /// move the task right after this task
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<i64>,
+}
+
+/// Task `task_id` is blocked by task `blocker_id` until the blocker is completed or cancelled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Dependency {
+    pub task_id: i64,
+    pub blocker_id: i64,
+}
+
+/// Body of `POST /tasks/{id}/blockers`
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct NewBlocker {
+    /// the task which has to be done first
+    pub blocker_id: i64,
 }
@@ -0,0 +1,160 @@
+use async_graphql::SimpleObject;
+use serde::{Deserialize, Serialize};
+// swagger openapi
+use utoipa::ToSchema;
+
+use super::task::TaskStatus;
+
+/// Length limit of a workflow name (varchar(255) in the database)
+pub const MAX_WORKFLOW_NAME_LENGTH: usize = 255;
+
+/// Length limit of a state name (varchar(64) in the database)
+pub const MAX_STATE_LENGTH: usize = 64;
+
+/// Fields of a task a transition can require, see [`Transition::required_fields`]
+pub const REQUIRED_FIELDS: [&str; 6] = [
+    "due_at",
+    "remind_at",
+    "priority",
+    "categories",
+    "rrule",
+    "completed_on",
+];
+
+/// States a task can be in and the allowed transitions between them, see `crate::workflows`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema, SimpleObject)]
+pub struct Workflow {
+    pub id: i64,
+    #[schema(example = "Review")]
+    pub name: String,
+    /// the first state is the one of new tasks
+    pub states: Vec<WorkflowState>,
+    pub transitions: Vec<Transition>,
+}
+
+/// A state of a workflow, which gives its tasks a status
+#[derive(
+    Clone, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::FromRow, ToSchema, SimpleObject,
+)]
+pub struct WorkflowState {
+    #[schema(example = "review")]
+    pub name: String,
+    pub status: TaskStatus,
+}
+
+/// An allowed change of the state of a task
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema, SimpleObject)]
+pub struct Transition {
+    #[schema(example = "review")]
+    pub from: String,
+    #[schema(example = "done")]
+    pub to: String,
+    /// fields the task must have after the transition, see [`REQUIRED_FIELDS`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(example = json!(["completed_on"]))]
+    pub required_fields: Vec<String>,
+}
+
+impl Workflow {
+    pub fn state(&self, name: &str) -> Option<&WorkflowState> {
+        self.states.iter().find(|state| state.name == name)
+    }
+
+    pub fn transition(&self, from: &str, to: &str) -> Option<&Transition> {
+        self.transitions
+            .iter()
+            .find(|transition| transition.from == from && transition.to == to)
+    }
+
+    /// States a task in state `from` can change to, in the order of the transitions
+    pub fn next_states(&self, from: &str) -> Vec<String> {
+        self.transitions
+            .iter()
+            .filter(|transition| transition.from == from)
+            .map(|transition| transition.to.clone())
+            .collect()
+    }
+}
+
+/// Fields of a workflow, to create or replace it
+#[derive(Default, Deserialize, Serialize, ToSchema)]
+pub struct NewWorkflow {
+    #[schema(example = "Review")]
+    pub name: String,
+    /// the first state is the one of new tasks
+    pub states: Vec<WorkflowState>,
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+}
+
+impl NewWorkflow {
+    /// Error message if the workflow can not be stored
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        } else if self.name.chars().count() > MAX_WORKFLOW_NAME_LENGTH {
+            return Err(format!(
+                "name is longer than {} characters",
+                MAX_WORKFLOW_NAME_LENGTH
+            ));
+        } else if self.states.is_empty() {
+            return Err("a workflow needs at least one state".to_string());
+        }
+        for (i, state) in self.states.iter().enumerate() {
+            if state.name.trim().is_empty() {
+                return Err("state names must not be empty".to_string());
+            } else if state.name.chars().count() > MAX_STATE_LENGTH {
+                return Err(format!(
+                    "state names are at most {} characters",
+                    MAX_STATE_LENGTH
+                ));
+            } else if self.states[..i]
+                .iter()
+                .any(|other| other.name == state.name)
+            {
+                return Err(format!("state {} is defined twice", state.name));
+            }
+        }
+        for (i, transition) in self.transitions.iter().enumerate() {
+            let known = |name: &str| self.states.iter().any(|state| state.name == name);
+            if let Some(unknown) = [&transition.from, &transition.to]
+                .into_iter()
+                .find(|name| !known(name))
+            {
+                return Err(format!("transition to or from unknown state {}", unknown));
+            } else if transition.from == transition.to {
+                return Err(format!(
+                    "transition from {} to itself, a task can always stay in its state",
+                    transition.from
+                ));
+            } else if self.transitions[..i]
+                .iter()
+                .any(|other| other.from == transition.from && other.to == transition.to)
+            {
+                return Err(format!(
+                    "transition from {} to {} is defined twice",
+                    transition.from, transition.to
+                ));
+            } else if let Some(field) = transition
+                .required_fields
+                .iter()
+                .find(|field| !REQUIRED_FIELDS.contains(&field.as_str()))
+            {
+                return Err(format!(
+                    "unknown required field {}, known are {}",
+                    field,
+                    REQUIRED_FIELDS.join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Body of `PUT /tasks/{id}/workflow`
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct TaskWorkflow {
+    /// the task starts in the first state of the workflow, null takes the task out of its
+    /// workflow
+    pub workflow_id: Option<i64>,
+}
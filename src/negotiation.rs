@@ -0,0 +1,338 @@
+//! Content negotiation between JSON, MessagePack, CBOR and YAML.
+//!
+//! Handlers take the request body as [`Negotiated`], decoded by its `Content-Type`, and answer with
+//! [`Accepted::encode`] in the format preferred by the `Accept` header. Unsupported request bodies
+//! are rejected with 415, requests accepting none of the formats with 406. Error bodies stay JSON.
+
+use async_trait::async_trait;
+use axum::body::{Bytes, HttpBody};
+use axum::extract::{FromRequest, RequestParts};
+use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{BoxError, Json};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use utoipa::openapi::schema::RefOr;
+use utoipa::openapi::{Content, ContentBuilder, OpenApi, Ref, ResponseBuilder};
+use utoipa::Modify;
+
+use crate::models::error::ErrorResponse;
+
+/// Encodings of request and response bodies
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+    Yaml,
+}
+
+impl Format {
+    /// All formats, JSON first as it is the default
+    pub const ALL: [Format; 4] = [
+        Format::Json,
+        Format::MessagePack,
+        Format::Cbor,
+        Format::Yaml,
+    ];
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+            Format::Yaml => "application/yaml",
+        }
+    }
+
+    /// Format of a media type without parameters, including the unofficial names in use
+    pub fn from_media_type(media_type: &str) -> Option<Format> {
+        match media_type.to_ascii_lowercase().as_str() {
+            "application/json" => Some(Format::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Format::MessagePack)
+            }
+            "application/cbor" => Some(Format::Cbor),
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => {
+                Some(Format::Yaml)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Json => serde_json::to_vec(value).map_err(|err| err.to_string()),
+            // with field names, a struct as array would break on skipped optional fields
+            Format::MessagePack => rmp_serde::to_vec_named(value).map_err(|err| err.to_string()),
+            Format::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::ser::into_writer(value, &mut bytes).map_err(|err| err.to_string())?;
+                Ok(bytes)
+            }
+            Format::Yaml => serde_yaml::to_string(value)
+                .map(String::into_bytes)
+                .map_err(|err| err.to_string()),
+        }
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            Format::Json => serde_json::from_slice(bytes).map_err(|err| err.to_string()),
+            Format::MessagePack => rmp_serde::from_slice(bytes).map_err(|err| err.to_string()),
+            Format::Cbor => ciborium::de::from_reader(bytes).map_err(|err| err.to_string()),
+            Format::Yaml => serde_yaml::from_slice(bytes).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/** The media type out of `offered` the Accept header prefers, by quality and then by the
+  order of `offered`. Without Accept header the first one, None if none is acceptable.
+*/
+pub fn preferred<'a>(headers: &HeaderMap, offered: &[&'a str]) -> Option<&'a str> {
+    let ranges: Vec<(String, f32)> = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|accept| accept.to_str().ok())
+        .flat_map(|accept| accept.split(','))
+        .filter_map(media_range)
+        .collect();
+    if ranges.is_empty() {
+        return offered.first().copied();
+    }
+    let mut best: Option<(&str, f32)> = None;
+    for media_type in offered {
+        // the most specific matching range decides
+        let quality = ranges
+            .iter()
+            .filter_map(|(range, quality)| {
+                specificity(range, media_type).map(|specificity| (specificity, *quality))
+            })
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, quality)| quality);
+        if let Some(quality) = quality.filter(|quality| *quality > 0.0) {
+            if best.is_none_or(|(_, best)| quality > best) {
+                best = Some((media_type, quality));
+            }
+        }
+    }
+    best.map(|(media_type, _)| media_type)
+}
+
+/// Lower case media range and its quality (`q`, 1 by default)
+fn media_range(range: &str) -> Option<(String, f32)> {
+    let mut parts = range.split(';');
+    let media_range = parts.next()?.trim().to_ascii_lowercase();
+    if media_range.is_empty() {
+        return None;
+    }
+    let quality = parts
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+        .and_then(|(_, quality)| quality.trim().parse().ok())
+        .unwrap_or(1.0);
+    Some((media_range, quality))
+}
+
+/// How specific `range` matches `media_type`: 2 exactly (also by another name of the format),
+/// 1 by subtype wildcard, 0 by `*/*`
+fn specificity(range: &str, media_type: &str) -> Option<u8> {
+    if range == "*/*" {
+        return Some(0);
+    }
+    let type_ = media_type.split('/').next().unwrap_or_default();
+    match range.split_once('/') {
+        Some((range_type, "*")) if range_type == type_ => Some(1),
+        _ if range == media_type || same_format(range, media_type) => Some(2),
+        _ => None,
+    }
+}
+
+fn same_format(range: &str, media_type: &str) -> bool {
+    Format::from_media_type(range).is_some_and(|format| format.media_type() == media_type)
+}
+
+/// Format of the response, chosen by the Accept header (406 if none is acceptable)
+#[derive(Clone, Copy, Debug)]
+pub struct Accepted(pub Format);
+
+impl Accepted {
+    /// Response with the value in the accepted format
+    pub fn encode<T: Serialize>(&self, value: T) -> Encoded<T> {
+        Encoded(self.0, value)
+    }
+
+    /// The accepted format out of the Accept header, None if none is acceptable
+    pub fn from_headers(headers: &HeaderMap) -> Option<Accepted> {
+        let offered = Format::ALL.map(|format| format.media_type());
+        preferred(headers, &offered)
+            .and_then(Format::from_media_type)
+            .map(Accepted)
+    }
+}
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for Accepted {
+    type Rejection = Response;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        Accepted::from_headers(req.headers()).ok_or_else(not_acceptable)
+    }
+}
+
+/// Answer to an Accept header which lists none of the offered formats
+pub fn not_acceptable() -> Response {
+    let offered: Vec<&str> = Format::ALL.iter().map(Format::media_type).collect();
+    (
+        StatusCode::NOT_ACCEPTABLE,
+        Json(ErrorResponse::new(format!(
+            "acceptable media types are {}",
+            offered.join(", ")
+        ))),
+    )
+        .into_response()
+}
+
+/// Response body, serialized in the given format
+pub struct Encoded<T>(pub Format, pub T);
+
+impl<T: Serialize> IntoResponse for Encoded<T> {
+    fn into_response(self) -> Response {
+        let Encoded(format, value) = self;
+        match format.serialize(&value) {
+            Ok(bytes) => (
+                [(CONTENT_TYPE, HeaderValue::from_static(format.media_type()))],
+                bytes,
+            )
+                .into_response(),
+            Err(err) => {
+                tracing::error!(error = %err, media_type = format.media_type(), "could not encode response");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse::new("could not encode response")),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+/** Request body, decoded by its Content-Type. Other types are rejected with 415,
+  bodies which can not be decoded with 400 (422 for JSON of the wrong shape, as `axum::Json` does).
+*/
+pub struct Negotiated<T>(pub T);
+
+#[async_trait]
+impl<T, B> FromRequest<B> for Negotiated<T>
+where
+    T: DeserializeOwned,
+    B: HttpBody + Send,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let content_type = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .and_then(|content_type| content_type.split(';').next())
+            .map(str::trim)
+            .unwrap_or_default();
+        let format = match Format::from_media_type(content_type) {
+            Some(format) => format,
+            None => {
+                let msg = format!("unsupported Content-Type {:?}", content_type);
+                return Err((
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    Json(ErrorResponse::new(msg)),
+                )
+                    .into_response());
+            }
+        };
+        let bytes = Bytes::from_request(req)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let status = match format {
+            // like axum::Json, well-formed JSON of the wrong shape is 422
+            Format::Json => match serde_json::from_slice::<T>(&bytes) {
+                Ok(value) => return Ok(Negotiated(value)),
+                Err(err) if err.is_data() => StatusCode::UNPROCESSABLE_ENTITY,
+                Err(_) => StatusCode::BAD_REQUEST,
+            },
+            _ => StatusCode::BAD_REQUEST,
+        };
+        format.deserialize(&bytes).map(Negotiated).map_err(|err| {
+            let msg = format!("invalid {} body: {}", format.media_type(), err);
+            (status, Json(ErrorResponse::new(msg))).into_response()
+        })
+    }
+}
+
+/** Adds the alternative media types to the OpenAPI spec of the negotiated `paths`:
+  every JSON request body and 2xx response is offered in all [`Format`]s, plus the 406 and 415 answers.
+*/
+pub struct NegotiatedMediaTypes {
+    pub paths: &'static [&'static str],
+}
+
+impl Modify for NegotiatedMediaTypes {
+    fn modify(&self, openapi: &mut OpenApi) {
+        for path in self.paths {
+            let item = match openapi.paths.paths.get_mut(*path) {
+                Some(item) => item,
+                None => continue,
+            };
+            for operation in item.operations.values_mut() {
+                let responses = &mut operation.responses.responses;
+                if let Some(body) = &mut operation.request_body {
+                    body.content
+                        .extend(alternatives(body.content.get(Format::Json.media_type())));
+                    responses.insert(
+                        "415".to_string(),
+                        error_response("Content-Type is none of the supported media types"),
+                    );
+                }
+                for (status, response) in responses.iter_mut() {
+                    if let (true, RefOr::T(response)) = (status.starts_with('2'), response) {
+                        response.content.extend(alternatives(
+                            response.content.get(Format::Json.media_type()),
+                        ));
+                    }
+                }
+                responses.insert(
+                    "406".to_string(),
+                    error_response("Accept lists none of the supported media types"),
+                );
+            }
+        }
+    }
+}
+
+/// The JSON content again for the other formats, they share the schema
+fn alternatives(json: Option<&Content>) -> Vec<(String, Content)> {
+    let json = match json {
+        Some(json) => json,
+        None => return Vec::new(),
+    };
+    Format::ALL[1..]
+        .iter()
+        .map(|format| (format.media_type().to_string(), json.clone()))
+        .collect()
+}
+
+fn error_response(description: &str) -> RefOr<utoipa::openapi::Response> {
+    RefOr::T(
+        ResponseBuilder::new()
+            .description(description)
+            .content(
+                Format::Json.media_type(),
+                ContentBuilder::new()
+                    .schema(Ref::from_schema_name("ErrorResponse"))
+                    .build(),
+            )
+            .build(),
+    )
+}
@@ -0,0 +1,118 @@
+//! Manual order of the tasks: every task has a rank, a key of the digits `0-9a-z` which sorts
+//! byte by byte, and the tasks are listed in the order of their ranks (ties by id).
+//!
+//! A rank is read as a base 36 fraction, `"i"` is one half and trailing zeros do not count.
+//! There is always a key between two different ranks, so moving a task (`POST /tasks/{id}/move`)
+//! only changes the rank of that task. Moving tasks between the same neighbours again and again
+//! makes the keys longer, when one would be longer than [`MAX_RANK_LENGTH`] the repositories
+//! give all tasks short keys again with [`spread`].
+
+/// Longest rank handed out by [`between`]
+pub const MAX_RANK_LENGTH: usize = 32;
+
+const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+const BASE: u8 = DIGITS.len() as u8;
+
+/// Digits of the keys appended by [`after`], so that appends only get longer after
+/// 36^4 / 2 of them
+const APPEND_WIDTH: usize = 4;
+
+fn digits(rank: &str) -> Vec<u8> {
+    rank.trim_end_matches('0')
+        .bytes()
+        .map(|digit| DIGITS.iter().position(|known| *known == digit).unwrap_or(0) as u8)
+        .collect()
+}
+
+fn rank(digits: &[u8]) -> String {
+    let len = digits.len() - digits.iter().rev().take_while(|digit| **digit == 0).count();
+    digits[..len]
+        .iter()
+        .map(|digit| DIGITS[*digit as usize] as char)
+        .collect()
+}
+
+/** A rank after `lower` and before `upper`, a missing bound is the start or the end of the
+  order. None if there is no such rank of at most [`MAX_RANK_LENGTH`] digits, e.g. because the
+  bounds are equal: the caller spreads the ranks and tries again.
+*/
+pub fn between(lower: Option<&str>, upper: Option<&str>) -> Option<String> {
+    let low = digits(lower.unwrap_or_default());
+    let high = upper.map(digits);
+    let digit = |digits: &[u8], i: usize| digits.get(i).copied().unwrap_or(0);
+    let mut key = Vec::new();
+    let mut bound = high.as_deref();
+    for i in 0..MAX_RANK_LENGTH {
+        let l = digit(&low, i);
+        let h = match bound {
+            Some(high) => *high.get(i)?,
+            None => BASE,
+        };
+        if h < l {
+            return None;
+        } else if h == l {
+            // common prefix
+            key.push(l);
+        } else if h - l > 1 {
+            key.push((l + h) / 2);
+            break;
+        } else if bound.is_some_and(|high| high.len() > i + 1) {
+            // `upper` goes on after this digit, so the digit alone is less
+            key.push(h);
+            break;
+        } else {
+            // any continuation of `lower` is less than `upper`
+            key.push(l);
+            bound = None;
+        }
+    }
+    let key = rank(&key);
+    let lower_ok = lower.is_none_or(|lower| lower < key.as_str());
+    let upper_ok = upper.is_none_or(|upper| key.as_str() < upper);
+    (key.len() <= MAX_RANK_LENGTH && lower_ok && upper_ok).then_some(key)
+}
+
+/** The rank of a task appended after the task ranked `last` (or of the first task).
+  Unlike [`between`] this counts up, so that the ranks grow slowly when tasks are added
+  one after another.
+*/
+pub fn after(last: Option<&str>) -> String {
+    let last = match last {
+        Some(last) => last,
+        None => return rank(&[BASE / 2]),
+    };
+    let mut key = digits(last);
+    key.resize(key.len().max(APPEND_WIDTH), 0);
+    match key.iter().rposition(|digit| *digit + 1 < BASE) {
+        Some(i) => {
+            key[i] += 1;
+            key.truncate(i + 1);
+        }
+        // all digits are z
+        None => key.push(1),
+    }
+    rank(&key)
+}
+
+/// `count` ranks in ascending order, evenly spread and as short as possible while every two
+/// of them are at least 36 apart in their last digit
+pub fn spread(count: usize) -> Vec<String> {
+    let slots = count as u128 + 1;
+    let mut width = 1;
+    let mut range = BASE as u128;
+    while range < slots * BASE as u128 {
+        width += 1;
+        range *= BASE as u128;
+    }
+    (1..slots)
+        .map(|slot| {
+            let mut value = slot * range / slots;
+            let mut key = vec![0; width];
+            for digit in key.iter_mut().rev() {
+                *digit = (value % BASE as u128) as u8;
+                value /= BASE as u128;
+            }
+            rank(&key)
+        })
+        .collect()
+}
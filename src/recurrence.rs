@@ -0,0 +1,388 @@
+//! Recurring tasks: the supported subset of the iCalendar RRULE (RFC 5545) and the next
+//! occurrence created when a recurring task is completed.
+//!
+//! Supported are `FREQ=DAILY`, `FREQ=WEEKLY` (optionally `BYDAY=MO,WE,...`), `FREQ=MONTHLY`
+//! (optionally `BYMONTHDAY=1,15,-1`), `INTERVAL`, and either `COUNT` or `UNTIL`. The series
+//! starts at the `due_at` of the task, which is its first occurrence, and every occurrence
+//! keeps its time of day (UTC). Months without a day of `BYMONTHDAY` are skipped, like RFC 5545
+//! demands.
+//!
+//! Other rules (e.g. `FREQ=YEARLY` from a calendar app) are stored as they are, but the tasks
+//! do not recur. Tasks completed through CalDAV or imports do not recur either, calendar apps
+//! handle the recurrence of their tasks themselves.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::task::{NewTask, Task, TaskStatus};
+
+/// Most occurrences returned by one preview
+pub const MAX_OCCURRENCES: usize = 1000;
+
+/// Longest range of one preview in days (ten years)
+pub const MAX_OCCURRENCE_DAYS: i64 = 10 * 366;
+
+/// Most occurrences one preview steps through, counting the ones between the `due_at` of the
+/// task and the start of the range
+pub const MAX_OCCURRENCE_STEPS: usize = 100_000;
+
+/// Periods in a row without an occurrence after which a series is considered ended,
+/// e.g. `FREQ=MONTHLY;INTERVAL=12;BYMONTHDAY=30` starting in February
+const MAX_EMPTY_PERIODS: u32 = 1000;
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("MO", Weekday::Mon),
+    ("TU", Weekday::Tue),
+    ("WE", Weekday::Wed),
+    ("TH", Weekday::Thu),
+    ("FR", Weekday::Fri),
+    ("SA", Weekday::Sat),
+    ("SU", Weekday::Sun),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum End {
+    Never,
+    /// number of occurrences including the first one
+    Count(u32),
+    /// time of the last possible occurrence
+    Until(DateTime<Utc>),
+}
+
+/// A parsed RRULE of the supported subset
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    /// weekdays of a weekly rule, empty for the weekday of the first occurrence
+    pub by_day: Vec<Weekday>,
+    /// days of a monthly rule, negative ones count from the end of the month,
+    /// empty for the day of the first occurrence
+    pub by_month_day: Vec<i32>,
+    pub end: End,
+}
+
+impl FromStr for RecurrenceRule {
+    type Err = String;
+
+    /// Case-insensitive, an `RRULE:` prefix is accepted
+    fn from_str(rule: &str) -> Result<RecurrenceRule, String> {
+        let upper = rule.trim().to_ascii_uppercase();
+        let upper = upper.strip_prefix("RRULE:").unwrap_or(&upper);
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_day = Vec::new();
+        let mut by_month_day = Vec::new();
+        let mut count = None;
+        let mut until = None;
+        for part in upper.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("invalid RRULE part {:?}", part))?;
+            match name {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(format!("unsupported FREQ {}", value)),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .ok_or_else(|| format!("invalid INTERVAL {:?}", value))?
+                }
+                "BYDAY" => {
+                    by_day = value
+                        .split(',')
+                        .map(|day| {
+                            WEEKDAYS
+                                .iter()
+                                .find(|(name, _)| *name == day)
+                                .map(|(_, weekday)| *weekday)
+                                .ok_or_else(|| format!("unsupported BYDAY {:?}", day))
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+                "BYMONTHDAY" => {
+                    by_month_day = value
+                        .split(',')
+                        .map(|day| {
+                            day.parse()
+                                .ok()
+                                .filter(|day: &i32| (1..=31).contains(&day.abs()))
+                                .ok_or_else(|| format!("invalid BYMONTHDAY {:?}", day))
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+                "COUNT" => {
+                    count = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|count| *count > 0)
+                            .ok_or_else(|| format!("invalid COUNT {:?}", value))?,
+                    )
+                }
+                "UNTIL" => until = Some(parse_until(value)?),
+                // weeks start on Monday, the only supported start
+                "WKST" if value == "MO" => {}
+                _ => return Err(format!("unsupported RRULE part {}", part)),
+            }
+        }
+        let frequency = frequency.ok_or("RRULE has no FREQ")?;
+        if !by_day.is_empty() && frequency != Frequency::Weekly {
+            return Err("BYDAY is only supported with FREQ=WEEKLY".to_string());
+        }
+        if !by_month_day.is_empty() && frequency != Frequency::Monthly {
+            return Err("BYMONTHDAY is only supported with FREQ=MONTHLY".to_string());
+        }
+        let end = match (count, until) {
+            (Some(_), Some(_)) => return Err("RRULE has both COUNT and UNTIL".to_string()),
+            (Some(count), None) => End::Count(count),
+            (None, Some(until)) => End::Until(until),
+            (None, None) => End::Never,
+        };
+        Ok(RecurrenceRule {
+            frequency,
+            interval,
+            by_day,
+            by_month_day,
+            end,
+        })
+    }
+}
+
+/// `YYYYMMDD` (the whole day) or `YYYYMMDDTHHMMSSZ`
+fn parse_until(value: &str) -> Result<DateTime<Utc>, String> {
+    let invalid = || format!("invalid UNTIL {:?}", value);
+    if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid())?;
+        let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).expect("valid time");
+        Ok(Utc.from_utc_datetime(&date.and_time(end_of_day)))
+    } else {
+        let time = value.strip_suffix('Z').ok_or_else(invalid)?;
+        chrono::NaiveDateTime::parse_from_str(time, "%Y%m%dT%H%M%S")
+            .map(|time| Utc.from_utc_datetime(&time))
+            .map_err(|_| invalid())
+    }
+}
+
+impl fmt::Display for RecurrenceRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<&str> = self
+                .by_day
+                .iter()
+                .filter_map(|day| {
+                    WEEKDAYS
+                        .iter()
+                        .find(|(_, weekday)| weekday == day)
+                        .map(|(name, _)| *name)
+                })
+                .collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if !self.by_month_day.is_empty() {
+            let days: Vec<String> = self.by_month_day.iter().map(i32::to_string).collect();
+            write!(f, ";BYMONTHDAY={}", days.join(","))?;
+        }
+        match self.end {
+            End::Never => Ok(()),
+            End::Count(count) => write!(f, ";COUNT={}", count),
+            End::Until(until) => write!(f, ";UNTIL={}", until.format("%Y%m%dT%H%M%SZ")),
+        }
+    }
+}
+
+impl RecurrenceRule {
+    /// Occurrences of the series starting at `start` (the first occurrence) in ascending order
+    pub fn occurrences(&self, start: DateTime<Utc>) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        let mut period = 0;
+        let mut empty_periods = 0;
+        let mut pending = vec![start];
+        let mut emitted = 0;
+        std::iter::from_fn(move || {
+            while pending.is_empty() && empty_periods < MAX_EMPTY_PERIODS {
+                pending = self
+                    .period(start, period)
+                    .into_iter()
+                    .filter(|time| *time > start)
+                    .rev()
+                    .collect();
+                period += 1;
+                empty_periods = if pending.is_empty() {
+                    empty_periods + 1
+                } else {
+                    0
+                };
+            }
+            let next = pending.pop()?;
+            emitted += 1;
+            match self.end {
+                End::Count(count) if emitted > count => None,
+                End::Until(until) if next > until => None,
+                _ => Some(next),
+            }
+        })
+    }
+
+    /// The occurrence after `current` in the series starting at `current`, with the rule the
+    /// next occurrence continues the series with (one less COUNT), None if the series ends
+    pub fn next(&self, current: DateTime<Utc>) -> Option<(DateTime<Utc>, RecurrenceRule)> {
+        let next = self.occurrences(current).nth(1)?;
+        let mut rule = self.clone();
+        if let End::Count(count) = &mut rule.end {
+            *count -= 1;
+        }
+        Some((next, rule))
+    }
+
+    /** Candidates of the `index`th period (day, week or month) after the one of `start`, sorted.
+      Empty beyond the last date chrono supports, which ends the series.
+    */
+    fn period(&self, start: DateTime<Utc>, index: u32) -> Vec<DateTime<Utc>> {
+        let time = start.time();
+        let at = |date: NaiveDate| Utc.from_utc_datetime(&date.and_time(time));
+        let steps = match i64::from(index).checked_mul(i64::from(self.interval)) {
+            Some(steps) => steps,
+            None => return Vec::new(),
+        };
+        let date = start.date_naive();
+        let add_days = |date: NaiveDate, days: i64| {
+            Duration::try_days(days).and_then(|days| date.checked_add_signed(days))
+        };
+        match self.frequency {
+            Frequency::Daily => add_days(date, steps).map(at).into_iter().collect(),
+            Frequency::Weekly => {
+                let monday = Duration::try_weeks(steps).and_then(|weeks| {
+                    add_days(date, -i64::from(date.weekday().num_days_from_monday()))?
+                        .checked_add_signed(weeks)
+                });
+                let monday = match monday {
+                    Some(monday) => monday,
+                    None => return Vec::new(),
+                };
+                let mut days: Vec<u32> = if self.by_day.is_empty() {
+                    vec![date.weekday().num_days_from_monday()]
+                } else {
+                    self.by_day
+                        .iter()
+                        .map(|day| day.num_days_from_monday())
+                        .collect()
+                };
+                days.sort_unstable();
+                days.dedup();
+                days.into_iter()
+                    .filter_map(|day| add_days(monday, i64::from(day)))
+                    .map(at)
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months = match (i64::from(date.year()) * 12 + i64::from(date.month0()))
+                    .checked_add(steps)
+                {
+                    Some(months) => months,
+                    None => return Vec::new(),
+                };
+                let (year, month) = match i32::try_from(months.div_euclid(12)) {
+                    Ok(year) => (year, months.rem_euclid(12) as u32 + 1),
+                    Err(_) => return Vec::new(),
+                };
+                let days_in_month = match days_in_month(year, month) {
+                    Some(days) => days,
+                    None => return Vec::new(),
+                };
+                let by_month_day = if self.by_month_day.is_empty() {
+                    vec![date.day() as i32]
+                } else {
+                    self.by_month_day.clone()
+                };
+                let mut days: Vec<u32> = by_month_day
+                    .into_iter()
+                    .map(|day| {
+                        if day < 0 {
+                            days_in_month + day + 1
+                        } else {
+                            day
+                        }
+                    })
+                    .filter(|day| (1..=days_in_month).contains(day))
+                    .map(|day| day as u32)
+                    .collect();
+                days.sort_unstable();
+                days.dedup();
+                days.into_iter()
+                    .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+                    .map(at)
+                    .collect()
+            }
+        }
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> Option<i32> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+    Some((next - first).num_days() as i32)
+}
+
+/** The next occurrence of the recurring `task` as new task, None if the task does not recur,
+  has no due date or its series ended. The reminder keeps its distance to the due date, the
+  occurrence is added to the end of the list of the task.
+*/
+pub fn next_occurrence(task: &Task) -> Option<NewTask> {
+    let due_at = task.due_at?;
+    let rule: RecurrenceRule = match task.rrule.as_deref()?.parse() {
+        Ok(rule) => rule,
+        Err(err) => {
+            tracing::warn!(task_id = task.id, error = %err, "task does not recur");
+            return None;
+        }
+    };
+    let (next, rule) = rule.next(due_at)?;
+    Some(NewTask {
+        task: task.task.clone(),
+        uid: None,
+        status: Some(TaskStatus::NeedsAction),
+        due_at: Some(next),
+        remind_at: task
+            .remind_at
+            .and_then(|remind_at| remind_at.checked_add_signed(next - due_at)),
+        priority: task.priority,
+        categories: task.categories.clone(),
+        rrule: Some(rule.to_string()),
+        created_on: Some(Utc::now().date_naive()),
+        completed_on: None,
+        parent_id: task.parent_id,
+        parent: None,
+        // archived lists take no new tasks
+        list_id: task.list_id.filter(|_| !task.archived),
+        // the occurrence starts over in the first state of the workflow
+        workflow_id: task.workflow_id,
+    })
+}
@@ -0,0 +1,410 @@
+//! Reminders of tasks, sent at their `remind_at` by a scheduler running inside the server.
+//!
+//! Which reminders were sent is stored with the task, so reminders due while the server was
+//! down are sent after a restart and sent ones are not repeated. Setting a new `remind_at`
+//! schedules a new reminder, completed and cancelled tasks are not reminded of.
+//! The scheduler sleeps until the next reminder, but at most `REMINDER_POLL_SECS`, so reminders
+//! set in the meantime (by this or another server) are picked up. Several servers sharing a
+//! database may each send a reminder.
+//!
+//! A reminder is only recorded as sent when the notifier succeeds. A failed one is retried after
+//! a minute, then after twice as long as before up to an hour, until it is sent or no longer due
+//! (its task was completed or got a new `remind_at`). A notifier taking longer than
+//! `REMINDER_TIMEOUT_SECS` counts as failed, so it can not hold up the other reminders for long. The retries are counted by the server, so a
+//! restart retries right away. Delivery is at least once when the server stops between sending
+//! and recording a reminder.
+//!
+//! Configuration (environment):
+//! - `REMINDER_NOTIFIER` - `log` (default), `webhook` or `email`
+//! - `REMINDER_WEBHOOK_URL` - the webhook gets every reminder as json POST request
+//! - `REMINDER_SMTP_HOST` - mail server, connected with TLS on port 465
+//! - `REMINDER_SMTP_USER`, `REMINDER_SMTP_PASSWORD` - login at the mail server (optional)
+//! - `REMINDER_EMAIL_FROM`, `REMINDER_EMAIL_TO` - sender and recipient of the emails
+//! - `REMINDER_POLL_SECS` - longest sleep of the scheduler (default 1)
+//! - `REMINDER_TIMEOUT_SECS` - longest time to send one reminder (default 30)
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hyper::client::HttpConnector;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Method, Request, Uri};
+use hyper_tls::HttpsConnector;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::models::task::Task;
+use crate::repository::{self, DynTaskRepository};
+
+/// A reminder as passed to the [`Notifier`], posted as json by the [`WebhookNotifier`]
+#[derive(Clone, Serialize)]
+pub struct Reminder {
+    pub remind_at: DateTime<Utc>,
+    pub task: Task,
+}
+
+/// Delivers reminders, see [`LogNotifier`], [`WebhookNotifier`] and [`EmailNotifier`]
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, reminder: &Reminder) -> anyhow::Result<()>;
+}
+
+/// Writes reminders to the log (level info)
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    async fn notify(&self, reminder: &Reminder) -> anyhow::Result<()> {
+        tracing::info!(
+            task_id = reminder.task.id,
+            remind_at = %reminder.remind_at,
+            task = %reminder.task.task,
+            "reminder"
+        );
+        Ok(())
+    }
+}
+
+/// Posts reminders as json to a url, responses other than 2xx are errors
+pub struct WebhookNotifier {
+    url: Uri,
+    client: hyper::Client<HttpsConnector<HttpConnector>>,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: Uri) -> WebhookNotifier {
+        WebhookNotifier {
+            url,
+            client: hyper::Client::builder().build(HttpsConnector::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, reminder: &Reminder) -> anyhow::Result<()> {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(reminder)?))?;
+        let response = self.client.request(request).await?;
+        anyhow::ensure!(
+            response.status().is_success(),
+            "webhook answered {}",
+            response.status()
+        );
+        Ok(())
+    }
+}
+
+/// Sends reminders as plain text emails
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Mailbox,
+}
+
+impl EmailNotifier {
+    /// Sends through `smtp_host` with TLS (port 465), logged in with `credentials` if given
+    pub fn new(
+        smtp_host: &str,
+        credentials: Option<Credentials>,
+        from: Mailbox,
+        to: Mailbox,
+    ) -> anyhow::Result<EmailNotifier> {
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::relay(smtp_host)?;
+        if let Some(credentials) = credentials {
+            transport = transport.credentials(credentials);
+        }
+        Ok(EmailNotifier {
+            transport: transport.build(),
+            from,
+            to,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    async fn notify(&self, reminder: &Reminder) -> anyhow::Result<()> {
+        let mut body = reminder.task.task.clone();
+        if let Some(due_at) = reminder.task.due_at {
+            body.push_str(&format!("\n\ndue at {}", due_at.to_rfc3339()));
+        }
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(format!("Reminder: {}", reminder.task.task))
+            .body(body)?;
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
+
+/// Source of the current time, a mock clock makes the scheduler testable
+#[async_trait]
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Return once `now()` is at or after `deadline`
+    async fn sleep_until(&self, deadline: DateTime<Utc>);
+}
+
+/// The system time
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        // negative durations (deadline passed) do not convert and do not sleep
+        if let Ok(duration) = (deadline - Utc::now()).to_std() {
+            tokio::time::sleep(duration).await;
+        }
+    }
+}
+
+/// Not `Debug`, it holds the smtp password
+#[derive(Clone)]
+pub enum NotifierConfig {
+    Log,
+    Webhook(Uri),
+    Email {
+        smtp_host: String,
+        /// user and password
+        credentials: Option<(String, String)>,
+        from: Mailbox,
+        to: Mailbox,
+    },
+}
+
+#[derive(Clone)]
+pub struct ReminderConfig {
+    pub notifier: NotifierConfig,
+    /// longest sleep of the scheduler
+    pub poll_interval: Duration,
+    /// longest time the notifier may take for one reminder
+    pub timeout: Duration,
+}
+
+impl ReminderConfig {
+    pub fn from_env() -> anyhow::Result<ReminderConfig> {
+        let notifier = match env::var("REMINDER_NOTIFIER").as_deref() {
+            Ok("log") | Err(_) => NotifierConfig::Log,
+            Ok("webhook") => NotifierConfig::Webhook(
+                env::var("REMINDER_WEBHOOK_URL")
+                    .context("REMINDER_WEBHOOK_URL is missing")?
+                    .parse()?,
+            ),
+            Ok("email") => NotifierConfig::Email {
+                smtp_host: env::var("REMINDER_SMTP_HOST")
+                    .context("REMINDER_SMTP_HOST is missing")?,
+                credentials: env::var("REMINDER_SMTP_USER")
+                    .ok()
+                    .map(|user| (user, env::var("REMINDER_SMTP_PASSWORD").unwrap_or_default())),
+                from: env::var("REMINDER_EMAIL_FROM")
+                    .context("REMINDER_EMAIL_FROM is missing")?
+                    .parse()?,
+                to: env::var("REMINDER_EMAIL_TO")
+                    .context("REMINDER_EMAIL_TO is missing")?
+                    .parse()?,
+            },
+            Ok(notifier) => anyhow::bail!("unknown REMINDER_NOTIFIER {:?}", notifier),
+        };
+        let poll_interval = match env::var("REMINDER_POLL_SECS") {
+            Ok(secs) => Duration::from_secs(secs.parse()?),
+            Err(_) => repository::WATCH_INTERVAL,
+        };
+        let timeout = match env::var("REMINDER_TIMEOUT_SECS") {
+            Ok(secs) => Duration::from_secs(secs.parse()?),
+            Err(_) => Duration::from_secs(30),
+        };
+        Ok(ReminderConfig {
+            notifier,
+            poll_interval,
+            timeout,
+        })
+    }
+
+    pub fn notifier(&self) -> anyhow::Result<Arc<dyn Notifier>> {
+        Ok(match &self.notifier {
+            NotifierConfig::Log => Arc::new(LogNotifier),
+            NotifierConfig::Webhook(url) => Arc::new(WebhookNotifier::new(url.clone())),
+            NotifierConfig::Email {
+                smtp_host,
+                credentials,
+                from,
+                to,
+            } => Arc::new(EmailNotifier::new(
+                smtp_host,
+                credentials
+                    .clone()
+                    .map(|(user, password)| Credentials::new(user, password)),
+                from.clone(),
+                to.clone(),
+            )?),
+        })
+    }
+}
+
+/// Delay of the first retry of a failed reminder, doubled with every further failure
+const RETRY_DELAY_SECS: i64 = 60;
+/// Longest delay between two retries
+const MAX_RETRY_DELAY_SECS: i64 = 60 * 60;
+
+/// A reminder the notifier failed to send
+struct Retry {
+    remind_at: DateTime<Utc>,
+    failures: u32,
+    retry_at: DateTime<Utc>,
+}
+
+/// Sends the reminders of the tasks in `repo` through `notifier` when `clock` reaches them
+pub struct Scheduler {
+    repo: DynTaskRepository,
+    notifier: Arc<dyn Notifier>,
+    clock: Arc<dyn Clock>,
+    poll_interval: Duration,
+    /// longest time the notifier may take for one reminder
+    timeout: Duration,
+    /// failed reminders by task id, never locked across an await
+    retries: Mutex<HashMap<i64, Retry>>,
+}
+
+impl Scheduler {
+    pub fn new(
+        repo: DynTaskRepository,
+        notifier: Arc<dyn Notifier>,
+        clock: Arc<dyn Clock>,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> Scheduler {
+        Scheduler {
+            repo,
+            notifier,
+            clock,
+            poll_interval,
+            timeout,
+            retries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /** Send all reminders due now, apart from failed ones waiting for their retry, and return
+      how many were sent
+    */
+    pub async fn send_due(&self) -> repository::Result<usize> {
+        let now = self.clock.now();
+        let due = self.repo.due_reminders(now).await?;
+        let pending: Vec<Reminder> = {
+            let mut retries = self.retries.lock().unwrap();
+            // reminders which are no longer due are not retried
+            retries.retain(|id, retry| {
+                due.iter()
+                    .any(|task| task.id == *id && task.remind_at == Some(retry.remind_at))
+            });
+            due.into_iter()
+                .filter_map(|task| {
+                    let remind_at = task.remind_at?;
+                    let waiting = retries
+                        .get(&task.id)
+                        .is_some_and(|retry| retry.retry_at > now);
+                    (!waiting).then_some(Reminder { remind_at, task })
+                })
+                .collect()
+        };
+        let mut sent = 0;
+        for reminder in pending {
+            let (id, remind_at) = (reminder.task.id, reminder.remind_at);
+            let result = tokio::time::timeout(self.timeout, self.notifier.notify(&reminder))
+                .await
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out after {:?}", self.timeout)));
+            match result {
+                Ok(()) => {
+                    self.retries.lock().unwrap().remove(&id);
+                    self.repo.reminder_sent(id, remind_at).await?;
+                    sent += 1;
+                }
+                Err(err) => {
+                    let mut retries = self.retries.lock().unwrap();
+                    let failures = retries.get(&id).map_or(0, |retry| retry.failures) + 1;
+                    let retry_at = now + retry_delay(failures);
+                    tracing::error!(
+                        task_id = id,
+                        error = %err,
+                        failures,
+                        %retry_at,
+                        "could not send reminder"
+                    );
+                    retries.insert(
+                        id,
+                        Retry {
+                            remind_at,
+                            failures,
+                            retry_at,
+                        },
+                    );
+                }
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Earliest retry of a failed reminder
+    fn next_retry(&self) -> Option<DateTime<Utc>> {
+        let retries = self.retries.lock().unwrap();
+        retries.values().map(|retry| retry.retry_at).min()
+    }
+
+    /// Send reminders until the task is aborted, database errors are logged and retried
+    pub async fn run(self) {
+        let poll_interval = chrono::Duration::from_std(self.poll_interval)
+            .unwrap_or_else(|_| chrono::Duration::seconds(1));
+        loop {
+            let now = self.clock.now();
+            let mut wake_at = now + poll_interval;
+            // after an error the due reminders are still pending, wait a whole interval
+            match self.send_due().await {
+                Ok(_) => match self.repo.next_reminder().await {
+                    // a past one failed and waits for its retry
+                    Ok(Some(next)) if next > now => wake_at = wake_at.min(next),
+                    Ok(_) => {}
+                    Err(err) => tracing::error!(error = %err, "could not find next reminder"),
+                },
+                Err(err) => tracing::error!(error = %err, "could not send reminders"),
+            }
+            if let Some(retry_at) = self.next_retry() {
+                wake_at = wake_at.min(retry_at);
+            }
+            self.clock.sleep_until(wake_at).await;
+        }
+    }
+}
+
+/// Delay of the retry after the `failures`th failure in a row
+fn retry_delay(failures: u32) -> chrono::Duration {
+    let doublings = failures.saturating_sub(1).min(16);
+    chrono::Duration::seconds((RETRY_DELAY_SECS << doublings).min(MAX_RETRY_DELAY_SECS))
+}
+
+/// Run the scheduler of `config` on the system clock in the background
+pub fn spawn(repo: DynTaskRepository, config: &ReminderConfig) -> anyhow::Result<JoinHandle<()>> {
+    let scheduler = Scheduler::new(
+        repo,
+        config.notifier()?,
+        Arc::new(SystemClock),
+        config.poll_interval,
+        config.timeout,
+    );
+    Ok(tokio::spawn(scheduler.run()))
+}
@@ -0,0 +1,1402 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::stream;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+
+use super::{
+    anchor_not_found, anchors_reversed, board_column, board_list_not_found, card_status,
+    check_anchors, check_list_order, check_open_blockers, check_wip_limit, completing,
+    no_rank_left, no_workflow, not_on_board, on_board, state_in_use, subtask_list,
+    with_imported_parent, workflow_not_found, Changes, Precondition, RepositoryError, Result,
+    TaskRepository, TaskStream, DEPENDENCY_CYCLE, LIST_ARCHIVED, LIST_NOT_EMPTY, WORKFLOW_IN_USE,
+};
+use crate::models::board::{Board, BoardColumn, CardMove, NewBoard};
+use crate::models::comment::{Comment, CommentVersion, NewComment, Notification};
+use crate::models::list::{NewTaskList, TaskList};
+use crate::models::tag::{name_key, NewTag, Tag, TagCount};
+use crate::models::task::{
+    Dependency, NewTask, RankAnchors, SubtaskPolicy, Task, TaskStatus, UpdateTask,
+};
+use crate::models::workflow::{NewWorkflow, Workflow};
+use crate::rank;
+use crate::recurrence;
+use crate::workflows;
+
+/** Tasks kept in a map in process memory - nothing is persisted.
+  Every instance is an isolated store, which makes it a good fit for tests and demos.
+  Ids are never reused (like postgres, unlike sqlite after deleting the newest task).
+*/
+#[derive(Default)]
+pub struct MemoryTaskRepository {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    tasks: BTreeMap<i64, Task>,
+    /// task id by uid
+    uids: HashMap<String, i64>,
+    last_id: i64,
+    last_revision: i64,
+    /// revision of the delete by uid
+    tombstones: HashMap<String, i64>,
+    /// remind_at of the last reminder sent by task id
+    reminded: HashMap<i64, DateTime<Utc>>,
+    /// (task_id, blocker_id)
+    dependencies: BTreeSet<(i64, i64)>,
+    /// the tasks hold copies of their tags
+    tags: BTreeMap<i64, Tag>,
+    last_tag_id: i64,
+    lists: BTreeMap<i64, TaskList>,
+    last_list_id: i64,
+    boards: BTreeMap<i64, Board>,
+    last_board_id: i64,
+    last_column_id: i64,
+    workflows: BTreeMap<i64, Workflow>,
+    last_workflow_id: i64,
+    comments: BTreeMap<i64, Comment>,
+    last_comment_id: i64,
+    /// earlier versions by comment id
+    comment_versions: HashMap<i64, Vec<CommentVersion>>,
+    /// ordered by id
+    notifications: Vec<Notification>,
+    last_notification_id: i64,
+}
+
+impl MemoryState {
+    fn next_revision(&mut self) -> i64 {
+        self.last_revision += 1;
+        self.last_revision
+    }
+
+    /// Ids of task `id` and all its subtasks, empty if there is no such task
+    fn subtree(&self, id: i64) -> Vec<i64> {
+        let mut subtree: Vec<i64> = self
+            .tasks
+            .keys()
+            .copied()
+            .filter(|key| *key == id)
+            .collect();
+        let mut index = 0;
+        while let Some(parent) = subtree.get(index).copied() {
+            subtree.extend(
+                self.tasks
+                    .values()
+                    .filter(|task| task.parent_id == Some(parent))
+                    .map(|task| task.id),
+            );
+            index += 1;
+        }
+        subtree
+    }
+
+    /// Delete the tasks `ids` (with their subtasks, if they are not among `ids`), leaving tombstones
+    fn remove_tasks(&mut self, ids: &[i64]) {
+        let revision = self.next_revision();
+        for id in ids {
+            if let Some(deleted) = self.tasks.remove(id) {
+                self.uids.remove(&deleted.uid);
+                self.tombstones.insert(deleted.uid, revision);
+            }
+            self.reminded.remove(id);
+        }
+        self.dependencies
+            .retain(|(task_id, blocker_id)| !ids.contains(task_id) && !ids.contains(blocker_id));
+        let comments: Vec<i64> = self
+            .comments
+            .values()
+            .filter(|comment| ids.contains(&comment.task_id))
+            .map(|comment| comment.id)
+            .collect();
+        for id in comments {
+            self.remove_comment(id);
+        }
+    }
+
+    fn remove_comment(&mut self, id: i64) {
+        self.comments.remove(&id);
+        self.comment_versions.remove(&id);
+        self.notifications
+            .retain(|notification| notification.comment_id != id);
+    }
+
+    /// Comment `id` of task `task_id` or NotFound
+    fn task_comment(&self, task_id: i64, id: i64) -> Result<&Comment> {
+        self.comments
+            .get(&id)
+            .filter(|comment| comment.task_id == task_id)
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    /// Notify the names of `mentions` but the writer, which were not notified of `comment` yet
+    fn notify(
+        &mut self,
+        comment: &Comment,
+        writer: &str,
+        mentions: &[String],
+        now: DateTime<Utc>,
+    ) -> Vec<Notification> {
+        let mut notified = Vec::new();
+        for name in mentions {
+            let known = self.notifications.iter().any(|notification| {
+                notification.comment_id == comment.id && &notification.recipient == name
+            });
+            if name == writer || known {
+                continue;
+            }
+            self.last_notification_id += 1;
+            let notification = Notification {
+                id: self.last_notification_id,
+                recipient: name.clone(),
+                task_id: comment.task_id,
+                comment_id: comment.id,
+                author: writer.to_string(),
+                created_at: now,
+            };
+            self.notifications.push(notification.clone());
+            notified.push(notification);
+        }
+        notified
+    }
+
+    /// Invalid if there is no list `id`, Conflict if it is archived
+    fn check_list(&self, id: i64) -> Result<()> {
+        match self.lists.get(&id) {
+            None => Err(RepositoryError::Invalid("list not found".to_string())),
+            Some(list) if list.archived => {
+                Err(RepositoryError::Conflict(LIST_ARCHIVED.to_string()))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Rank of the last task in the manual order, None if there are no tasks
+    fn last_rank(&self) -> Option<String> {
+        self.tasks.values().map(|task| &task.rank).max().cloned()
+    }
+
+    /// Ranks of the tasks task `id` is moved between, see `TaskRepository::rank_task`
+    fn neighbour_ranks(
+        &self,
+        id: i64,
+        anchors: &RankAnchors,
+    ) -> Result<(Option<String>, Option<String>)> {
+        let anchor_rank = |anchor: i64| {
+            self.tasks
+                .get(&anchor)
+                .map(|task| task.rank.clone())
+                .ok_or_else(anchor_not_found)
+        };
+        let others = || self.tasks.values().filter(move |task| task.id != id);
+        match (anchors.after, anchors.before) {
+            (Some(after), Some(before)) => {
+                let lower = anchor_rank(after)?;
+                let upper = anchor_rank(before)?;
+                match (&lower, after) < (&upper, before) {
+                    true => Ok((Some(lower), Some(upper))),
+                    false => Err(anchors_reversed()),
+                }
+            }
+            (Some(after), None) => {
+                let lower = anchor_rank(after)?;
+                let upper = others()
+                    .map(|task| (&task.rank, task.id))
+                    .filter(|next| *next > (&lower, after))
+                    .min()
+                    .map(|(rank, _)| rank.clone());
+                Ok((Some(lower), upper))
+            }
+            (None, Some(before)) => {
+                let upper = anchor_rank(before)?;
+                let lower = others()
+                    .map(|task| (&task.rank, task.id))
+                    .filter(|previous| *previous < (&upper, before))
+                    .max()
+                    .map(|(rank, _)| rank.clone());
+                Ok((lower, Some(upper)))
+            }
+            (None, None) => Ok((None, None)),
+        }
+    }
+
+    /// Rank of task `id` moved between `anchors`, spreading the ranks if there is no room
+    fn anchored_rank(&mut self, id: i64, anchors: &RankAnchors) -> Result<String> {
+        let (lower, upper) = self.neighbour_ranks(id, anchors)?;
+        match rank::between(lower.as_deref(), upper.as_deref()) {
+            Some(rank) => Ok(rank),
+            None => {
+                self.spread_ranks();
+                let (lower, upper) = self.neighbour_ranks(id, anchors)?;
+                rank::between(lower.as_deref(), upper.as_deref()).ok_or_else(no_rank_left)
+            }
+        }
+    }
+
+    /// Give all tasks new, evenly spread ranks in the same order, see `rank::spread`
+    fn spread_ranks(&mut self) {
+        let mut tasks: Vec<(String, i64)> = self
+            .tasks
+            .values()
+            .map(|task| (task.rank.clone(), task.id))
+            .collect();
+        tasks.sort_unstable();
+        for ((_, id), rank) in tasks.iter().zip(rank::spread(tasks.len())) {
+            if let Some(task) = self.tasks.get_mut(id) {
+                task.rank = rank;
+            }
+        }
+    }
+
+    /// Whether task `id` is task `blocker_id` or (indirectly) blocks it
+    fn blocks(&self, id: i64, blocker_id: i64) -> bool {
+        let mut blocking = vec![blocker_id];
+        let mut index = 0;
+        while let Some(current) = blocking.get(index).copied() {
+            if current == id {
+                return true;
+            }
+            for (task_id, blocker_id) in &self.dependencies {
+                if *task_id == current && !blocking.contains(blocker_id) {
+                    blocking.push(*blocker_id);
+                }
+            }
+            index += 1;
+        }
+        false
+    }
+
+    /// Tasks with a reminder which was not sent yet, with the time of the reminder
+    fn pending_reminders(&self) -> impl Iterator<Item = (DateTime<Utc>, &Task)> {
+        self.tasks.values().filter_map(|task| {
+            let remind_at = task.remind_at?;
+            let sent = self.reminded.get(&task.id) == Some(&remind_at);
+            (task.is_open() && !sent).then_some((remind_at, task))
+        })
+    }
+
+    /// Tags ordered by [`name_key`]
+    fn sorted_tags(&self) -> Vec<Tag> {
+        let mut tags: Vec<Tag> = self.tags.values().cloned().collect();
+        tags.sort_by_key(|tag| name_key(&tag.name));
+        tags
+    }
+
+    /// Conflict if another tag than `id` has the name
+    fn check_tag_name(&self, id: i64, name: &str) -> Result<()> {
+        match self
+            .tags
+            .values()
+            .any(|tag| tag.id != id && name_key(&tag.name) == name_key(name))
+        {
+            true => Err(RepositoryError::Conflict(
+                "there is a tag with this name already".to_string(),
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// Replace (or with None remove) the copies of tag `id` in the tasks
+    fn update_tag_copies(&mut self, id: i64, tag: Option<&Tag>) {
+        let revision = self.next_revision();
+        for task in self.tasks.values_mut() {
+            if let Some(position) = task.tags.iter().position(|copy| copy.id == id) {
+                match tag {
+                    Some(tag) => task.tags[position] = tag.clone(),
+                    None => {
+                        task.tags.remove(position);
+                    }
+                }
+                task.tags.sort_by_key(|tag| name_key(&tag.name));
+                task.revision = revision;
+            }
+        }
+    }
+
+    /** Store board `id`, the columns of `board` take the ids of the `columns` with their status
+      or new ids. Invalid if there is no list `board.list_id`.
+    */
+    fn store_board(
+        &mut self,
+        id: i64,
+        board: &NewBoard,
+        columns: Vec<BoardColumn>,
+    ) -> Result<Board> {
+        if board
+            .list_id
+            .is_some_and(|list_id| !self.lists.contains_key(&list_id))
+        {
+            return Err(board_list_not_found());
+        }
+        let columns = board
+            .columns
+            .iter()
+            .map(|column| {
+                let id = match columns.iter().find(|old| old.status == column.status) {
+                    Some(old) => old.id,
+                    None => {
+                        self.last_column_id += 1;
+                        self.last_column_id
+                    }
+                };
+                BoardColumn {
+                    id,
+                    name: column.name.clone(),
+                    status: column.status,
+                    wip_limit: column.wip_limit,
+                }
+            })
+            .collect();
+        let board = Board {
+            id,
+            name: board.name.clone(),
+            list_id: board.list_id,
+            columns,
+        };
+        self.boards.insert(id, board.clone());
+        Ok(board)
+    }
+
+    /// Name and status of the first state of workflow `id`, Invalid if there is no such workflow
+    fn initial_state(&self, id: i64) -> Result<(String, TaskStatus)> {
+        let workflow = self.workflows.get(&id).ok_or_else(workflow_not_found)?;
+        let initial = workflow.states.first().ok_or_else(workflow_not_found)?;
+        Ok((initial.name.clone(), initial.status))
+    }
+
+    /// Insert a new task or replace the task with the same uid
+    fn upsert(&mut self, task: &NewTask) -> Result<Task> {
+        let uid = task.uid_or_new();
+        // a replaced task stays where it is in the hierarchy, its list and the manual order and
+        // keeps its tags, like in the sql backends
+        let stored = self
+            .uids
+            .get(&uid)
+            .and_then(|id| self.tasks.get(id))
+            .cloned();
+        if let Some(stored) = stored.as_ref().filter(|stored| stored.is_open()) {
+            // like update_task, an open task is not completed while it is blocked
+            if task.status == Some(TaskStatus::Completed) {
+                self.check_blockers(&[stored.id])?;
+            }
+        }
+        let task = match stored {
+            // CalDAV and imports give a status only, the state follows it
+            Some(stored) => self.follow_workflow(&stored, None, task.replace(&stored))?,
+            None => {
+                self.last_id += 1;
+                let new = Task {
+                    id: self.last_id,
+                    uid,
+                    parent_id: task.parent_id,
+                    rank: rank::after(self.last_rank().as_deref()),
+                    ..Task::default()
+                };
+                task.replace(&new)
+            }
+        };
+        let task = Task {
+            revision: self.next_revision(),
+            ..task
+        };
+        self.uids.insert(task.uid.clone(), task.id);
+        self.tasks.insert(task.id, task.clone());
+        Ok(task)
+    }
+
+    /// Insert `task` at the end of the manual order, see [`TaskRepository::create_task`]
+    fn insert(&mut self, task: &NewTask) -> Result<Task> {
+        let list_id = match task.parent_id {
+            Some(parent_id) => {
+                let parent = self
+                    .tasks
+                    .get(&parent_id)
+                    .ok_or_else(|| RepositoryError::Invalid("parent task not found".to_string()))?;
+                subtask_list(parent.list_id, task.list_id)?
+            }
+            None => task.list_id,
+        };
+        if let Some(list_id) = list_id {
+            self.check_list(list_id)?;
+        }
+        let initial = match task.workflow_id {
+            Some(workflow_id) => Some(self.initial_state(workflow_id)?),
+            None => None,
+        };
+        let mut created = self.upsert(task)?;
+        created.list_id = list_id;
+        if let Some((name, status)) = initial {
+            created.workflow_id = task.workflow_id;
+            created.state = Some(name);
+            created.status = Some(status);
+        }
+        self.tasks.insert(created.id, created.clone());
+        Ok(created)
+    }
+
+    /// Ids of the open subtasks of task `id` at any depth
+    fn open_subtasks(&self, id: i64) -> Vec<i64> {
+        self.subtree(id)
+            .into_iter()
+            .skip(1)
+            .filter(|id| self.tasks.get(id).is_some_and(Task::is_open))
+            .collect()
+    }
+
+    /** The open subtasks of task `id` at any depth completed on `completed_on`, those with a
+      workflow in the state their workflow changes to, see [`MemoryState::follow_workflow`]
+    */
+    fn completed_subtasks(&self, id: i64, completed_on: NaiveDate) -> Result<Vec<Task>> {
+        self.open_subtasks(id)
+            .into_iter()
+            .filter_map(|id| self.tasks.get(&id))
+            .map(|task| {
+                let completed = Task {
+                    status: Some(TaskStatus::Completed),
+                    completed_on: Some(completed_on),
+                    ..task.clone()
+                };
+                self.follow_workflow(task, None, completed)
+            })
+            .collect()
+    }
+
+    /** `task` `changed` to `state` or, if None, the state its status leads to, with a workflow
+      in the state it changes to and its status, see `workflows::transition`. Invalid if `state`
+      is given and the task follows no workflow.
+    */
+    fn follow_workflow(&self, task: &Task, state: Option<&str>, changed: Task) -> Result<Task> {
+        let workflow = match task.workflow_id.and_then(|id| self.workflows.get(&id)) {
+            Some(workflow) => workflow,
+            None if state.is_some() => return Err(no_workflow()),
+            None => return Ok(changed),
+        };
+        let to = workflows::transition(workflow, task, state, &changed)?;
+        Ok(Task {
+            status: Some(to.status),
+            state: Some(to.name.clone()),
+            ..changed
+        })
+    }
+
+    /// Conflict if one of the tasks `completing` is blocked by an open task which is not
+    /// completed along with it
+    fn check_blockers(&self, completing: &[i64]) -> Result<()> {
+        let open_blockers = self
+            .dependencies
+            .iter()
+            .filter(|(task_id, blocker_id)| {
+                completing.contains(task_id)
+                    && !completing.contains(blocker_id)
+                    && self.tasks.get(blocker_id).is_some_and(Task::is_open)
+            })
+            .map(|(_, blocker_id)| *blocker_id)
+            .collect();
+        check_open_blockers(open_blockers)
+    }
+
+    /// Insert the next occurrence of task `id`, which was just completed, see
+    /// `crate::recurrence::next_occurrence`
+    fn create_next_occurrence(&mut self, id: i64) -> Result<Option<Task>> {
+        let next = self.tasks.get(&id).and_then(recurrence::next_occurrence);
+        match next {
+            Some(next) => Ok(Some(self.insert(&next)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// `tasks` in the manual order, by rank and then by id
+fn in_rank_order(mut tasks: Vec<Task>) -> Vec<Task> {
+    tasks.sort_by(|a, b| (&a.rank, a.id).cmp(&(&b.rank, b.id)));
+    tasks
+}
+
+impl MemoryTaskRepository {
+    pub fn new() -> MemoryTaskRepository {
+        MemoryTaskRepository::default()
+    }
+}
+
+#[async_trait]
+impl TaskRepository for MemoryTaskRepository {
+    async fn all_tasks(&self) -> Result<Vec<Task>> {
+        let state = self.state.lock().unwrap();
+        Ok(in_rank_order(state.tasks.values().cloned().collect()))
+    }
+
+    fn task_stream(&self) -> TaskStream {
+        // a snapshot, the lock must not be held while the stream is consumed
+        let tasks = in_rank_order(self.state.lock().unwrap().tasks.values().cloned().collect());
+        Box::pin(stream::iter(tasks.into_iter().map(Ok)))
+    }
+
+    async fn create_task(&self, task: &NewTask) -> Result<Task> {
+        self.state.lock().unwrap().insert(task)
+    }
+
+    async fn import_tasks(&self, tasks: &[NewTask]) -> Result<Vec<Result<Task>>> {
+        // one lock for all inserts, so no other request sees a partial import
+        let mut state = self.state.lock().unwrap();
+        let mut rows = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            let task = with_imported_parent(task, &rows);
+            rows.push(task.and_then(|task| state.upsert(&task)));
+        }
+        Ok(rows)
+    }
+
+    async fn task(&self, id: i64) -> Result<Task> {
+        let state = self.state.lock().unwrap();
+        state
+            .tasks
+            .get(&id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn update_task(
+        &self,
+        id: i64,
+        task: &UpdateTask,
+        subtasks: Option<SubtaskPolicy>,
+    ) -> Result<Option<Task>> {
+        let mut state = self.state.lock().unwrap();
+        let previous = state.tasks.get(&id).ok_or(RepositoryError::NotFound)?;
+        let followed =
+            state.follow_workflow(previous, task.state.as_deref(), task.apply(previous))?;
+        let task = &UpdateTask {
+            status: followed.status,
+            state: followed.state,
+            ..task.clone()
+        };
+        let completes = task.status == Some(TaskStatus::Completed);
+        if completes {
+            let open = match subtasks {
+                Some(_) => state.open_subtasks(id),
+                None => Vec::new(),
+            };
+            state.check_blockers(&completing(previous, subtasks, open)?)?;
+        }
+        let completed_subtasks = match completes && subtasks == Some(SubtaskPolicy::Complete) {
+            true => {
+                let completed_on = task.completed_on.unwrap_or_else(|| Utc::now().date_naive());
+                state.completed_subtasks(id, completed_on)?
+            }
+            false => Vec::new(),
+        };
+        let recurs = completes && previous.status != Some(TaskStatus::Completed);
+        let revision = state.next_revision();
+        let stored = state.tasks.get_mut(&id).ok_or(RepositoryError::NotFound)?;
+        *stored = Task {
+            revision,
+            ..task.apply(stored)
+        };
+        for subtask in completed_subtasks {
+            state.tasks.insert(
+                subtask.id,
+                Task {
+                    revision,
+                    ..subtask
+                },
+            );
+        }
+        match recurs {
+            true => state.create_next_occurrence(id),
+            false => Ok(None),
+        }
+    }
+
+    async fn delete_task(&self, id: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let subtree = state.subtree(id);
+        if subtree.is_empty() {
+            return Err(RepositoryError::NotFound);
+        }
+        state.remove_tasks(&subtree);
+        Ok(())
+    }
+
+    async fn put_task(
+        &self,
+        task: &NewTask,
+        precondition: Precondition<'_>,
+    ) -> Result<(Task, bool)> {
+        let mut state = self.state.lock().unwrap();
+        let stored = state
+            .uids
+            .get(&task.uid_or_new())
+            .and_then(|id| state.tasks.get(id))
+            .map(|stored| stored.revision);
+        if !precondition(stored) {
+            return Err(RepositoryError::PreconditionFailed);
+        }
+        Ok((state.upsert(task)?, stored.is_none()))
+    }
+
+    async fn delete_task_by_uid(&self, uid: &str, precondition: Precondition<'_>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let stored = state
+            .uids
+            .get(uid)
+            .and_then(|id| state.tasks.get(id))
+            .ok_or(RepositoryError::NotFound)?;
+        if !precondition(Some(stored.revision)) {
+            return Err(RepositoryError::PreconditionFailed);
+        }
+        let subtree = state.subtree(stored.id);
+        state.remove_tasks(&subtree);
+        Ok(())
+    }
+
+    async fn task_by_uid(&self, uid: &str) -> Result<Task> {
+        let state = self.state.lock().unwrap();
+        state
+            .uids
+            .get(uid)
+            .and_then(|id| state.tasks.get(id))
+            .cloned()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn changes_since(&self, revision: i64) -> Result<Changes> {
+        let state = self.state.lock().unwrap();
+        let changed: Vec<Task> = state
+            .tasks
+            .values()
+            .filter(|task| task.revision > revision)
+            .cloned()
+            .collect();
+        let deleted = state
+            .tombstones
+            .iter()
+            .filter(|(uid, deleted)| **deleted > revision && !state.uids.contains_key(*uid))
+            .map(|(uid, _)| uid.clone())
+            .collect();
+        Ok(Changes {
+            revision: state.last_revision,
+            changed,
+            deleted,
+        })
+    }
+
+    async fn current_revision(&self) -> Result<i64> {
+        Ok(self.state.lock().unwrap().last_revision)
+    }
+
+    async fn due_reminders(&self, until: DateTime<Utc>) -> Result<Vec<Task>> {
+        let state = self.state.lock().unwrap();
+        let mut due: Vec<(DateTime<Utc>, &Task)> = state
+            .pending_reminders()
+            .filter(|(remind_at, _)| *remind_at <= until)
+            .collect();
+        due.sort_by_key(|(remind_at, task)| (*remind_at, task.id));
+        Ok(due.into_iter().map(|(_, task)| task.clone()).collect())
+    }
+
+    async fn next_reminder(&self) -> Result<Option<DateTime<Utc>>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .pending_reminders()
+            .map(|(remind_at, _)| remind_at)
+            .min())
+    }
+
+    async fn reminder_sent(&self, id: i64, remind_at: DateTime<Utc>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.tasks.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        state.reminded.insert(id, remind_at);
+        Ok(())
+    }
+
+    async fn children(&self, id: i64) -> Result<Vec<Task>> {
+        let state = self.state.lock().unwrap();
+        if !state.tasks.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(in_rank_order(
+            state
+                .tasks
+                .values()
+                .filter(|task| task.parent_id == Some(id))
+                .cloned()
+                .collect(),
+        ))
+    }
+
+    async fn subtree(&self, id: i64) -> Result<Vec<Task>> {
+        let state = self.state.lock().unwrap();
+        let subtree = state.subtree(id);
+        if subtree.is_empty() {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(in_rank_order(
+            subtree
+                .into_iter()
+                .filter_map(|id| state.tasks.get(&id).cloned())
+                .collect(),
+        ))
+    }
+
+    async fn move_task(&self, id: i64, parent_id: Option<i64>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let subtree = state.subtree(id);
+        if subtree.is_empty() {
+            return Err(RepositoryError::NotFound);
+        }
+        if let Some(parent_id) = parent_id {
+            if subtree.contains(&parent_id) {
+                return Err(RepositoryError::Conflict(
+                    "a task can not become a subtask of itself or of its subtasks".to_string(),
+                ));
+            }
+            if !state.tasks.contains_key(&parent_id) {
+                return Err(RepositoryError::Invalid(
+                    "parent task not found".to_string(),
+                ));
+            }
+        }
+        let revision = state.next_revision();
+        let task = state.tasks.get_mut(&id).ok_or(RepositoryError::NotFound)?;
+        task.parent_id = parent_id;
+        task.revision = revision;
+        Ok(())
+    }
+
+    async fn add_dependency(&self, id: i64, blocker_id: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.tasks.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        if !state.tasks.contains_key(&blocker_id) {
+            return Err(RepositoryError::Invalid(
+                "blocker task not found".to_string(),
+            ));
+        }
+        if state.blocks(id, blocker_id) {
+            return Err(RepositoryError::Conflict(DEPENDENCY_CYCLE.to_string()));
+        }
+        state.dependencies.insert((id, blocker_id));
+        Ok(())
+    }
+
+    async fn remove_dependency(&self, id: i64, blocker_id: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match state.dependencies.remove(&(id, blocker_id)) {
+            true => Ok(()),
+            false => Err(RepositoryError::NotFound),
+        }
+    }
+
+    async fn blockers(&self, id: i64) -> Result<Vec<Task>> {
+        let state = self.state.lock().unwrap();
+        if !state.tasks.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(state
+            .dependencies
+            .range((id, i64::MIN)..=(id, i64::MAX))
+            .filter_map(|(_, blocker_id)| state.tasks.get(blocker_id).cloned())
+            .collect())
+    }
+
+    async fn dependencies(&self) -> Result<Vec<Dependency>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .dependencies
+            .iter()
+            .map(|(task_id, blocker_id)| Dependency {
+                task_id: *task_id,
+                blocker_id: *blocker_id,
+            })
+            .collect())
+    }
+
+    async fn tags(&self) -> Result<Vec<Tag>> {
+        Ok(self.state.lock().unwrap().sorted_tags())
+    }
+
+    async fn tag_counts(&self) -> Result<Vec<TagCount>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .sorted_tags()
+            .into_iter()
+            .map(|tag| TagCount {
+                tasks: state
+                    .tasks
+                    .values()
+                    .filter(|task| task.tags.iter().any(|copy| copy.id == tag.id))
+                    .count() as i64,
+                id: tag.id,
+                name: tag.name,
+                colour: tag.colour,
+            })
+            .collect())
+    }
+
+    async fn tag(&self, id: i64) -> Result<Tag> {
+        let state = self.state.lock().unwrap();
+        state
+            .tags
+            .get(&id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn create_tag(&self, tag: &NewTag) -> Result<Tag> {
+        let mut state = self.state.lock().unwrap();
+        state.check_tag_name(0, &tag.name)?;
+        state.last_tag_id += 1;
+        let tag = Tag {
+            id: state.last_tag_id,
+            name: tag.name.clone(),
+            colour: tag.colour.clone(),
+        };
+        state.tags.insert(tag.id, tag.clone());
+        Ok(tag)
+    }
+
+    async fn update_tag(&self, id: i64, tag: &NewTag) -> Result<Tag> {
+        let mut state = self.state.lock().unwrap();
+        if !state.tags.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        state.check_tag_name(id, &tag.name)?;
+        let tag = Tag {
+            id,
+            name: tag.name.clone(),
+            colour: tag.colour.clone(),
+        };
+        state.tags.insert(id, tag.clone());
+        state.update_tag_copies(id, Some(&tag));
+        Ok(tag)
+    }
+
+    async fn delete_tag(&self, id: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.tags.remove(&id).ok_or(RepositoryError::NotFound)?;
+        state.update_tag_copies(id, None);
+        Ok(())
+    }
+
+    async fn attach_tag(&self, id: i64, tag_id: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let tag = state
+            .tags
+            .get(&tag_id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound)?;
+        if !state.tasks.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        let revision = state.next_revision();
+        let task = state.tasks.get_mut(&id).ok_or(RepositoryError::NotFound)?;
+        if !task.tags.iter().any(|copy| copy.id == tag_id) {
+            task.tags.push(tag);
+            task.tags.sort_by_key(|tag| name_key(&tag.name));
+            task.revision = revision;
+        }
+        Ok(())
+    }
+
+    async fn detach_tag(&self, id: i64, tag_id: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let revision = state.next_revision();
+        let task = state.tasks.get_mut(&id).ok_or(RepositoryError::NotFound)?;
+        let position = task
+            .tags
+            .iter()
+            .position(|copy| copy.id == tag_id)
+            .ok_or(RepositoryError::NotFound)?;
+        task.tags.remove(position);
+        task.revision = revision;
+        Ok(())
+    }
+
+    async fn lists(&self) -> Result<Vec<TaskList>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.lists.values().cloned().collect())
+    }
+
+    async fn list(&self, id: i64) -> Result<TaskList> {
+        let state = self.state.lock().unwrap();
+        state
+            .lists
+            .get(&id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn create_list(&self, list: &NewTaskList) -> Result<TaskList> {
+        let mut state = self.state.lock().unwrap();
+        state.last_list_id += 1;
+        let list = TaskList {
+            id: state.last_list_id,
+            name: list.name.clone(),
+            archived: false,
+        };
+        state.lists.insert(list.id, list.clone());
+        Ok(list)
+    }
+
+    async fn update_list(&self, id: i64, list: &NewTaskList) -> Result<TaskList> {
+        let mut state = self.state.lock().unwrap();
+        let stored = state.lists.get_mut(&id).ok_or(RepositoryError::NotFound)?;
+        stored.name = list.name.clone();
+        Ok(stored.clone())
+    }
+
+    async fn archive_list(&self, id: i64, archived: bool) -> Result<TaskList> {
+        let mut state = self.state.lock().unwrap();
+        let stored = state.lists.get_mut(&id).ok_or(RepositoryError::NotFound)?;
+        stored.archived = archived;
+        let list = stored.clone();
+        let list_tasks: Vec<i64> = state
+            .tasks
+            .values()
+            .filter(|task| task.list_id == Some(id))
+            .map(|task| task.id)
+            .collect();
+        let mut subtree = BTreeSet::new();
+        for id in list_tasks {
+            subtree.extend(state.subtree(id));
+        }
+        let revision = state.next_revision();
+        for id in subtree {
+            if let Some(task) = state.tasks.get_mut(&id) {
+                if task.archived != archived {
+                    task.archived = archived;
+                    task.revision = revision;
+                }
+            }
+        }
+        Ok(list)
+    }
+
+    async fn delete_list(&self, id: i64, cascade: bool) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.lists.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        let tasks: Vec<i64> = state
+            .tasks
+            .values()
+            .filter(|task| task.list_id == Some(id))
+            .map(|task| task.id)
+            .collect();
+        if !tasks.is_empty() && !cascade {
+            return Err(RepositoryError::Conflict(LIST_NOT_EMPTY.to_string()));
+        }
+        let mut doomed = BTreeSet::new();
+        for id in tasks {
+            doomed.extend(state.subtree(id));
+        }
+        state.remove_tasks(&doomed.into_iter().collect::<Vec<i64>>());
+        state.lists.remove(&id);
+        state.boards.retain(|_, board| board.list_id != Some(id));
+        Ok(())
+    }
+
+    async fn list_tasks(&self, id: i64) -> Result<Vec<Task>> {
+        let state = self.state.lock().unwrap();
+        if !state.lists.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(in_rank_order(
+            state
+                .tasks
+                .values()
+                .filter(|task| task.list_id == Some(id))
+                .cloned()
+                .collect(),
+        ))
+    }
+
+    async fn move_to_list(&self, id: i64, list_id: Option<i64>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let subtree = state.subtree(id);
+        if subtree.is_empty() {
+            return Err(RepositoryError::NotFound);
+        }
+        if subtree
+            .iter()
+            .any(|id| state.tasks.get(id).is_some_and(|task| task.archived))
+        {
+            return Err(RepositoryError::Conflict(LIST_ARCHIVED.to_string()));
+        }
+        if let Some(list_id) = list_id {
+            state.check_list(list_id)?;
+        }
+        // the task first, then its subtasks
+        let mut subtasks = subtree[1..].to_vec();
+        subtasks.sort_unstable();
+        let revision = state.next_revision();
+        for id in std::iter::once(id).chain(subtasks) {
+            let rank = rank::after(state.last_rank().as_deref());
+            if let Some(task) = state.tasks.get_mut(&id) {
+                task.list_id = list_id;
+                task.rank = rank;
+                task.revision = revision;
+            }
+        }
+        Ok(())
+    }
+
+    async fn reorder_list(&self, id: i64, task_ids: &[i64]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.lists.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        let tasks = in_rank_order(
+            state
+                .tasks
+                .values()
+                .filter(|task| task.list_id == Some(id))
+                .cloned()
+                .collect(),
+        );
+        let (ids, ranks): (Vec<i64>, Vec<String>) =
+            tasks.into_iter().map(|task| (task.id, task.rank)).unzip();
+        check_list_order(ids, task_ids)?;
+        // the rank is not part of a task (see Task), so the revisions stay
+        for (task_id, rank) in task_ids.iter().zip(ranks) {
+            if let Some(task) = state.tasks.get_mut(task_id) {
+                task.rank = rank;
+            }
+        }
+        Ok(())
+    }
+
+    async fn rank_task(&self, id: i64, anchors: &RankAnchors) -> Result<()> {
+        check_anchors(id, anchors)?;
+        let mut state = self.state.lock().unwrap();
+        if !state.tasks.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        let rank = state.anchored_rank(id, anchors)?;
+        if let Some(task) = state.tasks.get_mut(&id) {
+            task.rank = rank;
+        }
+        Ok(())
+    }
+
+    async fn boards(&self) -> Result<Vec<Board>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.boards.values().cloned().collect())
+    }
+
+    async fn board(&self, id: i64) -> Result<Board> {
+        let state = self.state.lock().unwrap();
+        state
+            .boards
+            .get(&id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn create_board(&self, board: &NewBoard) -> Result<Board> {
+        let mut state = self.state.lock().unwrap();
+        state.last_board_id += 1;
+        let id = state.last_board_id;
+        state.store_board(id, board, Vec::new())
+    }
+
+    async fn update_board(&self, id: i64, board: &NewBoard) -> Result<Board> {
+        let mut state = self.state.lock().unwrap();
+        let stored = state.boards.get(&id).ok_or(RepositoryError::NotFound)?;
+        let columns = stored.columns.clone();
+        state.store_board(id, board, columns)
+    }
+
+    async fn delete_board(&self, id: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match state.boards.remove(&id) {
+            Some(_) => Ok(()),
+            None => Err(RepositoryError::NotFound),
+        }
+    }
+
+    async fn move_card(
+        &self,
+        board_id: i64,
+        id: i64,
+        card: &CardMove,
+        completed_on: NaiveDate,
+    ) -> Result<()> {
+        let anchors = card.anchors();
+        if let Some(anchors) = &anchors {
+            check_anchors(id, anchors)?;
+        }
+        let mut state = self.state.lock().unwrap();
+        let board = state
+            .boards
+            .get(&board_id)
+            .ok_or(RepositoryError::NotFound)?;
+        let column = board_column(&board.columns, card.column_id)?;
+        let task = state.tasks.get(&id).ok_or(RepositoryError::NotFound)?;
+        if !on_board(board.list_id, task.list_id, task.archived) {
+            return Err(not_on_board());
+        }
+        let status = card_status(task.status, task.completed_on, column, completed_on);
+        let moved = match status {
+            Some((status, completed_on)) => {
+                let moved = Task {
+                    status: Some(status),
+                    completed_on,
+                    ..task.clone()
+                };
+                Some(state.follow_workflow(task, None, moved)?)
+            }
+            None => None,
+        };
+        let completes = status.is_some_and(|(status, _)| status == TaskStatus::Completed);
+        if completes && task.is_open() {
+            state.check_blockers(&[id])?;
+        }
+        if status.is_some() {
+            let cards = state
+                .tasks
+                .values()
+                .filter(|card| {
+                    card.id != id
+                        && on_board(board.list_id, card.list_id, card.archived)
+                        && card.status.unwrap_or(TaskStatus::NeedsAction) == column.status
+                })
+                .count();
+            check_wip_limit(column, cards as i64)?;
+        }
+        let rank = match &anchors {
+            Some(anchors) => Some(state.anchored_rank(id, anchors)?),
+            None => None,
+        };
+        let revision = match status {
+            Some(_) => state.next_revision(),
+            None => 0,
+        };
+        if let Some(task) = state.tasks.get_mut(&id) {
+            if let Some(moved) = moved {
+                task.status = moved.status;
+                task.completed_on = moved.completed_on;
+                task.state = moved.state;
+                task.revision = revision;
+            }
+            if let Some(rank) = rank {
+                task.rank = rank;
+            }
+        }
+        if completes {
+            state.create_next_occurrence(id)?;
+        }
+        Ok(())
+    }
+
+    async fn workflows(&self) -> Result<Vec<Workflow>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.workflows.values().cloned().collect())
+    }
+
+    async fn workflow(&self, id: i64) -> Result<Workflow> {
+        let state = self.state.lock().unwrap();
+        state
+            .workflows
+            .get(&id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn create_workflow(&self, workflow: &NewWorkflow) -> Result<Workflow> {
+        let mut state = self.state.lock().unwrap();
+        state.last_workflow_id += 1;
+        let workflow = Workflow {
+            id: state.last_workflow_id,
+            name: workflow.name.clone(),
+            states: workflow.states.clone(),
+            transitions: workflow.transitions.clone(),
+        };
+        state.workflows.insert(workflow.id, workflow.clone());
+        Ok(workflow)
+    }
+
+    async fn update_workflow(&self, id: i64, workflow: &NewWorkflow) -> Result<Workflow> {
+        let mut state = self.state.lock().unwrap();
+        if !state.workflows.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        let workflow = Workflow {
+            id,
+            name: workflow.name.clone(),
+            states: workflow.states.clone(),
+            transitions: workflow.transitions.clone(),
+        };
+        let mut changed = Vec::new();
+        for task in state.tasks.values() {
+            if task.workflow_id != Some(id) {
+                continue;
+            }
+            let name = task.state.as_deref().unwrap_or_default();
+            match workflow.state(name) {
+                None => return Err(state_in_use(name)),
+                Some(new) if task.status != Some(new.status) => changed.push((task.id, new.status)),
+                Some(_) => {}
+            }
+        }
+        if !changed.is_empty() {
+            let revision = state.next_revision();
+            for (task_id, status) in changed {
+                if let Some(task) = state.tasks.get_mut(&task_id) {
+                    task.status = Some(status);
+                    task.revision = revision;
+                }
+            }
+        }
+        state.workflows.insert(id, workflow.clone());
+        Ok(workflow)
+    }
+
+    async fn delete_workflow(&self, id: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.workflows.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        if state
+            .tasks
+            .values()
+            .any(|task| task.workflow_id == Some(id))
+        {
+            return Err(RepositoryError::Conflict(WORKFLOW_IN_USE.to_string()));
+        }
+        state.workflows.remove(&id);
+        Ok(())
+    }
+
+    async fn set_workflow(&self, id: i64, workflow_id: Option<i64>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.tasks.contains_key(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        let initial = match workflow_id {
+            Some(workflow_id) => Some(state.initial_state(workflow_id)?),
+            None => None,
+        };
+        let revision = state.next_revision();
+        if let Some(task) = state.tasks.get_mut(&id) {
+            task.workflow_id = workflow_id;
+            task.state = None;
+            if let Some((name, status)) = initial {
+                task.state = Some(name);
+                task.status = Some(status);
+            }
+            task.revision = revision;
+        }
+        Ok(())
+    }
+
+    async fn comments(&self, task_id: i64) -> Result<Vec<Comment>> {
+        let state = self.state.lock().unwrap();
+        if !state.tasks.contains_key(&task_id) {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(state
+            .comments
+            .values()
+            .filter(|comment| comment.task_id == task_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn comment(&self, task_id: i64, id: i64) -> Result<Comment> {
+        let state = self.state.lock().unwrap();
+        state.task_comment(task_id, id).cloned()
+    }
+
+    async fn create_comment(
+        &self,
+        task_id: i64,
+        comment: &NewComment,
+        mentions: &[String],
+        now: DateTime<Utc>,
+    ) -> Result<(Comment, Vec<Notification>)> {
+        let mut state = self.state.lock().unwrap();
+        if !state.tasks.contains_key(&task_id) {
+            return Err(RepositoryError::NotFound);
+        }
+        state.last_comment_id += 1;
+        let created = Comment {
+            id: state.last_comment_id,
+            task_id,
+            author: comment.author.clone(),
+            body: comment.body.clone(),
+            mentions: mentions.to_vec(),
+            created_at: now,
+            edited_by: None,
+            edited_at: None,
+        };
+        state.comments.insert(created.id, created.clone());
+        let notified = state.notify(&created, &comment.author, mentions, now);
+        Ok((created, notified))
+    }
+
+    async fn update_comment(
+        &self,
+        task_id: i64,
+        id: i64,
+        comment: &NewComment,
+        mentions: &[String],
+        now: DateTime<Utc>,
+    ) -> Result<(Comment, Vec<Notification>)> {
+        let mut state = self.state.lock().unwrap();
+        let previous = state.task_comment(task_id, id)?.clone();
+        state
+            .comment_versions
+            .entry(id)
+            .or_default()
+            .push(CommentVersion {
+                author: previous
+                    .edited_by
+                    .clone()
+                    .unwrap_or(previous.author.clone()),
+                body: previous.body.clone(),
+                written_at: previous.edited_at.unwrap_or(previous.created_at),
+            });
+        let updated = Comment {
+            body: comment.body.clone(),
+            mentions: mentions.to_vec(),
+            edited_by: Some(comment.author.clone()),
+            edited_at: Some(now),
+            ..previous
+        };
+        state.comments.insert(id, updated.clone());
+        let notified = state.notify(&updated, &comment.author, mentions, now);
+        Ok((updated, notified))
+    }
+
+    async fn delete_comment(&self, task_id: i64, id: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.task_comment(task_id, id)?;
+        state.remove_comment(id);
+        Ok(())
+    }
+
+    async fn comment_history(&self, task_id: i64, id: i64) -> Result<Vec<CommentVersion>> {
+        let state = self.state.lock().unwrap();
+        state.task_comment(task_id, id)?;
+        Ok(state.comment_versions.get(&id).cloned().unwrap_or_default())
+    }
+
+    async fn notifications(
+        &self,
+        recipient: Option<&str>,
+        after: i64,
+    ) -> Result<Vec<Notification>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .notifications
+            .iter()
+            .filter(|notification| notification.id > after)
+            .filter(|notification| recipient.is_none_or(|name| notification.recipient == name))
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::Context;
+use sqlx::postgres::{PgPoolOptions, PgQueryResult};
+use sqlx::Postgres;
+
+use super::sql::{Dialect, SqlTaskRepository};
+
+pub type PostgresTaskRepository = SqlTaskRepository<Postgres>;
+
+impl Dialect for Postgres {
+    const SYSTEM: &'static str = "postgresql";
+    const TAGS: &'static str =
+        "COALESCE((SELECT json_agg(json_build_object('id', tag.id, 'name', tag.name, \
+         'colour', tag.colour) ORDER BY tag.name_key COLLATE \"C\") \
+         FROM tag JOIN task_tag ON task_tag.tag_id = tag.id WHERE task_tag.task_id = task.id), \
+         '[]')";
+    /// Concurrent transactions can commit their revisions out of order, so a sync client can
+    /// miss a change committed just after it synced - it shows up with the next change of that
+    /// task or the next full sync.
+    const NEXT_REVISION: &'static str = "nextval('task_revision')";
+    const FOR_UPDATE: &'static str = " FOR UPDATE";
+    const IS_DISTINCT_FROM: &'static str = "IS DISTINCT FROM";
+    const COLLATE: &'static str = " COLLATE \"C\"";
+    const JSON_IDS: &'static str = "SELECT value::bigint FROM json_array_elements_text($1::json)";
+    /// Unlike sqlite all writers can run concurrently
+    const LOCKS: Option<(&'static str, &'static str)> = Some((
+        "SELECT pg_advisory_xact_lock(hashtext($1))",
+        "SELECT pg_advisory_xact_lock_shared(hashtext($1))",
+    ));
+    const SNAPSHOT: Option<&'static str> = Some("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ");
+    const BACKUP: Option<&'static str> = None;
+
+    fn rows_affected(result: &PgQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
+impl PostgresTaskRepository {
+    /** Connect to an existing PostgreSQL database, create schema (invoke migrations)
+      and return a repository using a connection pool (sqlx - postgres).
+      Unlike sqlite all writers can run concurrently.
+    */
+    pub async fn connect(database_url: &str) -> anyhow::Result<PostgresTaskRepository> {
+        let pool = PgPoolOptions::new()
+            .max_connections(50)
+            .connect(database_url)
+            .await
+            .context("could not connect to database_url")?;
+
+        sqlx::migrate!("migrations/postgres").run(&pool).await?;
+
+        Ok(PostgresTaskRepository::new(pool))
+    }
+}
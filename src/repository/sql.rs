@@ -0,0 +1,2129 @@
+use async_stream::try_stream;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::TryStreamExt;
+use sqlx::database::HasArguments;
+use sqlx::{
+    ColumnIndex, Database, Decode, Encode, Executor, FromRow, IntoArguments, Pool, Transaction,
+    Type,
+};
+use std::path::Path;
+use tracing::Instrument;
+
+use super::{
+    anchor_not_found, anchors_reversed, board_column, board_list_not_found, card_status,
+    check_anchors, check_list_order, check_open_blockers, check_wip_limit, completing,
+    fields_column, fields_from_column, imported, no_rank_left, no_workflow, not_on_board, on_board,
+    parent_not_found, state_in_use, subtask_list, tag_name_taken, with_imported_parent,
+    workflow_not_found, Changes, Precondition, RepositoryError, Result, TaskRepository, TaskStream,
+    DEPENDENCY_CYCLE, LIST_ARCHIVED, LIST_NOT_EMPTY, WORKFLOW_IN_USE,
+};
+use crate::models::board::{Board, BoardColumn, CardMove, NewBoard};
+use crate::models::comment::{Comment, CommentVersion, NewComment, Notification};
+use crate::models::list::{NewTaskList, TaskList};
+use crate::models::tag::{name_key, NewTag, Tag, TagCount};
+use crate::models::task::{
+    Dependency, NewTask, RankAnchors, SubtaskPolicy, Task, TaskStatus, UpdateTask,
+};
+use crate::models::workflow::{NewWorkflow, Transition, Workflow};
+use crate::rank;
+use crate::recurrence;
+use crate::telemetry::db_span;
+use crate::workflows;
+
+/** What the sql backends do differently, see `super::sqlite` and `super::postgres`.
+  The queries name the differences as placeholders, e.g. `{for_update}`, which [`Dialect::sql`]
+  replaces: `concat!` only takes literals.
+*/
+pub trait Dialect: Database {
+    /// `db.system` of the spans, see [`db_span`]
+    const SYSTEM: &'static str;
+    /// `{tags}`: the tags of the task as json array, ordered by [`name_key`]
+    const TAGS: &'static str;
+    /// `{next_revision}`: revision for the next write
+    const NEXT_REVISION: &'static str;
+    /// `{for_update}`: suffix of a select whose rows stay as they are until the commit
+    const FOR_UPDATE: &'static str;
+    /// `{is_distinct_from}`: inequality which takes NULL as a value
+    const IS_DISTINCT_FROM: &'static str;
+    /// `{collate}`: collation of `name_key`, which is ordered by bytes like [`name_key`]
+    const COLLATE: &'static str;
+    /// `{json_ids}`: the ids of the json array `$1` as a column, for lists of ids
+    const JSON_IDS: &'static str;
+    /// Statements which take the exclusive and the shared lock of the key `$1` until the
+    /// commit, None if the writers are serialized anyway
+    const LOCKS: Option<(&'static str, &'static str)>;
+    /// Statement which gives the transaction one snapshot for all its reads, if it needs one
+    const SNAPSHOT: Option<&'static str>;
+    /// Statement which writes a consistent copy of the database to the file `$1`, if supported
+    const BACKUP: Option<&'static str>;
+
+    /// The time in `column` as a value whose order is the order of the times
+    fn time(column: &str) -> String {
+        column.to_string()
+    }
+
+    fn rows_affected(result: &Self::QueryResult) -> u64;
+
+    /// `template` with the placeholders replaced by this dialect
+    fn sql(template: &str) -> String {
+        template
+            .replace("{tags}", Self::TAGS)
+            .replace("{next_revision}", Self::NEXT_REVISION)
+            .replace("{for_update}", Self::FOR_UPDATE)
+            .replace("{is_distinct_from}", Self::IS_DISTINCT_FROM)
+            .replace("{collate}", Self::COLLATE)
+            .replace("{json_ids}", Self::JSON_IDS)
+    }
+}
+
+/// The tags of the task, see `task_columns!` and [`Dialect::TAGS`]
+macro_rules! tags_column {
+    () => {
+        "{tags} AS tags"
+    };
+}
+
+/// Revision for the next write, see [`Dialect::NEXT_REVISION`]
+macro_rules! next_revision {
+    () => {
+        "{next_revision}"
+    };
+}
+
+/// Tasks in a sql database, sqlite or postgres: one implementation for both, the differences
+/// are up to the [`Dialect`] of the database
+pub struct SqlTaskRepository<DB: Database> {
+    pool: Pool<DB>,
+}
+
+impl<DB: Database> SqlTaskRepository<DB> {
+    pub fn new(pool: Pool<DB>) -> SqlTaskRepository<DB> {
+        SqlTaskRepository { pool }
+    }
+}
+
+impl<DB> SqlTaskRepository<DB>
+where
+    DB: Dialect,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'r> &'r str: ColumnIndex<DB::Row>,
+    usize: ColumnIndex<DB::Row>,
+    for<'q> &'q str: Type<DB> + Encode<'q, DB>,
+    i64: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    bool: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    String: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    NaiveDate: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    DateTime<Utc>: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    i32: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    TaskStatus: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    Option<i64>: for<'q> Encode<'q, DB>,
+    Option<i32>: for<'q> Encode<'q, DB>,
+    Option<bool>: for<'q> Encode<'q, DB>,
+    Option<String>: for<'q> Encode<'q, DB>,
+    Option<NaiveDate>: for<'q> Encode<'q, DB>,
+    Option<DateTime<Utc>>: for<'q> Encode<'q, DB>,
+    Option<TaskStatus>: for<'q> Encode<'q, DB>,
+    for<'q> Option<&'q str>: Encode<'q, DB>,
+    for<'r> Task: FromRow<'r, DB::Row>,
+    for<'r> Comment: FromRow<'r, DB::Row>,
+    for<'r> CommentVersion: FromRow<'r, DB::Row>,
+    for<'r> Notification: FromRow<'r, DB::Row>,
+    for<'r> Tag: FromRow<'r, DB::Row>,
+    for<'r> TagCount: FromRow<'r, DB::Row>,
+    for<'r> TaskList: FromRow<'r, DB::Row>,
+    for<'r> BoardColumn: FromRow<'r, DB::Row>,
+    for<'r> Dependency: FromRow<'r, DB::Row>,
+{
+    /// Takes the lock of `key` until the commit, shared or exclusive, see [`Dialect::LOCKS`]
+    async fn lock(tx: &mut Transaction<'_, DB>, key: &str, shared: bool) -> Result<()> {
+        if let Some((exclusive, shared_lock)) = DB::LOCKS {
+            let sql = match shared {
+                true => shared_lock,
+                false => exclusive,
+            };
+            sqlx::query(sql)
+                .bind(key)
+                .execute(&mut **tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Condition of the reminders which are not sent yet, of open tasks
+    fn pending_reminders() -> String {
+        format!(
+            "remind_at IS NOT NULL AND (reminded_at IS NULL OR {} <> {}) \
+             AND (status IS NULL OR status NOT IN ('completed', 'cancelled'))",
+            DB::time("reminded_at"),
+            DB::time("remind_at")
+        )
+    }
+
+    /// New revision for task `id`, e.g. after its tags changed
+    async fn touch(tx: &mut Transaction<'_, DB>, id: i64) -> Result<()> {
+        let sql = &DB::sql(concat!(
+            "UPDATE task SET revision=",
+            next_revision!(),
+            " WHERE id=$1"
+        ));
+        sqlx::query(sql)
+            .bind(id)
+            .execute(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(())
+    }
+
+    /// Invalid if there is no list `id`, Conflict if it is archived; the list stays as it is
+    /// until the commit, which also serializes the appends to the list
+    async fn check_list(tx: &mut Transaction<'_, DB>, id: i64) -> Result<()> {
+        let sql = &DB::sql("SELECT archived FROM task_list WHERE id=$1{for_update}");
+        let archived: Option<bool> = sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match archived {
+            None => Err(RepositoryError::Invalid("list not found".to_string())),
+            Some(true) => Err(RepositoryError::Conflict(LIST_ARCHIVED.to_string())),
+            Some(false) => Ok(()),
+        }
+    }
+
+    /// List of the parent task `id`, Invalid if there is no such task
+    async fn parent_list(tx: &mut Transaction<'_, DB>, id: i64) -> Result<Option<i64>> {
+        let sql = "SELECT list_id FROM task WHERE id=$1";
+        let list_id: Option<Option<i64>> = sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        list_id.ok_or_else(|| RepositoryError::Invalid("parent task not found".to_string()))
+    }
+
+    /// One change of the ranks at a time, so that concurrent appends get different ranks
+    async fn lock_ranks(tx: &mut Transaction<'_, DB>) -> Result<()> {
+        Self::lock(tx, "task_rank", false).await
+    }
+
+    /// Rank of the last task in the manual order, None if there are no tasks; locks the ranks
+    async fn last_rank(tx: &mut Transaction<'_, DB>) -> Result<Option<String>> {
+        Self::lock_ranks(&mut *tx).await?;
+        let sql = "SELECT MAX(rank) FROM task";
+        let rank = sqlx::query_scalar(sql)
+            .fetch_one(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(rank)
+    }
+
+    /// Rank of task `id`, Invalid if there is no such anchor
+    async fn anchor_rank(tx: &mut Transaction<'_, DB>, id: i64) -> Result<String> {
+        let sql = "SELECT rank FROM task WHERE id=$1";
+        sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or_else(anchor_not_found)
+    }
+
+    /// Ranks of the tasks task `id` is moved between, see `TaskRepository::rank_task`
+    async fn neighbour_ranks(
+        tx: &mut Transaction<'_, DB>,
+        id: i64,
+        anchors: &RankAnchors,
+    ) -> Result<(Option<String>, Option<String>)> {
+        match (anchors.after, anchors.before) {
+            (Some(after), Some(before)) => {
+                let lower = Self::anchor_rank(&mut *tx, after).await?;
+                let upper = Self::anchor_rank(&mut *tx, before).await?;
+                match (&lower, after) < (&upper, before) {
+                    true => Ok((Some(lower), Some(upper))),
+                    false => Err(anchors_reversed()),
+                }
+            }
+            (Some(after), None) => {
+                let lower = Self::anchor_rank(&mut *tx, after).await?;
+                let sql = "SELECT rank FROM task WHERE (rank > $1 OR rank = $1 AND id > $2) \
+                           AND id <> $3 ORDER BY rank, id LIMIT 1";
+                let upper = sqlx::query_scalar(sql)
+                    .bind(&lower)
+                    .bind(after)
+                    .bind(id)
+                    .fetch_optional(&mut **tx)
+                    .instrument(db_span(DB::SYSTEM, sql))
+                    .await?;
+                Ok((Some(lower), upper))
+            }
+            (None, Some(before)) => {
+                let upper = Self::anchor_rank(&mut *tx, before).await?;
+                let sql = "SELECT rank FROM task WHERE (rank < $1 OR rank = $1 AND id < $2) \
+                           AND id <> $3 ORDER BY rank DESC, id DESC LIMIT 1";
+                let lower = sqlx::query_scalar(sql)
+                    .bind(&upper)
+                    .bind(before)
+                    .bind(id)
+                    .fetch_optional(&mut **tx)
+                    .instrument(db_span(DB::SYSTEM, sql))
+                    .await?;
+                Ok((lower, Some(upper)))
+            }
+            (None, None) => Ok((None, None)),
+        }
+    }
+
+    /// Rank of task `id` moved between `anchors`, spreading the ranks if there is no room
+    async fn anchored_rank(
+        tx: &mut Transaction<'_, DB>,
+        id: i64,
+        anchors: &RankAnchors,
+    ) -> Result<String> {
+        let (lower, upper) = Self::neighbour_ranks(&mut *tx, id, anchors).await?;
+        match rank::between(lower.as_deref(), upper.as_deref()) {
+            Some(rank) => Ok(rank),
+            None => {
+                Self::spread_ranks(&mut *tx).await?;
+                let (lower, upper) = Self::neighbour_ranks(&mut *tx, id, anchors).await?;
+                rank::between(lower.as_deref(), upper.as_deref()).ok_or_else(no_rank_left)
+            }
+        }
+    }
+
+    /// Give all tasks new, evenly spread ranks in the same order, see `rank::spread`
+    async fn spread_ranks(tx: &mut Transaction<'_, DB>) -> Result<()> {
+        let sql = "SELECT id FROM task ORDER BY rank, id";
+        let tasks: Vec<i64> = sqlx::query_scalar(sql)
+            .fetch_all(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let sql = "UPDATE task SET rank=$2 WHERE id=$1";
+        for (id, rank) in tasks.iter().zip(rank::spread(tasks.len())) {
+            sqlx::query(sql)
+                .bind(id)
+                .bind(rank)
+                .execute(&mut **tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Board `id` with its columns, NotFound if there is no such board
+    async fn load_board(tx: &mut Transaction<'_, DB>, id: i64) -> Result<Board> {
+        let sql = "SELECT id, name, list_id FROM board WHERE id=$1";
+        let (id, name, list_id): (i64, String, Option<i64>) = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or(RepositoryError::NotFound)?;
+        let sql = "SELECT id, name, status, wip_limit FROM board_column WHERE board_id=$1 \
+                   ORDER BY position";
+        let columns = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(Board {
+            id,
+            name,
+            list_id,
+            columns,
+        })
+    }
+
+    /** Store the fields of board `id`: columns with a status of the board keep their id, the
+      others are deleted or added. Invalid if there is no list `board.list_id`.
+    */
+    async fn store_board(tx: &mut Transaction<'_, DB>, id: i64, board: &NewBoard) -> Result<()> {
+        if let Some(list_id) = board.list_id {
+            let sql = "SELECT id FROM task_list WHERE id=$1";
+            let found: Option<i64> = sqlx::query_scalar(sql)
+                .bind(list_id)
+                .fetch_optional(&mut **tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+            found.ok_or_else(board_list_not_found)?;
+        }
+        let sql = "UPDATE board SET name=$2, list_id=$3 WHERE id=$1";
+        sqlx::query(sql)
+            .bind(id)
+            .bind(&board.name)
+            .bind(board.list_id)
+            .execute(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let sql = "SELECT id, name, status, wip_limit FROM board_column WHERE board_id=$1";
+        let stored: Vec<BoardColumn> = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let sql = "DELETE FROM board_column WHERE id=$1";
+        for column in stored {
+            if !board.columns.iter().any(|new| new.status == column.status) {
+                sqlx::query(sql)
+                    .bind(column.id)
+                    .execute(&mut **tx)
+                    .instrument(db_span(DB::SYSTEM, sql))
+                    .await?;
+            }
+        }
+        let sql = "INSERT INTO board_column (board_id, position, name, status, wip_limit) \
+                   VALUES ($1, $2, $3, $4, $5) ON CONFLICT (board_id, status) DO UPDATE \
+                   SET position=excluded.position, name=excluded.name, wip_limit=excluded.wip_limit";
+        for (position, column) in board.columns.iter().enumerate() {
+            sqlx::query(sql)
+                .bind(id)
+                .bind(position as i64)
+                .bind(&column.name)
+                .bind(column.status)
+                .bind(column.wip_limit)
+                .execute(&mut **tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Name and status of the first state of workflow `id`, Invalid if there is no such workflow
+    async fn initial_state(tx: &mut Transaction<'_, DB>, id: i64) -> Result<(String, TaskStatus)> {
+        let sql = "SELECT name, status FROM workflow_state WHERE workflow_id=$1 \
+                   ORDER BY position LIMIT 1";
+        sqlx::query_as(sql)
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or_else(workflow_not_found)
+    }
+
+    /// Workflow `id` with its states and transitions, NotFound if there is no such workflow
+    async fn load_workflow(tx: &mut Transaction<'_, DB>, id: i64) -> Result<Workflow> {
+        let sql = "SELECT name FROM workflow WHERE id=$1";
+        let name: String = sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or(RepositoryError::NotFound)?;
+        let sql = "SELECT name, status FROM workflow_state WHERE workflow_id=$1 ORDER BY position";
+        let states = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let sql = "SELECT from_state, to_state, required_fields FROM workflow_transition \
+                   WHERE workflow_id=$1 ORDER BY position";
+        let transitions: Vec<(String, String, String)> = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(Workflow {
+            id,
+            name,
+            states,
+            transitions: transitions
+                .into_iter()
+                .map(|(from, to, fields)| Transition {
+                    from,
+                    to,
+                    required_fields: fields_from_column(&fields),
+                })
+                .collect(),
+        })
+    }
+
+    /** `task` `changed` to `state` or, if None, the state its status leads to, with a workflow
+      in the state it changes to and its status, see `workflows::transition`. Invalid if `state`
+      is given and the task follows no workflow.
+    */
+    async fn follow_workflow(
+        tx: &mut Transaction<'_, DB>,
+        task: &Task,
+        state: Option<&str>,
+        changed: Task,
+    ) -> Result<Task> {
+        let workflow = match task.workflow_id {
+            Some(workflow_id) => Self::load_workflow(tx, workflow_id).await?,
+            None if state.is_some() => return Err(no_workflow()),
+            None => return Ok(changed),
+        };
+        let to = workflows::transition(&workflow, task, state, &changed)?;
+        Ok(Task {
+            status: Some(to.status),
+            state: Some(to.name.clone()),
+            ..changed
+        })
+    }
+
+    /** Store the fields of workflow `id`, the tasks in a state get its status. Conflict if a
+      state of a task is left out.
+    */
+    async fn store_workflow(
+        tx: &mut Transaction<'_, DB>,
+        id: i64,
+        workflow: &NewWorkflow,
+    ) -> Result<()> {
+        let sql = "SELECT DISTINCT state FROM task WHERE workflow_id=$1";
+        let used: Vec<String> = sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_all(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if let Some(state) = used
+            .iter()
+            .find(|used| !workflow.states.iter().any(|state| &state.name == *used))
+        {
+            return Err(state_in_use(state));
+        }
+        let sql = "UPDATE workflow SET name=$2 WHERE id=$1";
+        sqlx::query(sql)
+            .bind(id)
+            .bind(&workflow.name)
+            .execute(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        for sql in [
+            "DELETE FROM workflow_state WHERE workflow_id=$1",
+            "DELETE FROM workflow_transition WHERE workflow_id=$1",
+        ] {
+            sqlx::query(sql)
+                .bind(id)
+                .execute(&mut **tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+        }
+        let sql = "INSERT INTO workflow_state (workflow_id, position, name, status) \
+                   VALUES ($1, $2, $3, $4)";
+        let update_tasks = &DB::sql(concat!(
+            "UPDATE task SET status=$3, revision=",
+            next_revision!(),
+            " WHERE workflow_id=$1 AND state=$2 AND status {is_distinct_from} $3"
+        ));
+        for (position, state) in workflow.states.iter().enumerate() {
+            sqlx::query(sql)
+                .bind(id)
+                .bind(position as i64)
+                .bind(&state.name)
+                .bind(state.status)
+                .execute(&mut **tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+            sqlx::query(update_tasks)
+                .bind(id)
+                .bind(&state.name)
+                .bind(state.status)
+                .execute(&mut **tx)
+                .instrument(db_span(DB::SYSTEM, update_tasks))
+                .await?;
+        }
+        let sql = "INSERT INTO workflow_transition \
+                   (workflow_id, position, from_state, to_state, required_fields) \
+                   VALUES ($1, $2, $3, $4, $5)";
+        for (position, transition) in workflow.transitions.iter().enumerate() {
+            sqlx::query(sql)
+                .bind(id)
+                .bind(position as i64)
+                .bind(&transition.from)
+                .bind(&transition.to)
+                .bind(fields_column(&transition.required_fields))
+                .execute(&mut **tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// NotFound unless there is a task `id`
+    async fn check_task(tx: &mut Transaction<'_, DB>, id: i64) -> Result<()> {
+        let sql = "SELECT EXISTS (SELECT 1 FROM task WHERE id=$1)";
+        let exists: bool = sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_one(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match exists {
+            true => Ok(()),
+            false => Err(RepositoryError::NotFound),
+        }
+    }
+
+    /** Notify the names of `mentions` but the writer, which were not notified of `comment` yet.
+      One transaction notifies at a time until its commit: the ids are handed out in the order
+      of the inserts, not of the commits, so a poller with the last id seen as `after` could
+      miss a notification committed later with a lower id.
+    */
+    async fn notify(
+        tx: &mut Transaction<'_, DB>,
+        comment: &Comment,
+        writer: &str,
+        mentions: &[String],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Notification>> {
+        if mentions.iter().all(|name| name == writer) {
+            return Ok(Vec::new());
+        }
+        Self::lock(&mut *tx, "notification", false).await?;
+        let sql = concat!(
+            "INSERT INTO notification (recipient, task_id, comment_id, author, created_at) \
+             VALUES ($1, $2, $3, $4, $5) ON CONFLICT (comment_id, recipient) DO NOTHING RETURNING ",
+            notification_columns!()
+        );
+        let mut notified = Vec::new();
+        for name in mentions.iter().filter(|name| *name != writer) {
+            let notification: Vec<Notification> = sqlx::query_as(sql)
+                .bind(name)
+                .bind(comment.task_id)
+                .bind(comment.id)
+                .bind(writer)
+                .bind(now)
+                .fetch_all(&mut **tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+            notified.extend(notification);
+        }
+        Ok(notified)
+    }
+
+    /** Insert `task` with `rank` or replace the task with its uid (a re-import), which stays where
+      it is in the hierarchy, its list and the manual order: calendar apps do not send these.
+      Conflict if it completes a task blocked by an open task. A task with a workflow changes to the
+      state its status leads to, see `workflows::transition`.
+    */
+    async fn upsert(
+        tx: &mut Transaction<'_, DB>,
+        uid: &str,
+        task: &NewTask,
+        rank: &str,
+    ) -> Result<Task> {
+        let sql = &DB::sql(concat!(
+            "SELECT ",
+            task_columns!(),
+            " FROM task WHERE uid=$1{for_update}"
+        ));
+        let stored: Option<Task> = sqlx::query_as(sql)
+            .bind(uid)
+            .fetch_optional(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let (status, state) = match stored {
+            Some(stored) => {
+                if stored.is_open() && task.status == Some(TaskStatus::Completed) {
+                    // like update_task, an open task is not completed while it is blocked
+                    Self::check_blockers(&mut *tx, &[stored.id]).await?;
+                }
+                // CalDAV and imports give a status only, the state follows it
+                let replaced =
+                    Self::follow_workflow(&mut *tx, &stored, None, task.replace(&stored)).await?;
+                (replaced.status, replaced.state)
+            }
+            None => (task.status, None),
+        };
+        let sql = &DB::sql(concat!(
+            "INSERT INTO task (uid, task, status, due_at, priority, categories, rrule, created_on, \
+             completed_on, remind_at, parent_id, rank, state, revision) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, ",
+            next_revision!(),
+            ") ON CONFLICT (uid) DO UPDATE SET task = excluded.task, status = excluded.status, \
+             due_at = excluded.due_at, priority = excluded.priority, categories = excluded.categories, \
+             rrule = excluded.rrule, created_on = excluded.created_on, \
+             completed_on = excluded.completed_on, remind_at = excluded.remind_at, \
+             state = excluded.state, revision = excluded.revision RETURNING ",
+            task_columns!()
+        ));
+        sqlx::query_as(sql)
+            .bind(uid)
+            .bind(&task.task)
+            .bind(status)
+            .bind(task.due_at)
+            .bind(task.priority)
+            .bind(&task.categories)
+            .bind(&task.rrule)
+            .bind(task.created_on)
+            .bind(task.completed_on)
+            .bind(task.remind_at)
+            .bind(task.parent_id)
+            .bind(rank)
+            .bind(state)
+            // fetch_all, see insert_task: an unfinished statement would block the commit
+            .fetch_all(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .pop()
+            .ok_or(RepositoryError::Database(sqlx::Error::RowNotFound))
+    }
+
+    /// Insert `task` at the end of the manual order, see [`TaskRepository::create_task`]
+    async fn insert_task(tx: &mut Transaction<'_, DB>, task: &NewTask) -> Result<Task> {
+        // we use "RETURNING" - non-standard SQL syntax (which is supported by sqlite and postgres) to return the new ID created by the database
+        // to our caller
+        // fetch_all steps the statement to completion: with fetch_one sqlite only commits the insert
+        // when the pooled connection is used again, so other connections (e.g. backups) would miss it
+        let sql = &DB::sql(concat!(
+            "INSERT INTO task (uid, task, status, due_at, priority, categories, rrule, created_on, \
+             completed_on, remind_at, parent_id, list_id, rank, workflow_id, state, revision) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, ",
+            next_revision!(),
+            ") RETURNING ",
+            task_columns!()
+        ));
+        let list_id = match task.parent_id {
+            Some(parent_id) => {
+                subtask_list(Self::parent_list(&mut *tx, parent_id).await?, task.list_id)?
+            }
+            None => task.list_id,
+        };
+        if let Some(list_id) = list_id {
+            Self::check_list(&mut *tx, list_id).await?;
+        }
+        let (state, status) = match task.workflow_id {
+            Some(workflow_id) => {
+                let (state, status) = Self::initial_state(&mut *tx, workflow_id).await?;
+                (Some(state), Some(status))
+            }
+            None => (None, task.status),
+        };
+        let rank = rank::after(Self::last_rank(&mut *tx).await?.as_deref());
+        let mut tasks: Vec<Task> = sqlx::query_as(sql)
+            .bind(task.uid_or_new())
+            .bind(&task.task)
+            .bind(status)
+            .bind(task.due_at)
+            .bind(task.priority)
+            .bind(&task.categories)
+            .bind(&task.rrule)
+            .bind(task.created_on)
+            .bind(task.completed_on)
+            .bind(task.remind_at)
+            .bind(task.parent_id)
+            .bind(list_id)
+            .bind(rank)
+            .bind(task.workflow_id)
+            .bind(state)
+            .fetch_all(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await
+            .map_err(parent_not_found)?;
+        tasks
+            .pop()
+            .ok_or(RepositoryError::Database(sqlx::Error::RowNotFound))
+    }
+
+    /// Task `id` or NotFound, it stays as it is until the commit
+    async fn lock_task(tx: &mut Transaction<'_, DB>, id: i64) -> Result<Task> {
+        let sql = &DB::sql(concat!(
+            "SELECT ",
+            task_columns!(),
+            " FROM task WHERE id=$1{for_update}"
+        ));
+        sqlx::query_as(sql)
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    /// Ids of the open subtasks of task `id` at any depth
+    async fn open_subtasks(tx: &mut Transaction<'_, DB>, id: i64) -> Result<Vec<i64>> {
+        let sql = concat!(
+            subtree_ids!(),
+            "SELECT id FROM task WHERE id IN (SELECT id FROM subtree) AND id <> $1 \
+             AND (status IS NULL OR status NOT IN ('completed', 'cancelled'))"
+        );
+        Ok(sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_all(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?)
+    }
+
+    /// Complete the open subtasks of task `id` at any depth on `completed_on`, those with a
+    /// workflow along its transitions
+    async fn complete_subtasks(
+        tx: &mut Transaction<'_, DB>,
+        id: i64,
+        completed_on: NaiveDate,
+    ) -> Result<()> {
+        let sql = &DB::sql(concat!(
+            subtree_ids!(),
+            "SELECT ",
+            task_columns!(),
+            " FROM task WHERE id IN (SELECT id FROM subtree) AND id <> $1 \
+             AND workflow_id IS NOT NULL \
+             AND (status IS NULL OR status NOT IN ('completed', 'cancelled')){for_update}"
+        ));
+        let following: Vec<Task> = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        // the subtasks with a workflow change to its next completed state
+        let sql = "UPDATE task SET state=$2 WHERE id=$1";
+        for task in following {
+            let completed = Task {
+                status: Some(TaskStatus::Completed),
+                completed_on: Some(completed_on),
+                ..task.clone()
+            };
+            let completed = Self::follow_workflow(&mut *tx, &task, None, completed).await?;
+            sqlx::query(sql)
+                .bind(task.id)
+                .bind(&completed.state)
+                .execute(&mut **tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+        }
+        let sql = &DB::sql(concat!(
+            subtree_ids!(),
+            "UPDATE task SET status='completed', completed_on=$2, revision=",
+            next_revision!(),
+            " WHERE id IN (SELECT id FROM subtree) AND id <> $1 \
+             AND (status IS NULL OR status NOT IN ('completed', 'cancelled'))"
+        ));
+        sqlx::query(sql)
+            .bind(id)
+            .bind(completed_on)
+            .execute(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(())
+    }
+
+    /** Conflict if one of the tasks `completing` is blocked by an open task which is not completed
+      along with it, one query for all of them. Until the commit no dependency is added.
+    */
+    async fn check_blockers(tx: &mut Transaction<'_, DB>, completing: &[i64]) -> Result<()> {
+        if completing.is_empty() {
+            return Ok(());
+        }
+        // shared: completions run concurrently, but not along with add_dependency
+        Self::lock(&mut *tx, "task_dependency", true).await?;
+        let sql = &DB::sql(
+            "SELECT task.id FROM task_dependency JOIN task ON task.id = task_dependency.blocker_id \
+             WHERE task_dependency.task_id IN ({json_ids}) AND task.id NOT IN ({json_ids}) \
+             AND (task.status IS NULL OR task.status NOT IN ('completed', 'cancelled'))",
+        );
+        let open_blockers: Vec<i64> = sqlx::query_scalar(sql)
+            .bind(serde_json::json!(completing).to_string())
+            .fetch_all(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        check_open_blockers(open_blockers)
+    }
+
+    /// Insert the next occurrence of `completed`, which was just completed, see
+    /// `crate::recurrence::next_occurrence`
+    async fn create_next_occurrence(
+        tx: &mut Transaction<'_, DB>,
+        completed: &Task,
+    ) -> Result<Option<Task>> {
+        match recurrence::next_occurrence(completed) {
+            Some(next) => Ok(Some(Self::insert_task(tx, &next).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Delete task `id` with its subtasks, leaving tombstones, or NotFound
+    async fn delete_subtree(tx: &mut Transaction<'_, DB>, id: i64) -> Result<()> {
+        // the tombstone tells syncing clients (CalDAV) that the task is gone
+        let sql = &DB::sql(concat!(
+            subtree_ids!(),
+            "INSERT INTO task_tombstone (uid, revision) SELECT uid, ",
+            next_revision!(),
+            " FROM task WHERE id IN (SELECT id FROM subtree) \
+             ON CONFLICT (uid) DO UPDATE SET revision = excluded.revision"
+        ));
+        sqlx::query(sql)
+            .bind(id)
+            .execute(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        // the subtasks and the dependencies are deleted by the foreign keys
+        let sql = "DELETE FROM task WHERE id=$1";
+        let result = sqlx::query(sql)
+            .bind(id)
+            .execute(&mut **tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match DB::rows_affected(&result) {
+            0 => Err(RepositoryError::NotFound),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl<DB> TaskRepository for SqlTaskRepository<DB>
+where
+    DB: Dialect,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'r> &'r str: ColumnIndex<DB::Row>,
+    usize: ColumnIndex<DB::Row>,
+    for<'q> &'q str: Type<DB> + Encode<'q, DB>,
+    i64: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    bool: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    String: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    NaiveDate: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    DateTime<Utc>: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    i32: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    TaskStatus: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    Option<i64>: for<'q> Encode<'q, DB>,
+    Option<i32>: for<'q> Encode<'q, DB>,
+    Option<bool>: for<'q> Encode<'q, DB>,
+    Option<String>: for<'q> Encode<'q, DB>,
+    Option<NaiveDate>: for<'q> Encode<'q, DB>,
+    Option<DateTime<Utc>>: for<'q> Encode<'q, DB>,
+    Option<TaskStatus>: for<'q> Encode<'q, DB>,
+    for<'q> Option<&'q str>: Encode<'q, DB>,
+    for<'r> Task: FromRow<'r, DB::Row>,
+    for<'r> Comment: FromRow<'r, DB::Row>,
+    for<'r> CommentVersion: FromRow<'r, DB::Row>,
+    for<'r> Notification: FromRow<'r, DB::Row>,
+    for<'r> Tag: FromRow<'r, DB::Row>,
+    for<'r> TagCount: FromRow<'r, DB::Row>,
+    for<'r> TaskList: FromRow<'r, DB::Row>,
+    for<'r> BoardColumn: FromRow<'r, DB::Row>,
+    for<'r> Dependency: FromRow<'r, DB::Row>,
+{
+    async fn all_tasks(&self) -> Result<Vec<Task>> {
+        let sql = &DB::sql(concat!(
+            "SELECT ",
+            task_columns!(),
+            " FROM task ORDER BY rank, id"
+        ));
+        let tasks = sqlx::query_as(sql)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(tasks)
+    }
+
+    fn task_stream(&self) -> TaskStream {
+        let pool = self.pool.clone();
+        Box::pin(try_stream! {
+            let sql = &DB::sql(concat!("SELECT ", task_columns!(), " FROM task ORDER BY rank, id"));
+            let span = db_span(DB::SYSTEM, sql);
+            let mut tasks = sqlx::query_as::<_, Task>(sql).fetch(&pool);
+            while let Some(task) = tasks.try_next().instrument(span.clone()).await? {
+                yield task;
+            }
+        })
+    }
+
+    async fn create_task(&self, task: &NewTask) -> Result<Task> {
+        let mut tx = self.pool.begin().await?;
+        if task.parent_id.is_some() {
+            // the parent keeps its list until the commit, see move_to_list
+            Self::lock(&mut tx, "task_move", true).await?;
+        }
+        let task = Self::insert_task(&mut tx, task).await?;
+        tx.commit().await?;
+        Ok(task)
+    }
+
+    async fn import_tasks(&self, tasks: &[NewTask]) -> Result<Vec<Result<Task>>> {
+        let mut tx = self.pool.begin().await?;
+        let mut rows = Vec::with_capacity(tasks.len());
+        // replaced tasks keep their rank, new ones are appended
+        let mut rank = Self::last_rank(&mut tx).await?;
+        for task in tasks {
+            let next_rank = rank::after(rank.as_deref());
+            let stored = match with_imported_parent(task, &rows) {
+                Ok(task) => Self::upsert(&mut tx, &task.uid_or_new(), &task, &next_rank).await,
+                Err(err) => Err(err),
+            };
+            if stored.is_ok() {
+                rank = Some(next_rank);
+            }
+            rows.push(imported(stored)?);
+        }
+        tx.commit().await?;
+        Ok(rows)
+    }
+
+    async fn task(&self, id: i64) -> Result<Task> {
+        let sql = &DB::sql(concat!(
+            "SELECT ",
+            task_columns!(),
+            " FROM task WHERE id=$1"
+        ));
+        sqlx::query_as(sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn task_by_uid(&self, uid: &str) -> Result<Task> {
+        let sql = &DB::sql(concat!(
+            "SELECT ",
+            task_columns!(),
+            " FROM task WHERE uid=$1"
+        ));
+        sqlx::query_as(sql)
+            .bind(uid)
+            .fetch_optional(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn update_task(
+        &self,
+        id: i64,
+        task: &UpdateTask,
+        subtasks: Option<SubtaskPolicy>,
+    ) -> Result<Option<Task>> {
+        let sql = &DB::sql(concat!(
+            "UPDATE task SET task=$1, status=$2, due_at=$3, priority=$4, categories=$5, rrule=$6, \
+             created_on=$7, completed_on=$8, remind_at=$9, state=COALESCE($11, state), revision=",
+            next_revision!(),
+            " WHERE id=$10 RETURNING ",
+            task_columns!()
+        ));
+        let mut tx = self.pool.begin().await?;
+        // a concurrent update waits for the task and then finds it changed, e.g. completed or
+        // in another state of its workflow
+        let previous = Self::lock_task(&mut tx, id).await?;
+        let followed = Self::follow_workflow(
+            &mut tx,
+            &previous,
+            task.state.as_deref(),
+            task.apply(&previous),
+        )
+        .await?;
+        let task = &UpdateTask {
+            status: followed.status,
+            state: followed.state,
+            ..task.clone()
+        };
+        let completes = task.status == Some(TaskStatus::Completed);
+        if completes {
+            let open = match subtasks {
+                Some(_) => Self::open_subtasks(&mut tx, id).await?,
+                None => Vec::new(),
+            };
+            Self::check_blockers(&mut tx, &completing(&previous, subtasks, open)?).await?;
+        }
+        // fetch_all, see insert_task
+        let updated: Task = sqlx::query_as(sql)
+            .bind(&task.task)
+            .bind(task.status)
+            .bind(task.due_at)
+            .bind(task.priority)
+            .bind(&task.categories)
+            .bind(&task.rrule)
+            .bind(task.created_on)
+            .bind(task.completed_on)
+            .bind(task.remind_at)
+            .bind(id)
+            .bind(&task.state)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .pop()
+            .ok_or(RepositoryError::NotFound)?;
+        let mut next = None;
+        if completes {
+            if subtasks == Some(SubtaskPolicy::Complete) {
+                let completed_on = task.completed_on.unwrap_or_else(|| Utc::now().date_naive());
+                Self::complete_subtasks(&mut tx, id, completed_on).await?;
+            }
+            if previous.status != Some(TaskStatus::Completed) {
+                next = Self::create_next_occurrence(&mut tx, &updated).await?;
+            }
+        }
+        tx.commit().await?;
+        Ok(next)
+    }
+
+    async fn delete_task(&self, id: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        Self::delete_subtree(&mut tx, id).await?;
+        Ok(tx.commit().await?)
+    }
+
+    async fn put_task(
+        &self,
+        task: &NewTask,
+        precondition: Precondition<'_>,
+    ) -> Result<(Task, bool)> {
+        let uid = task.uid_or_new();
+        let mut tx = self.pool.begin().await?;
+        // concurrent writes of a new task with the uid wait for each other
+        Self::lock(&mut tx, &uid, false).await?;
+        let sql = &DB::sql("SELECT revision FROM task WHERE uid=$1{for_update}");
+        let stored: Option<i64> = sqlx::query_scalar(sql)
+            .bind(&uid)
+            .fetch_optional(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if !precondition(stored) {
+            return Err(RepositoryError::PreconditionFailed);
+        }
+        let rank = rank::after(Self::last_rank(&mut tx).await?.as_deref());
+        let task = Self::upsert(&mut tx, &uid, task, &rank).await?;
+        tx.commit().await?;
+        Ok((task, stored.is_none()))
+    }
+
+    async fn delete_task_by_uid(&self, uid: &str, precondition: Precondition<'_>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let sql = &DB::sql("SELECT id, revision FROM task WHERE uid=$1{for_update}");
+        let (id, revision): (i64, i64) = sqlx::query_as(sql)
+            .bind(uid)
+            .fetch_optional(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or(RepositoryError::NotFound)?;
+        if !precondition(Some(revision)) {
+            return Err(RepositoryError::PreconditionFailed);
+        }
+        Self::delete_subtree(&mut tx, id).await?;
+        Ok(tx.commit().await?)
+    }
+
+    async fn children(&self, id: i64) -> Result<Vec<Task>> {
+        let sql = &DB::sql(concat!(
+            "SELECT ",
+            task_columns!(),
+            " FROM task WHERE parent_id=$1 ORDER BY rank, id"
+        ));
+        let children: Vec<Task> = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if children.is_empty() {
+            self.task(id).await?;
+        }
+        Ok(children)
+    }
+
+    async fn subtree(&self, id: i64) -> Result<Vec<Task>> {
+        let sql = &DB::sql(concat!(
+            subtree_ids!(),
+            "SELECT ",
+            task_columns!(),
+            " FROM task WHERE id IN (SELECT id FROM subtree) ORDER BY rank, id"
+        ));
+        let tasks: Vec<Task> = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match tasks.is_empty() {
+            true => Err(RepositoryError::NotFound),
+            false => Ok(tasks),
+        }
+    }
+
+    async fn move_task(&self, id: i64, parent_id: Option<i64>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        // one move at a time, concurrent moves could create a cycle together
+        Self::lock(&mut tx, "task_move", false).await?;
+        let sql = concat!(subtree_ids!(), "SELECT id FROM subtree");
+        let subtree: Vec<i64> = sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if subtree.is_empty() {
+            return Err(RepositoryError::NotFound);
+        }
+        if let Some(parent_id) = parent_id {
+            if subtree.contains(&parent_id) {
+                return Err(RepositoryError::Conflict(
+                    "a task can not become a subtask of itself or of its subtasks".to_string(),
+                ));
+            }
+        }
+        let sql = &DB::sql(concat!(
+            "UPDATE task SET parent_id=$2, revision=",
+            next_revision!(),
+            " WHERE id=$1"
+        ));
+        sqlx::query(sql)
+            .bind(id)
+            .bind(parent_id)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await
+            .map_err(parent_not_found)?;
+        Ok(tx.commit().await?)
+    }
+
+    async fn add_dependency(&self, id: i64, blocker_id: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        // one change at a time, concurrent dependencies could create a cycle together
+        Self::lock(&mut tx, "task_dependency", false).await?;
+        let sql = "SELECT id FROM task WHERE id IN ($1, $2)";
+        let found: Vec<i64> = sqlx::query_scalar(sql)
+            .bind(id)
+            .bind(blocker_id)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if !found.contains(&id) {
+            return Err(RepositoryError::NotFound);
+        }
+        if !found.contains(&blocker_id) {
+            return Err(RepositoryError::Invalid(
+                "blocker task not found".to_string(),
+            ));
+        }
+        let sql = blocks_query!();
+        let cycle: bool = sqlx::query_scalar(sql)
+            .bind(id)
+            .bind(blocker_id)
+            .fetch_one(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if cycle {
+            return Err(RepositoryError::Conflict(DEPENDENCY_CYCLE.to_string()));
+        }
+        let sql = "INSERT INTO task_dependency (task_id, blocker_id) VALUES ($1, $2) \
+                   ON CONFLICT DO NOTHING";
+        sqlx::query(sql)
+            .bind(id)
+            .bind(blocker_id)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(tx.commit().await?)
+    }
+
+    async fn remove_dependency(&self, id: i64, blocker_id: i64) -> Result<()> {
+        let sql = "DELETE FROM task_dependency WHERE task_id=$1 AND blocker_id=$2";
+        let result = sqlx::query(sql)
+            .bind(id)
+            .bind(blocker_id)
+            .execute(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match DB::rows_affected(&result) {
+            0 => Err(RepositoryError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn blockers(&self, id: i64) -> Result<Vec<Task>> {
+        let sql = &DB::sql(concat!(
+            "SELECT ",
+            task_columns!(),
+            " FROM task WHERE id IN (SELECT blocker_id FROM task_dependency WHERE task_id=$1) \
+             ORDER BY id"
+        ));
+        let blockers: Vec<Task> = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if blockers.is_empty() {
+            self.task(id).await?;
+        }
+        Ok(blockers)
+    }
+
+    async fn dependencies(&self) -> Result<Vec<Dependency>> {
+        let sql = "SELECT task_id, blocker_id FROM task_dependency ORDER BY task_id, blocker_id";
+        Ok(sqlx::query_as(sql)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?)
+    }
+
+    async fn tags(&self) -> Result<Vec<Tag>> {
+        let sql = &DB::sql("SELECT id, name, colour FROM tag ORDER BY name_key{collate}");
+        Ok(sqlx::query_as(sql)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?)
+    }
+
+    async fn tag_counts(&self) -> Result<Vec<TagCount>> {
+        let sql = &DB::sql(
+            "SELECT tag.id, tag.name, tag.colour, COUNT(task_tag.task_id) AS tasks \
+             FROM tag LEFT JOIN task_tag ON task_tag.tag_id = tag.id \
+             GROUP BY tag.id, tag.name, tag.colour ORDER BY tag.name_key{collate}",
+        );
+        Ok(sqlx::query_as(sql)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?)
+    }
+
+    async fn tag(&self, id: i64) -> Result<Tag> {
+        let sql = "SELECT id, name, colour FROM tag WHERE id=$1";
+        sqlx::query_as(sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn create_tag(&self, tag: &NewTag) -> Result<Tag> {
+        // fetch_all, see insert_task
+        let sql = "INSERT INTO tag (name, colour, name_key) VALUES ($1, $2, $3) \
+                   RETURNING id, name, colour";
+        let mut tags: Vec<Tag> = sqlx::query_as(sql)
+            .bind(&tag.name)
+            .bind(&tag.colour)
+            .bind(name_key(&tag.name))
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await
+            .map_err(tag_name_taken)?;
+        tags.pop()
+            .ok_or(RepositoryError::Database(sqlx::Error::RowNotFound))
+    }
+
+    async fn update_tag(&self, id: i64, tag: &NewTag) -> Result<Tag> {
+        let mut tx = self.pool.begin().await?;
+        let sql = "UPDATE tag SET name=$2, colour=$3, name_key=$4 WHERE id=$1 \
+                   RETURNING id, name, colour";
+        let mut updated: Vec<Tag> = sqlx::query_as(sql)
+            .bind(id)
+            .bind(&tag.name)
+            .bind(&tag.colour)
+            .bind(name_key(&tag.name))
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await
+            .map_err(tag_name_taken)?;
+        let updated = updated.pop().ok_or(RepositoryError::NotFound)?;
+        // the tasks embed the tag
+        let sql = &DB::sql(concat!(
+            "UPDATE task SET revision=",
+            next_revision!(),
+            " WHERE id IN (SELECT task_id FROM task_tag WHERE tag_id=$1)"
+        ));
+        sqlx::query(sql)
+            .bind(id)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn delete_tag(&self, id: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let sql = &DB::sql(concat!(
+            "UPDATE task SET revision=",
+            next_revision!(),
+            " WHERE id IN (SELECT task_id FROM task_tag WHERE tag_id=$1)"
+        ));
+        sqlx::query(sql)
+            .bind(id)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        // the attachments are deleted by the foreign key
+        let sql = "DELETE FROM tag WHERE id=$1";
+        let result = sqlx::query(sql)
+            .bind(id)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match DB::rows_affected(&result) {
+            0 => Err(RepositoryError::NotFound),
+            _ => Ok(tx.commit().await?),
+        }
+    }
+
+    async fn attach_tag(&self, id: i64, tag_id: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let sql = "INSERT INTO task_tag (task_id, tag_id) \
+                   SELECT task.id, tag.id FROM task, tag WHERE task.id=$1 AND tag.id=$2 \
+                   ON CONFLICT DO NOTHING";
+        let result = sqlx::query(sql)
+            .bind(id)
+            .bind(tag_id)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if DB::rows_affected(&result) == 0 {
+            // attached already, or the task or the tag is missing
+            let sql = "SELECT COUNT(*) FROM task_tag WHERE task_id=$1 AND tag_id=$2";
+            let attached: i64 = sqlx::query_scalar(sql)
+                .bind(id)
+                .bind(tag_id)
+                .fetch_one(&mut *tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+            return match attached {
+                0 => Err(RepositoryError::NotFound),
+                _ => Ok(()),
+            };
+        }
+        Self::touch(&mut tx, id).await?;
+        Ok(tx.commit().await?)
+    }
+
+    async fn detach_tag(&self, id: i64, tag_id: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let sql = "DELETE FROM task_tag WHERE task_id=$1 AND tag_id=$2";
+        let result = sqlx::query(sql)
+            .bind(id)
+            .bind(tag_id)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if DB::rows_affected(&result) == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Self::touch(&mut tx, id).await?;
+        Ok(tx.commit().await?)
+    }
+
+    async fn lists(&self) -> Result<Vec<TaskList>> {
+        let sql = "SELECT id, name, archived FROM task_list ORDER BY id";
+        Ok(sqlx::query_as(sql)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?)
+    }
+
+    async fn list(&self, id: i64) -> Result<TaskList> {
+        let sql = "SELECT id, name, archived FROM task_list WHERE id=$1";
+        sqlx::query_as(sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn create_list(&self, list: &NewTaskList) -> Result<TaskList> {
+        // fetch_all, see insert_task
+        let sql = "INSERT INTO task_list (name) VALUES ($1) RETURNING id, name, archived";
+        let mut lists: Vec<TaskList> = sqlx::query_as(sql)
+            .bind(&list.name)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        lists
+            .pop()
+            .ok_or(RepositoryError::Database(sqlx::Error::RowNotFound))
+    }
+
+    async fn update_list(&self, id: i64, list: &NewTaskList) -> Result<TaskList> {
+        let sql = "UPDATE task_list SET name=$2 WHERE id=$1 RETURNING id, name, archived";
+        let mut lists: Vec<TaskList> = sqlx::query_as(sql)
+            .bind(id)
+            .bind(&list.name)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        lists.pop().ok_or(RepositoryError::NotFound)
+    }
+
+    async fn archive_list(&self, id: i64, archived: bool) -> Result<TaskList> {
+        let mut tx = self.pool.begin().await?;
+        let sql = "UPDATE task_list SET archived=$2 WHERE id=$1 RETURNING id, name, archived";
+        let mut lists: Vec<TaskList> = sqlx::query_as(sql)
+            .bind(id)
+            .bind(archived)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let list = lists.pop().ok_or(RepositoryError::NotFound)?;
+        let sql = &DB::sql(concat!(
+            list_subtree_ids!(),
+            "UPDATE task SET archived=$2, revision=",
+            next_revision!(),
+            " WHERE id IN (SELECT id FROM subtree) AND archived <> $2"
+        ));
+        sqlx::query(sql)
+            .bind(id)
+            .bind(archived)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        tx.commit().await?;
+        Ok(list)
+    }
+
+    async fn delete_list(&self, id: i64, cascade: bool) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        // no task can be added to the list until the commit, see check_list
+        let sql = &DB::sql("SELECT id FROM task_list WHERE id=$1{for_update}");
+        sqlx::query(sql)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or(RepositoryError::NotFound)?;
+        let sql = "SELECT COUNT(*) FROM task WHERE list_id=$1";
+        let tasks: i64 = sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if tasks > 0 && !cascade {
+            return Err(RepositoryError::Conflict(LIST_NOT_EMPTY.to_string()));
+        }
+        if tasks > 0 {
+            // tombstones like delete_task, the subtasks are deleted by the foreign keys
+            let sql = &DB::sql(concat!(
+                list_subtree_ids!(),
+                "INSERT INTO task_tombstone (uid, revision) SELECT uid, ",
+                next_revision!(),
+                " FROM task WHERE id IN (SELECT id FROM subtree) \
+                 ON CONFLICT (uid) DO UPDATE SET revision = excluded.revision"
+            ));
+            sqlx::query(sql)
+                .bind(id)
+                .execute(&mut *tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+            let sql = "DELETE FROM task WHERE list_id=$1";
+            sqlx::query(sql)
+                .bind(id)
+                .execute(&mut *tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+        }
+        let sql = "DELETE FROM task_list WHERE id=$1";
+        let result = sqlx::query(sql)
+            .bind(id)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match DB::rows_affected(&result) {
+            0 => Err(RepositoryError::NotFound),
+            _ => Ok(tx.commit().await?),
+        }
+    }
+
+    async fn list_tasks(&self, id: i64) -> Result<Vec<Task>> {
+        let sql = &DB::sql(concat!(
+            "SELECT ",
+            task_columns!(),
+            " FROM task WHERE list_id=$1 ORDER BY rank, id"
+        ));
+        let tasks: Vec<Task> = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if tasks.is_empty() {
+            self.list(id).await?;
+        }
+        Ok(tasks)
+    }
+
+    async fn move_to_list(&self, id: i64, list_id: Option<i64>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        // like move_task, so the subtasks stay the same until the commit
+        Self::lock(&mut tx, "task_move", false).await?;
+        // the task first, then its subtasks
+        let sql = concat!(
+            subtree_ids!(),
+            "SELECT id, archived FROM task WHERE id IN (SELECT id FROM subtree) \
+             ORDER BY id <> $1, id"
+        );
+        let subtree: Vec<(i64, bool)> = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if subtree.is_empty() {
+            return Err(RepositoryError::NotFound);
+        }
+        if subtree.iter().any(|(_, archived)| *archived) {
+            return Err(RepositoryError::Conflict(LIST_ARCHIVED.to_string()));
+        }
+        if let Some(list_id) = list_id {
+            Self::check_list(&mut tx, list_id).await?;
+        }
+        let mut rank = Self::last_rank(&mut tx).await?;
+        let sql = &DB::sql(concat!(
+            "UPDATE task SET list_id=$2, rank=$3, revision=",
+            next_revision!(),
+            " WHERE id=$1"
+        ));
+        for (id, _) in subtree {
+            let next_rank = rank::after(rank.as_deref());
+            sqlx::query(sql)
+                .bind(id)
+                .bind(list_id)
+                .bind(&next_rank)
+                .execute(&mut *tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+            rank = Some(next_rank);
+        }
+        Ok(tx.commit().await?)
+    }
+
+    async fn reorder_list(&self, id: i64, task_ids: &[i64]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let sql = &DB::sql("SELECT id FROM task_list WHERE id=$1{for_update}");
+        let found: Option<i64> = sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if found.is_none() {
+            return Err(RepositoryError::NotFound);
+        }
+        Self::lock_ranks(&mut tx).await?;
+        let sql = "SELECT id, rank FROM task WHERE list_id=$1 ORDER BY rank, id";
+        let tasks: Vec<(i64, String)> = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let (ids, ranks): (Vec<i64>, Vec<String>) = tasks.into_iter().unzip();
+        check_list_order(ids, task_ids)?;
+        // the rank is not part of a task (see Task), so the revisions stay
+        let sql = "UPDATE task SET rank=$2 WHERE id=$1";
+        for (task_id, rank) in task_ids.iter().zip(ranks) {
+            sqlx::query(sql)
+                .bind(task_id)
+                .bind(rank)
+                .execute(&mut *tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+        }
+        Ok(tx.commit().await?)
+    }
+
+    async fn rank_task(&self, id: i64, anchors: &RankAnchors) -> Result<()> {
+        check_anchors(id, anchors)?;
+        let mut tx = self.pool.begin().await?;
+        let sql = "SELECT id FROM task WHERE id=$1";
+        let found: Option<i64> = sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if found.is_none() {
+            return Err(RepositoryError::NotFound);
+        }
+        Self::lock_ranks(&mut tx).await?;
+        let rank = Self::anchored_rank(&mut tx, id, anchors).await?;
+        let sql = "UPDATE task SET rank=$2 WHERE id=$1";
+        sqlx::query(sql)
+            .bind(id)
+            .bind(rank)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(tx.commit().await?)
+    }
+
+    async fn boards(&self) -> Result<Vec<Board>> {
+        let mut tx = self.pool.begin().await?;
+        let sql = "SELECT id FROM board ORDER BY id";
+        let ids: Vec<i64> = sqlx::query_scalar(sql)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let mut boards = Vec::with_capacity(ids.len());
+        for id in ids {
+            boards.push(Self::load_board(&mut tx, id).await?);
+        }
+        Ok(boards)
+    }
+
+    async fn board(&self, id: i64) -> Result<Board> {
+        let mut tx = self.pool.begin().await?;
+        Self::load_board(&mut tx, id).await
+    }
+
+    async fn create_board(&self, board: &NewBoard) -> Result<Board> {
+        let mut tx = self.pool.begin().await?;
+        // fetch_all, see insert_task
+        let sql = "INSERT INTO board (name) VALUES ($1) RETURNING id";
+        let mut ids: Vec<i64> = sqlx::query_scalar(sql)
+            .bind(&board.name)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let id = ids
+            .pop()
+            .ok_or(RepositoryError::Database(sqlx::Error::RowNotFound))?;
+        Self::store_board(&mut tx, id, board).await?;
+        let board = Self::load_board(&mut tx, id).await?;
+        tx.commit().await?;
+        Ok(board)
+    }
+
+    async fn update_board(&self, id: i64, board: &NewBoard) -> Result<Board> {
+        let mut tx = self.pool.begin().await?;
+        Self::load_board(&mut tx, id).await?;
+        Self::store_board(&mut tx, id, board).await?;
+        let board = Self::load_board(&mut tx, id).await?;
+        tx.commit().await?;
+        Ok(board)
+    }
+
+    async fn delete_board(&self, id: i64) -> Result<()> {
+        // the columns are deleted by the foreign key
+        let sql = "DELETE FROM board WHERE id=$1";
+        let result = sqlx::query(sql)
+            .bind(id)
+            .execute(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match DB::rows_affected(&result) {
+            0 => Err(RepositoryError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn move_card(
+        &self,
+        board_id: i64,
+        id: i64,
+        card: &CardMove,
+        completed_on: NaiveDate,
+    ) -> Result<()> {
+        let anchors = card.anchors();
+        if let Some(anchors) = &anchors {
+            check_anchors(id, anchors)?;
+        }
+        let mut tx = self.pool.begin().await?;
+        // one card move at a time, so that no two moves fill the same last place of a column
+        Self::lock(&mut tx, "board_card", false).await?;
+        let board = Self::load_board(&mut tx, board_id).await?;
+        let column = board_column(&board.columns, card.column_id)?;
+        // like update_task, a concurrent update finds the task changed
+        let previous = Self::lock_task(&mut tx, id).await?;
+        if !on_board(board.list_id, previous.list_id, previous.archived) {
+            return Err(not_on_board());
+        }
+        let status = previous.status;
+        let card_status = card_status(status, previous.completed_on, column, completed_on);
+        if let Some((new_status, completed_on)) = card_status {
+            let moved = Task {
+                status: Some(new_status),
+                completed_on,
+                ..previous.clone()
+            };
+            let moved = Self::follow_workflow(&mut tx, &previous, None, moved).await?;
+            let sql = "SELECT COUNT(*) FROM task WHERE id <> $1 AND NOT archived \
+                       AND ($2 IS NULL OR list_id = $2) AND COALESCE(status, 'needs-action') = $3";
+            let cards: i64 = sqlx::query_scalar(sql)
+                .bind(id)
+                .bind(board.list_id)
+                .bind(column.status)
+                .fetch_one(&mut *tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+            check_wip_limit(column, cards)?;
+            let completes = new_status == TaskStatus::Completed;
+            if completes && status != Some(TaskStatus::Cancelled) {
+                Self::check_blockers(&mut tx, &[id]).await?;
+            }
+            let sql = &DB::sql(concat!(
+                "UPDATE task SET status=$2, completed_on=$3, state=COALESCE($4, state), revision=",
+                next_revision!(),
+                " WHERE id=$1"
+            ));
+            sqlx::query(sql)
+                .bind(id)
+                .bind(new_status)
+                .bind(completed_on)
+                .bind(&moved.state)
+                .execute(&mut *tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+            if completes {
+                // before the ranks are locked, like create_task locks the list first
+                let completed = Self::lock_task(&mut tx, id).await?;
+                Self::create_next_occurrence(&mut tx, &completed).await?;
+            }
+        }
+        if let Some(anchors) = &anchors {
+            Self::lock_ranks(&mut tx).await?;
+            let rank = Self::anchored_rank(&mut tx, id, anchors).await?;
+            let sql = "UPDATE task SET rank=$2 WHERE id=$1";
+            sqlx::query(sql)
+                .bind(id)
+                .bind(rank)
+                .execute(&mut *tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+        }
+        Ok(tx.commit().await?)
+    }
+
+    async fn workflows(&self) -> Result<Vec<Workflow>> {
+        let mut tx = self.pool.begin().await?;
+        let sql = "SELECT id FROM workflow ORDER BY id";
+        let ids: Vec<i64> = sqlx::query_scalar(sql)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let mut workflows = Vec::with_capacity(ids.len());
+        for id in ids {
+            workflows.push(Self::load_workflow(&mut tx, id).await?);
+        }
+        Ok(workflows)
+    }
+
+    async fn workflow(&self, id: i64) -> Result<Workflow> {
+        let mut tx = self.pool.begin().await?;
+        Self::load_workflow(&mut tx, id).await
+    }
+
+    async fn create_workflow(&self, workflow: &NewWorkflow) -> Result<Workflow> {
+        let mut tx = self.pool.begin().await?;
+        // fetch_all, see insert_task
+        let sql = "INSERT INTO workflow (name) VALUES ($1) RETURNING id";
+        let mut ids: Vec<i64> = sqlx::query_scalar(sql)
+            .bind(&workflow.name)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let id = ids
+            .pop()
+            .ok_or(RepositoryError::Database(sqlx::Error::RowNotFound))?;
+        Self::store_workflow(&mut tx, id, workflow).await?;
+        let workflow = Self::load_workflow(&mut tx, id).await?;
+        tx.commit().await?;
+        Ok(workflow)
+    }
+
+    async fn update_workflow(&self, id: i64, workflow: &NewWorkflow) -> Result<Workflow> {
+        let mut tx = self.pool.begin().await?;
+        Self::load_workflow(&mut tx, id).await?;
+        Self::store_workflow(&mut tx, id, workflow).await?;
+        let workflow = Self::load_workflow(&mut tx, id).await?;
+        tx.commit().await?;
+        Ok(workflow)
+    }
+
+    async fn delete_workflow(&self, id: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let sql = "SELECT EXISTS (SELECT 1 FROM task WHERE workflow_id=$1)";
+        let used: bool = sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if used {
+            return Err(RepositoryError::Conflict(WORKFLOW_IN_USE.to_string()));
+        }
+        // the states and transitions are deleted by the foreign keys
+        let sql = "DELETE FROM workflow WHERE id=$1";
+        let result = sqlx::query(sql)
+            .bind(id)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match DB::rows_affected(&result) {
+            0 => Err(RepositoryError::NotFound),
+            _ => Ok(tx.commit().await?),
+        }
+    }
+
+    async fn set_workflow(&self, id: i64, workflow_id: Option<i64>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let (state, status) = match workflow_id {
+            Some(workflow_id) => {
+                let (state, status) = Self::initial_state(&mut tx, workflow_id).await?;
+                (Some(state), Some(status))
+            }
+            None => (None, None),
+        };
+        let sql = &DB::sql(concat!(
+            "UPDATE task SET workflow_id=$2, state=$3, status=COALESCE($4, status), revision=",
+            next_revision!(),
+            " WHERE id=$1"
+        ));
+        let result = sqlx::query(sql)
+            .bind(id)
+            .bind(workflow_id)
+            .bind(state)
+            .bind(status)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match DB::rows_affected(&result) {
+            0 => Err(RepositoryError::NotFound),
+            _ => Ok(tx.commit().await?),
+        }
+    }
+
+    async fn comments(&self, task_id: i64) -> Result<Vec<Comment>> {
+        let mut tx = self.pool.begin().await?;
+        Self::check_task(&mut tx, task_id).await?;
+        let sql = concat!(
+            "SELECT ",
+            comment_columns!(),
+            " FROM task_comment WHERE task_id=$1 ORDER BY id"
+        );
+        let comments = sqlx::query_as(sql)
+            .bind(task_id)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(comments)
+    }
+
+    async fn comment(&self, task_id: i64, id: i64) -> Result<Comment> {
+        let sql = concat!(
+            "SELECT ",
+            comment_columns!(),
+            " FROM task_comment WHERE id=$1 AND task_id=$2"
+        );
+        sqlx::query_as(sql)
+            .bind(id)
+            .bind(task_id)
+            .fetch_optional(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn create_comment(
+        &self,
+        task_id: i64,
+        comment: &NewComment,
+        mentions: &[String],
+        now: DateTime<Utc>,
+    ) -> Result<(Comment, Vec<Notification>)> {
+        let sql = concat!(
+            "INSERT INTO task_comment (task_id, author, body, mentions, created_at) \
+             VALUES ($1, $2, $3, $4, $5) RETURNING ",
+            comment_columns!()
+        );
+        let mut tx = self.pool.begin().await?;
+        Self::check_task(&mut tx, task_id).await?;
+        // fetch_all, see insert_task
+        let mut created: Vec<Comment> = sqlx::query_as(sql)
+            .bind(task_id)
+            .bind(&comment.author)
+            .bind(&comment.body)
+            .bind(fields_column(mentions))
+            .bind(now)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let created = created
+            .pop()
+            .ok_or(RepositoryError::Database(sqlx::Error::RowNotFound))?;
+        let notified = Self::notify(&mut tx, &created, &comment.author, mentions, now).await?;
+        tx.commit().await?;
+        Ok((created, notified))
+    }
+
+    async fn update_comment(
+        &self,
+        task_id: i64,
+        id: i64,
+        comment: &NewComment,
+        mentions: &[String],
+        now: DateTime<Utc>,
+    ) -> Result<(Comment, Vec<Notification>)> {
+        let mut tx = self.pool.begin().await?;
+        let sql = "INSERT INTO comment_version (comment_id, author, body, written_at) \
+                   SELECT id, COALESCE(edited_by, author), body, COALESCE(edited_at, created_at) \
+                   FROM task_comment WHERE id=$1 AND task_id=$2";
+        let result = sqlx::query(sql)
+            .bind(id)
+            .bind(task_id)
+            .execute(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if DB::rows_affected(&result) == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        let sql = concat!(
+            "UPDATE task_comment SET body=$2, mentions=$3, edited_by=$4, edited_at=$5 \
+             WHERE id=$1 RETURNING ",
+            comment_columns!()
+        );
+        let mut updated: Vec<Comment> = sqlx::query_as(sql)
+            .bind(id)
+            .bind(&comment.body)
+            .bind(fields_column(mentions))
+            .bind(&comment.author)
+            .bind(now)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let updated = updated
+            .pop()
+            .ok_or(RepositoryError::Database(sqlx::Error::RowNotFound))?;
+        let notified = Self::notify(&mut tx, &updated, &comment.author, mentions, now).await?;
+        tx.commit().await?;
+        Ok((updated, notified))
+    }
+
+    async fn delete_comment(&self, task_id: i64, id: i64) -> Result<()> {
+        // the history and the notifications are deleted by the foreign keys
+        let sql = "DELETE FROM task_comment WHERE id=$1 AND task_id=$2";
+        let result = sqlx::query(sql)
+            .bind(id)
+            .bind(task_id)
+            .execute(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match DB::rows_affected(&result) {
+            0 => Err(RepositoryError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn comment_history(&self, task_id: i64, id: i64) -> Result<Vec<CommentVersion>> {
+        let mut tx = self.pool.begin().await?;
+        let sql = "SELECT EXISTS (SELECT 1 FROM task_comment WHERE id=$1 AND task_id=$2)";
+        let exists: bool = sqlx::query_scalar(sql)
+            .bind(id)
+            .bind(task_id)
+            .fetch_one(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        if !exists {
+            return Err(RepositoryError::NotFound);
+        }
+        let sql = "SELECT author, body, written_at FROM comment_version WHERE comment_id=$1 \
+                   ORDER BY id";
+        let history = sqlx::query_as(sql)
+            .bind(id)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(history)
+    }
+
+    async fn notifications(
+        &self,
+        recipient: Option<&str>,
+        after: i64,
+    ) -> Result<Vec<Notification>> {
+        let sql = concat!(
+            "SELECT ",
+            notification_columns!(),
+            " FROM notification WHERE id > $1 AND ($2 IS NULL OR recipient = $2) ORDER BY id"
+        );
+        let notifications = sqlx::query_as(sql)
+            .bind(after)
+            .bind(recipient)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(notifications)
+    }
+
+    async fn changes_since(&self, revision: i64) -> Result<Changes> {
+        // one snapshot, so the revision matches the changes
+        let mut tx = self.pool.begin().await?;
+        if let Some(sql) = DB::SNAPSHOT {
+            sqlx::query(sql)
+                .execute(&mut *tx)
+                .instrument(db_span(DB::SYSTEM, sql))
+                .await?;
+        }
+        let sql = "SELECT COALESCE(MAX(revision), 0) FROM \
+                   (SELECT revision FROM task UNION ALL SELECT revision FROM task_tombstone) \
+                   AS revisions";
+        let current = sqlx::query_scalar(sql)
+            .fetch_one(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let sql = &DB::sql(concat!(
+            "SELECT ",
+            task_columns!(),
+            " FROM task WHERE revision > $1 ORDER BY revision"
+        ));
+        let changed = sqlx::query_as(sql)
+            .bind(revision)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        let sql = "SELECT uid FROM task_tombstone WHERE revision > $1 \
+                   AND uid NOT IN (SELECT uid FROM task) ORDER BY revision";
+        let deleted = sqlx::query_scalar(sql)
+            .bind(revision)
+            .fetch_all(&mut *tx)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        tx.commit().await?;
+        Ok(Changes {
+            revision: current,
+            changed,
+            deleted,
+        })
+    }
+
+    async fn current_revision(&self) -> Result<i64> {
+        // each inner MAX is looked up in an index
+        let sql = "SELECT COALESCE(MAX(revision), 0) FROM \
+                   (SELECT MAX(revision) AS revision FROM task \
+                   UNION ALL SELECT MAX(revision) FROM task_tombstone) AS revisions";
+        Ok(sqlx::query_scalar(sql)
+            .fetch_one(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?)
+    }
+
+    async fn due_reminders(&self, until: DateTime<Utc>) -> Result<Vec<Task>> {
+        let remind_at = DB::time("remind_at");
+        let sql = &format!(
+            "{} WHERE {} AND {} <= {} ORDER BY {}, id",
+            DB::sql(concat!("SELECT ", task_columns!(), " FROM task")),
+            Self::pending_reminders(),
+            remind_at,
+            DB::time("$1"),
+            remind_at
+        );
+        Ok(sqlx::query_as(sql)
+            .bind(until)
+            .fetch_all(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?)
+    }
+
+    async fn next_reminder(&self) -> Result<Option<DateTime<Utc>>> {
+        let sql = &format!(
+            "SELECT remind_at FROM task WHERE {} ORDER BY {} LIMIT 1",
+            Self::pending_reminders(),
+            DB::time("remind_at")
+        );
+        Ok(sqlx::query_scalar(sql)
+            .fetch_optional(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?)
+    }
+
+    async fn reminder_sent(&self, id: i64, remind_at: DateTime<Utc>) -> Result<()> {
+        let sql = "UPDATE task SET reminded_at=$1 WHERE id=$2";
+        let result = sqlx::query(sql)
+            .bind(remind_at)
+            .bind(id)
+            .execute(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        match DB::rows_affected(&result) {
+            0 => Err(RepositoryError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn backup_to(&self, path: &Path) -> Result<()> {
+        let sql = DB::BACKUP.ok_or(RepositoryError::Unsupported("backup"))?;
+        sqlx::query(sql)
+            .bind(path.to_string_lossy().as_ref())
+            .execute(&self.pool)
+            .instrument(db_span(DB::SYSTEM, sql))
+            .await?;
+        Ok(())
+    }
+}
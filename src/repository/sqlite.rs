@@ -0,0 +1,88 @@
+use anyhow::Context;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteQueryResult};
+use sqlx::{ConnectOptions, Connection, Sqlite};
+use std::str::FromStr;
+
+use super::sql::{Dialect, SqlTaskRepository};
+
+pub type SqliteTaskRepository = SqlTaskRepository<Sqlite>;
+
+impl Dialect for Sqlite {
+    const SYSTEM: &'static str = "sqlite";
+    const TAGS: &'static str =
+        "(SELECT json_group_array(json_object('id', tag.id, 'name', tag.name, 'colour', tag.colour)) \
+         FROM (SELECT tag.* FROM tag JOIN task_tag ON task_tag.tag_id = tag.id \
+         WHERE task_tag.task_id = task.id ORDER BY tag.name_key) AS tag)";
+    /// sqlite serializes all writers, so MAX + 1 is unique
+    const NEXT_REVISION: &'static str = "(SELECT COALESCE(MAX(revision), 0) + 1 FROM \
+         (SELECT revision FROM task UNION ALL SELECT revision FROM task_tombstone) AS revisions)";
+    /// sqlite serializes the writers, so the rows stay as they are until the commit anyway
+    const FOR_UPDATE: &'static str = "";
+    const IS_DISTINCT_FROM: &'static str = "IS NOT";
+    /// the default collation BINARY compares the bytes
+    const COLLATE: &'static str = "";
+    /// sqlite has no array parameters
+    const JSON_IDS: &'static str = "SELECT value FROM json_each($1)";
+    const LOCKS: Option<(&'static str, &'static str)> = None;
+    /// a transaction reads from one snapshot anyway
+    const SNAPSHOT: Option<&'static str> = None;
+    /// VACUUM INTO reads from a single read transaction, so the copy is consistent even while
+    /// other connections keep writing (WAL mode)
+    const BACKUP: Option<&'static str> = Some("VACUUM INTO $1");
+
+    /// stored as text, julianday compares the times rather than the strings
+    fn time(column: &str) -> String {
+        format!("julianday({})", column)
+    }
+
+    fn rows_affected(result: &SqliteQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
+impl SqliteTaskRepository {
+    /** Create database file (e.g. "tasks.db") if it does not exist.
+      Create schema (invoke migrations).
+      Return a repository using a connection pool (sqlx - sqlite)
+    */
+    pub async fn connect(database_url: &str) -> anyhow::Result<SqliteTaskRepository> {
+        if database_url.contains(":memory:") {
+            return SqliteTaskRepository::in_memory().await;
+        }
+
+        // create database if it does not exist
+        let conn = SqliteConnectOptions::from_str(database_url)?
+            .journal_mode(SqliteJournalMode::Wal)
+            .create_if_missing(true)
+            .connect()
+            .await?;
+        conn.close().await?;
+
+        // prepare connection pool
+        let pool = SqlitePoolOptions::new()
+            .max_connections(50)
+            .connect(database_url)
+            .await
+            .context("could not connect to database_url")?;
+
+        // prepare schema in db if it does not yet exist
+        sqlx::migrate!("migrations/sqlite").run(&pool).await?;
+
+        Ok(SqliteTaskRepository::new(pool))
+    }
+
+    /** Private, empty database in memory, e.g. one per test.
+      Every connection to sqlite::memory: opens a new database, so the pool
+      keeps exactly one connection open for the lifetime of the repository.
+    */
+    pub async fn in_memory() -> anyhow::Result<SqliteTaskRepository> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await?;
+        sqlx::migrate!("migrations/sqlite").run(&pool).await?;
+        Ok(SqliteTaskRepository::new(pool))
+    }
+}
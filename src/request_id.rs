@@ -0,0 +1,53 @@
+//! Request correlation ids.
+//!
+//! Every request gets an `X-Request-Id`: the one sent by the client if it is usable,
+//! otherwise a freshly generated UUID. The id is
+//! - recorded on the request span (see `telemetry::make_span`), so every log line carries it
+//! - echoed in the `X-Request-Id` response header
+//! - available to handlers via [`current`], e.g. to include it in error bodies
+
+use axum::http::{HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// longer client supplied ids are replaced by a generated one
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Middleware (use with `axum::middleware::from_fn`). Must wrap the `TraceLayer`
+/// so that the request span already sees the final id.
+pub async fn propagate_request_id<B>(mut request: Request<B>, next: Next<B>) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    // only visible ascii is accepted above, so the id is always a valid header value
+    let header_value = HeaderValue::from_str(&id).expect("request id is a valid header value");
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header_value.clone());
+
+    let mut response = REQUEST_ID.scope(id, next.run(request)).await;
+    response
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header_value);
+    response
+}
+
+/// Request id of the request currently being handled (None outside of a request).
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
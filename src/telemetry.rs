@@ -0,0 +1,152 @@
+//! Tracing setup: logs on stdout plus optional OpenTelemetry span export.
+//!
+//! Logs are human readable by default, set `LOG_FORMAT=json` for JSON lines
+//! (one object per event including the fields of the enclosing spans, e.g. `request_id`).
+//!
+//! OTLP export (gRPC) is switched on by setting `OTEL_EXPORTER_OTLP_ENDPOINT`, e.g.
+//!
+//! ```not_rust
+//! OTEL_EXPORTER_OTLP_ENDPOINT=http://localhost:4317 cargo run --release
+//! ```
+//!
+//! Sampling is configured with the standard `OTEL_TRACES_SAMPLER` and `OTEL_TRACES_SAMPLER_ARG`
+//! variables (`always_on`, `always_off`, `traceidratio`, `parentbased_always_on` (default),
+//! `parentbased_always_off`, `parentbased_traceidratio`), the service name with `OTEL_SERVICE_NAME`.
+
+use axum::http::Request;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::{trace, Resource};
+use opentelemetry::KeyValue;
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::WithExportConfig;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+use crate::request_id::REQUEST_ID_HEADER;
+
+const SERVICE_NAME: &str = "axum_crud_api";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    /// `LOG_FORMAT=json` selects JSON lines, anything else human readable text
+    pub fn from_env() -> LogFormat {
+        match std::env::var("LOG_FORMAT") {
+            Ok(format) if format.eq_ignore_ascii_case("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        }
+    }
+}
+
+/// Log layer writing events in the given format to `writer`.
+pub fn fmt_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer.json().with_current_span(false).boxed(),
+    }
+}
+
+pub fn init_tracing() {
+    let fmt_filter = tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        tracing_subscriber::EnvFilter::new(if cfg!(test) {
+            "tower_http=error"
+        } else {
+            "axum_crud_api=debug,tower_http=debug"
+        })
+    });
+
+    // the otel layer has its own filter so that exported spans do not depend on RUST_LOG
+    let otel_layer = otlp_tracer().map(|tracer| {
+        tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(LevelFilter::INFO)
+    });
+
+    tracing_subscriber::registry()
+        .with(fmt_layer(LogFormat::from_env(), std::io::stdout).with_filter(fmt_filter))
+        .with(otel_layer)
+        .init();
+}
+
+/// Flush pending spans to the collector. Call before the process exits.
+pub fn shutdown_tracing() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/** Install an OTLP batch exporter if `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+  Returns None (no export) otherwise or if the exporter cannot be created.
+*/
+fn otlp_tracer() -> Option<trace::Tracer> {
+    std::env::var(opentelemetry_otlp::OTEL_EXPORTER_OTLP_ENDPOINT).ok()?;
+
+    let service_name =
+        std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| SERVICE_NAME.to_string());
+    // trace::config() reads OTEL_TRACES_SAMPLER and OTEL_TRACES_SAMPLER_ARG
+    let trace_config = trace::config().with_resource(Resource::new(vec![KeyValue::new(
+        "service.name",
+        service_name,
+    )]));
+
+    match opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_env())
+        .with_trace_config(trace_config)
+        .install_batch(opentelemetry::runtime::Tokio)
+    {
+        Ok(tracer) => Some(tracer),
+        Err(err) => {
+            eprintln!(
+                "could not install OTLP exporter, spans are not exported: {:?}",
+                err
+            );
+            None
+        }
+    }
+}
+
+/** Create the span for an incoming http request (use with `TraceLayer::make_span_with`).
+  If the request carries a W3C `traceparent` header the span continues that trace.
+*/
+pub fn make_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "http_request",
+        request_id = %request_id,
+        otel.name = %format!("{} {}", request.method(), request.uri().path()),
+        otel.kind = "server",
+        http.method = %request.method(),
+        http.target = %request.uri(),
+    );
+    let parent_context = TraceContextPropagator::new().extract(&HeaderExtractor(request.headers()));
+    span.set_parent(parent_context);
+    span
+}
+
+/// Span around a single sqlx statement, attributes follow the OpenTelemetry database conventions.
+pub fn db_span(system: &'static str, statement: &str) -> Span {
+    tracing::info_span!(
+        "db_query",
+        otel.name = %statement.split_whitespace().next().unwrap_or("query"),
+        otel.kind = "client",
+        db.system = system,
+        db.statement = %statement,
+    )
+}
@@ -0,0 +1,102 @@
+use crate::repository::memory::MemoryTaskRepository;
+use crate::{app, bind};
+use axum::Router;
+use hyper::{body::to_bytes, Body, Method, Request, StatusCode};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn memory_app() -> Router {
+    app(Arc::new(MemoryTaskRepository::new()))
+}
+
+#[tokio::test]
+async fn test_create_and_get_task_in_process() -> anyhow::Result<()> {
+    let app = memory_app();
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .uri("/tasks")
+        .body(Body::from(r#"{"task":"my first test task"}"#))?;
+    // oneshot consumes the service, the clone shares the same store
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers()["location"], "/tasks/1");
+
+    let req = Request::builder().uri("/tasks/1").body(Body::empty())?;
+    let resp = app.oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body_bytes = to_bytes(resp.into_body()).await?;
+    assert_eq!(body_bytes, r#"{"id":1,"task":"my first test task"}"#);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_invalid_tasks_are_unprocessable() -> anyhow::Result<()> {
+    let app = memory_app();
+
+    for (method, uri, body, msg) in [
+        (
+            Method::POST,
+            "/tasks",
+            r#"{"task":"  "}"#,
+            "task must not be empty",
+        ),
+        (
+            Method::POST,
+            "/tasks",
+            r#"{"task":"ok","priority":0}"#,
+            "priority must be between 1 and 9",
+        ),
+        (
+            Method::PUT,
+            "/tasks/1",
+            r#"{"task":"ok","priority":10}"#,
+            "priority must be between 1 and 9",
+        ),
+    ] {
+        let req = Request::builder()
+            .method(method)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .uri(uri)
+            .body(Body::from(body))?;
+        let resp = app.clone().oneshot(req).await?;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = serde_json::from_slice(&to_bytes(resp.into_body()).await?)?;
+        assert_eq!(body["msg"], msg);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_app_nested_in_other_router() -> anyhow::Result<()> {
+    let app = Router::new().nest("/todo", memory_app());
+
+    let req = Request::builder().uri("/todo/tasks").body(Body::empty())?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body_bytes = to_bytes(resp.into_body()).await?;
+    assert_eq!(body_bytes, r#"[]"#);
+
+    let req = Request::builder().uri("/tasks").body(Body::empty())?;
+    let resp = app.oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bind_port_zero_returns_actual_address() -> anyhow::Result<()> {
+    let server = bind(&SocketAddr::from(([127, 0, 0, 1], 0)), memory_app())?;
+    let addr = server.local_addr();
+    assert_ne!(addr.port(), 0);
+    tokio::spawn(server);
+
+    let resp = hyper::Client::new()
+        .get(format!("http://{}/hello", addr).parse()?)
+        .await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body_bytes = to_bytes(resp.into_body()).await?;
+    assert_eq!(body_bytes, "Hello, World");
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use crate::backup::{self, BackupConfig, BackupError};
+use crate::models::task::NewTask;
+use crate::repository::memory::MemoryTaskRepository;
+use crate::repository::sqlite::SqliteTaskRepository;
+use crate::repository::{DynTaskRepository, TaskRepository};
+use axum::http::{Method, Request, StatusCode};
+use hyper::{body::to_bytes, Body};
+use std::path::Path;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn backup_config(dir: &Path, compress: bool) -> BackupConfig {
+    BackupConfig {
+        dir: dir.join("backups"),
+        compress,
+        keep_last: 7,
+        max_age: None,
+    }
+}
+
+/// sqlite database file in `dir` with one task
+async fn sqlite_file_repository(dir: &Path) -> anyhow::Result<(String, SqliteTaskRepository)> {
+    let url = format!("sqlite:{}", dir.join("tasks.db").display());
+    let repo = SqliteTaskRepository::connect(&url).await?;
+    repo.create_task(&NewTask {
+        task: "backed up task".to_string(),
+        ..Default::default()
+    })
+    .await?;
+    Ok((url, repo))
+}
+
+#[tokio::test]
+async fn test_admin_create_and_list_backups() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (_, repo) = sqlite_file_repository(dir.path()).await?;
+    let repo: DynTaskRepository = Arc::new(repo);
+    let app = crate::admin_app(repo, backup_config(dir.path(), false));
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("/admin/backups")
+        .body(Body::empty())?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let created: serde_json::Value = serde_json::from_slice(&to_bytes(resp.into_body()).await?)?;
+    assert_eq!(created["compressed"], false);
+
+    let req = Request::builder()
+        .uri("/admin/backups")
+        .body(Body::empty())?;
+    let resp = app.oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let listed: serde_json::Value = serde_json::from_slice(&to_bytes(resp.into_body()).await?)?;
+    assert_eq!(listed.as_array().map(Vec::len), Some(1));
+    assert_eq!(listed[0]["name"], created["name"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_admin_backup_unsupported_by_memory_store() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let app = crate::admin_app(
+        Arc::new(MemoryTaskRepository::new()),
+        backup_config(dir.path(), false),
+    );
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("/admin/backups")
+        .body(Body::empty())?;
+    let resp = app.oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::NOT_IMPLEMENTED);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compressed_backup_restores_into_new_database() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = backup_config(dir.path(), true);
+    let (_, repo) = sqlite_file_repository(dir.path()).await?;
+    let created = backup::create_backup(&repo, &config).await?;
+    assert!(created.compressed);
+    assert!(created.name.ends_with(".db.gz"));
+
+    let restored_url = format!("sqlite:{}", dir.path().join("restored.db").display());
+    backup::restore_backup(&config, &created.name, &restored_url).await?;
+
+    let restored = SqliteTaskRepository::connect(&restored_url).await?;
+    let tasks = restored.all_tasks().await?;
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].task, "backed up task");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_rejects_corrupt_backup() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = backup_config(dir.path(), false);
+    std::fs::create_dir_all(&config.dir)?;
+    let name = "backup-20221117T120000.000Z.db";
+    std::fs::write(config.dir.join(name), "not a database")?;
+    let database = dir.path().join("live.db");
+    std::fs::write(&database, "live data")?;
+
+    let result =
+        backup::restore_backup(&config, name, &format!("sqlite:{}", database.display())).await;
+    assert!(matches!(result, Err(BackupError::Integrity(..))));
+    assert_eq!(std::fs::read_to_string(&database)?, "live data");
+    assert!(!dir.path().join("live.db.restore").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_prune_keeps_newest_backups() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut config = backup_config(dir.path(), false);
+    std::fs::create_dir_all(&config.dir)?;
+    for name in [
+        "backup-20221115T120000.000Z.db",
+        "backup-20221116T120000.000Z.db.gz",
+        "backup-20221117T120000.000Z.db",
+        "unrelated.txt",
+    ] {
+        std::fs::write(config.dir.join(name), "")?;
+    }
+    config.keep_last = 1;
+
+    let report = backup::prune_backups(&config)?;
+    assert_eq!(report.kept, 1);
+    let removed: Vec<&str> = report.removed.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(
+        removed,
+        [
+            "backup-20221116T120000.000Z.db.gz",
+            "backup-20221115T120000.000Z.db"
+        ]
+    );
+    let remaining: Vec<String> = backup::list_backups(&config.dir)?
+        .into_iter()
+        .map(|b| b.name)
+        .collect();
+    assert_eq!(remaining, ["backup-20221117T120000.000Z.db"]);
+    assert!(config.dir.join("unrelated.txt").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_refuses_database_in_use() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = backup_config(dir.path(), false);
+    let (url, repo) = sqlite_file_repository(dir.path()).await?;
+    let created = backup::create_backup(&repo, &config).await?;
+
+    let result = backup::restore_backup(&config, &created.name, &url).await;
+    assert!(matches!(result, Err(BackupError::InUse(_))));
+    assert_eq!(repo.all_tasks().await?.len(), 1);
+    assert!(!dir.path().join("tasks.db.restore").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_keeps_uncheckpointed_data() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = backup_config(dir.path(), false);
+    let (_, repo) = sqlite_file_repository(dir.path()).await?;
+    let created = backup::create_backup(&repo, &config).await?;
+    repo.create_task(&NewTask {
+        task: "task after the backup".to_string(),
+        ..Default::default()
+    })
+    .await?;
+
+    // the files of a crashed server: the new task is only in the WAL
+    let crashed = dir.path().join("crashed");
+    std::fs::create_dir(&crashed)?;
+    for file in ["tasks.db", "tasks.db-wal", "tasks.db-shm"] {
+        std::fs::copy(dir.path().join(file), crashed.join(file))?;
+    }
+    let crashed_url = format!("sqlite:{}", crashed.join("tasks.db").display());
+    for _ in 0..2 {
+        backup::restore_backup(&config, &created.name, &crashed_url).await?;
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+
+    let restored = SqliteTaskRepository::connect(&crashed_url).await?;
+    assert_eq!(restored.all_tasks().await?.len(), 1);
+    let mut kept: Vec<String> = std::fs::read_dir(&crashed)?
+        .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string()))
+        .filter(|name| {
+            !name
+                .as_ref()
+                .is_ok_and(|name| !name.contains("pre-restore"))
+        })
+        .collect::<Result<_, _>>()?;
+    kept.sort();
+    // each restore keeps its own copy, the first one with the task only the WAL had
+    assert_eq!(kept.len(), 2);
+    let first =
+        SqliteTaskRepository::connect(&format!("sqlite:{}", crashed.join(&kept[0]).display()))
+            .await?;
+    assert_eq!(first.all_tasks().await?.len(), 2);
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use super::{request, sqlite_memory_repository};
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+/// Names of the tasks in each column of a board
+fn cards(board: &Value) -> Vec<Vec<&str>> {
+    board["columns"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|column| {
+            column["tasks"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|task| task["task"].as_str().unwrap())
+                .collect()
+        })
+        .collect()
+}
+
+/// Board 1 with the columns 1 "todo", 2 "doing" (at most one task) and 3 "done"
+/// and the tasks 1 "plan", 2 "build" and 3 "ship"
+async fn release_board(app: &axum::Router) -> anyhow::Result<()> {
+    for task in ["plan", "build", "ship"] {
+        let (status, _) = request(app, Method::POST, "/tasks", json!({ "task": task })).await?;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    let board = json!({
+        "name": "release",
+        "columns": [
+            {"name": "todo", "status": "needs-action"},
+            {"name": "doing", "status": "in-process", "wip_limit": 1},
+            {"name": "done", "status": "completed"}
+        ]
+    });
+    let (status, body) = request(app, Method::POST, "/boards", board).await?;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["columns"][1]["id"], 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_board_crud() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    release_board(&app).await?;
+
+    let (_, body) = request(&app, Method::GET, "/boards", Value::Null).await?;
+    assert_eq!(body[0]["name"], "release");
+    assert_eq!(
+        body[0]["columns"][1],
+        json!({"id": 2, "name": "doing", "status": "in-process", "wip_limit": 1})
+    );
+    let (status, body) = request(&app, Method::GET, "/boards/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        cards(&body),
+        [vec!["plan", "build", "ship"], vec![], vec![]]
+    );
+
+    for (board, msg) in [
+        (
+            json!({"name": "x", "columns": []}),
+            "a board needs at least one column",
+        ),
+        (
+            json!({"name": "x", "columns": [
+                {"name": "a", "status": "completed"},
+                {"name": "b", "status": "completed"}
+            ]}),
+            "status completed is in more than one column",
+        ),
+        (
+            json!({"name": "x", "columns": [{"name": "a", "status": "completed", "wip_limit": 0}]}),
+            "wip_limit must be at least 1",
+        ),
+    ] {
+        let (status, body) = request(&app, Method::POST, "/boards", board).await?;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["msg"], msg);
+    }
+
+    let board = json!({"name": "short", "columns": [{"name": "done", "status": "completed"}]});
+    let (status, body) = request(&app, Method::PUT, "/boards/1", board).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["columns"],
+        json!([{"id": 3, "name": "done", "status": "completed"}])
+    );
+    let (status, _) = request(&app, Method::DELETE, "/boards/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = request(&app, Method::GET, "/boards/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["msg"], "board not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_move_card() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    release_board(&app).await?;
+
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/boards/1/cards/3/move",
+        json!({"column_id": 2}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cards(&body), [vec!["plan", "build"], vec!["ship"], vec![]]);
+    let (_, body) = request(&app, Method::GET, "/tasks/3", Value::Null).await?;
+    assert_eq!(body["status"], "in-process");
+
+    // the doing column is full
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/boards/1/cards/1/move",
+        json!({"column_id": 2}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        body["msg"],
+        "column doing is at its work in progress limit of 1"
+    );
+
+    // status and place at once
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/boards/1/cards/3/move",
+        json!({"column_id": 1, "before": 1}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        cards(&body),
+        [vec!["ship", "plan", "build"], vec![], vec![]]
+    );
+
+    for (uri, card, status) in [
+        (
+            "/boards/1/cards/1/move",
+            json!({"column_id": 4711}),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        (
+            "/boards/4711/cards/1/move",
+            json!({"column_id": 1}),
+            StatusCode::NOT_FOUND,
+        ),
+        (
+            "/boards/1/cards/4711/move",
+            json!({"column_id": 1}),
+            StatusCode::NOT_FOUND,
+        ),
+    ] {
+        let (actual, _) = request(&app, Method::POST, uri, card).await?;
+        assert_eq!(actual, status, "{}", uri);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_move_card_to_done() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    release_board(&app).await?;
+
+    // ship is blocked by build
+    let (status, _) = request(
+        &app,
+        Method::POST,
+        "/tasks/3/blockers",
+        json!({"blocker_id": 2}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/boards/1/cards/3/move",
+        json!({"column_id": 3}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["msg"], "task is blocked by the open tasks 2");
+
+    // a recurring task gets its next occurrence in the todo column
+    let task = json!({
+        "task": "build",
+        "due_at": "2022-11-28T09:00:00Z",
+        "rrule": "FREQ=WEEKLY"
+    });
+    let (status, _) = request(&app, Method::PUT, "/tasks/2", task).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/boards/1/cards/2/move",
+        json!({"column_id": 3}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        cards(&body),
+        [vec!["plan", "ship", "build"], vec![], vec!["build"]]
+    );
+    assert!(body["columns"][2]["tasks"][0]["completed_on"].is_string());
+    assert_eq!(
+        body["columns"][0]["tasks"][2]["due_at"],
+        "2022-12-05T09:00:00Z"
+    );
+    Ok(())
+}
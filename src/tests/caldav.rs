@@ -0,0 +1,290 @@
+use crate::app;
+use crate::repository::memory::MemoryTaskRepository;
+use crate::repository::DynTaskRepository;
+use axum::response::Response;
+use axum::Router;
+use hyper::{body::to_bytes, Body, Method, Request, StatusCode};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+const CALENDAR: &str = "/caldav/tasks/";
+
+const SYNC_REQUEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:sync-collection xmlns:d="DAV:">
+  <d:sync-token>TOKEN</d:sync-token>
+  <d:sync-level>1</d:sync-level>
+  <d:prop><d:getetag/></d:prop>
+</d:sync-collection>"#;
+
+fn vtodo(uid: &str, summary: &str) -> String {
+    format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VTODO\r\n\
+         UID:{}\r\nDTSTAMP:20221121T100000Z\r\nSUMMARY:{}\r\nSTATUS:NEEDS-ACTION\r\n\
+         END:VTODO\r\nEND:VCALENDAR\r\n",
+        uid, summary
+    )
+}
+
+async fn send(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    headers: &[(&str, &str)],
+    body: impl Into<String>,
+) -> anyhow::Result<Response> {
+    let mut req = Request::builder()
+        .method(Method::from_bytes(method.as_bytes())?)
+        .uri(uri);
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    Ok(app
+        .clone()
+        .oneshot(req.body(Body::from(body.into()))?)
+        .await?)
+}
+
+async fn body_text(resp: Response) -> anyhow::Result<String> {
+    Ok(String::from_utf8(
+        to_bytes(resp.into_body()).await?.to_vec(),
+    )?)
+}
+
+/// Text of the first `<d:sync-token>` of a multistatus
+fn sync_token(multistatus: &str) -> String {
+    let start = multistatus.find("<d:sync-token>").unwrap() + "<d:sync-token>".len();
+    let end = multistatus[start..].find('<').unwrap();
+    multistatus[start..start + end].to_string()
+}
+
+#[tokio::test]
+async fn test_put_get_and_delete_with_etags() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let app = app(repo.clone());
+    let uri = "/caldav/tasks/todo%401.ics";
+
+    let resp = send(
+        &app,
+        "PUT",
+        uri,
+        &[("If-None-Match", "*")],
+        vtodo("todo@1", "buy milk"),
+    )
+    .await?;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let etag = resp.headers()["etag"].to_str()?.to_string();
+    assert_eq!(repo.all_tasks().await?[0].task, "buy milk");
+
+    let resp = send(
+        &app,
+        "PUT",
+        uri,
+        &[("If-None-Match", "*")],
+        vtodo("todo@1", "again"),
+    )
+    .await?;
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+    let resp = send(&app, "GET", uri, &[], "").await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["etag"], etag.as_str());
+    let calendar = body_text(resp).await?;
+    assert!(calendar.contains("UID:todo@1\r\n"));
+    assert!(calendar.contains("SUMMARY:buy milk\r\n"));
+
+    // an edit in the app updates the task, a stale ETag is refused
+    let resp = send(
+        &app,
+        "PUT",
+        uri,
+        &[("If-Match", &etag)],
+        vtodo("todo@1", "buy oat milk"),
+    )
+    .await?;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_ne!(resp.headers()["etag"], etag.as_str());
+    let tasks = repo.all_tasks().await?;
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].task, "buy oat milk");
+    let resp = send(&app, "DELETE", uri, &[("If-Match", &etag)], "").await?;
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+    let resp = send(&app, "DELETE", uri, &[], "").await?;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert!(repo.all_tasks().await?.is_empty());
+    let resp = send(&app, "GET", uri, &[], "").await?;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_put_rejects_uid_of_other_resource() -> anyhow::Result<()> {
+    let app = app(Arc::new(MemoryTaskRepository::new()));
+    let resp = send(&app, "PUT", "/caldav/tasks/a.ics", &[], vtodo("b", "task")).await?;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = send(&app, "PUT", "/caldav/tasks/a.ics", &[], "not a calendar").await?;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_collection_reports_changes_and_deletes() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let app = app(repo.clone());
+    for uid in ["one", "two"] {
+        let uri = format!("{}{}.ics", CALENDAR, uid);
+        send(&app, "PUT", &uri, &[], vtodo(uid, uid)).await?;
+    }
+    send(&app, "DELETE", "/caldav/tasks/two.ics", &[], "").await?;
+
+    // the initial sync lists the existing tasks only
+    let resp = send(
+        &app,
+        "REPORT",
+        CALENDAR,
+        &[],
+        SYNC_REQUEST.replace("TOKEN", ""),
+    )
+    .await?;
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    let multistatus = body_text(resp).await?;
+    assert!(multistatus.contains("<d:href>/caldav/tasks/one.ics</d:href>"));
+    assert!(!multistatus.contains("two.ics"));
+    let token = sync_token(&multistatus);
+
+    let resp = send(
+        &app,
+        "REPORT",
+        CALENDAR,
+        &[],
+        SYNC_REQUEST.replace("TOKEN", &token),
+    )
+    .await?;
+    let unchanged = body_text(resp).await?;
+    assert!(!unchanged.contains("<d:response>"));
+    assert_eq!(sync_token(&unchanged), token);
+
+    // a change through the json api and a delete show up in the next sync
+    let one = repo.all_tasks().await?[0].id;
+    let update = Request::builder()
+        .method(Method::PUT)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .uri(format!("/tasks/{}", one))
+        .body(Body::from(r#"{"task":"one, renamed"}"#))?;
+    assert_eq!(app.clone().oneshot(update).await?.status(), StatusCode::OK);
+    send(
+        &app,
+        "PUT",
+        "/caldav/tasks/three.ics",
+        &[],
+        vtodo("three", "three"),
+    )
+    .await?;
+    send(&app, "DELETE", "/caldav/tasks/three.ics", &[], "").await?;
+
+    let resp = send(
+        &app,
+        "REPORT",
+        CALENDAR,
+        &[],
+        SYNC_REQUEST.replace("TOKEN", &token),
+    )
+    .await?;
+    let changes = body_text(resp).await?;
+    assert!(changes.contains(
+        "<d:response><d:href>/caldav/tasks/one.ics</d:href><d:propstat><d:prop><d:getetag>"
+    ));
+    assert!(changes.contains(
+        "<d:response><d:href>/caldav/tasks/three.ics</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>"
+    ));
+    assert_ne!(sync_token(&changes), token);
+
+    let resp = send(
+        &app,
+        "REPORT",
+        CALENDAR,
+        &[],
+        SYNC_REQUEST.replace("TOKEN", "http://other/1"),
+    )
+    .await?;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(body_text(resp).await?.contains("<d:valid-sync-token/>"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_propfind_and_calendar_reports() -> anyhow::Result<()> {
+    let app = app(Arc::new(MemoryTaskRepository::new()));
+    send(
+        &app,
+        "PUT",
+        "/caldav/tasks/one.ics",
+        &[],
+        vtodo("one", "first"),
+    )
+    .await?;
+
+    let propfind = r#"<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav" xmlns:x="urn:x">
+        <d:prop><d:resourcetype/><c:supported-calendar-component-set/><d:getetag/><x:unknown/></d:prop>
+        </d:propfind>"#;
+    let resp = send(&app, "PROPFIND", CALENDAR, &[("Depth", "1")], propfind).await?;
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    let multistatus = body_text(resp).await?;
+    assert!(multistatus.contains("<d:resourcetype><d:collection/><c:calendar/></d:resourcetype>"));
+    assert!(multistatus.contains(r#"<c:comp name="VTODO"/>"#));
+    assert!(multistatus.contains(r#"<d:href>/caldav/tasks/one.ics</d:href><d:propstat><d:prop><d:resourcetype/><d:getetag>&quot;1&quot;</d:getetag>"#));
+    assert!(multistatus
+        .contains(r#"<x:unknown xmlns:x="urn:x"/></d:prop><d:status>HTTP/1.1 404 Not Found"#));
+
+    let query = r#"<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+        <d:prop><d:getetag/><c:calendar-data/></d:prop>
+        <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VTODO"/></c:comp-filter></c:filter>
+        </c:calendar-query>"#;
+    let resp = send(&app, "REPORT", CALENDAR, &[], query).await?;
+    let multistatus = body_text(resp).await?;
+    assert!(multistatus.contains("SUMMARY:first&#13;\n"));
+    let resp = send(
+        &app,
+        "REPORT",
+        CALENDAR,
+        &[],
+        query.replace("VTODO", "VEVENT"),
+    )
+    .await?;
+    assert!(!body_text(resp).await?.contains("<d:response>"));
+
+    let multiget = r#"<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+        <d:prop><d:getetag/></d:prop>
+        <d:href>/caldav/tasks/one.ics</d:href><d:href>/caldav/tasks/gone.ics</d:href>
+        </c:calendar-multiget>"#;
+    let resp = send(&app, "REPORT", CALENDAR, &[], multiget).await?;
+    let multistatus = body_text(resp).await?;
+    assert!(multistatus.contains("<d:getetag>&quot;1&quot;</d:getetag>"));
+    assert!(multistatus.contains(
+        "<d:href>/caldav/tasks/gone.ics</d:href><d:status>HTTP/1.1 404 Not Found</d:status>"
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_discovery_through_well_known_and_principal() -> anyhow::Result<()> {
+    let app = Router::new().nest("/todo", app(Arc::new(MemoryTaskRepository::new())));
+
+    let resp = send(&app, "PROPFIND", "/todo/.well-known/caldav", &[], "").await?;
+    assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(resp.headers()["location"], "/todo/caldav/");
+
+    let resp = send(&app, "OPTIONS", "/todo/caldav/", &[], "").await?;
+    assert!(resp.headers()["dav"].to_str()?.contains("calendar-access"));
+
+    let resp = send(&app, "PROPFIND", "/todo/caldav/", &[("Depth", "1")], "").await?;
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    let multistatus = body_text(resp).await?;
+    assert!(multistatus
+        .contains("<c:calendar-home-set><d:href>/todo/caldav/</d:href></c:calendar-home-set>"));
+    assert!(multistatus.contains("<d:href>/todo/caldav/tasks/</d:href>"));
+
+    let resp = send(&app, "POST", "/todo/caldav/tasks/", &[], "").await?;
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    Ok(())
+}
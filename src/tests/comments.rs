@@ -0,0 +1,133 @@
+use super::{request, sqlite_memory_repository};
+use crate::comments::mentions;
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+/// Recipients of the notifications in `body`
+fn recipients(body: &Value) -> Vec<&str> {
+    body.as_array()
+        .unwrap()
+        .iter()
+        .map(|notification| notification["recipient"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_mentions() {
+    let body = "@alice and @bob.\n\
+                - ask (@carol) about `@dave`, not mail@example.com or \\@erin\n\
+                ```\n\
+                @frank\n\
+                ```\n\
+                @alice again, @grace-hopper, ``a ` @heidi`` and @ivan_1";
+    assert_eq!(
+        mentions(body),
+        ["alice", "bob", "carol", "grace-hopper", "ivan_1"]
+    );
+    assert!(mentions("@ alone, trailing @").is_empty());
+    // an unclosed code span is text
+    assert_eq!(mentions("`@judy"), Vec::<String>::new());
+    assert_eq!(mentions("` @judy"), ["judy"]);
+}
+
+#[tokio::test]
+async fn test_comment_crud() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    let (status, _) = request(&app, Method::POST, "/tasks", json!({"task": "release"})).await?;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let comment = json!({"author": "alice", "body": "@bob can you *review* it?"});
+    let (status, body) = request(&app, Method::POST, "/tasks/1/comments", comment).await?;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["id"], 1);
+    assert_eq!(body["author"], "alice");
+    assert_eq!(body["mentions"], json!(["bob"]));
+    assert!(body.get("edited_at").is_none());
+
+    for (comment, msg) in [
+        (
+            json!({"author": "", "body": "hi"}),
+            "author must not be empty",
+        ),
+        (
+            json!({"author": "alice", "body": " "}),
+            "body must not be empty",
+        ),
+    ] {
+        let (status, body) = request(&app, Method::POST, "/tasks/1/comments", comment).await?;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["msg"], msg);
+    }
+    let comment = json!({"author": "alice", "body": "hi"});
+    let (status, _) = request(&app, Method::POST, "/tasks/4711/comments", comment).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // bob is notified once, carol on her first mention, alice not of her own mention
+    let edit = json!({"author": "bob", "body": "@bob @carol @alice done"});
+    let (status, body) = request(&app, Method::PUT, "/tasks/1/comments/1", edit).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["author"], "alice");
+    assert_eq!(body["edited_by"], "bob");
+    assert_eq!(body["mentions"], json!(["bob", "carol", "alice"]));
+    let (_, body) = request(&app, Method::GET, "/notifications", Value::Null).await?;
+    assert_eq!(recipients(&body), ["bob", "carol", "alice"]);
+    assert_eq!(body[1]["author"], "bob");
+    assert_eq!(body[1]["comment_id"], 1);
+    let (_, body) = request(&app, Method::GET, "/notifications?after=1", Value::Null).await?;
+    assert_eq!(recipients(&body), ["carol", "alice"]);
+    let (_, body) = request(
+        &app,
+        Method::GET,
+        "/notifications?recipient=bob",
+        Value::Null,
+    )
+    .await?;
+    assert_eq!(recipients(&body), ["bob"]);
+
+    let (status, body) = request(
+        &app,
+        Method::GET,
+        "/tasks/1/comments/1/history",
+        Value::Null,
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["author"], "alice");
+    assert_eq!(body[0]["body"], "@bob can you *review* it?");
+
+    let (_, body) = request(&app, Method::GET, "/tasks/1/comments", Value::Null).await?;
+    assert_eq!(body[0]["body"], "@bob @carol @alice done");
+    let (status, body) = request(&app, Method::GET, "/tasks/2/comments/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["msg"], "comment not found");
+    let (status, _) = request(&app, Method::DELETE, "/tasks/1/comments/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = request(&app, Method::GET, "/tasks/1/comments/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = request(&app, Method::GET, "/notifications", Value::Null).await?;
+    assert_eq!(body, json!([]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_comments_are_deleted_with_their_task() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    let (_, _) = request(&app, Method::POST, "/tasks", json!({"task": "release"})).await?;
+    let subtask = json!({"task": "changelog", "parent_id": 1});
+    let (status, _) = request(&app, Method::POST, "/tasks", subtask).await?;
+    assert_eq!(status, StatusCode::CREATED);
+    for uri in ["/tasks/1/comments", "/tasks/2/comments"] {
+        let comment = json!({"author": "alice", "body": "@bob please"});
+        let (status, _) = request(&app, Method::POST, uri, comment).await?;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let (status, _) = request(&app, Method::DELETE, "/tasks/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = request(&app, Method::GET, "/tasks/2/comments", Value::Null).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = request(&app, Method::GET, "/notifications", Value::Null).await?;
+    assert_eq!(body, json!([]));
+    Ok(())
+}
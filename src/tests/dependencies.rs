@@ -0,0 +1,233 @@
+use super::{request, sqlite_memory_repository};
+use crate::dependencies::topological_order;
+use crate::models::task::{Dependency, Task};
+use crate::repository::RepositoryError;
+use axum::http::{Method, Request, StatusCode};
+use hyper::{body::to_bytes, Body};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+fn task(id: i64) -> Task {
+    Task {
+        id,
+        task: format!("task {}", id),
+        ..Task::default()
+    }
+}
+
+fn blocked(task_id: i64, blocker_id: i64) -> Dependency {
+    Dependency {
+        task_id,
+        blocker_id,
+    }
+}
+
+#[test]
+fn test_topological_order() {
+    let tasks = || (1..=5).map(task).collect::<Vec<Task>>();
+    let order = |dependencies: &[Dependency]| -> Vec<i64> {
+        topological_order(tasks(), dependencies)
+            .unwrap()
+            .iter()
+            .map(|task| task.id)
+            .collect()
+    };
+    assert_eq!(order(&[]), [1, 2, 3, 4, 5]);
+    // 1 waits for 4, which waits for 5 and 3; unrelated tasks stay in id order
+    assert_eq!(
+        order(&[blocked(1, 4), blocked(4, 5), blocked(4, 3)]),
+        [2, 3, 5, 4, 1]
+    );
+    // dependencies of other tasks do not matter
+    assert_eq!(order(&[blocked(1, 42), blocked(42, 2)]), [1, 2, 3, 4, 5]);
+    assert!(matches!(
+        topological_order(tasks(), &[blocked(1, 2), blocked(2, 1)]),
+        Err(RepositoryError::Conflict(_))
+    ));
+}
+
+/// release checklist: 1 "build", 2 "test" blocked by 1, 3 "announce" blocked by 2
+async fn release(app: &axum::Router) -> anyhow::Result<()> {
+    for task in ["build", "test", "announce"] {
+        request(app, Method::POST, "/tasks", json!({ "task": task })).await?;
+    }
+    for (id, blocker_id) in [(3, 2), (2, 1)] {
+        let (status, body) = request(
+            app,
+            Method::POST,
+            &format!("/tasks/{}/blockers", id),
+            json!({ "blocker_id": blocker_id }),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body, json!({"task_id": id, "blocker_id": blocker_id}));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_blockers_and_order() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    release(&app).await?;
+
+    let (status, body) = request(&app, Method::GET, "/tasks/order", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    let order: Vec<&str> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["task"].as_str().unwrap())
+        .collect();
+    assert_eq!(order, ["build", "test", "announce"]);
+
+    let (status, body) = request(&app, Method::GET, "/tasks/3/blockers", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!([{"id": 2, "task": "test"}]));
+
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/tasks/1/blockers",
+        json!({"blocker_id": 3}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        body["msg"],
+        "a task can not be blocked by itself or by a task it blocks"
+    );
+    let (status, _) = request(
+        &app,
+        Method::POST,
+        "/tasks/1/blockers",
+        json!({"blocker_id": 4711}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = request(&app, Method::GET, "/tasks/4711/blockers", Value::Null).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = request(&app, Method::DELETE, "/tasks/3/blockers/2", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = request(&app, Method::DELETE, "/tasks/3/blockers/2", Value::Null).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = request(&app, Method::GET, "/tasks/3/blockers", Value::Null).await?;
+    assert_eq!(body, json!([]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_blocked_task_can_not_be_completed() -> anyhow::Result<()> {
+    let repo = sqlite_memory_repository().await?;
+    let app = crate::app(repo.clone());
+    release(&app).await?;
+    let complete = |task: &str| json!({"task": task, "status": "completed"});
+
+    let (status, body) = request(&app, Method::PUT, "/tasks/2", complete("test")).await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["msg"], "task is blocked by the open tasks 1");
+    assert!(repo.task(2).await?.is_open());
+
+    // other changes of a blocked task are fine
+    let (status, _) = request(&app, Method::PUT, "/tasks/2", json!({"task": "run tests"})).await?;
+    assert_eq!(status, StatusCode::OK);
+
+    for (id, task) in [(1, "build"), (2, "run tests")] {
+        let (status, _) =
+            request(&app, Method::PUT, &format!("/tasks/{}", id), complete(task)).await?;
+        assert_eq!(status, StatusCode::OK);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_completing_subtasks_checks_their_blockers() -> anyhow::Result<()> {
+    let repo = sqlite_memory_repository().await?;
+    let app = crate::app(repo.clone());
+    release(&app).await?;
+    // 4 "release" has the subtasks 2 "test" and 3 "announce"
+    request(&app, Method::POST, "/tasks", json!({"task": "release"})).await?;
+    for id in [2, 3] {
+        request(
+            &app,
+            Method::PUT,
+            &format!("/tasks/{}/parent", id),
+            json!({"parent_id": 4}),
+        )
+        .await?;
+    }
+    let complete = json!({"task": "release", "status": "completed"});
+
+    // "announce" is blocked by "test", which is completed along with it, but "build" is open
+    let (status, body) = request(
+        &app,
+        Method::PUT,
+        "/tasks/4?subtasks=complete",
+        complete.clone(),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["msg"], "task is blocked by the open tasks 1");
+
+    request(
+        &app,
+        Method::PUT,
+        "/tasks/1",
+        json!({"task": "build", "status": "cancelled"}),
+    )
+    .await?;
+    let (status, _) = request(&app, Method::PUT, "/tasks/4?subtasks=complete", complete).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!repo.task(3).await?.is_open());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_caldav_and_imports_can_not_complete_blocked_tasks() -> anyhow::Result<()> {
+    let repo = sqlite_memory_repository().await?;
+    let app = crate::app(repo.clone());
+    release(&app).await?;
+    let uid = repo.task(2).await?.uid;
+    let completed = format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VTODO\r\n\
+         UID:{}\r\nDTSTAMP:20221121T100000Z\r\nSUMMARY:test\r\nSTATUS:COMPLETED\r\n\
+         END:VTODO\r\nEND:VCALENDAR\r\n",
+        uid
+    );
+    let send = |method: Method, uri: String, content_type: &str| {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", content_type)
+            .body(Body::from(completed.clone()))
+    };
+
+    let resp = app
+        .clone()
+        .oneshot(send(
+            Method::PUT,
+            format!("/caldav/tasks/{}.ics", uid),
+            "text/calendar",
+        )?)
+        .await?;
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+    assert!(repo.task(2).await?.is_open());
+
+    let resp = app
+        .clone()
+        .oneshot(send(
+            Method::POST,
+            "/tasks/import".to_string(),
+            "text/calendar",
+        )?)
+        .await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let report: Value = serde_json::from_slice(&to_bytes(resp.into_body()).await?)?;
+    assert_eq!(report["accepted"], json!([]));
+    assert_eq!(
+        report["rejected"],
+        json!([{"line": 4, "reason": "task is blocked by the open tasks 1"}])
+    );
+    assert!(repo.task(2).await?.is_open());
+    Ok(())
+}
@@ -0,0 +1,241 @@
+use crate::app;
+use crate::graphql::schema;
+use crate::models::task::NewTask;
+use crate::repository::memory::MemoryTaskRepository;
+use crate::repository::DynTaskRepository;
+use axum::Router;
+use futures::StreamExt;
+use hyper::{body::to_bytes, Body, Method, Request, StatusCode};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+async fn execute(app: &Router, query: &str) -> anyhow::Result<Value> {
+    let req = Request::builder()
+        .method(Method::POST)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .uri("/graphql")
+        .body(Body::from(json!({ "query": query }).to_string()))?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    Ok(serde_json::from_slice(&to_bytes(resp.into_body()).await?)?)
+}
+
+#[tokio::test]
+async fn test_graphql_mutations_and_queries() -> anyhow::Result<()> {
+    let app = app(Arc::new(MemoryTaskRepository::new()));
+
+    let created = execute(
+        &app,
+        r#"mutation { createTask(task: {task: "buy milk", categories: "shopping", priority: 2}) { id task priority } }"#,
+    )
+    .await?;
+    assert_eq!(
+        created["data"]["createTask"],
+        json!({"id": 1, "task": "buy milk", "priority": 2})
+    );
+    let invalid = execute(&app, r#"mutation { createTask(task: {task: " "}) { id } }"#).await?;
+    assert_eq!(invalid["errors"][0]["message"], "task must not be empty");
+    let invalid = execute(
+        &app,
+        r#"mutation { updateTask(id: 1, task: {task: "buy milk", priority: 10}) { id } }"#,
+    )
+    .await?;
+    assert_eq!(
+        invalid["errors"][0]["message"],
+        "priority must be between 1 and 9"
+    );
+
+    let updated = execute(
+        &app,
+        r#"mutation { updateTask(id: 1, task: {task: "buy oat milk", status: COMPLETED}) { task status categories } }"#,
+    )
+    .await?;
+    assert_eq!(
+        updated["data"]["updateTask"],
+        json!({"task": "buy oat milk", "status": "COMPLETED", "categories": null})
+    );
+
+    let task = execute(
+        &app,
+        "{ task(id: 1) { task } missing: task(id: 2) { task } }",
+    )
+    .await?;
+    assert_eq!(
+        task["data"],
+        json!({"task": {"task": "buy oat milk"}, "missing": null})
+    );
+
+    let deleted = execute(&app, "mutation { deleteTask(id: 1) }").await?;
+    assert_eq!(deleted["data"]["deleteTask"], true);
+    let deleted = execute(&app, "mutation { deleteTask(id: 1) }").await?;
+    assert_eq!(deleted["errors"][0]["extensions"]["code"], "NOT_FOUND");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_graphql_filters_and_pages() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let tasks: Vec<NewTask> = ["Milk", "bread", "call mom", "more milk"]
+        .iter()
+        .map(|task| NewTask {
+            task: task.to_string(),
+            categories: task.contains("mom").then(|| "Phone".to_string()),
+            ..Default::default()
+        })
+        .collect();
+    repo.import_tasks(&tasks).await?;
+    let app = app(repo);
+
+    let page = execute(
+        &app,
+        r#"{ tasks(filter: {search: "milk"}, offset: 1, limit: 1) { total tasks { id } } }"#,
+    )
+    .await?;
+    assert_eq!(
+        page["data"]["tasks"],
+        json!({"total": 2, "tasks": [{"id": 4}]})
+    );
+    let page = execute(
+        &app,
+        r#"{ tasks(filter: {category: "phone"}) { tasks { task } } }"#,
+    )
+    .await?;
+    assert_eq!(
+        page["data"]["tasks"]["tasks"],
+        json!([{"task": "call mom"}])
+    );
+    let page = execute(&app, "{ tasks(limit: 1001) { total } }").await?;
+    assert!(page["errors"].is_array());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_graphql_subtasks() -> anyhow::Result<()> {
+    let app = app(Arc::new(MemoryTaskRepository::new()));
+    execute(
+        &app,
+        r#"mutation { createTask(task: {task: "trip"}) { id } }"#,
+    )
+    .await?;
+    execute(
+        &app,
+        r#"mutation { createTask(task: {task: "pack", parentId: 1}) { id } }"#,
+    )
+    .await?;
+
+    let cycle = execute(&app, "mutation { moveTask(id: 1, parentId: 2) { id } }").await?;
+    assert_eq!(
+        cycle["errors"][0]["extensions"]["code"], "CONFLICT",
+        "{}",
+        cycle
+    );
+    let blocked = execute(
+        &app,
+        r#"mutation { updateTask(id: 1, task: {task: "trip", status: COMPLETED}, subtasks: BLOCK) { id } }"#,
+    )
+    .await?;
+    assert_eq!(blocked["errors"][0]["message"], "task has 1 open subtasks");
+    execute(
+        &app,
+        r#"mutation { updateTask(id: 1, task: {task: "trip", status: COMPLETED}, subtasks: COMPLETE) { id } }"#,
+    )
+    .await?;
+    let moved = execute(
+        &app,
+        "mutation { moveTask(id: 2, parentId: null) { parentId status } }",
+    )
+    .await?;
+    assert_eq!(
+        moved["data"]["moveTask"],
+        json!({"parentId": null, "status": "COMPLETED"})
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_graphql_blockers() -> anyhow::Result<()> {
+    let app = app(Arc::new(MemoryTaskRepository::new()));
+    for task in ["build", "ship"] {
+        execute(
+            &app,
+            &format!(
+                r#"mutation {{ createTask(task: {{task: "{}"}}) {{ id }} }}"#,
+                task
+            ),
+        )
+        .await?;
+    }
+    let added = execute(&app, "mutation { addBlocker(id: 2, blockerId: 1) }").await?;
+    assert_eq!(added["data"]["addBlocker"], true);
+    let cycle = execute(&app, "mutation { addBlocker(id: 1, blockerId: 2) }").await?;
+    assert_eq!(cycle["errors"][0]["extensions"]["code"], "CONFLICT");
+    let blockers = execute(&app, "{ blockers(id: 2) { task } }").await?;
+    assert_eq!(blockers["data"]["blockers"], json!([{"task": "build"}]));
+    let blocked = execute(
+        &app,
+        r#"mutation { updateTask(id: 2, task: {task: "ship", status: COMPLETED}) { id } }"#,
+    )
+    .await?;
+    assert_eq!(
+        blocked["errors"][0]["message"],
+        "task is blocked by the open tasks 1"
+    );
+    let removed = execute(&app, "mutation { removeBlocker(id: 2, blockerId: 1) }").await?;
+    assert_eq!(removed["data"]["removeBlocker"], true);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_graphql_task_changes_subscription() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let schema = schema(repo.clone(), Duration::from_millis(10));
+    let first = repo
+        .create_task(&NewTask {
+            task: "before".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    let mut changes = schema
+        .execute_stream("subscription { taskChanges(revision: 0) { changed { task } deleted } }");
+    let response = changes.next().await.unwrap().into_result().unwrap();
+    assert_eq!(
+        response.data.into_json()?,
+        json!({"taskChanges": {"changed": [{"task": "before"}], "deleted": []}})
+    );
+
+    repo.delete_task(first.id).await?;
+    let response = changes.next().await.unwrap().into_result().unwrap();
+    assert_eq!(
+        response.data.into_json()?,
+        json!({"taskChanges": {"changed": [], "deleted": [first.uid]}})
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_graphiql_playground() -> anyhow::Result<()> {
+    let app = app(Arc::new(MemoryTaskRepository::new()));
+    let req = Request::builder().uri("/graphiql").body(Body::empty())?;
+    let resp = app.oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let page = String::from_utf8(to_bytes(resp.into_body()).await?.to_vec())?;
+    assert!(page.contains("/graphql/ws"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_graphiql_playground_nested() -> anyhow::Result<()> {
+    let app = Router::new().nest("/todo", app(Arc::new(MemoryTaskRepository::new())));
+    let req = Request::builder()
+        .uri("/todo/graphiql")
+        .body(Body::empty())?;
+    let resp = app.oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let page = String::from_utf8(to_bytes(resp.into_body()).await?.to_vec())?;
+    assert!(page.contains("'/todo/graphql'"));
+    assert!(page.contains("'/todo/graphql/ws'"));
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use crate::grpc::proto::task_service_client::TaskServiceClient;
+use crate::grpc::proto::{
+    CreateTaskRequest, DeleteTaskRequest, GetTaskRequest, ListTasksRequest, TaskFields, TaskStatus,
+    UpdateTaskRequest, WatchRequest,
+};
+use crate::grpc::{bind, service};
+use crate::repository::memory::MemoryTaskRepository;
+use crate::repository::DynTaskRepository;
+use futures::TryStreamExt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Channel;
+use tonic::Code;
+
+async fn client(addr: SocketAddr) -> anyhow::Result<TaskServiceClient<Channel>> {
+    Ok(TaskServiceClient::connect(format!("http://{}", addr)).await?)
+}
+
+fn fields(task: &str) -> Option<TaskFields> {
+    Some(TaskFields {
+        task: task.to_string(),
+        ..Default::default()
+    })
+}
+
+#[tokio::test]
+async fn test_grpc_crud() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let (addr, server) = bind(&SocketAddr::from(([127, 0, 0, 1], 0)), repo.clone()).await?;
+    assert_ne!(addr.port(), 0);
+    tokio::spawn(server);
+    let mut client = client(addr).await?;
+
+    let created = client
+        .create_task(CreateTaskRequest {
+            task: Some(TaskFields {
+                task: "buy milk".to_string(),
+                status: TaskStatus::InProcess as i32,
+                due_at: Some(prost_types::Timestamp {
+                    seconds: 1668963600,
+                    nanos: 0,
+                }),
+                created_on: Some("2022-11-20".to_string()),
+                ..Default::default()
+            }),
+        })
+        .await?
+        .into_inner();
+    assert_eq!(created.id, 1);
+    assert_eq!(created.status, TaskStatus::InProcess as i32);
+    // the same store as the REST api
+    let stored = repo.task(1).await?;
+    assert_eq!(stored.uid, created.uid);
+    assert_eq!(
+        stored.due_at.unwrap().to_rfc3339(),
+        "2022-11-20T17:00:00+00:00"
+    );
+
+    let err = client
+        .create_task(CreateTaskRequest { task: fields(" ") })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert_eq!(err.message(), "task must not be empty");
+
+    let updated = client
+        .update_task(UpdateTaskRequest {
+            id: 1,
+            task: fields("buy oat milk"),
+        })
+        .await?
+        .into_inner();
+    assert_eq!(updated.task, "buy oat milk");
+    assert_eq!(updated.status, TaskStatus::Unspecified as i32);
+    assert_eq!(updated.created_on, None);
+    client
+        .create_task(CreateTaskRequest {
+            task: fields("bread"),
+        })
+        .await?;
+
+    let tasks: Vec<String> = client
+        .list_tasks(ListTasksRequest {})
+        .await?
+        .into_inner()
+        .map_ok(|task| task.task)
+        .try_collect()
+        .await?;
+    assert_eq!(tasks, ["buy oat milk", "bread"]);
+
+    client.delete_task(DeleteTaskRequest { id: 1 }).await?;
+    let err = client.get_task(GetTaskRequest { id: 1 }).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_grpc_watch() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(service(repo, Duration::from_millis(10)))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    let mut client = client(addr).await?;
+
+    let mut changes = client
+        .watch(WatchRequest { revision: None })
+        .await?
+        .into_inner();
+    let created = client
+        .create_task(CreateTaskRequest {
+            task: fields("watched"),
+        })
+        .await?
+        .into_inner();
+    let first = changes.message().await?.unwrap();
+    assert_eq!(first.changed.len(), 1);
+    assert_eq!(first.changed[0], created);
+    assert_eq!(first.revision, created.revision);
+
+    client
+        .delete_task(DeleteTaskRequest { id: created.id })
+        .await?;
+    let second = changes.message().await?.unwrap();
+    assert!(second.changed.is_empty());
+    assert_eq!(second.deleted, [created.uid]);
+    Ok(())
+}
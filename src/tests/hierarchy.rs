@@ -0,0 +1,187 @@
+use super::{request, sqlite_memory_repository};
+use crate::hierarchy::TaskTree;
+use crate::models::task::{Task, TaskStatus};
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+/// 1 "release" with subtasks 2 "write notes" (with subtask 4 "proofread") and 3 "tag"
+async fn release(app: &axum::Router) -> anyhow::Result<()> {
+    for task in [
+        json!({"task": "release"}),
+        json!({"task": "write notes", "parent_id": 1}),
+        json!({"task": "tag", "parent_id": 1}),
+        json!({"task": "proofread", "parent_id": 2}),
+    ] {
+        let (status, _) = request(app, Method::POST, "/tasks", task).await?;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    Ok(())
+}
+
+fn titles(tree: &TaskTree) -> Value {
+    let subtasks: Vec<Value> = tree.subtasks.iter().map(titles).collect();
+    json!({ tree.task.task.as_str(): subtasks })
+}
+
+#[tokio::test]
+async fn test_children_and_tree() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    release(&app).await?;
+
+    let (status, body) = request(&app, Method::GET, "/tasks/1/children", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!([
+            {"id": 2, "task": "write notes", "parent_id": 1},
+            {"id": 3, "task": "tag", "parent_id": 1},
+        ])
+    );
+
+    let (status, body) = request(&app, Method::GET, "/tasks/1/tree", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    let tree: TaskTree = serde_json::from_value(body)?;
+    assert_eq!(
+        titles(&tree),
+        json!({"release": [{"write notes": [{"proofread": []}]}, {"tag": []}]})
+    );
+
+    for uri in ["/tasks/4711/children", "/tasks/4711/tree"] {
+        let (status, _) = request(&app, Method::GET, uri, Value::Null).await?;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+    }
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/tasks",
+        json!({"task": "orphan", "parent_id": 4711}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["msg"], "parent task not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_move_subtree() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    release(&app).await?;
+
+    // a task can not move below itself
+    for parent_id in [2, 4] {
+        let (status, _) = request(
+            &app,
+            Method::PUT,
+            "/tasks/2/parent",
+            json!({ "parent_id": parent_id }),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+    let (status, _) = request(
+        &app,
+        Method::PUT,
+        "/tasks/2/parent",
+        json!({"parent_id": 4711}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = request(
+        &app,
+        Method::PUT,
+        "/tasks/4711/parent",
+        json!({"parent_id": null}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = request(
+        &app,
+        Method::PUT,
+        "/tasks/2/parent",
+        json!({"parent_id": 3}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["parent_id"], 3);
+    let (_, body) = request(&app, Method::GET, "/tasks/1/tree", Value::Null).await?;
+    assert_eq!(
+        titles(&serde_json::from_value(body)?),
+        json!({"release": [{"tag": [{"write notes": [{"proofread": []}]}]}]})
+    );
+
+    let (status, body) = request(
+        &app,
+        Method::PUT,
+        "/tasks/2/parent",
+        json!({"parent_id": null}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("parent_id").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_delete_cascades() -> anyhow::Result<()> {
+    let repo = sqlite_memory_repository().await?;
+    let app = crate::app(repo.clone());
+    release(&app).await?;
+
+    let (status, _) = request(&app, Method::DELETE, "/tasks/2", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    let left: Vec<String> = repo
+        .all_tasks()
+        .await?
+        .into_iter()
+        .map(|task| task.task)
+        .collect();
+    assert_eq!(left, ["release", "tag"]);
+    assert_eq!(repo.changes_since(0).await?.deleted.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_completing_parent() -> anyhow::Result<()> {
+    let repo = sqlite_memory_repository().await?;
+    let app = crate::app(repo.clone());
+    release(&app).await?;
+    let completed = json!({"task": "write notes", "status": "completed"});
+
+    let (status, body) = request(
+        &app,
+        Method::PUT,
+        "/tasks/2?subtasks=block",
+        completed.clone(),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["msg"], "task has 1 open subtasks");
+    assert_eq!(repo.task(2).await?.status, None);
+
+    let (status, _) = request(
+        &app,
+        Method::PUT,
+        "/tasks/2?subtasks=complete",
+        completed.clone(),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    let proofread: Task = repo.task(4).await?;
+    assert_eq!(proofread.status, Some(TaskStatus::Completed));
+    assert!(proofread.completed_on.is_some());
+
+    // without subtasks= the subtasks are left alone, completed ones do not block
+    let (status, _) = request(
+        &app,
+        Method::PUT,
+        "/tasks/1",
+        json!({"task": "release", "status": "completed"}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(repo.task(3).await?.status, None);
+    let (status, _) = request(&app, Method::PUT, "/tasks/2?subtasks=block", completed).await?;
+    assert_eq!(status, StatusCode::OK);
+    Ok(())
+}
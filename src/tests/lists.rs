@@ -0,0 +1,150 @@
+use super::{request, sqlite_memory_repository};
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+fn names(tasks: &Value) -> Vec<&str> {
+    tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["task"].as_str().unwrap())
+        .collect()
+}
+
+/// list 1 "garden" with the tasks 1 "mow" and 2 "weed", list 2 "house" with 3 "paint"
+async fn garden_and_house(app: &axum::Router) -> anyhow::Result<()> {
+    for list in ["garden", "house"] {
+        let (status, _) = request(app, Method::POST, "/lists", json!({ "name": list })).await?;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    for (list_id, task) in [(1, "mow"), (1, "weed"), (2, "paint")] {
+        let (status, _) = request(
+            app,
+            Method::POST,
+            &format!("/lists/{}/tasks", list_id),
+            json!({ "task": task }),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_crud() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+
+    let (status, body) = request(&app, Method::POST, "/lists", json!({"name": "garden"})).await?;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body, json!({"id": 1, "name": "garden", "archived": false}));
+    let (status, body) = request(&app, Method::POST, "/lists", json!({"name": " "})).await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["msg"], "name must not be empty");
+
+    let (status, body) = request(&app, Method::PUT, "/lists/1", json!({"name": "yard"})).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["name"], "yard");
+    let (_, body) = request(&app, Method::GET, "/lists", Value::Null).await?;
+    assert_eq!(body, json!([{"id": 1, "name": "yard", "archived": false}]));
+
+    let (status, _) = request(&app, Method::DELETE, "/lists/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    for method in [Method::GET, Method::DELETE] {
+        let (status, body) = request(&app, method, "/lists/1", Value::Null).await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["msg"], "list not found");
+    }
+    let (status, body) =
+        request(&app, Method::POST, "/lists/1/tasks", json!({"task": "mow"})).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["msg"], "list not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_tasks_and_order() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    garden_and_house(&app).await?;
+
+    let (_, body) = request(&app, Method::GET, "/tasks/2", Value::Null).await?;
+    assert_eq!(body, json!({"id": 2, "task": "weed", "list_id": 1}));
+    let (_, body) = request(&app, Method::GET, "/tasks?list_id=1", Value::Null).await?;
+    assert_eq!(names(&body), ["mow", "weed"]);
+
+    let (status, body) = request(&app, Method::PUT, "/tasks/3/list", json!({"list_id": 1})).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["list_id"], 1);
+    let (status, body) = request(
+        &app,
+        Method::PUT,
+        "/lists/1/order",
+        json!({"task_ids": [3, 1, 2]}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(names(&body), ["paint", "mow", "weed"]);
+    let (_, body) = request(&app, Method::GET, "/lists/1/tasks", Value::Null).await?;
+    assert_eq!(names(&body), ["paint", "mow", "weed"]);
+    let (status, _) = request(
+        &app,
+        Method::PUT,
+        "/lists/1/order",
+        json!({"task_ids": [3, 1]}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, body) =
+        request(&app, Method::PUT, "/tasks/3/list", json!({"list_id": null})).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"id": 3, "task": "paint"}));
+    let (status, body) =
+        request(&app, Method::PUT, "/tasks/3/list", json!({"list_id": 4711})).await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["msg"], "list not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_archive_and_delete_list() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    garden_and_house(&app).await?;
+
+    let (status, body) = request(&app, Method::POST, "/lists/1/archive", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["archived"], true);
+    let (_, body) = request(&app, Method::GET, "/tasks", Value::Null).await?;
+    assert_eq!(names(&body), ["paint"]);
+    let (_, body) = request(&app, Method::GET, "/tasks?archived=true", Value::Null).await?;
+    assert_eq!(names(&body), ["mow", "weed"]);
+    let (_, body) = request(&app, Method::GET, "/lists/1/tasks", Value::Null).await?;
+    assert_eq!(body[0]["archived"], true);
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/lists/1/tasks",
+        json!({"task": "rake"}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["msg"], "the list is archived, restore it first");
+    let (status, _) = request(&app, Method::PUT, "/tasks/1/list", json!({"list_id": 2})).await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = request(&app, Method::DELETE, "/lists/1/archive", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = request(&app, Method::GET, "/tasks", Value::Null).await?;
+    assert_eq!(names(&body), ["mow", "weed", "paint"]);
+
+    let (status, body) = request(&app, Method::DELETE, "/lists/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        body["msg"],
+        "the list has tasks, move them or delete them along (cascade=true)"
+    );
+    let (status, _) = request(&app, Method::DELETE, "/lists/1?cascade=true", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = request(&app, Method::GET, "/tasks", Value::Null).await?;
+    assert_eq!(names(&body), ["paint"]);
+    Ok(())
+}
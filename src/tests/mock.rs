@@ -1,31 +1,119 @@
-use crate::run;
+use crate::reminders::{Clock, Notifier, Reminder};
+use crate::repository::memory::MemoryTaskRepository;
+use crate::repository::sqlite::SqliteTaskRepository;
+use crate::repository::DynTaskRepository;
+use crate::{app, bind};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::thread;
-use std::time::Duration;
-use tokio::time::sleep;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
 
+/// Kind of store behind a test server
+#[derive(Clone, Copy, Debug)]
+pub enum Store {
+    Memory,
+    /// private sqlite database in memory
+    Sqlite,
+}
+
+impl Store {
+    /// Fresh (empty) store of this kind
+    pub async fn repository(self) -> anyhow::Result<DynTaskRepository> {
+        Ok(match self {
+            Store::Memory => Arc::new(MemoryTaskRepository::new()),
+            Store::Sqlite => Arc::new(SqliteTaskRepository::in_memory().await?),
+        })
+    }
+}
+
+/// Server running the real app on a free port of 127.0.0.1
+/// until the runtime of the testcase shuts down.
 pub struct Server {
-    pub started: AtomicBool,
+    pub addr: SocketAddr,
 }
 
 impl Server {
-    pub fn new() -> Server {
-        Server {
-            started: AtomicBool::new(false),
+    /// Start a server with a fresh (empty) store of the given kind
+    pub async fn start(store: Store) -> anyhow::Result<Server> {
+        Server::start_with(store.repository().await?).await
+    }
+
+    /// Start a server on top of the given store
+    pub async fn start_with(repo: DynTaskRepository) -> anyhow::Result<Server> {
+        // port 0: the os picks a free port, so every testcase gets its own server
+        let server = bind(&SocketAddr::from(([127, 0, 0, 1], 0)), app(repo))?;
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        Ok(Server { addr })
+    }
+
+    pub fn uri(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+}
+
+/// Clock which only moves when the testcase advances it
+pub struct MockClock {
+    now: watch::Sender<DateTime<Utc>>,
+    /// deadline of the last `sleep_until`
+    pub sleeping_until: watch::Sender<Option<DateTime<Utc>>>,
+}
+
+impl MockClock {
+    pub fn new(now: DateTime<Utc>) -> MockClock {
+        MockClock {
+            now: watch::channel(now).0,
+            sleeping_until: watch::channel(None).0,
         }
     }
 
-    pub async fn init_server(&mut self) {
-        if !self.started.load(Ordering::Relaxed) {
-            thread::spawn(move || {
-                let rt = tokio::runtime::Runtime::new().expect("runtime starts");
-                rt.spawn(run());
-                loop {
-                    thread::sleep(Duration::from_millis(100_000));
-                }
-            });
-            sleep(Duration::from_millis(100)).await;
-            self.started.store(true, Ordering::Relaxed);
+    pub fn advance(&self, duration: Duration) {
+        self.now.send_modify(|now| *now += duration);
+    }
+}
+
+#[async_trait]
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.borrow()
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        let mut now = self.now.subscribe();
+        self.sleeping_until.send_replace(Some(deadline));
+        while *now.borrow_and_update() < deadline {
+            if now.changed().await.is_err() {
+                return;
+            }
         }
     }
 }
+
+/// Notifier passing the reminders to the testcase, or failing if `fail` is set
+pub struct RecordingNotifier {
+    pub sent: mpsc::UnboundedSender<Reminder>,
+    /// shared, so the testcase can switch it after handing the notifier to a scheduler
+    pub fail: Arc<AtomicBool>,
+}
+
+impl RecordingNotifier {
+    pub fn new() -> (RecordingNotifier, mpsc::UnboundedReceiver<Reminder>) {
+        let (sent, received) = mpsc::unbounded_channel();
+        let notifier = RecordingNotifier {
+            sent,
+            fail: Arc::new(AtomicBool::new(false)),
+        };
+        (notifier, received)
+    }
+}
+
+#[async_trait]
+impl Notifier for RecordingNotifier {
+    async fn notify(&self, reminder: &Reminder) -> anyhow::Result<()> {
+        anyhow::ensure!(!self.fail.load(Ordering::SeqCst), "notifier failed");
+        self.sent.send(reminder.clone())?;
+        Ok(())
+    }
+}
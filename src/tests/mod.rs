@@ -1,58 +1,66 @@
-use hyper::{body::to_bytes, client::HttpConnector, Body, Client as HyperClient, Method, Request};
+use crate::repository::{sqlite::SqliteTaskRepository, DynTaskRepository};
+use hyper::{
+    body::to_bytes, client::HttpConnector, Body, Client as HyperClient, Method, Request, StatusCode,
+};
 use hyper_tls::HttpsConnector;
 use mock::*;
-use sqlx::Connection;
-use sqlx::{
-    sqlite::{SqliteConnectOptions, SqliteJournalMode},
-    ConnectOptions,
-};
-use std::str::FromStr;
+use serde_json::Value;
 use std::sync::Arc;
-use tokio::sync::{Mutex, OwnedMutexGuard};
+use tower::ServiceExt;
 
+mod app;
+mod backup;
+mod boards;
+mod caldav;
+mod comments;
+mod dependencies;
+mod graphql;
+mod grpc;
+mod hierarchy;
+mod lists;
 mod mock;
+mod negotiation;
+mod rank;
+mod recurrence;
+mod reminders;
+mod repository;
+mod tags;
+mod telemetry;
+mod transfer;
+mod workflows;
 
-const TEST_HOST: &str = "http://127.0.0.1:3000";
 const POST_TASK_URI: &str = "/tasks";
 const GET_TASKS_URI: &str = "/tasks";
 const DELETE_TASK_URI: &str = "/tasks/";
 const PUT_TASK_URI: &str = "/tasks/";
 const GET_TASK_URI: &str = "/tasks/";
 
-// we use a single instance of Server which has the shared state in the sqlite database
-// and we need to make sure that only one testcase locks this resource
-// as long as the testcase runs - otherwise the results of list tasks and the
-// assigned ids (primary key in sqlite) will not be reliable and the tests may fail
-// so each testcase has to use
-// let mut locked_server: OwnedMutexGuard<Server> = SERVER.clone().lock_owned().await;
-// init_and_lock_real_server(&mut locked_server).await?;
-lazy_static! {
-    static ref SERVER: Arc<Mutex<Server>> = Arc::new(Mutex::new(Server::new()));
-}
-
-/**
- * Create a defined state in the database by deleting all tasks.
- * Here we rely on sqlite delete truncate optimization - which
- * also resets the primary key id.
- * Note: because all testcases share the same database we cannot
- * run the tests in parallel.
- */
-async fn delete_all_tasks() -> anyhow::Result<()> {
-    // tabula rasa for reentrant tests
-    let mut conn = SqliteConnectOptions::from_str(&crate::DATABASE_URL)?
-        .journal_mode(SqliteJournalMode::Wal)
-        .create_if_missing(true)
-        .connect()
-        .await?;
-    sqlx::query("DELETE FROM task").execute(&mut conn).await?;
-    conn.close().await?;
-    Ok(())
+/// Fresh private sqlite database in memory
+async fn sqlite_memory_repository() -> anyhow::Result<DynTaskRepository> {
+    Ok(Arc::new(SqliteTaskRepository::in_memory().await?))
 }
 
-async fn init_and_lock_real_server(server: &mut OwnedMutexGuard<Server>) -> anyhow::Result<()> {
-    server.init_server().await;
-    delete_all_tasks().await?;
-    Ok(())
+/// Send `body` as json to `app`, returns the status and the json response (Null if empty)
+async fn request(
+    app: &axum::Router,
+    method: Method,
+    uri: &str,
+    body: Value,
+) -> anyhow::Result<(StatusCode, Value)> {
+    let req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))?;
+    let resp = app.clone().oneshot(req).await?;
+    let status = resp.status();
+    let body = to_bytes(resp.into_body()).await?;
+    let body = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&body)?
+    };
+    Ok((status, body))
 }
 
 fn http_client() -> HyperClient<HttpsConnector<HttpConnector>> {
@@ -60,16 +68,14 @@ fn http_client() -> HyperClient<HttpsConnector<HttpConnector>> {
     HyperClient::builder().build::<_, Body>(https)
 }
 
-#[tokio::test]
-async fn test_create_one_task_and_list_tasks_e2e() -> anyhow::Result<()> {
-    let mut locked_server: OwnedMutexGuard<Server> = SERVER.clone().lock_owned().await;
-    init_and_lock_real_server(&mut locked_server).await?;
+async fn test_create_one_task_and_list_tasks_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
     let http_client = http_client();
 
     let req = Request::builder()
         .method(Method::POST)
         .header(hyper::header::CONTENT_TYPE, "application/json")
-        .uri(TEST_HOST.to_string() + POST_TASK_URI)
+        .uri(server.uri(POST_TASK_URI))
         .body(Body::from(r#"{"task":"my first test task"}"#))
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -79,7 +85,7 @@ async fn test_create_one_task_and_list_tasks_e2e() -> anyhow::Result<()> {
 
     let req = Request::builder()
         .method(Method::GET)
-        .uri(TEST_HOST.to_string() + GET_TASKS_URI)
+        .uri(server.uri(GET_TASKS_URI))
         .body(Body::empty())
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -89,16 +95,14 @@ async fn test_create_one_task_and_list_tasks_e2e() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[tokio::test]
-async fn test_create_one_task_and_get_task_e2e() -> anyhow::Result<()> {
-    let mut locked_server: OwnedMutexGuard<Server> = SERVER.clone().lock_owned().await;
-    init_and_lock_real_server(&mut locked_server).await?;
+async fn test_create_one_task_and_get_task_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
     let http_client = http_client();
 
     let req = Request::builder()
         .method(Method::POST)
         .header(hyper::header::CONTENT_TYPE, "application/json")
-        .uri(TEST_HOST.to_string() + POST_TASK_URI)
+        .uri(server.uri(POST_TASK_URI))
         .body(Body::from(r#"{"task":"my first test task"}"#))
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -108,7 +112,7 @@ async fn test_create_one_task_and_get_task_e2e() -> anyhow::Result<()> {
 
     let req = Request::builder()
         .method(Method::GET)
-        .uri(TEST_HOST.to_string() + GET_TASK_URI + "1")
+        .uri(server.uri(GET_TASK_URI) + "1")
         .body(Body::empty())
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -118,16 +122,14 @@ async fn test_create_one_task_and_get_task_e2e() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[tokio::test]
-async fn test_create_two_tasks_and_list_tasks_e2e() -> anyhow::Result<()> {
-    let mut locked_server: OwnedMutexGuard<Server> = SERVER.clone().lock_owned().await;
-    init_and_lock_real_server(&mut locked_server).await?;
+async fn test_create_two_tasks_and_list_tasks_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
     let http_client = http_client();
 
     let req = Request::builder()
         .method(Method::POST)
         .header(hyper::header::CONTENT_TYPE, "application/json")
-        .uri(TEST_HOST.to_string() + POST_TASK_URI)
+        .uri(server.uri(POST_TASK_URI))
         .body(Body::from(r#"{"task":"my first test task"}"#))
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -138,7 +140,7 @@ async fn test_create_two_tasks_and_list_tasks_e2e() -> anyhow::Result<()> {
     let req = Request::builder()
         .method(Method::POST)
         .header(hyper::header::CONTENT_TYPE, "application/json")
-        .uri(TEST_HOST.to_string() + POST_TASK_URI)
+        .uri(server.uri(POST_TASK_URI))
         .body(Body::from(r#"{"task":"my second test task"}"#))
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -148,7 +150,7 @@ async fn test_create_two_tasks_and_list_tasks_e2e() -> anyhow::Result<()> {
 
     let req = Request::builder()
         .method(Method::GET)
-        .uri(TEST_HOST.to_string() + GET_TASKS_URI)
+        .uri(server.uri(GET_TASKS_URI))
         .body(Body::empty())
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -161,14 +163,12 @@ async fn test_create_two_tasks_and_list_tasks_e2e() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[tokio::test]
-async fn test_list_empty_tasks_e2e() -> anyhow::Result<()> {
-    let mut locked_server: OwnedMutexGuard<Server> = SERVER.clone().lock_owned().await;
-    init_and_lock_real_server(&mut locked_server).await?;
+async fn test_list_empty_tasks_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
     let http_client = http_client();
     let req = Request::builder()
         .method(Method::GET)
-        .uri(TEST_HOST.to_string() + GET_TASKS_URI)
+        .uri(server.uri(GET_TASKS_URI))
         .body(Body::empty())
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -178,16 +178,14 @@ async fn test_list_empty_tasks_e2e() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[tokio::test]
-async fn test_create_and_delete_one_task_and_list_tasks_e2e() -> anyhow::Result<()> {
-    let mut locked_server: OwnedMutexGuard<Server> = SERVER.clone().lock_owned().await;
-    init_and_lock_real_server(&mut locked_server).await?;
+async fn test_create_and_delete_one_task_and_list_tasks_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
     let http_client = http_client();
 
     let req = Request::builder()
         .method(Method::POST)
         .header(hyper::header::CONTENT_TYPE, "application/json")
-        .uri(TEST_HOST.to_string() + POST_TASK_URI)
+        .uri(server.uri(POST_TASK_URI))
         .body(Body::from(r#"{"task":"my first test task"}"#))
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -197,7 +195,7 @@ async fn test_create_and_delete_one_task_and_list_tasks_e2e() -> anyhow::Result<
 
     let req = Request::builder()
         .method(Method::DELETE)
-        .uri(TEST_HOST.to_string() + DELETE_TASK_URI + "1")
+        .uri(server.uri(DELETE_TASK_URI) + "1")
         .body(Body::empty())
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -205,7 +203,7 @@ async fn test_create_and_delete_one_task_and_list_tasks_e2e() -> anyhow::Result<
 
     let req = Request::builder()
         .method(Method::GET)
-        .uri(TEST_HOST.to_string() + GET_TASKS_URI)
+        .uri(server.uri(GET_TASKS_URI))
         .body(Body::empty())
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -215,16 +213,14 @@ async fn test_create_and_delete_one_task_and_list_tasks_e2e() -> anyhow::Result<
     Ok(())
 }
 
-#[tokio::test]
-async fn test_create_and_update_one_task_and_list_tasks_e2e() -> anyhow::Result<()> {
-    let mut locked_server: OwnedMutexGuard<Server> = SERVER.clone().lock_owned().await;
-    init_and_lock_real_server(&mut locked_server).await?;
+async fn test_create_and_update_one_task_and_list_tasks_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
     let http_client = http_client();
 
     let req = Request::builder()
         .method(Method::POST)
         .header(hyper::header::CONTENT_TYPE, "application/json")
-        .uri(TEST_HOST.to_string() + POST_TASK_URI)
+        .uri(server.uri(POST_TASK_URI))
         .body(Body::from(r#"{"task":"my first test task"}"#))
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -235,7 +231,7 @@ async fn test_create_and_update_one_task_and_list_tasks_e2e() -> anyhow::Result<
     let req = Request::builder()
         .method(Method::PUT)
         .header(hyper::header::CONTENT_TYPE, "application/json")
-        .uri(TEST_HOST.to_string() + PUT_TASK_URI + "1")
+        .uri(server.uri(PUT_TASK_URI) + "1")
         .body(Body::from(r#"{"task":"my first updated test task"}"#))
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -245,7 +241,7 @@ async fn test_create_and_update_one_task_and_list_tasks_e2e() -> anyhow::Result<
 
     let req = Request::builder()
         .method(Method::GET)
-        .uri(TEST_HOST.to_string() + GET_TASKS_URI)
+        .uri(server.uri(GET_TASKS_URI))
         .body(Body::empty())
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -258,14 +254,12 @@ async fn test_create_and_update_one_task_and_list_tasks_e2e() -> anyhow::Result<
     Ok(())
 }
 
-#[tokio::test]
-async fn test_get_wrong_task_e2e() -> anyhow::Result<()> {
-    let mut locked_server: OwnedMutexGuard<Server> = SERVER.clone().lock_owned().await;
-    init_and_lock_real_server(&mut locked_server).await?;
+async fn test_get_wrong_task_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
     let http_client = http_client();
     let req = Request::builder()
         .method(Method::GET)
-        .uri(TEST_HOST.to_string() + GET_TASK_URI + "4711")
+        .uri(server.uri(GET_TASK_URI) + "4711")
         .body(Body::empty())
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -273,14 +267,12 @@ async fn test_get_wrong_task_e2e() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[tokio::test]
-async fn test_delete_wrong_task_e2e() -> anyhow::Result<()> {
-    let mut locked_server: OwnedMutexGuard<Server> = SERVER.clone().lock_owned().await;
-    init_and_lock_real_server(&mut locked_server).await?;
+async fn test_delete_wrong_task_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
     let http_client = http_client();
     let req = Request::builder()
         .method(Method::DELETE)
-        .uri(TEST_HOST.to_string() + DELETE_TASK_URI + "4711")
+        .uri(server.uri(DELETE_TASK_URI) + "4711")
         .body(Body::empty())
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
@@ -288,18 +280,103 @@ async fn test_delete_wrong_task_e2e() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[tokio::test]
-async fn test_update_wrong_task_e2e() -> anyhow::Result<()> {
-    let mut locked_server: OwnedMutexGuard<Server> = SERVER.clone().lock_owned().await;
-    init_and_lock_real_server(&mut locked_server).await?;
+async fn test_update_wrong_task_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
     let http_client = http_client();
     let req = Request::builder()
         .method(Method::PUT)
         .header(hyper::header::CONTENT_TYPE, "application/json")
-        .uri(TEST_HOST.to_string() + PUT_TASK_URI + "4711")
+        .uri(server.uri(PUT_TASK_URI) + "4711")
         .body(Body::from(r#"{"task":"my first updated test task"}"#))
         .unwrap();
     let resp = http_client.request(req).await.unwrap();
     assert_eq!(resp.status(), 404);
     Ok(())
 }
+
+async fn test_request_id_is_echoed_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
+    let http_client = http_client();
+    let req = Request::builder()
+        .method(Method::GET)
+        .header("x-request-id", "my-request-42")
+        .uri(server.uri(GET_TASKS_URI))
+        .body(Body::empty())
+        .unwrap();
+    let resp = http_client.request(req).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["x-request-id"], "my-request-42");
+    Ok(())
+}
+
+async fn test_request_id_is_generated_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
+    let http_client = http_client();
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri(server.uri(GET_TASKS_URI))
+        .body(Body::empty())
+        .unwrap();
+    let resp = http_client.request(req).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let request_id = resp.headers()["x-request-id"].to_str()?;
+    assert!(uuid::Uuid::parse_str(request_id).is_ok());
+    Ok(())
+}
+
+async fn test_error_body_contains_request_id_e2e(store: Store) -> anyhow::Result<()> {
+    let server = Server::start(store).await?;
+    let http_client = http_client();
+    let req = Request::builder()
+        .method(Method::GET)
+        .header("x-request-id", "my-request-42")
+        .uri(server.uri(GET_TASK_URI) + "4711")
+        .body(Body::empty())
+        .unwrap();
+    let resp = http_client.request(req).await.unwrap();
+    assert_eq!(resp.status(), 404);
+    let body_bytes = to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(
+        body_bytes,
+        r#"{"msg":"task not found","request_id":"my-request-42"}"#
+    );
+    Ok(())
+}
+
+/// One `#[tokio::test]` per store for each `async fn check(store: Store)`
+macro_rules! store_tests {
+    ($($check:ident),* $(,)?) => {
+        mod memory {
+            $(
+                #[tokio::test]
+                async fn $check() -> anyhow::Result<()> {
+                    super::$check(super::Store::Memory).await
+                }
+            )*
+        }
+
+        mod sqlite {
+            $(
+                #[tokio::test]
+                async fn $check() -> anyhow::Result<()> {
+                    super::$check(super::Store::Sqlite).await
+                }
+            )*
+        }
+    };
+}
+
+store_tests!(
+    test_create_one_task_and_list_tasks_e2e,
+    test_create_one_task_and_get_task_e2e,
+    test_create_two_tasks_and_list_tasks_e2e,
+    test_list_empty_tasks_e2e,
+    test_create_and_delete_one_task_and_list_tasks_e2e,
+    test_create_and_update_one_task_and_list_tasks_e2e,
+    test_get_wrong_task_e2e,
+    test_delete_wrong_task_e2e,
+    test_update_wrong_task_e2e,
+    test_request_id_is_echoed_e2e,
+    test_request_id_is_generated_e2e,
+    test_error_body_contains_request_id_e2e,
+);
@@ -0,0 +1,142 @@
+use crate::app;
+use crate::models::task::{Task, TaskStatus};
+use crate::negotiation::{preferred, Format};
+use crate::repository::memory::MemoryTaskRepository;
+use crate::ApiDoc;
+use axum::http::HeaderMap;
+use axum::Router;
+use hyper::{body::to_bytes, Body, Method, Request, StatusCode};
+use serde_json::json;
+use std::sync::Arc;
+use tower::ServiceExt;
+use utoipa::OpenApi;
+
+fn memory_app() -> Router {
+    app(Arc::new(MemoryTaskRepository::new()))
+}
+
+fn accept(accept: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(hyper::header::ACCEPT, accept.parse().unwrap());
+    headers
+}
+
+#[test]
+fn test_preferred_media_type() {
+    let offered = ["application/json", "application/cbor", "text/markdown"];
+    assert_eq!(
+        preferred(&HeaderMap::new(), &offered),
+        Some("application/json")
+    );
+    assert_eq!(
+        preferred(&accept("application/cbor"), &offered),
+        Some("application/cbor")
+    );
+    // quality first, ties by the order of the server
+    assert_eq!(
+        preferred(
+            &accept("application/json;q=0.5, text/*;q=0.8, application/cbor;q=0.8"),
+            &offered
+        ),
+        Some("application/cbor")
+    );
+    // the most specific range wins, q=0 excludes
+    assert_eq!(
+        preferred(&accept("*/*, application/json;q=0"), &offered),
+        Some("application/cbor")
+    );
+    assert_eq!(preferred(&accept("image/png, text/html"), &offered), None);
+}
+
+#[tokio::test]
+async fn test_tasks_in_all_formats() -> anyhow::Result<()> {
+    let app = memory_app();
+    let task = json!({"task": "compact", "status": "completed", "priority": 2});
+    for (index, format) in Format::ALL.into_iter().enumerate() {
+        let req = Request::builder()
+            .method(Method::POST)
+            .header(hyper::header::CONTENT_TYPE, format.media_type())
+            .header(hyper::header::ACCEPT, format.media_type())
+            .uri("/tasks")
+            .body(Body::from(format.serialize(&task).unwrap()))?;
+        let resp = app.clone().oneshot(req).await?;
+        assert_eq!(resp.status(), StatusCode::CREATED, "{:?}", format);
+        assert_eq!(resp.headers()["content-type"], format.media_type());
+        let created: Task = format
+            .deserialize(&to_bytes(resp.into_body()).await?)
+            .unwrap();
+        assert_eq!(created.id, index as i64 + 1);
+        assert_eq!(created.task, "compact");
+        assert_eq!(created.status, Some(TaskStatus::Completed));
+        assert_eq!(created.priority, Some(2));
+    }
+
+    let req = Request::builder()
+        .uri("/tasks/1")
+        .header(hyper::header::ACCEPT, "application/x-msgpack")
+        .body(Body::empty())?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.headers()["content-type"], "application/msgpack");
+
+    let req = Request::builder()
+        .uri("/tasks")
+        .header(hyper::header::ACCEPT, "application/yaml")
+        .body(Body::empty())?;
+    let resp = app.oneshot(req).await?;
+    let tasks: Vec<Task> = Format::Yaml
+        .deserialize(&to_bytes(resp.into_body()).await?)
+        .unwrap();
+    assert_eq!(tasks.len(), 4);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unsupported_media_types() -> anyhow::Result<()> {
+    let app = memory_app();
+    let req = Request::builder()
+        .method(Method::POST)
+        .header(hyper::header::CONTENT_TYPE, "application/xml")
+        .uri("/tasks")
+        .body(Body::from("<task>xml</task>"))?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .header(hyper::header::CONTENT_TYPE, "application/cbor")
+        .uri("/tasks")
+        .body(Body::from("not cbor"))?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    for uri in ["/tasks", "/tasks/1"] {
+        let req = Request::builder()
+            .uri(uri)
+            .header(hyper::header::ACCEPT, "application/xml")
+            .body(Body::empty())?;
+        let resp = app.clone().oneshot(req).await?;
+        assert_eq!(resp.status(), StatusCode::NOT_ACCEPTABLE, "{}", uri);
+        // errors are always JSON
+        assert_eq!(resp.headers()["content-type"], "application/json");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_openapi_documents_media_types() {
+    let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    let post = &doc["paths"]["/tasks"]["post"];
+    for format in Format::ALL {
+        let media_type = format.media_type();
+        assert!(post["requestBody"]["content"][media_type].is_object());
+        assert!(post["responses"]["201"]["content"][media_type].is_object());
+    }
+    assert!(post["responses"]["415"].is_object());
+    assert!(post["responses"]["406"].is_object());
+    let list = &doc["paths"]["/tasks"]["get"]["responses"]["200"]["content"];
+    assert!(list["text/markdown"].is_object());
+    assert!(list["application/cbor"].is_object());
+    // other paths keep JSON only
+    let import = &doc["paths"]["/tasks/import"]["post"]["responses"];
+    assert!(import["406"].is_null());
+}
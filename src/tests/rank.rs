@@ -0,0 +1,148 @@
+use super::{request, sqlite_memory_repository};
+use crate::rank::{after, between, spread, MAX_RANK_LENGTH};
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+fn names(tasks: &Value) -> Vec<&str> {
+    tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["task"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_between() {
+    for (lower, upper, expected) in [
+        (None, None, "i"),
+        (Some("i"), None, "r"),
+        (None, Some("i"), "9"),
+        (Some("a"), Some("c"), "b"),
+        (Some("a"), Some("b"), "ai"),
+        (Some("a"), Some("b1"), "b"),
+        (Some("az"), Some("b"), "azi"),
+        // trailing zeros do not count, like the padded ranks of the migration
+        (Some("0000000001"), Some("0000000002"), "0000000001i"),
+        (Some("0000000009"), Some("0000000010"), "000000000m"),
+        (Some("000000001"), Some("0000000011"), "0000000010i"),
+    ] {
+        let rank = between(lower, upper).unwrap();
+        assert_eq!(rank, expected, "{:?} {:?}", lower, upper);
+    }
+    for (lower, upper) in [("b", "b"), ("b", "a"), ("b", "b0"), ("1", "01")] {
+        assert_eq!(
+            between(Some(lower), Some(upper)),
+            None,
+            "{} {}",
+            lower,
+            upper
+        );
+    }
+    // halving the same gap again and again runs out of digits
+    let mut upper = "b".to_string();
+    for _ in 0..MAX_RANK_LENGTH * 8 {
+        match between(Some("a"), Some(&upper)) {
+            Some(rank) => upper = rank,
+            None => return,
+        }
+    }
+    panic!("the ranks grew beyond {} digits", MAX_RANK_LENGTH);
+}
+
+#[test]
+fn test_after() {
+    assert_eq!(after(None), "i");
+    assert_eq!(after(Some("i")), "i001");
+    assert_eq!(after(Some("i00z")), "i01");
+    assert_eq!(after(Some("zzzz")), "zzzz1");
+    assert_eq!(after(Some("0000000005")), "0000000006");
+    // appends stay short
+    let mut rank = after(None);
+    for _ in 0..10_000 {
+        let next = after(Some(&rank));
+        assert!(next > rank);
+        rank = next;
+    }
+    assert_eq!(rank.len(), 4);
+}
+
+#[test]
+fn test_spread() {
+    assert!(spread(0).is_empty());
+    assert_eq!(spread(1), ["i"]);
+    assert_eq!(spread(2), ["c", "o"]);
+    let ranks = spread(1000);
+    assert!(ranks.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(ranks
+        .iter()
+        .all(|rank| rank.len() <= 3 && !rank.ends_with('0')));
+    // room for moves between every two ranks
+    assert!(ranks
+        .windows(2)
+        .all(|pair| between(Some(&pair[0]), Some(&pair[1])).is_some()));
+}
+
+#[tokio::test]
+async fn test_move_task() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    for task in ["wash", "dry", "fold"] {
+        request(&app, Method::POST, "/tasks", json!({ "task": task })).await?;
+    }
+
+    let (status, body) = request(&app, Method::POST, "/tasks/3/move", json!({"before": 1})).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"id": 3, "task": "fold"}));
+    let (_, body) = request(&app, Method::GET, "/tasks", Value::Null).await?;
+    assert_eq!(names(&body), ["fold", "wash", "dry"]);
+    request(&app, Method::POST, "/tasks/1/move", json!({"after": 2})).await?;
+    let (_, body) = request(&app, Method::GET, "/tasks", Value::Null).await?;
+    assert_eq!(names(&body), ["fold", "dry", "wash"]);
+    request(
+        &app,
+        Method::POST,
+        "/tasks/3/move",
+        json!({"after": 2, "before": 1}),
+    )
+    .await?;
+    let (_, body) = request(&app, Method::GET, "/tasks", Value::Null).await?;
+    assert_eq!(names(&body), ["dry", "fold", "wash"]);
+
+    for (uri, anchors, status, msg) in [
+        (
+            "/tasks/1/move",
+            json!({}),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "before or after is required",
+        ),
+        (
+            "/tasks/1/move",
+            json!({"after": 1}),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "a task can not be moved next to itself",
+        ),
+        (
+            "/tasks/1/move",
+            json!({"after": 4711}),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "anchor task not found",
+        ),
+        (
+            "/tasks/1/move",
+            json!({"after": 3, "before": 2}),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "after must be ordered before before",
+        ),
+        (
+            "/tasks/4711/move",
+            json!({"after": 1}),
+            StatusCode::NOT_FOUND,
+            "task not found",
+        ),
+    ] {
+        let (actual, body) = request(&app, Method::POST, uri, anchors.clone()).await?;
+        assert_eq!(actual, status, "{}", anchors);
+        assert_eq!(body["msg"], msg, "{}", anchors);
+    }
+    Ok(())
+}
@@ -0,0 +1,270 @@
+use super::{request, sqlite_memory_repository};
+use crate::models::task::{Occurrence, Task, TaskStatus};
+use crate::recurrence::{End, Frequency, RecurrenceRule};
+use axum::http::{Method, StatusCode};
+use chrono::{DateTime, TimeZone, Utc, Weekday};
+
+fn at(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, day, 9, 30, 0).unwrap()
+}
+
+fn occurrences(rule: &str, start: DateTime<Utc>, n: usize) -> Vec<DateTime<Utc>> {
+    let rule: RecurrenceRule = rule.parse().unwrap();
+    rule.occurrences(start).take(n).collect()
+}
+
+#[test]
+fn test_parse_rrule() {
+    let rule: RecurrenceRule = "rrule:freq=weekly;interval=2;byday=MO,FR;count=5"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        rule,
+        RecurrenceRule {
+            frequency: Frequency::Weekly,
+            interval: 2,
+            by_day: vec![Weekday::Mon, Weekday::Fri],
+            by_month_day: vec![],
+            end: End::Count(5),
+        }
+    );
+    assert_eq!(
+        rule.to_string(),
+        "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR;COUNT=5"
+    );
+    let rule: RecurrenceRule = "FREQ=MONTHLY;BYMONTHDAY=1,-1;UNTIL=20221231"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        rule.to_string(),
+        "FREQ=MONTHLY;BYMONTHDAY=1,-1;UNTIL=20221231T235959Z"
+    );
+
+    for (rule, err) in [
+        ("FREQ=YEARLY", "unsupported FREQ YEARLY"),
+        ("INTERVAL=2", "RRULE has no FREQ"),
+        (
+            "FREQ=DAILY;BYDAY=MO",
+            "BYDAY is only supported with FREQ=WEEKLY",
+        ),
+        ("FREQ=WEEKLY;BYDAY=1MO", "unsupported BYDAY \"1MO\""),
+        ("FREQ=MONTHLY;BYMONTHDAY=32", "invalid BYMONTHDAY \"32\""),
+        ("FREQ=DAILY;COUNT=0", "invalid COUNT \"0\""),
+        (
+            "FREQ=DAILY;COUNT=2;UNTIL=20221231",
+            "RRULE has both COUNT and UNTIL",
+        ),
+        ("FREQ=DAILY;BYHOUR=9", "unsupported RRULE part BYHOUR=9"),
+    ] {
+        assert_eq!(rule.parse::<RecurrenceRule>().unwrap_err(), err, "{}", rule);
+    }
+}
+
+#[test]
+fn test_daily_and_weekly_occurrences() {
+    assert_eq!(
+        occurrences("FREQ=DAILY;INTERVAL=2", at(2022, 11, 30), 3),
+        [at(2022, 11, 30), at(2022, 12, 2), at(2022, 12, 4)]
+    );
+    // Thursday 2022-11-24, then the Mondays and Fridays of every other week
+    assert_eq!(
+        occurrences("FREQ=WEEKLY;INTERVAL=2;BYDAY=FR,MO", at(2022, 11, 24), 5),
+        [
+            at(2022, 11, 24),
+            at(2022, 11, 25),
+            at(2022, 12, 5),
+            at(2022, 12, 9),
+            at(2022, 12, 19),
+        ]
+    );
+    assert_eq!(
+        occurrences("FREQ=WEEKLY", at(2022, 11, 24), 2),
+        [at(2022, 11, 24), at(2022, 12, 1)]
+    );
+}
+
+#[test]
+fn test_monthly_occurrences() {
+    // months without a 31st are skipped
+    assert_eq!(
+        occurrences("FREQ=MONTHLY", at(2023, 1, 31), 3),
+        [at(2023, 1, 31), at(2023, 3, 31), at(2023, 5, 31)]
+    );
+    assert_eq!(
+        occurrences("FREQ=MONTHLY;BYMONTHDAY=15,-1", at(2023, 1, 20), 4),
+        [
+            at(2023, 1, 20),
+            at(2023, 1, 31),
+            at(2023, 2, 15),
+            at(2023, 2, 28)
+        ]
+    );
+    // never matching rules end instead of looping forever
+    assert_eq!(
+        occurrences("FREQ=MONTHLY;INTERVAL=12;BYMONTHDAY=30", at(2023, 2, 1), 3),
+        [at(2023, 2, 1)]
+    );
+}
+
+#[test]
+fn test_count_and_until() {
+    assert_eq!(
+        occurrences("FREQ=DAILY;COUNT=2", at(2022, 11, 24), 10),
+        [at(2022, 11, 24), at(2022, 11, 25)]
+    );
+    assert_eq!(
+        occurrences("FREQ=DAILY;UNTIL=20221126", at(2022, 11, 24), 10),
+        [at(2022, 11, 24), at(2022, 11, 25), at(2022, 11, 26)]
+    );
+    assert_eq!(
+        occurrences("FREQ=DAILY;UNTIL=20221126T090000Z", at(2022, 11, 24), 10),
+        [at(2022, 11, 24), at(2022, 11, 25)]
+    );
+
+    let rule: RecurrenceRule = "FREQ=WEEKLY;COUNT=2".parse().unwrap();
+    let (next, rule) = rule.next(at(2022, 11, 24)).unwrap();
+    assert_eq!(next, at(2022, 12, 1));
+    assert_eq!(rule.to_string(), "FREQ=WEEKLY;COUNT=1");
+    assert!(rule.next(next).is_none());
+}
+
+#[test]
+fn test_series_ends_at_the_last_supported_date() {
+    for rule in [
+        "FREQ=DAILY;INTERVAL=100000000",
+        "FREQ=WEEKLY;INTERVAL=4294967295;BYDAY=MO,TH",
+        "FREQ=MONTHLY;INTERVAL=4294967295",
+    ] {
+        let rule: RecurrenceRule = rule.parse().unwrap();
+        assert_eq!(rule.next(at(2022, 11, 24)), None, "{}", rule);
+        assert_eq!(rule.occurrences(at(2022, 11, 24)).count(), 1, "{}", rule);
+    }
+}
+
+#[tokio::test]
+async fn test_completing_recurring_task_creates_next_occurrence() -> anyhow::Result<()> {
+    let repo = sqlite_memory_repository().await?;
+    let app = crate::app(repo.clone());
+    let task = serde_json::json!({
+        "task": "take out the trash",
+        "due_at": "2022-11-24T18:00:00Z",
+        "remind_at": "2022-11-24T17:00:00Z",
+        "rrule": "FREQ=WEEKLY;BYDAY=TH;COUNT=2",
+    });
+    let (status, _) = request(&app, Method::POST, "/tasks", task.clone()).await?;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let mut completed = task.clone();
+    completed["status"] = "completed".into();
+    let (status, _) = request(&app, Method::PUT, "/tasks/1", completed.clone()).await?;
+    assert_eq!(status, StatusCode::OK);
+    let next: Task = repo.task(2).await?;
+    assert_eq!(next.task, "take out the trash");
+    assert_eq!(next.status, Some(TaskStatus::NeedsAction));
+    assert_eq!(
+        next.due_at,
+        Some(Utc.with_ymd_and_hms(2022, 12, 1, 18, 0, 0).unwrap())
+    );
+    assert_eq!(
+        next.remind_at,
+        Some(Utc.with_ymd_and_hms(2022, 12, 1, 17, 0, 0).unwrap())
+    );
+    assert_eq!(next.rrule.as_deref(), Some("FREQ=WEEKLY;BYDAY=TH;COUNT=1"));
+
+    // saving the completed task again does not create another occurrence,
+    // neither does completing the last one
+    request(&app, Method::PUT, "/tasks/1", completed).await?;
+    let last = serde_json::json!({
+        "task": "take out the trash",
+        "due_at": next.due_at,
+        "rrule": next.rrule,
+        "status": "completed",
+    });
+    request(&app, Method::PUT, "/tasks/2", last).await?;
+    assert_eq!(repo.all_tasks().await?.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_occurrences_preview() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+    for task in [
+        serde_json::json!({"task": "water plants", "due_at": "2022-11-24T09:30:00Z", "rrule": "FREQ=DAILY;INTERVAL=3"}),
+        serde_json::json!({"task": "once", "due_at": "2022-11-24T09:30:00Z"}),
+        serde_json::json!({"task": "no due date", "rrule": "FREQ=DAILY"}),
+        serde_json::json!({"task": "yearly", "due_at": "2022-11-24T09:30:00Z", "rrule": "FREQ=YEARLY"}),
+    ] {
+        request(&app, Method::POST, "/tasks", task).await?;
+    }
+
+    let (status, body) = request(
+        &app,
+        Method::GET,
+        "/tasks/1/occurrences?from=2022-11-25T00:00:00Z&to=2022-12-03T09:30:00Z",
+        serde_json::Value::Null,
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    let due: Vec<DateTime<Utc>> = serde_json::from_value::<Vec<Occurrence>>(body)?
+        .into_iter()
+        .map(|occurrence| occurrence.due_at)
+        .collect();
+    assert_eq!(due, [at(2022, 11, 27), at(2022, 11, 30), at(2022, 12, 3)]);
+
+    let (_, body) = request(
+        &app,
+        Method::GET,
+        "/tasks/2/occurrences?from=2022-01-01T00:00:00Z",
+        serde_json::Value::Null,
+    )
+    .await?;
+    assert_eq!(
+        body,
+        serde_json::json!([{"due_at": "2022-11-24T09:30:00Z"}])
+    );
+
+    for (id, msg) in [(3, "task has no due_at"), (4, "unsupported FREQ YEARLY")] {
+        let (status, body) = request(
+            &app,
+            Method::GET,
+            &format!("/tasks/{}/occurrences", id),
+            serde_json::Value::Null,
+        )
+        .await?;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["msg"], msg);
+    }
+    for (range, msg) in [
+        (
+            "from=2022-12-01T00:00:00Z&to=2022-11-01T00:00:00Z",
+            "`to` is before `from`",
+        ),
+        (
+            "from=2022-01-01T00:00:00Z&to=2042-01-01T00:00:00Z",
+            "the range is longer than 3660 days",
+        ),
+        (
+            "from=3022-01-01T00:00:00Z",
+            "the range starts more than 100000 occurrences after the due_at",
+        ),
+    ] {
+        let (status, body) = request(
+            &app,
+            Method::GET,
+            &format!("/tasks/1/occurrences?{}", range),
+            serde_json::Value::Null,
+        )
+        .await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["msg"], msg);
+    }
+    let (status, _) = request(
+        &app,
+        Method::GET,
+        "/tasks/4711/occurrences",
+        serde_json::Value::Null,
+    )
+    .await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
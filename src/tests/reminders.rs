@@ -0,0 +1,284 @@
+use super::{sqlite_memory_repository, MockClock, RecordingNotifier};
+use crate::models::task::{DueFilter, NewTask, Task, TaskFilter, TaskStatus};
+use crate::reminders::Clock;
+use crate::reminders::{Notifier, Reminder, Scheduler, WebhookNotifier};
+use crate::repository::sqlite::SqliteTaskRepository;
+use crate::repository::DynTaskRepository;
+use async_trait::async_trait;
+use axum::body::Bytes;
+use axum::http::{Method, Request, StatusCode};
+use axum::routing::post;
+use axum::{Extension, Router};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use hyper::{body::to_bytes, Body};
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tower::ServiceExt;
+
+fn now() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2022, 11, 24, 10, 0, 0).unwrap()
+}
+
+fn reminded_task(task: &str, remind_at: DateTime<Utc>) -> NewTask {
+    NewTask {
+        task: task.to_string(),
+        remind_at: Some(remind_at),
+        ..Default::default()
+    }
+}
+
+fn scheduler(
+    repo: DynTaskRepository,
+    notifier: RecordingNotifier,
+    clock: Arc<MockClock>,
+) -> Scheduler {
+    Scheduler::new(
+        repo,
+        Arc::new(notifier),
+        clock,
+        std::time::Duration::from_secs(24 * 60 * 60),
+        std::time::Duration::from_secs(5),
+    )
+}
+
+/// Never answers for the task "hangs", records the other reminders
+struct HangingNotifier(RecordingNotifier);
+
+#[async_trait]
+impl Notifier for HangingNotifier {
+    async fn notify(&self, reminder: &Reminder) -> anyhow::Result<()> {
+        if reminder.task.task == "hangs" {
+            futures::future::pending::<()>().await;
+        }
+        self.0.notify(reminder).await
+    }
+}
+
+#[tokio::test]
+async fn test_scheduler_sleeps_until_the_next_reminder() -> anyhow::Result<()> {
+    let repo = sqlite_memory_repository().await?;
+    let clock = Arc::new(MockClock::new(now()));
+    let remind_at = now() + Duration::hours(1);
+    let task = repo
+        .create_task(&reminded_task("call mum", remind_at))
+        .await?;
+    let (notifier, mut received) = RecordingNotifier::new();
+    let mut sleeping_until = clock.sleeping_until.subscribe();
+    tokio::spawn(scheduler(repo.clone(), notifier, clock.clone()).run());
+
+    sleeping_until.changed().await?;
+    assert_eq!(*sleeping_until.borrow_and_update(), Some(remind_at));
+    assert!(received.try_recv().is_err());
+
+    clock.advance(Duration::hours(1));
+    let reminder = tokio::time::timeout(std::time::Duration::from_secs(5), received.recv())
+        .await?
+        .unwrap();
+    assert_eq!(reminder.task.id, task.id);
+    assert_eq!(reminder.remind_at, remind_at);
+
+    // with nothing left to send it sleeps for the poll interval
+    sleeping_until.changed().await?;
+    assert_eq!(
+        *sleeping_until.borrow_and_update(),
+        Some(remind_at + Duration::days(1))
+    );
+    assert!(repo
+        .due_reminders(now() + Duration::days(2))
+        .await?
+        .is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failed_reminder_is_retried() -> anyhow::Result<()> {
+    let repo = sqlite_memory_repository().await?;
+    let clock = Arc::new(MockClock::new(now()));
+    let task = repo
+        .create_task(&reminded_task("water plants", now()))
+        .await?;
+    let (notifier, mut received) = RecordingNotifier::new();
+    let fail = notifier.fail.clone();
+    fail.store(true, Ordering::SeqCst);
+    let scheduler = scheduler(repo.clone(), notifier, clock.clone());
+    assert_eq!(scheduler.send_due().await?, 0);
+    assert_eq!(repo.due_reminders(now()).await?.len(), 1);
+
+    // the retries back off: after 1 minute, then after 2 more minutes
+    clock.advance(Duration::minutes(1));
+    assert_eq!(scheduler.send_due().await?, 0);
+    fail.store(false, Ordering::SeqCst);
+    clock.advance(Duration::minutes(1));
+    assert_eq!(scheduler.send_due().await?, 0);
+    clock.advance(Duration::minutes(1));
+    assert_eq!(scheduler.send_due().await?, 1);
+    assert_eq!(received.recv().await.unwrap().task.id, task.id);
+    assert_eq!(scheduler.send_due().await?, 0);
+    assert!(repo.due_reminders(clock.now()).await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_hanging_notifier_times_out() -> anyhow::Result<()> {
+    let repo = sqlite_memory_repository().await?;
+    let clock = Arc::new(MockClock::new(now()));
+    repo.create_task(&reminded_task("hangs", now())).await?;
+    let task = repo.create_task(&reminded_task("feed cat", now())).await?;
+    let (notifier, mut received) = RecordingNotifier::new();
+    let scheduler = Scheduler::new(
+        repo.clone(),
+        Arc::new(HangingNotifier(notifier)),
+        clock.clone(),
+        std::time::Duration::from_secs(24 * 60 * 60),
+        std::time::Duration::from_millis(50),
+    );
+
+    // the hanging reminder failed and waits for its retry, the other one was sent
+    assert_eq!(scheduler.send_due().await?, 1);
+    assert_eq!(received.recv().await.unwrap().task.id, task.id);
+    let due = repo.due_reminders(now()).await?;
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].task, "hangs");
+    assert_eq!(scheduler.send_due().await?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reminders_survive_restart() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let url = format!("sqlite:{}", dir.path().join("tasks.db").display());
+    let clock = Arc::new(MockClock::new(now()));
+
+    let repo: DynTaskRepository = Arc::new(SqliteTaskRepository::connect(&url).await?);
+    repo.create_task(&reminded_task("sent before the restart", now()))
+        .await?;
+    repo.create_task(&reminded_task("due while down", now() + Duration::hours(1)))
+        .await?;
+    let (notifier, mut received) = RecordingNotifier::new();
+    assert_eq!(
+        scheduler(repo.clone(), notifier, clock.clone())
+            .send_due()
+            .await?,
+        1
+    );
+    assert_eq!(
+        received.recv().await.unwrap().task.task,
+        "sent before the restart"
+    );
+    drop(repo);
+
+    clock.advance(Duration::hours(2));
+    let repo: DynTaskRepository = Arc::new(SqliteTaskRepository::connect(&url).await?);
+    let (notifier, mut received) = RecordingNotifier::new();
+    assert_eq!(scheduler(repo, notifier, clock).send_due().await?, 1);
+    assert_eq!(received.recv().await.unwrap().task.task, "due while down");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_webhook_notifier_posts_reminder() -> anyhow::Result<()> {
+    let (posted, mut received) = mpsc::unbounded_channel::<Bytes>();
+    let webhook = Router::new()
+        .route(
+            "/hook",
+            post(
+                |Extension(posted): Extension<mpsc::UnboundedSender<Bytes>>, body: Bytes| async move {
+                    posted.send(body).unwrap();
+                    StatusCode::NO_CONTENT
+                },
+            ),
+        )
+        .layer(Extension(posted));
+    let server = crate::bind(&SocketAddr::from(([127, 0, 0, 1], 0)), webhook)?;
+    let url = format!("http://{}/hook", server.local_addr()).parse()?;
+    tokio::spawn(server);
+
+    let reminder = Reminder {
+        remind_at: now(),
+        task: Task {
+            id: 7,
+            task: "call mum".to_string(),
+            uid: "uid-7".to_string(),
+            remind_at: Some(now()),
+            ..Task::default()
+        },
+    };
+    WebhookNotifier::new(url).notify(&reminder).await?;
+    let body: serde_json::Value = serde_json::from_slice(&received.recv().await.unwrap())?;
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "remind_at": "2022-11-24T10:00:00Z",
+            "task": {"id": 7, "task": "call mum", "remind_at": "2022-11-24T10:00:00Z"}
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_due_filters() {
+    let due = |task: &str, due_at: DateTime<Utc>, status| Task {
+        id: 1,
+        task: task.to_string(),
+        status,
+        due_at: Some(due_at),
+        ..Task::default()
+    };
+    let overdue = TaskFilter {
+        due: Some(DueFilter::Overdue),
+        ..Default::default()
+    };
+    let today = TaskFilter {
+        due: Some(DueFilter::Today),
+        ..Default::default()
+    };
+    let earlier_today = due("earlier today", now() - Duration::hours(1), None);
+    let yesterday_done = due(
+        "done",
+        now() - Duration::days(1),
+        Some(TaskStatus::Completed),
+    );
+    let tomorrow = due("tomorrow", now() + Duration::hours(15), None);
+    assert!(overdue.matches_at(&earlier_today, now()));
+    assert!(today.matches_at(&earlier_today, now()));
+    assert!(!overdue.matches_at(&yesterday_done, now()));
+    assert!(!today.matches_at(&yesterday_done, now()));
+    assert!(!overdue.matches_at(&tomorrow, now()));
+    assert!(!today.matches_at(&tomorrow, now()));
+}
+
+#[tokio::test]
+async fn test_list_overdue_tasks() -> anyhow::Result<()> {
+    let repo = sqlite_memory_repository().await?;
+    for (task, due_at) in [
+        ("overdue", Utc::now() - Duration::days(2)),
+        ("later", Utc::now() + Duration::days(2)),
+    ] {
+        repo.create_task(&NewTask {
+            task: task.to_string(),
+            due_at: Some(due_at),
+            ..Default::default()
+        })
+        .await?;
+    }
+    let app = crate::app(repo);
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri("/tasks?due=overdue")
+        .body(Body::empty())?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let tasks: Vec<Task> = serde_json::from_slice(&to_bytes(resp.into_body()).await?)?;
+    let tasks: Vec<&str> = tasks.iter().map(|task| task.task.as_str()).collect();
+    assert_eq!(tasks, ["overdue"]);
+
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri("/tasks?due=someday")
+        .body(Body::empty())?;
+    let resp = app.oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    Ok(())
+}
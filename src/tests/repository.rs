@@ -0,0 +1,1182 @@
+use super::sqlite_memory_repository;
+use crate::models::board::{CardMove, NewBoard, NewBoardColumn};
+use crate::models::comment::{CommentVersion, NewComment};
+use crate::models::list::NewTaskList;
+use crate::models::tag::NewTag;
+use crate::models::task::{
+    Dependency, NewTask, RankAnchors, SubtaskPolicy, Task, TaskStatus, UpdateTask,
+};
+use crate::models::workflow::{NewWorkflow, Transition, WorkflowState};
+use crate::rank::MAX_RANK_LENGTH;
+use crate::repository::memory::MemoryTaskRepository;
+use crate::repository::postgres::PostgresTaskRepository;
+use crate::repository::{RepositoryError, TaskRepository};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use futures::TryStreamExt;
+use sqlx::Connection;
+
+/// e.g. postgres://postgres@127.0.0.1:5432/tasks_test - all data in the database is deleted!
+const POSTGRES_TEST_URL: &str = "POSTGRES_TEST_URL";
+
+/// The postgres tests share the database of POSTGRES_TEST_URL, so they run one at a time
+static POSTGRES: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+fn new_task(task: &str) -> NewTask {
+    NewTask {
+        task: task.to_string(),
+        ..Default::default()
+    }
+}
+
+/// 2022-11-24 at `hour` o'clock
+fn at(hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2022, 11, 24, hour, 0, 0).unwrap()
+}
+
+fn ids(tasks: Vec<Task>) -> Vec<i64> {
+    tasks.iter().map(|t| t.id).collect()
+}
+
+fn state(name: &str, status: TaskStatus) -> WorkflowState {
+    WorkflowState {
+        name: name.to_string(),
+        status,
+    }
+}
+
+fn transition(from: &str, to: &str, required_fields: &[&str]) -> Transition {
+    Transition {
+        from: from.to_string(),
+        to: to.to_string(),
+        required_fields: required_fields.iter().map(|f| f.to_string()).collect(),
+    }
+}
+
+/// Tasks are created, updated, deleted, imported and streamed with ids starting at 1
+async fn check_tasks(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    assert!(repo.all_tasks().await?.is_empty());
+
+    let first = repo.create_task(&new_task("my first test task")).await?;
+    let second = repo.create_task(&new_task("my second test task")).await?;
+    assert_eq!((first.id, first.task.as_str()), (1, "my first test task"));
+    assert_eq!(second.id, 2);
+
+    let ids: Vec<i64> = repo.all_tasks().await?.iter().map(|t| t.id).collect();
+    assert_eq!(ids, vec![1, 2]);
+
+    let update = UpdateTask {
+        task: "my first updated test task".to_string(),
+        ..Default::default()
+    };
+    repo.update_task(1, &update, None).await?;
+    assert_eq!(repo.task(1).await?.task, "my first updated test task");
+
+    repo.delete_task(1).await?;
+    assert!(matches!(repo.task(1).await, Err(RepositoryError::NotFound)));
+    assert!(matches!(
+        repo.update_task(4711, &update, None).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert!(matches!(
+        repo.delete_task(4711).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert_eq!(repo.all_tasks().await?.len(), 1);
+
+    let imported = repo
+        .import_tasks(&[
+            new_task("imported one"),
+            NewTask {
+                parent: Some(0),
+                ..new_task("imported two")
+            },
+        ])
+        .await?
+        .into_iter()
+        .collect::<Result<Vec<Task>, _>>()?;
+    assert_eq!(imported[1].parent_id, Some(imported[0].id));
+    let imported: Vec<&str> = imported.iter().map(|t| t.task.as_str()).collect();
+    assert_eq!(imported, ["imported one", "imported two"]);
+    assert!(repo.import_tasks(&[]).await?.is_empty());
+
+    let streamed: Vec<i64> = repo.task_stream().map_ok(|t| t.id).try_collect().await?;
+    assert_eq!(streamed, vec![2, 3, 4]);
+
+    // importing a task with a known uid replaces it
+    let stored = repo.task(3).await?;
+    let reimported = NewTask {
+        uid: Some(stored.uid.clone()),
+        status: Some(TaskStatus::Completed),
+        due_at: Some(Utc.with_ymd_and_hms(2022, 11, 20, 17, 0, 0).unwrap()),
+        priority: Some(1),
+        categories: Some("home,chores".to_string()),
+        rrule: Some("FREQ=DAILY".to_string()),
+        ..new_task("imported one, completed")
+    };
+    let updated = repo.import_tasks(&[reimported]).await?;
+    assert!(matches!(&updated[0], Ok(task) if task.id == 3));
+    let updated = repo.task(3).await?;
+    assert_eq!(updated.uid, stored.uid);
+    assert_eq!(updated.task, "imported one, completed");
+    assert_eq!(updated.status, Some(TaskStatus::Completed));
+    assert_eq!(
+        updated.due_at,
+        Some(Utc.with_ymd_and_hms(2022, 11, 20, 17, 0, 0).unwrap())
+    );
+    assert_eq!(updated.priority, Some(1));
+    assert_eq!(updated.categories.as_deref(), Some("home,chores"));
+    assert_eq!(updated.rrule.as_deref(), Some("FREQ=DAILY"));
+    assert_eq!(repo.all_tasks().await?.len(), 3);
+    Ok(())
+}
+
+/// Every write gets a new revision, deletes are remembered by uid
+async fn check_revisions(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    let first = repo.create_task(&new_task("first")).await?;
+    let second = repo.create_task(&new_task("second")).await?;
+    let third = repo.create_task(&new_task("third")).await?;
+    repo.delete_task(first.id).await?;
+    let all = repo.changes_since(0).await?;
+    assert_eq!(all.changed.len(), 2);
+    assert_eq!(all.deleted, vec![first.uid.clone()]);
+    assert!(repo.changes_since(all.revision).await?.changed.is_empty());
+    let update = UpdateTask {
+        task: "second, updated".to_string(),
+        ..Default::default()
+    };
+    repo.update_task(second.id, &update, None).await?;
+    repo.delete_task(third.id).await?;
+    let changes = repo.changes_since(all.revision).await?;
+    assert!(changes.revision > all.revision);
+    assert_eq!(ids(changes.changed), vec![second.id]);
+    assert_eq!(changes.deleted, vec![third.uid.clone()]);
+    assert_eq!(repo.task_by_uid(&second.uid).await?.id, second.id);
+    assert!(matches!(
+        repo.task_by_uid(&third.uid).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert_eq!(repo.current_revision().await?, changes.revision);
+
+    // conditional writes by uid see the revision of the stored task, None if there is none
+    let stored = repo.task(second.id).await?;
+    let put = NewTask {
+        uid: Some(stored.uid.clone()),
+        ..new_task("second, put")
+    };
+    assert!(matches!(
+        repo.put_task(&put, &|revision| revision.is_none()).await,
+        Err(RepositoryError::PreconditionFailed)
+    ));
+    let (put, created) = repo
+        .put_task(&put, &|revision| revision == Some(stored.revision))
+        .await?;
+    assert_eq!((put.id, created), (second.id, false));
+    assert!(put.revision > stored.revision);
+    assert_eq!(repo.current_revision().await?, put.revision);
+    assert!(matches!(
+        repo.delete_task_by_uid(&stored.uid, &|revision| revision == Some(stored.revision))
+            .await,
+        Err(RepositoryError::PreconditionFailed)
+    ));
+    assert!(matches!(
+        repo.delete_task_by_uid(&third.uid, &|_| true).await,
+        Err(RepositoryError::NotFound)
+    ));
+    Ok(())
+}
+
+/// Reminders are pending until sent, a new remind_at is a new reminder
+async fn check_reminders(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    assert_eq!(repo.next_reminder().await?, None);
+    let early = repo
+        .create_task(&NewTask {
+            remind_at: Some(at(9)),
+            ..new_task("early")
+        })
+        .await?;
+    let late = repo
+        .create_task(&NewTask {
+            remind_at: Some(at(12)),
+            ..new_task("late")
+        })
+        .await?;
+    repo.create_task(&NewTask {
+        remind_at: Some(at(8)),
+        status: Some(TaskStatus::Completed),
+        ..new_task("done")
+    })
+    .await?;
+    assert_eq!(repo.next_reminder().await?, Some(at(9)));
+    let due: Vec<i64> = repo
+        .due_reminders(at(10))
+        .await?
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(due, vec![early.id]);
+    let revision = repo.changes_since(i64::MAX).await?.revision;
+    repo.reminder_sent(early.id, at(9)).await?;
+    assert_eq!(repo.changes_since(i64::MAX).await?.revision, revision);
+    assert!(repo.due_reminders(at(10)).await?.is_empty());
+    assert_eq!(repo.next_reminder().await?, Some(at(12)));
+    let snoozed = UpdateTask {
+        task: "early".to_string(),
+        remind_at: Some(at(11)),
+        ..Default::default()
+    };
+    repo.update_task(early.id, &snoozed, None).await?;
+    let due: Vec<i64> = repo
+        .due_reminders(at(12))
+        .await?
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(due, vec![early.id, late.id]);
+    assert!(matches!(
+        repo.reminder_sent(4711, at(9)).await,
+        Err(RepositoryError::NotFound)
+    ));
+    Ok(())
+}
+
+/// Subtasks move and are deleted with their parent, cycles are refused
+async fn check_subtasks(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    let subtask = |task: &str, parent_id| NewTask {
+        parent_id: Some(parent_id),
+        ..new_task(task)
+    };
+    let root = repo.create_task(&new_task("root")).await?;
+    let child = repo.create_task(&subtask("child", root.id)).await?;
+    let grandchild = repo.create_task(&subtask("grandchild", child.id)).await?;
+    let other = repo.create_task(&new_task("other")).await?;
+    assert!(matches!(
+        repo.create_task(&subtask("orphan", 4711)).await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    assert_eq!(ids(repo.children(root.id).await?), vec![child.id]);
+    assert!(repo.children(grandchild.id).await?.is_empty());
+    assert!(matches!(
+        repo.children(4711).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert_eq!(
+        ids(repo.subtree(root.id).await?),
+        vec![root.id, child.id, grandchild.id]
+    );
+    assert!(matches!(
+        repo.move_task(root.id, Some(grandchild.id)).await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    assert!(matches!(
+        repo.move_task(root.id, Some(root.id)).await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    assert!(matches!(
+        repo.move_task(root.id, Some(4711)).await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    assert!(matches!(
+        repo.move_task(4711, None).await,
+        Err(RepositoryError::NotFound)
+    ));
+    let revision = repo.changes_since(i64::MAX).await?.revision;
+    repo.move_task(child.id, Some(other.id)).await?;
+    assert_eq!(repo.task(child.id).await?.parent_id, Some(other.id));
+    assert!(repo.task(child.id).await?.revision > revision);
+    assert_eq!(
+        ids(repo.subtree(other.id).await?),
+        vec![child.id, grandchild.id, other.id]
+    );
+    let complete = UpdateTask {
+        task: "other".to_string(),
+        status: Some(TaskStatus::Completed),
+        completed_on: Some(at(9).date_naive()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        repo.update_task(other.id, &complete, Some(SubtaskPolicy::Block))
+            .await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    assert_eq!(repo.task(other.id).await?.status, None);
+    repo.update_task(other.id, &complete, Some(SubtaskPolicy::Complete))
+        .await?;
+    let completed = repo.task(grandchild.id).await?;
+    assert_eq!(completed.status, Some(TaskStatus::Completed));
+    assert_eq!(completed.completed_on, Some(at(9).date_naive()));
+    repo.move_task(child.id, None).await?;
+    assert_eq!(repo.task(child.id).await?.parent_id, None);
+    repo.move_task(child.id, Some(other.id)).await?;
+    let revision = repo.changes_since(i64::MAX).await?.revision;
+    repo.delete_task(other.id).await?;
+    let mut deleted = repo.changes_since(revision).await?.deleted;
+    deleted.sort();
+    let mut uids = vec![other.uid, child.uid, grandchild.uid];
+    uids.sort();
+    assert_eq!(deleted, uids);
+    assert!(matches!(
+        repo.task(grandchild.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert_eq!(repo.task(root.id).await?.id, root.id);
+    Ok(())
+}
+
+/// Dependencies never form a cycle and are deleted with their tasks
+async fn check_dependencies(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    let build = repo.create_task(&new_task("build")).await?;
+    let test = repo.create_task(&new_task("test")).await?;
+    let ship = repo.create_task(&new_task("ship")).await?;
+    repo.add_dependency(test.id, build.id).await?;
+    repo.add_dependency(ship.id, test.id).await?;
+    repo.add_dependency(ship.id, test.id).await?;
+    for (id, blocker_id) in [(build.id, ship.id), (build.id, test.id), (test.id, test.id)] {
+        assert!(matches!(
+            repo.add_dependency(id, blocker_id).await,
+            Err(RepositoryError::Conflict(_))
+        ));
+    }
+    assert!(matches!(
+        repo.add_dependency(4711, build.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert!(matches!(
+        repo.add_dependency(build.id, 4711).await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    repo.add_dependency(ship.id, build.id).await?;
+    assert_eq!(ids(repo.blockers(ship.id).await?), vec![build.id, test.id]);
+    assert!(repo.blockers(build.id).await?.is_empty());
+    assert!(matches!(
+        repo.blockers(4711).await,
+        Err(RepositoryError::NotFound)
+    ));
+    let complete = |task: &Task| UpdateTask {
+        task: task.task.clone(),
+        status: Some(TaskStatus::Completed),
+        ..Default::default()
+    };
+    assert!(matches!(
+        repo.update_task(ship.id, &complete(&ship), None).await,
+        Err(RepositoryError::Conflict(msg)) if msg == format!("task is blocked by the open tasks {}, {}", build.id, test.id)
+    ));
+    let reimported = NewTask {
+        uid: Some(ship.uid.clone()),
+        status: Some(TaskStatus::Completed),
+        ..new_task("ship")
+    };
+    let rows = repo
+        .import_tasks(&[
+            reimported.clone(),
+            NewTask {
+                parent: Some(0),
+                ..new_task("ship notes")
+            },
+        ])
+        .await?;
+    assert!(matches!(&rows[0], Err(RepositoryError::Conflict(_))));
+    assert!(matches!(&rows[1], Err(RepositoryError::Invalid(_))));
+    assert!(matches!(
+        repo.put_task(&reimported, &|_| true).await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    assert_eq!(repo.task(ship.id).await?.status, None);
+    repo.remove_dependency(ship.id, build.id).await?;
+    assert!(matches!(
+        repo.remove_dependency(ship.id, build.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    let dependency = |task_id, blocker_id| Dependency {
+        task_id,
+        blocker_id,
+    };
+    assert_eq!(
+        repo.dependencies().await?,
+        vec![dependency(test.id, build.id), dependency(ship.id, test.id)]
+    );
+    repo.delete_task(test.id).await?;
+    assert!(repo.dependencies().await?.is_empty());
+    Ok(())
+}
+
+/// Tags are embedded in their tasks, changing them changes the tasks
+async fn check_tags(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    let build = repo.create_task(&new_task("build")).await?;
+    let ship = repo.create_task(&new_task("ship")).await?;
+    let new_tag = |name: &str| NewTag {
+        name: name.to_string(),
+        colour: None,
+    };
+    let urgent = repo.create_tag(&new_tag("urgent")).await?;
+    let docs = repo.create_tag(&new_tag("docs")).await?;
+    assert!(matches!(
+        repo.create_tag(&new_tag("URGENT")).await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    // the same folding and order in every backend, also beyond ASCII
+    let apples = repo.create_tag(&new_tag("Äpfel")).await?;
+    assert!(matches!(
+        repo.create_tag(&new_tag("äPFEL")).await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    let names: Vec<String> = repo.tags().await?.into_iter().map(|t| t.name).collect();
+    assert_eq!(names, vec!["docs", "urgent", "Äpfel"]);
+    repo.delete_tag(apples.id).await?;
+    let tag_names = |task: Task| -> Vec<String> { task.tags.into_iter().map(|t| t.name).collect() };
+    let revision = repo.changes_since(i64::MAX).await?.revision;
+    repo.attach_tag(ship.id, urgent.id).await?;
+    repo.attach_tag(ship.id, docs.id).await?;
+    repo.attach_tag(ship.id, docs.id).await?;
+    repo.attach_tag(build.id, urgent.id).await?;
+    assert_eq!(tag_names(repo.task(ship.id).await?), vec!["docs", "urgent"]);
+    let mut changed = ids(repo.changes_since(revision).await?.changed);
+    changed.sort();
+    assert_eq!(changed, vec![build.id, ship.id]);
+    assert!(matches!(
+        repo.attach_tag(4711, urgent.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert!(matches!(
+        repo.attach_tag(ship.id, 4711).await,
+        Err(RepositoryError::NotFound)
+    ));
+    let counts: Vec<(String, i64)> = repo
+        .tag_counts()
+        .await?
+        .into_iter()
+        .map(|count| (count.name, count.tasks))
+        .collect();
+    assert_eq!(
+        counts,
+        vec![("docs".to_string(), 1), ("urgent".to_string(), 2)]
+    );
+    let revision = repo.changes_since(i64::MAX).await?.revision;
+    let renamed = NewTag {
+        name: "asap".to_string(),
+        colour: Some("#ff0000".to_string()),
+    };
+    assert_eq!(repo.update_tag(urgent.id, &renamed).await?.name, "asap");
+    assert!(matches!(
+        repo.update_tag(urgent.id, &new_tag("Docs")).await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    assert!(matches!(
+        repo.update_tag(4711, &new_tag("other")).await,
+        Err(RepositoryError::NotFound)
+    ));
+    let changed = ids(repo.changes_since(revision).await?.changed);
+    assert_eq!(changed.len(), 2);
+    let ship_tags = repo.task(ship.id).await?.tags;
+    assert_eq!(ship_tags[0].name, "asap");
+    assert_eq!(ship_tags[0].colour.as_deref(), Some("#ff0000"));
+    repo.detach_tag(ship.id, docs.id).await?;
+    assert!(matches!(
+        repo.detach_tag(ship.id, docs.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    repo.delete_tag(urgent.id).await?;
+    assert!(repo.task(build.id).await?.tags.is_empty());
+    assert!(matches!(
+        repo.tag(urgent.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert_eq!(repo.tags().await?, vec![docs]);
+    Ok(())
+}
+
+/// Lists keep their tasks in order and are archived along with them
+async fn check_lists(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    let new_list = |name: &str| NewTaskList {
+        name: name.to_string(),
+    };
+    let in_list = |task: &str, list_id| NewTask {
+        list_id: Some(list_id),
+        ..new_task(task)
+    };
+    let garden = repo.create_list(&new_list("garden")).await?;
+    let house = repo.create_list(&new_list("house")).await?;
+    let mow = repo.create_task(&in_list("mow", garden.id)).await?;
+    let weed = repo.create_task(&in_list("weed", garden.id)).await?;
+    let paint = repo.create_task(&in_list("paint", house.id)).await?;
+    let fence = repo
+        .create_task(&NewTask {
+            parent_id: Some(paint.id),
+            ..new_task("fence")
+        })
+        .await?;
+    // a subtask is in the list of its parent
+    assert_eq!(fence.list_id, Some(house.id));
+    assert!(matches!(
+        repo.create_task(&NewTask {
+            parent_id: Some(paint.id),
+            ..in_list("gate", garden.id)
+        })
+        .await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    assert!(mow.rank < weed.rank);
+    assert!(matches!(
+        repo.create_task(&in_list("dig", 4711)).await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    // the subtask moves along, to the end of the list
+    repo.move_to_list(paint.id, Some(garden.id)).await?;
+    assert_eq!(
+        ids(repo.list_tasks(garden.id).await?),
+        vec![mow.id, weed.id, paint.id, fence.id]
+    );
+    assert!(repo.list_tasks(house.id).await?.is_empty());
+    assert!(matches!(
+        repo.move_to_list(paint.id, Some(4711)).await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    assert!(matches!(
+        repo.move_to_list(4711, None).await,
+        Err(RepositoryError::NotFound)
+    ));
+    let order = [fence.id, paint.id, weed.id, mow.id];
+    repo.reorder_list(garden.id, &order).await?;
+    assert_eq!(ids(repo.list_tasks(garden.id).await?), order);
+    assert!(matches!(
+        repo.reorder_list(garden.id, &[mow.id, weed.id]).await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    assert!(matches!(
+        repo.list_tasks(4711).await,
+        Err(RepositoryError::NotFound)
+    ));
+
+    // a subtask from elsewhere is archived and deleted along with its parent, like its list
+    let rake = repo.create_task(&new_task("rake")).await?;
+    repo.move_task(rake.id, Some(weed.id)).await?;
+    let revision = repo.changes_since(i64::MAX).await?.revision;
+    assert!(repo.archive_list(garden.id, true).await?.archived);
+    assert_eq!(repo.changes_since(revision).await?.changed.len(), 5);
+    assert!(repo.task(mow.id).await?.archived);
+    assert!(repo.task(rake.id).await?.archived);
+    assert!(matches!(
+        repo.move_to_list(mow.id, Some(house.id)).await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    assert!(matches!(
+        repo.create_task(&in_list("dig", garden.id)).await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    assert!(!repo.archive_list(garden.id, false).await?.archived);
+    assert!(!repo.task(mow.id).await?.archived);
+    assert!(!repo.task(rake.id).await?.archived);
+
+    assert!(matches!(
+        repo.delete_list(garden.id, false).await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    repo.delete_list(house.id, false).await?;
+    assert!(matches!(
+        repo.list(house.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    let revision = repo.changes_since(i64::MAX).await?.revision;
+    repo.delete_list(garden.id, true).await?;
+    assert_eq!(repo.changes_since(revision).await?.deleted.len(), 5);
+    assert!(matches!(
+        repo.task(fence.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert!(repo.lists().await?.is_empty());
+    Ok(())
+}
+
+/// Moving a task in the manual order changes only its rank
+async fn check_ranks(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    let a = repo.create_task(&new_task("a")).await?;
+    let b = repo.create_task(&new_task("b")).await?;
+    let c = repo.create_task(&new_task("c")).await?;
+    let ranked = |tasks: Vec<Task>| -> Vec<i64> {
+        tasks
+            .into_iter()
+            .map(|task| task.id)
+            .filter(|id| [a.id, b.id, c.id].contains(id))
+            .collect()
+    };
+    let anchors = |before: Option<i64>, after: Option<i64>| RankAnchors { before, after };
+    let revision = repo.changes_since(i64::MAX).await?.revision;
+    repo.rank_task(c.id, &anchors(Some(a.id), None)).await?;
+    assert_eq!(ranked(repo.all_tasks().await?), vec![c.id, a.id, b.id]);
+    repo.rank_task(a.id, &anchors(None, Some(b.id))).await?;
+    assert_eq!(ranked(repo.all_tasks().await?), vec![c.id, b.id, a.id]);
+    repo.rank_task(c.id, &anchors(Some(a.id), Some(b.id)))
+        .await?;
+    assert_eq!(ranked(repo.all_tasks().await?), vec![b.id, c.id, a.id]);
+    assert_eq!(repo.task(b.id).await?.rank, b.rank);
+    assert!(repo.changes_since(revision).await?.changed.is_empty());
+    for (id, anchors, invalid) in [
+        (a.id, anchors(None, None), true),
+        (a.id, anchors(Some(a.id), None), true),
+        (a.id, anchors(None, Some(4711)), true),
+        (a.id, anchors(Some(b.id), Some(c.id)), true),
+        (4711, anchors(Some(a.id), None), false),
+    ] {
+        let result = repo.rank_task(id, &anchors).await;
+        match invalid {
+            true => assert!(matches!(result, Err(RepositoryError::Invalid(_)))),
+            false => assert!(matches!(result, Err(RepositoryError::NotFound))),
+        }
+    }
+    // the gap after b halves with every move, until the ranks are spread out again
+    for _ in 0..100 {
+        repo.rank_task(a.id, &anchors(None, Some(b.id))).await?;
+        repo.rank_task(c.id, &anchors(None, Some(b.id))).await?;
+    }
+    let tasks = repo.all_tasks().await?;
+    assert!(tasks.iter().all(|task| task.rank.len() <= MAX_RANK_LENGTH));
+    assert_eq!(ranked(tasks), vec![b.id, c.id, a.id]);
+    Ok(())
+}
+
+/// A card move sets status and rank at once, within the work in progress limits
+async fn check_boards(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    let a = repo.create_task(&new_task("a")).await?;
+    let b = repo.create_task(&new_task("b")).await?;
+    let c = repo.create_task(&new_task("c")).await?;
+    let column = |name: &str, status, wip_limit| NewBoardColumn {
+        name: name.to_string(),
+        status,
+        wip_limit,
+    };
+    let new_board = |list_id, columns| NewBoard {
+        name: "flow".to_string(),
+        list_id,
+        columns,
+    };
+    let board = repo
+        .create_board(&new_board(
+            None,
+            vec![
+                column("todo", TaskStatus::NeedsAction, None),
+                column("doing", TaskStatus::InProcess, Some(1)),
+                column("done", TaskStatus::Completed, None),
+            ],
+        ))
+        .await?;
+    assert_eq!(repo.boards().await?, vec![board.clone()]);
+    assert_eq!(repo.board(board.id).await?, board);
+    let (todo, doing, done) = (
+        board.columns[0].id,
+        board.columns[1].id,
+        board.columns[2].id,
+    );
+    let card = |column_id, before| CardMove {
+        column_id,
+        before,
+        after: None,
+    };
+    let today = NaiveDate::from_ymd_opt(2022, 11, 30).unwrap();
+    let revision = repo.changes_since(i64::MAX).await?.revision;
+    repo.move_card(board.id, a.id, &card(doing, Some(c.id)), today)
+        .await?;
+    assert_eq!(repo.task(a.id).await?.status, Some(TaskStatus::InProcess));
+    assert_eq!(ids(repo.all_tasks().await?), vec![b.id, a.id, c.id]);
+    assert_eq!(repo.changes_since(revision).await?.changed.len(), 1);
+    assert!(matches!(
+        repo.move_card(board.id, c.id, &card(doing, None), today)
+            .await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    repo.move_card(board.id, a.id, &card(doing, Some(a.id)), today)
+        .await
+        .expect_err("a task can not be moved next to itself");
+    repo.move_card(board.id, a.id, &card(doing, None), today)
+        .await?;
+    repo.move_card(board.id, a.id, &card(done, None), today)
+        .await?;
+    let completed = repo.task(a.id).await?;
+    assert_eq!(completed.status, Some(TaskStatus::Completed));
+    assert_eq!(completed.completed_on, Some(today));
+    repo.move_card(board.id, a.id, &card(todo, None), today)
+        .await?;
+    let reopened = repo.task(a.id).await?;
+    assert_eq!(reopened.status, Some(TaskStatus::NeedsAction));
+    assert_eq!(reopened.completed_on, None);
+    for (board_id, id, column_id, invalid) in [
+        (board.id, a.id, 4711, true),
+        (4711, a.id, todo, false),
+        (board.id, 4711, todo, false),
+    ] {
+        let result = repo
+            .move_card(board_id, id, &card(column_id, None), today)
+            .await;
+        match invalid {
+            true => assert!(matches!(result, Err(RepositoryError::Invalid(_)))),
+            false => assert!(matches!(result, Err(RepositoryError::NotFound))),
+        }
+    }
+    // columns keep their id as long as their status stays on the board
+    let updated = repo
+        .update_board(
+            board.id,
+            &new_board(
+                None,
+                vec![
+                    column("doing", TaskStatus::InProcess, Some(2)),
+                    column("todo", TaskStatus::NeedsAction, None),
+                ],
+            ),
+        )
+        .await?;
+    let columns: Vec<i64> = updated.columns.iter().map(|column| column.id).collect();
+    assert_eq!(columns, vec![doing, todo]);
+    assert_eq!(updated.columns[0].wip_limit, Some(2));
+    assert!(matches!(
+        repo.update_board(4711, &new_board(None, Vec::new())).await,
+        Err(RepositoryError::NotFound)
+    ));
+
+    // a board of a list shows its tasks only and is deleted along with the list
+    assert!(matches!(
+        repo.create_board(&new_board(Some(4711), Vec::new())).await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    let list = repo
+        .create_list(&NewTaskList {
+            name: "chores".to_string(),
+        })
+        .await?;
+    let list_board = repo
+        .create_board(&new_board(
+            Some(list.id),
+            vec![column("todo", TaskStatus::NeedsAction, None)],
+        ))
+        .await?;
+    let list_todo = list_board.columns[0].id;
+    assert!(matches!(
+        repo.move_card(list_board.id, a.id, &card(list_todo, None), today)
+            .await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    repo.delete_list(list.id, false).await?;
+    assert!(matches!(
+        repo.board(list_board.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    repo.delete_board(board.id).await?;
+    assert!(matches!(
+        repo.delete_board(board.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert!(repo.boards().await?.is_empty());
+    Ok(())
+}
+
+/// Tasks start in the first state of their workflow and take the status of their state
+async fn check_workflows(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    let mut new_workflow = NewWorkflow {
+        name: "review".to_string(),
+        states: vec![
+            state("open", TaskStatus::NeedsAction),
+            state("review", TaskStatus::InProcess),
+            state("done", TaskStatus::Completed),
+        ],
+        transitions: vec![
+            transition("open", "review", &[]),
+            transition("review", "done", &["completed_on"]),
+        ],
+    };
+    let workflow = repo.create_workflow(&new_workflow).await?;
+    assert_eq!(workflow.states, new_workflow.states);
+    assert_eq!(workflow.transitions, new_workflow.transitions);
+    assert_eq!(repo.workflows().await?, vec![workflow.clone()]);
+    assert_eq!(repo.workflow(workflow.id).await?, workflow);
+    assert!(matches!(
+        repo.create_task(&NewTask {
+            workflow_id: Some(4711),
+            ..new_task("nowhere")
+        })
+        .await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    let reviewed = repo
+        .create_task(&NewTask {
+            status: Some(TaskStatus::Completed),
+            workflow_id: Some(workflow.id),
+            ..new_task("reviewed")
+        })
+        .await?;
+    assert_eq!(reviewed.workflow_id, Some(workflow.id));
+    assert_eq!(reviewed.state.as_deref(), Some("open"));
+    assert_eq!(reviewed.status, Some(TaskStatus::NeedsAction));
+    let update = UpdateTask {
+        task: "reviewed".to_string(),
+        status: Some(TaskStatus::InProcess),
+        state: Some("review".to_string()),
+        ..Default::default()
+    };
+    repo.update_task(reviewed.id, &update, None).await?;
+    repo.update_task(
+        reviewed.id,
+        &UpdateTask {
+            state: None,
+            ..update
+        },
+        None,
+    )
+    .await?;
+    assert_eq!(
+        repo.task(reviewed.id).await?.state.as_deref(),
+        Some("review")
+    );
+
+    // the tasks in a state get its new status, a state with tasks must stay
+    new_workflow.states[1].status = TaskStatus::NeedsAction;
+    repo.update_workflow(workflow.id, &new_workflow).await?;
+    assert_eq!(
+        repo.task(reviewed.id).await?.status,
+        Some(TaskStatus::NeedsAction)
+    );
+    new_workflow.states.remove(1);
+    new_workflow.transitions.clear();
+    assert!(matches!(
+        repo.update_workflow(workflow.id, &new_workflow).await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    assert!(matches!(
+        repo.delete_workflow(workflow.id).await,
+        Err(RepositoryError::Conflict(_))
+    ));
+    repo.set_workflow(reviewed.id, None).await?;
+    let unfollowed = repo.task(reviewed.id).await?;
+    assert_eq!((unfollowed.workflow_id, unfollowed.state), (None, None));
+    assert_eq!(unfollowed.status, Some(TaskStatus::NeedsAction));
+    repo.set_workflow(reviewed.id, Some(workflow.id)).await?;
+    assert_eq!(repo.task(reviewed.id).await?.state.as_deref(), Some("open"));
+    assert!(matches!(
+        repo.set_workflow(reviewed.id, Some(4711)).await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    assert!(matches!(
+        repo.set_workflow(4711, None).await,
+        Err(RepositoryError::NotFound)
+    ));
+    repo.delete_task(reviewed.id).await?;
+    repo.delete_workflow(workflow.id).await?;
+    assert!(matches!(
+        repo.workflow(workflow.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert!(repo.workflows().await?.is_empty());
+    Ok(())
+}
+
+/// Subtasks are completed along with their parent, CalDAV and imports follow the transitions
+async fn check_workflow_transitions(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    let checklist = repo
+        .create_workflow(&NewWorkflow {
+            name: "checklist".to_string(),
+            states: vec![
+                state("todo", TaskStatus::NeedsAction),
+                state("checked", TaskStatus::Completed),
+                state("later", TaskStatus::InProcess),
+            ],
+            transitions: vec![
+                transition("todo", "checked", &["completed_on"]),
+                transition("todo", "later", &[]),
+            ],
+        })
+        .await?;
+    let item = |task: &str, parent_id| NewTask {
+        parent_id: Some(parent_id),
+        workflow_id: Some(checklist.id),
+        ..new_task(task)
+    };
+    let complete = UpdateTask {
+        task: "pack".to_string(),
+        status: Some(TaskStatus::Completed),
+        ..Default::default()
+    };
+    let pack = repo.create_task(&new_task("pack")).await?;
+    let tent = repo.create_task(&item("tent", pack.id)).await?;
+    repo.update_task(pack.id, &complete, Some(SubtaskPolicy::Complete))
+        .await?;
+    let checked = repo.task(tent.id).await?;
+    assert_eq!(checked.state.as_deref(), Some("checked"));
+    assert_eq!(checked.status, Some(TaskStatus::Completed));
+
+    let unpack = repo.create_task(&new_task("unpack")).await?;
+    let stove = repo.create_task(&item("stove", unpack.id)).await?;
+    let later = UpdateTask {
+        task: "stove".to_string(),
+        status: Some(TaskStatus::InProcess),
+        state: Some("later".to_string()),
+        ..Default::default()
+    };
+    repo.update_task(stove.id, &later, None).await?;
+    // the repository checks the transitions of updates against the task as it is written
+    let back = UpdateTask {
+        state: Some("todo".to_string()),
+        ..later.clone()
+    };
+    assert!(matches!(
+        repo.update_task(stove.id, &back, None).await,
+        Err(RepositoryError::Transition { .. })
+    ));
+    assert!(matches!(
+        repo.update_task(unpack.id, &later, None).await,
+        Err(RepositoryError::Invalid(_))
+    ));
+    assert!(matches!(
+        repo.update_task(unpack.id, &complete, Some(SubtaskPolicy::Complete))
+            .await,
+        Err(RepositoryError::Transition { .. })
+    ));
+    assert!(repo.task(unpack.id).await?.is_open());
+    assert_eq!(repo.task(stove.id).await?.state.as_deref(), Some("later"));
+    let replaced = |status| NewTask {
+        uid: Some(stove.uid.clone()),
+        status,
+        ..new_task("stove")
+    };
+    assert!(matches!(
+        repo.put_task(&replaced(Some(TaskStatus::Completed)), &|_| true)
+            .await,
+        Err(RepositoryError::Transition { .. })
+    ));
+    let imported = repo
+        .import_tasks(&[replaced(Some(TaskStatus::Completed)), replaced(None)])
+        .await?;
+    assert!(matches!(
+        imported[0],
+        Err(RepositoryError::Transition { .. })
+    ));
+    // the status follows the state
+    assert!(
+        matches!(&imported[1], Ok(task) if task.status == Some(TaskStatus::InProcess)
+            && task.state.as_deref() == Some("later"))
+    );
+    repo.delete_task(pack.id).await?;
+    repo.delete_task(unpack.id).await?;
+    repo.delete_workflow(checklist.id).await?;
+    Ok(())
+}
+
+/// Comments, their history and notifications go along with their task
+async fn check_comments(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    let discussed = repo.create_task(&new_task("discussed")).await?;
+    let subtask = repo
+        .create_task(&NewTask {
+            parent_id: Some(discussed.id),
+            ..new_task("discussed too")
+        })
+        .await?;
+    let written = Utc.with_ymd_and_hms(2022, 12, 2, 9, 0, 0).unwrap();
+    let edited = Utc.with_ymd_and_hms(2022, 12, 2, 10, 0, 0).unwrap();
+    let comment = |author: &str, body: &str| NewComment {
+        author: author.to_string(),
+        body: body.to_string(),
+    };
+    let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<String>>();
+    let (created, notified) = repo
+        .create_comment(
+            discussed.id,
+            &comment("alice", "@bob @alice"),
+            &names(&["bob", "alice"]),
+            written,
+        )
+        .await?;
+    assert_eq!(created.mentions, ["bob", "alice"]);
+    assert_eq!(created.created_at, written);
+    assert_eq!(
+        notified
+            .iter()
+            .map(|n| (n.recipient.as_str(), n.author.as_str()))
+            .collect::<Vec<_>>(),
+        [("bob", "alice")]
+    );
+    assert_eq!(repo.comments(discussed.id).await?, vec![created.clone()]);
+    assert_eq!(repo.comment(discussed.id, created.id).await?, created);
+    assert!(matches!(
+        repo.comment(subtask.id, created.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert!(matches!(
+        repo.create_comment(4711, &comment("alice", "hi"), &[], written)
+            .await,
+        Err(RepositoryError::NotFound)
+    ));
+    let (updated, notified) = repo
+        .update_comment(
+            discussed.id,
+            created.id,
+            &comment("bob", "@bob @carol"),
+            &names(&["bob", "carol"]),
+            edited,
+        )
+        .await?;
+    assert_eq!(
+        (updated.author.as_str(), updated.edited_by.as_deref()),
+        ("alice", Some("bob"))
+    );
+    assert_eq!(updated.edited_at, Some(edited));
+    assert_eq!(
+        notified
+            .iter()
+            .map(|n| n.recipient.as_str())
+            .collect::<Vec<_>>(),
+        ["carol"]
+    );
+    assert_eq!(
+        repo.comment_history(discussed.id, created.id).await?,
+        vec![CommentVersion {
+            author: "alice".to_string(),
+            body: "@bob @alice".to_string(),
+            written_at: written,
+        }]
+    );
+    let all = repo.notifications(None, 0).await?;
+    assert_eq!(all.len(), 2);
+    assert_eq!(repo.notifications(None, all[0].id).await?, all[1..]);
+    assert_eq!(repo.notifications(Some("bob"), 0).await?, all[..1]);
+    let (other, seen) = repo
+        .create_comment(
+            subtask.id,
+            &comment("carol", "@dave"),
+            &names(&["dave"]),
+            written,
+        )
+        .await?;
+    repo.delete_comment(subtask.id, other.id).await?;
+    assert!(matches!(
+        repo.delete_comment(subtask.id, other.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert_eq!(repo.notifications(None, 0).await?, all);
+    // the ids of deleted notifications are not handed out again, a poller which has seen the
+    // newest one gets the next
+    let (_, notified) = repo
+        .create_comment(
+            subtask.id,
+            &comment("carol", "@dave"),
+            &names(&["dave"]),
+            written,
+        )
+        .await?;
+    assert_eq!(repo.notifications(None, seen[0].id).await?, notified);
+    repo.delete_task(discussed.id).await?;
+    assert!(matches!(
+        repo.comments(subtask.id).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert!(repo.notifications(None, 0).await?.is_empty());
+    Ok(())
+}
+
+/// Concurrent completions of a recurring task create one next occurrence
+async fn check_recurring_completion(repo: &dyn TaskRepository) -> anyhow::Result<()> {
+    let daily = repo
+        .create_task(&NewTask {
+            due_at: Some(at(9)),
+            rrule: Some("FREQ=DAILY".to_string()),
+            ..new_task("daily")
+        })
+        .await?;
+    let complete = UpdateTask {
+        task: "daily".to_string(),
+        status: Some(TaskStatus::Completed),
+        due_at: Some(at(9)),
+        rrule: Some("FREQ=DAILY".to_string()),
+        ..Default::default()
+    };
+    // sqlite refuses the later writer, the others complete the task once
+    let (a, b) = futures::join!(
+        repo.update_task(daily.id, &complete, None),
+        repo.update_task(daily.id, &complete, None)
+    );
+    let next: Vec<Task> = [a, b].into_iter().filter_map(|next| next.ok()?).collect();
+    assert_eq!(next.len(), 1);
+    assert_eq!(next[0].due_at, Some(at(9) + chrono::Duration::days(1)));
+    let occurrences = repo
+        .all_tasks()
+        .await?
+        .into_iter()
+        .filter(|task| task.task == "daily")
+        .count();
+    assert_eq!(occurrences, 2);
+    Ok(())
+}
+
+/// Empty repository in the database of POSTGRES_TEST_URL assigning ids starting with 1,
+/// None if it is not set
+async fn postgres_repository() -> anyhow::Result<Option<PostgresTaskRepository>> {
+    let database_url = match std::env::var(POSTGRES_TEST_URL) {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("{} not set, skipping PostgreSQL test", POSTGRES_TEST_URL);
+            return Ok(None);
+        }
+    };
+    let repo = PostgresTaskRepository::connect(&database_url).await?;
+    let mut conn = sqlx::postgres::PgConnection::connect(&database_url).await?;
+    sqlx::query(
+        "TRUNCATE task, task_tombstone, task_dependency, task_tag, tag, task_list, board, board_column, \
+         workflow, workflow_state, workflow_transition, task_comment, comment_version, notification \
+         RESTART IDENTITY",
+    )
+    .execute(&mut conn)
+    .await?;
+    Ok(Some(repo))
+}
+
+/**
+ * The same behaviour is expected from every backend: each check gets a test per backend
+ * running on an empty repository, which assigns ids starting with 1.
+ * The postgres tests run only if POSTGRES_TEST_URL points to a (local) PostgreSQL server.
+ */
+macro_rules! repository_tests {
+    ($($check:ident),* $(,)?) => {
+        mod memory {
+            $(
+                #[tokio::test]
+                async fn $check() -> anyhow::Result<()> {
+                    super::$check(&super::MemoryTaskRepository::new()).await
+                }
+            )*
+        }
+
+        mod sqlite {
+            $(
+                #[tokio::test]
+                async fn $check() -> anyhow::Result<()> {
+                    let repo = super::sqlite_memory_repository().await?;
+                    super::$check(repo.as_ref()).await
+                }
+            )*
+        }
+
+        mod postgres {
+            $(
+                #[tokio::test]
+                async fn $check() -> anyhow::Result<()> {
+                    let _alone = super::POSTGRES.lock().await;
+                    match super::postgres_repository().await? {
+                        Some(repo) => super::$check(&repo).await,
+                        None => Ok(()),
+                    }
+                }
+            )*
+        }
+    };
+}
+
+repository_tests!(
+    check_tasks,
+    check_revisions,
+    check_reminders,
+    check_subtasks,
+    check_dependencies,
+    check_tags,
+    check_lists,
+    check_ranks,
+    check_boards,
+    check_workflows,
+    check_workflow_transitions,
+    check_comments,
+    check_recurring_completion,
+);
@@ -0,0 +1,164 @@
+use super::{request, sqlite_memory_repository};
+use crate::models::tag::NewTag;
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+fn names(tasks: &Value) -> Vec<&str> {
+    tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["task"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_validate_tag() {
+    let tag = |name: &str, colour: Option<&str>| NewTag {
+        name: name.to_string(),
+        colour: colour.map(str::to_string),
+    };
+    assert!(tag("release", Some("#1E90ff")).validate().is_ok());
+    assert!(tag("release", None).validate().is_ok());
+    for (tag, err) in [
+        (tag(" ", None), "name must not be empty"),
+        (
+            tag("a,b", None),
+            "name must not contain commas or start or end with spaces",
+        ),
+        (
+            tag(" release", None),
+            "name must not contain commas or start or end with spaces",
+        ),
+        (
+            tag(&"x".repeat(65), None),
+            "name is longer than 64 characters",
+        ),
+        (tag("release", Some("blue")), "colour must be like #rrggbb"),
+        (
+            tag("release", Some("#12345g")),
+            "colour must be like #rrggbb",
+        ),
+    ] {
+        assert_eq!(tag.validate().unwrap_err(), err);
+    }
+}
+
+#[tokio::test]
+async fn test_tag_crud() -> anyhow::Result<()> {
+    let app = crate::app(sqlite_memory_repository().await?);
+
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/tags",
+        json!({"name": "release", "colour": "#1e90ff"}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(
+        body,
+        json!({"id": 1, "name": "release", "colour": "#1e90ff"})
+    );
+    let (status, _) = request(&app, Method::POST, "/tags", json!({"name": "Release"})).await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/tags",
+        json!({"name": "ops", "colour": "red"}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["msg"], "colour must be like #rrggbb");
+
+    let (status, body) = request(&app, Method::PUT, "/tags/1", json!({"name": "shipping"})).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"id": 1, "name": "shipping"}));
+    let (_, body) = request(&app, Method::GET, "/tags/1", Value::Null).await?;
+    assert_eq!(body, json!({"id": 1, "name": "shipping"}));
+    let (_, body) = request(&app, Method::GET, "/tags", Value::Null).await?;
+    assert_eq!(body, json!([{"id": 1, "name": "shipping"}]));
+
+    let (status, _) = request(&app, Method::DELETE, "/tags/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    for method in [Method::GET, Method::DELETE] {
+        let (status, body) = request(&app, method, "/tags/1", Value::Null).await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["msg"], "tag not found");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_tagged_tasks() -> anyhow::Result<()> {
+    let repo = sqlite_memory_repository().await?;
+    let app = crate::app(repo.clone());
+    for task in ["write notes", "fix bug", "water plants"] {
+        request(&app, Method::POST, "/tasks", json!({ "task": task })).await?;
+    }
+    for tag in ["release", "urgent", "home"] {
+        request(&app, Method::POST, "/tags", json!({ "name": tag })).await?;
+    }
+    // 1 release, 2 release and urgent, 3 home
+    for (task, tag) in [(1, 1), (2, 2), (2, 1), (3, 3)] {
+        let (status, _) = request(
+            &app,
+            Method::PUT,
+            &format!("/tasks/{}/tags/{}", task, tag),
+            Value::Null,
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (_, body) = request(&app, Method::GET, "/tasks/2", Value::Null).await?;
+    assert_eq!(
+        body,
+        json!({"id": 2, "task": "fix bug", "tags": [
+            {"id": 1, "name": "release"},
+            {"id": 2, "name": "urgent"},
+        ]})
+    );
+
+    for (query, expected) in [
+        ("tags=release", vec!["write notes", "fix bug"]),
+        ("tags=Release,urgent", vec!["fix bug"]),
+        ("tags=release,urgent&tag_match=all", vec!["fix bug"]),
+        (
+            "tags=urgent,home&tag_match=any",
+            vec!["fix bug", "water plants"],
+        ),
+        ("tags=unknown", vec![]),
+    ] {
+        let (status, body) =
+            request(&app, Method::GET, &format!("/tasks?{}", query), Value::Null).await?;
+        assert_eq!(status, StatusCode::OK, "{}", query);
+        assert_eq!(names(&body), expected, "{}", query);
+    }
+
+    let (_, body) = request(&app, Method::GET, "/tags/counts", Value::Null).await?;
+    assert_eq!(
+        body,
+        json!([
+            {"id": 3, "name": "home", "tasks": 1},
+            {"id": 1, "name": "release", "tasks": 2},
+            {"id": 2, "name": "urgent", "tasks": 1},
+        ])
+    );
+
+    let (status, body) = request(&app, Method::DELETE, "/tasks/2/tags/2", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["tags"], json!([{"id": 1, "name": "release"}]));
+    let (status, body) = request(&app, Method::DELETE, "/tasks/2/tags/2", Value::Null).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["msg"], "tag is not attached to the task");
+    let (status, _) = request(&app, Method::PUT, "/tasks/2/tags/4711", Value::Null).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // deleting a task keeps its tags
+    request(&app, Method::DELETE, "/tasks/3", Value::Null).await?;
+    let (_, body) = request(&app, Method::GET, "/tags/counts", Value::Null).await?;
+    assert_eq!(body[0], json!({"id": 3, "name": "home", "tasks": 0}));
+    Ok(())
+}
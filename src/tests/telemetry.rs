@@ -0,0 +1,166 @@
+use super::sqlite_memory_repository;
+use crate::controllers::task::{all_tasks, task};
+use crate::models::task::TaskFilter;
+use crate::negotiation::{Accepted, Format};
+use crate::telemetry::{fmt_layer, make_span, LogFormat};
+use axum::extract::{Path, Query};
+use axum::http::{HeaderMap, Request};
+use axum::Extension;
+use opentelemetry::sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry::sdk::trace::{self as sdktrace, Sampler, TracerProvider};
+use opentelemetry::trace::{SpanId, TraceId, TracerProvider as _};
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tracing::instrument::WithSubscriber;
+use tracing::Instrument;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACEPARENT_SAMPLED: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+const TRACEPARENT_NOT_SAMPLED: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00";
+
+/// Stand-in for an OTLP collector: keeps every exported span in memory.
+#[derive(Clone, Debug, Default)]
+struct InProcessCollector {
+    spans: Arc<Mutex<Vec<SpanData>>>,
+}
+
+impl SpanExporter for InProcessCollector {
+    fn export(
+        &mut self,
+        batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send>> {
+        self.spans.lock().unwrap().extend(batch);
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
+
+/// Log writer that appends everything to a shared buffer.
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/**
+ * Handle GET /tasks against an in-memory database while spans are exported
+ * to the in-process collector. Returns all spans the collector received.
+ */
+async fn traced_all_tasks(sampler: Sampler, traceparent: &str) -> anyhow::Result<Vec<SpanData>> {
+    let collector = InProcessCollector::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(collector.clone())
+        .with_config(sdktrace::config().with_sampler(sampler))
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+    let repo = sqlite_memory_repository().await?;
+
+    let request = Request::builder()
+        .uri("/tasks")
+        .header("traceparent", traceparent)
+        .body(())?;
+    async {
+        let span = make_span(&request);
+        all_tasks(
+            HeaderMap::new(),
+            Query(TaskFilter::default()),
+            Extension(repo),
+        )
+        .instrument(span)
+        .await;
+    }
+    .with_subscriber(subscriber)
+    .await;
+
+    provider.force_flush();
+    let spans = collector.spans.lock().unwrap().clone();
+    Ok(spans)
+}
+
+#[tokio::test]
+async fn test_traceparent_is_propagated_to_request_and_db_spans() -> anyhow::Result<()> {
+    let spans = traced_all_tasks(Sampler::AlwaysOn, TRACEPARENT_SAMPLED).await?;
+    assert_eq!(spans.len(), 2);
+
+    let request_span = spans.iter().find(|s| s.name == "GET /tasks").unwrap();
+    let db_span = spans.iter().find(|s| s.name == "SELECT").unwrap();
+    let trace_id = TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736")?;
+    assert_eq!(request_span.span_context.trace_id(), trace_id);
+    assert_eq!(
+        request_span.parent_span_id,
+        SpanId::from_hex("00f067aa0ba902b7")?
+    );
+    assert_eq!(db_span.span_context.trace_id(), trace_id);
+    assert_eq!(db_span.parent_span_id, request_span.span_context.span_id());
+    assert!(db_span
+        .attributes
+        .iter()
+        .any(|(key, value)| key.as_str() == "db.statement"
+            && value.as_str().starts_with("SELECT id, task, uid")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sampler_respects_parent_decision() -> anyhow::Result<()> {
+    let parent_based = || Sampler::ParentBased(Box::new(Sampler::AlwaysOff));
+
+    let spans = traced_all_tasks(parent_based(), TRACEPARENT_SAMPLED).await?;
+    assert_eq!(spans.len(), 2);
+
+    let spans = traced_all_tasks(parent_based(), TRACEPARENT_NOT_SAMPLED).await?;
+    assert!(spans.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sampler_always_off_exports_nothing() -> anyhow::Result<()> {
+    let spans = traced_all_tasks(Sampler::AlwaysOff, TRACEPARENT_SAMPLED).await?;
+    assert!(spans.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_json_logs_have_structured_fields_and_request_id() -> anyhow::Result<()> {
+    let logs = CapturedLogs::default();
+    let writer = logs.clone();
+    let subscriber =
+        tracing_subscriber::registry().with(fmt_layer(LogFormat::Json, move || writer.clone()));
+    let repo = sqlite_memory_repository().await?;
+
+    let request = Request::builder()
+        .uri("/tasks/4711")
+        .header("x-request-id", "my-request-42")
+        .body(())?;
+    async {
+        let span = make_span(&request);
+        task(Path(4711), Accepted(Format::Json), Extension(repo))
+            .instrument(span)
+            .await;
+    }
+    .with_subscriber(subscriber)
+    .await;
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone())?;
+    let event: serde_json::Value = logs
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?
+        .into_iter()
+        .find(|event| event["level"] == "ERROR")
+        .unwrap();
+    assert_eq!(event["fields"]["message"], "could not find task");
+    assert_eq!(event["fields"]["task_id"], 4711);
+    assert!(event["fields"]["error"].is_string());
+    assert_eq!(event["spans"][0]["request_id"], "my-request-42");
+    Ok(())
+}
@@ -0,0 +1,523 @@
+use crate::app;
+use crate::models::task::{NewTask, TaskStatus};
+use crate::repository::memory::MemoryTaskRepository;
+use crate::repository::sqlite::SqliteTaskRepository;
+use crate::repository::DynTaskRepository;
+use axum::Router;
+use chrono::{NaiveDate, TimeZone, Utc};
+use hyper::body::{to_bytes, HttpBody};
+use hyper::{Body, Method, Request, StatusCode};
+use sqlx::sqlite::SqlitePoolOptions;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+fn import_request(csv: &str) -> anyhow::Result<Request<Body>> {
+    Ok(Request::builder()
+        .method(Method::POST)
+        .header(hyper::header::CONTENT_TYPE, "text/csv")
+        .uri("/tasks/import")
+        .body(Body::from(csv.to_string()))?)
+}
+
+#[tokio::test]
+async fn test_import_csv_reports_rows_by_line() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let csv = "id,Task,notes\n\
+               7,first,x\n\
+               ,\"  \",y\n\
+               8,\"with, comma\",z\n\
+               only one field\n";
+
+    let resp = app(repo.clone()).oneshot(import_request(csv)?).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body_bytes = to_bytes(resp.into_body()).await?;
+    assert_eq!(
+        body_bytes,
+        r#"{"accepted":[{"line":2,"task":{"id":1,"task":"first"}},{"line":4,"task":{"id":2,"task":"with, comma"}}],"#.to_string()
+            + r#""rejected":[{"line":3,"reason":"task must not be empty"},{"line":5,"reason":"expected 3 fields but found 1"}]}"#
+    );
+    assert_eq!(repo.all_tasks().await?.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_csv_without_task_column() -> anyhow::Result<()> {
+    let resp = app(Arc::new(MemoryTaskRepository::new()))
+        .oneshot(import_request("id,title\n1,first\n")?)
+        .await?;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_csv_can_be_imported_again() -> anyhow::Result<()> {
+    let app = app(Arc::new(MemoryTaskRepository::new()));
+    let csv = "task,status,due_at,priority\n\
+               first,completed,2022-11-20T18:00:00+01:00,1\n\
+               \"quoted \"\"task\"\", with comma\",,,\n\
+               \"two\nlines\",,,\n";
+    let resp = app.clone().oneshot(import_request(csv)?).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = Request::builder()
+        .uri("/tasks/export.csv")
+        .body(Body::empty())?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "text/csv; charset=utf-8");
+    let exported = String::from_utf8(to_bytes(resp.into_body()).await?.to_vec())?;
+    assert_eq!(
+        exported,
+        "id,task,status,due_at,priority,categories,rrule,created_on,completed_on,remind_at\n\
+         1,first,completed,2022-11-20T17:00:00+00:00,1,,,,,\n\
+         2,\"quoted \"\"task\"\", with comma\",,,,,,,,\n\
+         3,\"two\nlines\",,,,,,,,\n"
+    );
+
+    // re-importing the export creates copies with new ids
+    let resp = app.clone().oneshot(import_request(&exported)?).await?;
+    let report: serde_json::Value = serde_json::from_slice(&to_bytes(resp.into_body()).await?)?;
+    assert_eq!(report["accepted"][2]["task"]["id"], 6);
+    assert_eq!(report["accepted"][2]["task"]["task"], "two\nlines");
+    assert_eq!(report["rejected"], serde_json::json!([]));
+    assert_eq!(
+        report["accepted"][0]["task"]["due_at"],
+        "2022-11-20T17:00:00Z"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_csv_rejects_invalid_fields() -> anyhow::Result<()> {
+    let csv = "task,status,due_at,priority\n\
+               a,done,,\n\
+               b,,tomorrow,\n\
+               c,,,10\n";
+    let resp = app(Arc::new(MemoryTaskRepository::new()))
+        .oneshot(import_request(csv)?)
+        .await?;
+    let report: serde_json::Value = serde_json::from_slice(&to_bytes(resp.into_body()).await?)?;
+    assert_eq!(
+        report["rejected"],
+        serde_json::json!([
+            {"line": 2, "reason": "unknown status \"done\""},
+            {"line": 3, "reason": "invalid due_at \"tomorrow\", expected RFC 3339"},
+            {"line": 4, "reason": "priority must be between 1 and 9"},
+        ])
+    );
+    Ok(())
+}
+
+fn ics_import_request(ics: &str) -> anyhow::Result<Request<Body>> {
+    Ok(Request::builder()
+        .method(Method::POST)
+        .header(hyper::header::CONTENT_TYPE, "text/calendar")
+        .uri("/tasks/import")
+        .body(Body::from(ics.to_string()))?)
+}
+
+async fn export_ics(app: &Router) -> anyhow::Result<String> {
+    let req = Request::builder()
+        .uri("/tasks/export.ics")
+        .body(Body::empty())?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers()["content-type"],
+        "text/calendar; charset=utf-8"
+    );
+    Ok(String::from_utf8(
+        to_bytes(resp.into_body()).await?.to_vec(),
+    )?)
+}
+
+#[tokio::test]
+async fn test_import_ics_vtodos() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    // folded lines, escaped text, an event and an alarm which are not imported
+    let ics = "BEGIN:VCALENDAR\r\n\
+               VERSION:2.0\r\n\
+               PRODID:-//Example//EN\r\n\
+               BEGIN:VEVENT\r\n\
+               UID:event-1\r\n\
+               SUMMARY:Not a task\r\n\
+               END:VEVENT\r\n\
+               BEGIN:VTODO\r\n\
+               UID:todo-1@example.com\r\n\
+               SUMMARY:Water plants\\, all of\r\n  them\r\n\
+               STATUS:NEEDS-ACTION\r\n\
+               DUE;TZID=Europe/Berlin:20221120T170000\r\n\
+               PRIORITY:0\r\n\
+               CATEGORIES:home,garden\r\n\
+               CATEGORIES:weekly\r\n\
+               RRULE:FREQ=WEEKLY;BYDAY=SA\r\n\
+               BEGIN:VALARM\r\n\
+               SUMMARY:alarm\r\n\
+               END:VALARM\r\n\
+               END:VTODO\r\n\
+               BEGIN:VTODO\r\n\
+               UID:todo-2@example.com\r\n\
+               DUE;VALUE=DATE:20221121\r\n\
+               END:VTODO\r\n\
+               BEGIN:VTODO\r\n\
+               SUMMARY:Without uid\r\n\
+               STATUS:WAITING\r\n\
+               END:VTODO\r\n\
+               BEGIN:VTODO\r\n\
+               SUMMARY:Unknown zone\r\n\
+               DUE;TZID=W. Europe Standard Time:20221120T170000\r\n\
+               END:VTODO\r\n\
+               END:VCALENDAR\r\n";
+
+    let resp = app(repo.clone()).oneshot(ics_import_request(ics)?).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let report: serde_json::Value = serde_json::from_slice(&to_bytes(resp.into_body()).await?)?;
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "accepted": [{"line": 8, "task": {
+                "id": 1,
+                "task": "Water plants, all of them",
+                "status": "needs-action",
+                "due_at": "2022-11-20T16:00:00Z",
+                "categories": "home,garden,weekly",
+                "rrule": "FREQ=WEEKLY;BYDAY=SA"
+            }}],
+            "rejected": [
+                {"line": 22, "reason": "VTODO has no SUMMARY"},
+                {"line": 26, "reason": "unknown status \"WAITING\""},
+                {"line": 30, "reason": "unknown TZID \"W. Europe Standard Time\""}
+            ]
+        })
+    );
+    assert_eq!(repo.task(1).await?.uid, "todo-1@example.com");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reimport_of_ics_export_updates_tasks() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let app = app(repo.clone());
+    let long_task = "A task with a summary which is too long for one content line; \
+                     it has to be folded. Ünïcödé must not be split";
+    repo.create_task(&NewTask {
+        task: long_task.to_string(),
+        status: Some(TaskStatus::InProcess),
+        due_at: Some(Utc.with_ymd_and_hms(2022, 11, 20, 17, 0, 0).unwrap()),
+        priority: Some(2),
+        categories: Some("home,chores".to_string()),
+        rrule: Some("FREQ=MONTHLY;BYMONTHDAY=1".to_string()),
+        ..Default::default()
+    })
+    .await?;
+    repo.create_task(&NewTask {
+        task: "plain".to_string(),
+        ..Default::default()
+    })
+    .await?;
+
+    let exported = export_ics(&app).await?;
+    assert!(exported.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(exported.ends_with("END:VTODO\r\nEND:VCALENDAR\r\n"));
+    assert!(exported.lines().all(|line| line.len() <= 76));
+    for line in [
+        "STATUS:IN-PROCESS",
+        "DUE:20221120T170000Z",
+        "PRIORITY:2",
+        "CATEGORIES:home,chores",
+        "RRULE:FREQ=MONTHLY;BYMONTHDAY=1",
+    ] {
+        assert!(exported.lines().any(|l| l == line), "missing {}", line);
+    }
+
+    // update in the calendar app, then import again
+    let edited = exported.replace("PRIORITY:2", "PRIORITY:5");
+    let resp = app.clone().oneshot(ics_import_request(&edited)?).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let tasks = repo.all_tasks().await?;
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[0].task, long_task);
+    assert_eq!(tasks[0].priority, Some(5));
+    assert_eq!(tasks[0].status, Some(TaskStatus::InProcess));
+    assert_eq!(tasks[1].task, "plain");
+    assert_eq!(tasks[1].status, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_ics_without_calendar() -> anyhow::Result<()> {
+    let resp = app(Arc::new(MemoryTaskRepository::new()))
+        .oneshot(ics_import_request("task\nfirst\n")?)
+        .await?;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_csv_is_all_or_nothing() -> anyhow::Result<()> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await?;
+    sqlx::migrate!("migrations/sqlite").run(&pool).await?;
+    sqlx::query(
+        "CREATE TRIGGER reject_boom BEFORE INSERT ON task WHEN NEW.task = 'boom' \
+         BEGIN SELECT RAISE(ABORT, 'boom'); END",
+    )
+    .execute(&pool)
+    .await?;
+    let repo: DynTaskRepository = Arc::new(SqliteTaskRepository::new(pool));
+
+    let resp = app(repo.clone())
+        .oneshot(import_request("task\nfirst\nboom\nlast\n")?)
+        .await?;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(repo.all_tasks().await?.is_empty());
+    Ok(())
+}
+
+fn todo_txt_import_request(todo_txt: &str) -> anyhow::Result<Request<Body>> {
+    Ok(Request::builder()
+        .method(Method::POST)
+        .header(hyper::header::CONTENT_TYPE, "text/plain")
+        .uri("/tasks/import")
+        .body(Body::from(todo_txt.to_string()))?)
+}
+
+async fn export_todo_txt(app: &Router) -> anyhow::Result<String> {
+    let req = Request::builder()
+        .uri("/tasks/export.txt")
+        .body(Body::empty())?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    Ok(String::from_utf8(
+        to_bytes(resp.into_body()).await?.to_vec(),
+    )?)
+}
+
+#[tokio::test]
+async fn test_todo_txt_round_trip_is_lossless() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let app = app(repo.clone());
+    let todo_txt = "(A) 2022-11-01 call mom +family @phone due:2022-11-25\n\
+                    x 2022-11-20 2022-11-02 buy milk +shopping pri:B\n\
+                    x 2022-11-21 (C) not a priority after x\n\
+                    2022-11-03 +work review  pull request key:value http://example.com\n\
+                    plain task\n\
+                    (K) 2022-11-04 some day\n\
+                    x done long ago created:2022-10-01 pri:Z\n";
+    let resp = app
+        .clone()
+        .oneshot(todo_txt_import_request(todo_txt)?)
+        .await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let tasks = repo.all_tasks().await?;
+    assert_eq!(tasks[0].task, "call mom +family @phone due:2022-11-25");
+    assert_eq!(tasks[0].priority, Some(1));
+    assert_eq!(tasks[0].created_on, NaiveDate::from_ymd_opt(2022, 11, 1));
+    assert_eq!(tasks[0].categories.as_deref(), Some("family,phone"));
+    assert_eq!(
+        tasks[0].due_at,
+        Some(Utc.with_ymd_and_hms(2022, 11, 25, 0, 0, 0).unwrap())
+    );
+    assert_eq!(tasks[1].status, Some(TaskStatus::Completed));
+    assert_eq!(tasks[1].completed_on, NaiveDate::from_ymd_opt(2022, 11, 20));
+    assert_eq!(tasks[1].created_on, NaiveDate::from_ymd_opt(2022, 11, 2));
+    assert_eq!(tasks[1].priority, Some(2));
+    assert_eq!(tasks[2].priority, None);
+    assert_eq!(tasks[5].task, "some day pri:K");
+    assert_eq!(tasks[5].priority, None);
+    assert_eq!(tasks[6].created_on, NaiveDate::from_ymd_opt(2022, 10, 1));
+
+    assert_eq!(export_todo_txt(&app).await?, todo_txt);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_todo_txt_export_follows_field_changes() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let app = app(repo.clone());
+    let todo_txt = "(B) water plants +home @garden due:2022-11-25\n";
+    app.clone()
+        .oneshot(todo_txt_import_request(todo_txt)?)
+        .await?;
+
+    let update = Request::builder()
+        .method(Method::PUT)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .uri("/tasks/1")
+        .body(Body::from(
+            r#"{"task":"water plants +home @garden due:2022-11-25","status":"completed",
+                "priority":2,"categories":"garden,weekend","completed_on":"2022-11-26"}"#,
+        ))?;
+    assert_eq!(app.clone().oneshot(update).await?.status(), StatusCode::OK);
+    assert_eq!(
+        export_todo_txt(&app).await?,
+        "x 2022-11-26 water plants @garden +weekend pri:B\n"
+    );
+
+    let update = Request::builder()
+        .method(Method::PUT)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .uri("/tasks/1")
+        .body(Body::from(
+            r#"{"task":"water plants\r\nand the lawn","status":"completed",
+                "created_on":"2022-11-01"}"#,
+        ))?;
+    assert_eq!(app.clone().oneshot(update).await?.status(), StatusCode::OK);
+    assert_eq!(
+        export_todo_txt(&app).await?,
+        "x water plants and the lawn created:2022-11-01\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_todo_txt_rejects_unmapped_lines() -> anyhow::Result<()> {
+    let resp = app(Arc::new(MemoryTaskRepository::new()))
+        .oneshot(todo_txt_import_request(
+            "x done pri:a\n\n(A) fine\ndue:2022-11-5 sloppy date\nx 2022-11-20\n",
+        )?)
+        .await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let report: serde_json::Value = serde_json::from_slice(&to_bytes(resp.into_body()).await?)?;
+    assert_eq!(report["accepted"][0]["line"], 3);
+    assert_eq!(report["accepted"][1]["task"]["task"], "2022-11-20");
+    assert_eq!(
+        report["rejected"],
+        serde_json::json!([
+            {"line": 1, "reason": "invalid pri:a"},
+            {"line": 4, "reason": "invalid due:2022-11-5, expected YYYY-MM-DD"},
+        ])
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_markdown_checklist_import_and_export() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let app = app(repo.clone());
+    let notes = "# Weekly meeting\n\
+                 Attendees: everybody\n\
+                 - [ ] send minutes\n\
+                 ## Office, Facilities\n\
+                 * [x] book room\n  \
+                   - [ ] nested item\n\
+                 \t- [ ] nested deeper\n\
+                 1. [ ]   \n   \
+                    - [ ] below an empty item\n\
+                 ```\n\
+                 - [ ] example in a code block\n\
+                 ```\n\
+                 - not a checklist item\n\
+                 - [X] checked too\n";
+    let req = Request::builder()
+        .method(Method::POST)
+        .header(hyper::header::CONTENT_TYPE, "text/markdown")
+        .uri("/tasks/import")
+        .body(Body::from(notes))?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let report: serde_json::Value = serde_json::from_slice(&to_bytes(resp.into_body()).await?)?;
+    let lines: Vec<&serde_json::Value> = report["accepted"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| &row["line"])
+        .collect();
+    assert_eq!(lines, [3, 5, 6, 7, 14]);
+    assert_eq!(
+        report["rejected"],
+        serde_json::json!([
+            {"line": 8, "reason": "task must not be empty"},
+            {"line": 9, "reason": "the parent on line 8 is rejected"},
+        ])
+    );
+    let tasks = repo.all_tasks().await?;
+    assert_eq!(tasks[0].categories.as_deref(), Some("Weekly meeting"));
+    assert_eq!(tasks[1].status, Some(TaskStatus::Completed));
+    assert_eq!(tasks[2].categories.as_deref(), Some("Office, Facilities"));
+    assert_eq!(tasks[2].parent_id, Some(tasks[1].id));
+    assert_eq!(tasks[3].parent_id, Some(tasks[2].id));
+    assert_eq!(tasks[4].parent_id, None);
+
+    let req = Request::builder()
+        .uri("/tasks")
+        .header(
+            hyper::header::ACCEPT,
+            "text/markdown, application/json;q=0.5",
+        )
+        .body(Body::empty())?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(
+        resp.headers()["content-type"],
+        "text/markdown; charset=utf-8"
+    );
+    let exported = String::from_utf8(to_bytes(resp.into_body()).await?.to_vec())?;
+    assert_eq!(
+        exported,
+        "## Weekly meeting\n\n\
+         - [ ] send minutes\n\n\
+         ## Office, Facilities\n\n\
+         - [x] book room\n  \
+           - [ ] nested item\n    \
+             - [ ] nested deeper\n\
+         - [x] checked too\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ndjson_export() -> anyhow::Result<()> {
+    let repo: DynTaskRepository = Arc::new(MemoryTaskRepository::new());
+    let tasks = ["first", "second"].map(|task| NewTask {
+        task: task.to_string(),
+        ..Default::default()
+    });
+    repo.import_tasks(&tasks).await?;
+    let app = app(repo);
+    let expected = "{\"id\":1,\"task\":\"first\"}\n{\"id\":2,\"task\":\"second\"}\n";
+
+    let req = Request::builder()
+        .uri("/tasks/export.ndjson")
+        .body(Body::empty())?;
+    let resp = app.clone().oneshot(req).await?;
+    assert_eq!(resp.headers()["content-type"], "application/x-ndjson");
+    assert_eq!(to_bytes(resp.into_body()).await?, expected);
+
+    let req = Request::builder()
+        .uri("/tasks")
+        .header(hyper::header::ACCEPT, "application/x-ndjson")
+        .body(Body::empty())?;
+    let resp = app.oneshot(req).await?;
+    assert_eq!(resp.headers()["content-type"], "application/x-ndjson");
+    assert_eq!(to_bytes(resp.into_body()).await?, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ndjson_export_stops_when_client_disconnects() -> anyhow::Result<()> {
+    // one connection only: while the export holds it, no other query can run
+    let repo: DynTaskRepository = Arc::new(SqliteTaskRepository::in_memory().await?);
+    let tasks: Vec<NewTask> = (0..1000)
+        .map(|index| NewTask {
+            task: format!("task {}", index),
+            ..Default::default()
+        })
+        .collect();
+    repo.import_tasks(&tasks).await?;
+
+    let req = Request::builder()
+        .uri("/tasks/export.ndjson")
+        .body(Body::empty())?;
+    let mut body = app(repo.clone()).oneshot(req).await?.into_body();
+    let first = body.data().await.unwrap()?;
+    assert_eq!(first, "{\"id\":1,\"task\":\"task 0\"}\n");
+    drop(body);
+
+    let task = tokio::time::timeout(Duration::from_secs(5), repo.task(1000)).await??;
+    assert_eq!(task.task, "task 999");
+    Ok(())
+}
@@ -0,0 +1,244 @@
+use super::{request, sqlite_memory_repository};
+use crate::backup::BackupConfig;
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+
+/// Task and admin routes on a fresh database
+async fn app() -> anyhow::Result<axum::Router> {
+    let repo = sqlite_memory_repository().await?;
+    let backups = BackupConfig {
+        dir: std::env::temp_dir().join("workflow-test-backups"),
+        compress: false,
+        keep_last: 7,
+        max_age: None,
+    };
+    Ok(crate::app(repo.clone()).merge(crate::admin_app(repo, backups)))
+}
+
+/// Workflow 1 "review": open -> doing -> review -> done (requires completed_on),
+/// review -> doing, and task 1 "release" following it
+async fn review_workflow(app: &axum::Router) -> anyhow::Result<()> {
+    let workflow = json!({
+        "name": "review",
+        "states": [
+            {"name": "open", "status": "needs-action"},
+            {"name": "doing", "status": "in-process"},
+            {"name": "review", "status": "in-process"},
+            {"name": "done", "status": "completed"}
+        ],
+        "transitions": [
+            {"from": "open", "to": "doing"},
+            {"from": "doing", "to": "review"},
+            {"from": "review", "to": "doing"},
+            {"from": "review", "to": "done", "required_fields": ["completed_on"]}
+        ]
+    });
+    let (status, body) = request(app, Method::POST, "/admin/workflows", workflow).await?;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["id"], 1);
+    let task = json!({"task": "release", "workflow_id": 1});
+    let (status, body) = request(app, Method::POST, "/tasks", task).await?;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["state"], "open");
+    assert_eq!(body["status"], "needs-action");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_workflow_crud() -> anyhow::Result<()> {
+    let app = app().await?;
+    review_workflow(&app).await?;
+
+    let (_, body) = request(&app, Method::GET, "/admin/workflows", Value::Null).await?;
+    assert_eq!(body[0]["name"], "review");
+    assert_eq!(
+        body[0]["transitions"][3],
+        json!({"from": "review", "to": "done", "required_fields": ["completed_on"]})
+    );
+
+    for (workflow, msg) in [
+        (
+            json!({"name": "x", "states": []}),
+            "a workflow needs at least one state",
+        ),
+        (
+            json!({"name": "x", "states": [
+                {"name": "a", "status": "needs-action"},
+                {"name": "a", "status": "completed"}
+            ]}),
+            "state a is defined twice",
+        ),
+        (
+            json!({"name": "x", "states": [{"name": "a", "status": "needs-action"}],
+                   "transitions": [{"from": "a", "to": "b"}]}),
+            "transition to or from unknown state b",
+        ),
+    ] {
+        let (status, body) = request(&app, Method::POST, "/admin/workflows", workflow).await?;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["msg"], msg);
+    }
+
+    // the task is in state open, which must stay
+    let workflow = json!({"name": "short", "states": [{"name": "done", "status": "completed"}]});
+    let (status, body) = request(&app, Method::PUT, "/admin/workflows/1", workflow).await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["msg"], "tasks are in state open, it must stay");
+    let (status, _) = request(&app, Method::DELETE, "/admin/workflows/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let body = json!({"workflow_id": null});
+    let (status, body) = request(&app, Method::PUT, "/tasks/1/workflow", body).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("workflow_id").is_none());
+    let (status, _) = request(&app, Method::DELETE, "/admin/workflows/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = request(&app, Method::GET, "/admin/workflows/1", Value::Null).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["msg"], "workflow not found");
+    let body = json!({"workflow_id": 1});
+    let (status, body) = request(&app, Method::PUT, "/tasks/1/workflow", body).await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["msg"], "workflow not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_follows_transitions() -> anyhow::Result<()> {
+    let app = app().await?;
+    review_workflow(&app).await?;
+
+    // done is no next state of open
+    let task = json!({"task": "release", "status": "completed"});
+    let (status, body) = request(&app, Method::PUT, "/tasks/1", task).await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        body["msg"],
+        "the workflow allows no change from open to completed"
+    );
+    assert_eq!(body["allowed_states"], json!(["doing"]));
+
+    // the one next state with the status
+    let task = json!({"task": "release", "status": "in-process"});
+    let (status, _) = request(&app, Method::PUT, "/tasks/1", task).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = request(&app, Method::GET, "/tasks/1", Value::Null).await?;
+    assert_eq!(body["state"], "doing");
+
+    // review must precede done
+    let task = json!({"task": "release", "state": "done", "completed_on": "2022-12-01"});
+    let (status, body) = request(&app, Method::PUT, "/tasks/1", task).await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        body["msg"],
+        "the workflow allows no change from doing to done"
+    );
+    assert_eq!(body["allowed_states"], json!(["review"]));
+
+    let task = json!({"task": "release", "state": "review"});
+    let (status, _) = request(&app, Method::PUT, "/tasks/1", task).await?;
+    assert_eq!(status, StatusCode::OK);
+    let task = json!({"task": "release", "state": "done"});
+    let (status, body) = request(&app, Method::PUT, "/tasks/1", task).await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["msg"], "completed_on is required to change to done");
+    let task = json!({"task": "release", "state": "done", "completed_on": "2022-12-01"});
+    let (status, _) = request(&app, Method::PUT, "/tasks/1", task).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = request(&app, Method::GET, "/tasks/1", Value::Null).await?;
+    assert_eq!(body["state"], "done");
+    assert_eq!(body["status"], "completed");
+
+    // the status follows the state, staying needs no transition
+    let task = json!({"task": "released"});
+    let (status, _) = request(&app, Method::PUT, "/tasks/1", task).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = request(&app, Method::GET, "/tasks/1", Value::Null).await?;
+    assert_eq!(body["status"], "completed");
+
+    let task = json!({"task": "release", "state": "shipped"});
+    let (status, body) = request(&app, Method::PUT, "/tasks/1", task).await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["msg"], "unknown state shipped");
+    let (_, _) = request(&app, Method::POST, "/tasks", json!({"task": "free"})).await?;
+    let task = json!({"task": "free", "state": "done"});
+    let (status, body) = request(&app, Method::PUT, "/tasks/2", task).await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["msg"], "the task follows no workflow");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_card_move_follows_transitions() -> anyhow::Result<()> {
+    let app = app().await?;
+    review_workflow(&app).await?;
+    let board = json!({
+        "name": "release",
+        "columns": [
+            {"name": "todo", "status": "needs-action"},
+            {"name": "doing", "status": "in-process"},
+            {"name": "done", "status": "completed"}
+        ]
+    });
+    let (status, _) = request(&app, Method::POST, "/boards", board).await?;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/boards/1/cards/1/move",
+        json!({"column_id": 3}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["allowed_states"], json!(["doing"]));
+    let (status, body) = request(
+        &app,
+        Method::POST,
+        "/boards/1/cards/1/move",
+        json!({"column_id": 2}),
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["columns"][1]["tasks"][0]["state"], "doing");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_patch_follows_transitions() -> anyhow::Result<()> {
+    let app = app().await?;
+    review_workflow(&app).await?;
+
+    // the fields left out stay, null clears a field
+    let patch = json!({"status": "in-process", "priority": 2});
+    let (status, body) = request(&app, Method::PATCH, "/tasks/1", patch).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["task"], "release");
+    let (_, body) = request(&app, Method::GET, "/tasks/1", Value::Null).await?;
+    assert_eq!(body["state"], "doing");
+    assert_eq!(body["priority"], 2);
+    let patch = json!({"priority": null});
+    let (status, _) = request(&app, Method::PATCH, "/tasks/1", patch).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = request(&app, Method::GET, "/tasks/1", Value::Null).await?;
+    assert!(body.get("priority").is_none());
+    assert_eq!(body["state"], "doing");
+
+    let patch = json!({"state": "done", "completed_on": "2022-12-01"});
+    let (status, body) = request(&app, Method::PATCH, "/tasks/1", patch).await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["allowed_states"], json!(["review"]));
+
+    let (status, _) = request(&app, Method::PATCH, "/tasks/1", json!({"task": null})).await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, body) = request(&app, Method::PATCH, "/tasks/1", json!([])).await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["msg"], "the patch must be an object");
+    let patch = json!({"priority": 1, "prio": 3});
+    let (status, body) = request(&app, Method::PATCH, "/tasks/1", patch).await?;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["msg"], "unknown field `prio`");
+    let (status, _) = request(&app, Method::PATCH, "/tasks/4711", json!({})).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
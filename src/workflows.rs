@@ -0,0 +1,127 @@
+//! Workflows: administrators define the states of a workflow, each giving its tasks a status,
+//! and the allowed transitions between them (`/admin/workflows`), e.g. that review must
+//! precede done. A task follows at most one workflow (`PUT /tasks/{id}/workflow`) and starts in
+//! its first state.
+//!
+//! Updates of a task with a workflow (`PUT` and `PATCH /tasks/{id}`, GraphQL `updateTask`, gRPC
+//! and card moves of `crate::boards`) change its state along the transitions only, anything
+//! else is a Transition error (409 with the allowed next states). An update names the new state
+//! or only a status, then the task changes to the next state with that status, if there is
+//! exactly one. A transition can require fields the task must have afterwards. The status of
+//! the task always is the one of its state.
+//!
+//! The repositories apply [`transition`] in the transaction of the write, so concurrent updates
+//! can not skip a transition: to updates and card moves, to the subtasks completed along with
+//! their parent and to the tasks replaced by CalDAV and imports, which give a status only.
+
+use crate::models::task::{Task, TaskStatus};
+use crate::models::workflow::{Workflow, WorkflowState};
+use crate::repository::{self, RepositoryError};
+
+/** The state `task`, which follows `workflow`, changes to as `changed`: `state` if given,
+  otherwise the next state with the status of `changed`, see [`next_state`], which must allow
+  the transition with the fields of `changed`, see [`check_transition`].
+*/
+pub fn transition<'a>(
+    workflow: &'a Workflow,
+    task: &Task,
+    state: Option<&str>,
+    changed: &Task,
+) -> repository::Result<&'a WorkflowState> {
+    let from = current_state(workflow, task);
+    let to = next_state(workflow, from, state, changed.status)?;
+    check_transition(workflow, from, &to.name, |field| task_has(changed, field))?;
+    Ok(to)
+}
+
+/// The state of `task`, which follows `workflow`
+pub fn current_state<'a>(workflow: &'a Workflow, task: &'a Task) -> &'a str {
+    match &task.state {
+        Some(state) => state,
+        // a workflow has at least one state
+        None => &workflow.states[0].name,
+    }
+}
+
+/** The state a task in state `from` changes to: `state` if given (Invalid if the workflow has
+  no such state), otherwise `from` if `status` is None or the one of `from`, otherwise the one
+  next state with `status`. A Transition error if there is no such next state or more than one.
+*/
+pub fn next_state<'a>(
+    workflow: &'a Workflow,
+    from: &str,
+    state: Option<&str>,
+    status: Option<TaskStatus>,
+) -> repository::Result<&'a WorkflowState> {
+    let current = workflow
+        .state(from)
+        .ok_or_else(|| RepositoryError::Invalid(format!("unknown state {}", from)))?;
+    if let Some(state) = state {
+        return workflow
+            .state(state)
+            .ok_or_else(|| RepositoryError::Invalid(format!("unknown state {}", state)));
+    }
+    let status = match status {
+        Some(status) if status != current.status => status,
+        _ => return Ok(current),
+    };
+    let mut candidates = workflow
+        .next_states(from)
+        .into_iter()
+        .filter_map(|name| workflow.state(&name))
+        .filter(|state| state.status == status);
+    match (candidates.next(), candidates.next()) {
+        (Some(state), None) => Ok(state),
+        _ => Err(RepositoryError::Transition {
+            from: from.to_string(),
+            to: status.to_string(),
+            allowed: workflow.next_states(from),
+        }),
+    }
+}
+
+/** A Transition error unless `workflow` allows the change from state `from` to `to`, an
+  Invalid error if a field the transition requires is not set (`is_set`). Staying in a state
+  is always allowed.
+*/
+pub fn check_transition(
+    workflow: &Workflow,
+    from: &str,
+    to: &str,
+    is_set: impl Fn(&str) -> bool,
+) -> repository::Result<()> {
+    if from == to {
+        return Ok(());
+    }
+    let transition = workflow
+        .transition(from, to)
+        .ok_or_else(|| RepositoryError::Transition {
+            from: from.to_string(),
+            to: to.to_string(),
+            allowed: workflow.next_states(from),
+        })?;
+    match transition
+        .required_fields
+        .iter()
+        .find(|field| !is_set(field))
+    {
+        Some(field) => Err(RepositoryError::Invalid(format!(
+            "{} is required to change to {}",
+            field, to
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Whether `field` (see `crate::models::workflow::REQUIRED_FIELDS`) of `task` is set
+fn task_has(task: &Task, field: &str) -> bool {
+    match field {
+        "due_at" => task.due_at.is_some(),
+        "remind_at" => task.remind_at.is_some(),
+        "priority" => task.priority.is_some(),
+        "categories" => task.categories.as_deref().is_some_and(|c| !c.is_empty()),
+        "rrule" => task.rrule.is_some(),
+        "completed_on" => task.completed_on.is_some(),
+        _ => false,
+    }
+}